@@ -0,0 +1,165 @@
+//! Inspect the contents of a landfill directory
+//!
+//! Structures carry no type information on disk, so each command names the
+//! branch to read and interprets it as a specific structure. The directory
+//! is opened read-only, and is never written to.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::ExitCode;
+
+use landfill::{
+    AppendOnly, Content, FramedAppendOnly, Journal, Landfill, Snapshot,
+    Substructure,
+};
+
+const USAGE: &str = "\
+usage: landfill-inspect <dir> <command>
+
+commands:
+  branches                      list files and their sizes
+  journal <name>                print the value of a Journal<u64>
+  dump <name> [offset] [len]    hexdump bytes of an AppendOnly
+  records <name>                list the records of a FramedAppendOnly
+  verify <name>                 verify a Content store using blake3";
+
+// bytes shown per line of a hexdump, and of each record preview
+const DUMP_WIDTH: u64 = 16;
+const PREVIEW_LEN: usize = 32;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[&str]) -> io::Result<()> {
+    let usage = || io::Error::new(io::ErrorKind::InvalidInput, USAGE);
+
+    let (dir, command) = match args {
+        [dir, command @ ..] if !command.is_empty() => (Path::new(dir), command),
+        _ => return Err(usage()),
+    };
+
+    if command == ["branches"] {
+        return branches(dir);
+    }
+
+    let lf = Landfill::open_read_only(dir)?;
+    if let [_, name, ..] = command {
+        check_branch(dir, name)?;
+    }
+
+    match command {
+        ["journal", name] => {
+            let journal: Journal<u64> = lf.substructure(*name)?;
+            println!("{}", journal.current());
+        }
+        ["dump", name, rest @ ..] if rest.len() <= 2 => {
+            let offset = rest.first().map(|n| parse(n)).transpose()?;
+            let len = rest.get(1).map(|n| parse(n)).transpose()?;
+            let ao: AppendOnly = lf.substructure(*name)?;
+            dump(&ao, offset.unwrap_or(0), len.unwrap_or(256))?;
+        }
+        ["records", name] => {
+            let framed: FramedAppendOnly = lf.substructure(*name)?;
+            for (offset, payload) in framed.iter() {
+                let preview = &payload[..payload.len().min(PREVIEW_LEN)];
+                println!("{offset:>12} {:>8}  {}", payload.len(), hex(preview));
+            }
+        }
+        ["verify", name] => {
+            let content: Content<blake3::Hasher> = lf.substructure(*name)?;
+            let report = content.verify()?;
+            println!("checked {}", report.checked);
+            for problem in &report.problems {
+                println!("problem: {problem}");
+            }
+            if !report.is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} problems found", report.problems.len()),
+                ));
+            }
+        }
+        _ => return Err(usage()),
+    }
+
+    Ok(())
+}
+
+fn parse(n: &str) -> io::Result<u64> {
+    n.parse().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("not a number: {n}"),
+        )
+    })
+}
+
+// Fail unless the directory holds files of the branch `name`, so that a
+// mistyped name is reported rather than read as an empty structure
+fn check_branch(dir: &Path, name: &str) -> io::Result<()> {
+    let prefix = format!("{name}_");
+    for entry in fs::read_dir(dir)? {
+        let file = entry?.file_name();
+        let file = file.to_string_lossy();
+        if file == name || file.starts_with(&prefix) {
+            return Ok(());
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no branch named {name}"),
+    ))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn branches(dir: &Path) -> io::Result<()> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        files.push((name, entry.metadata()?.len()));
+    }
+    files.sort();
+
+    for (name, size) in files {
+        println!("{size:>12}  {name}");
+    }
+    Ok(())
+}
+
+// Print a hexdump of `len` bytes at `offset`, stopping at the writehead
+fn dump(ao: &AppendOnly, offset: u64, len: u64) -> io::Result<()> {
+    let end = offset.saturating_add(len).min(ao.epoch());
+    // rows are aligned, lanes start at aligned offsets so no row spans lanes
+    let mut row = offset - offset % DUMP_WIDTH;
+
+    while row < end {
+        let row_len = DUMP_WIDTH.min(end - row) as u32;
+        let bytes = ao.get_cold(row, row_len)?;
+        let ascii: String = bytes
+            .iter()
+            .map(|b| match b {
+                0x20..=0x7e => *b as char,
+                _ => '.',
+            })
+            .collect();
+        println!("{row:>12}  {:<32}  {ascii}", hex(&bytes));
+        row += DUMP_WIDTH;
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use std::io;
+
+#[cfg(feature = "failpoints")]
+use std::collections::HashMap;
+
+#[cfg(feature = "failpoints")]
+use parking_lot::Mutex;
+
+/// Points in the write path where failures can be injected
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Failpoint {
+    /// A value has been written, but the journal recording it has not been
+    /// updated yet
+    DataWritten,
+    /// A journal is about to be flushed, after the data it covers
+    JournalFlush,
+    /// A new lane file is about to be created
+    LaneCreation,
+}
+
+/// What happens when a failpoint is hit
+#[cfg(feature = "failpoints")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailAction {
+    /// Return an io error from the operation
+    Error,
+    /// Panic, as if the process crashed at this point
+    Panic,
+}
+
+// The failpoints set in a landfill, without the feature nothing can be set
+#[derive(Debug, Default)]
+pub(crate) struct Failpoints(
+    #[cfg(feature = "failpoints")] Mutex<HashMap<Failpoint, FailAction>>,
+);
+
+impl Failpoints {
+    #[cfg(feature = "failpoints")]
+    pub fn set(&self, point: Failpoint, action: Option<FailAction>) {
+        let mut points = self.0.lock();
+        match action {
+            Some(action) => points.insert(point, action),
+            None => points.remove(&point),
+        };
+    }
+
+    #[inline(always)]
+    pub fn hit(&self, point: Failpoint) -> io::Result<()> {
+        #[cfg(feature = "failpoints")]
+        match self.0.lock().get(&point) {
+            Some(FailAction::Error) => {
+                return Err(io::Error::other(format!(
+                    "Failpoint {point:?} hit"
+                )))
+            }
+            Some(FailAction::Panic) => panic!("Failpoint {point:?} hit"),
+            None => (),
+        }
+        let _ = point;
+        Ok(())
+    }
+}
@@ -1,20 +1,59 @@
 use std::{
     cell::UnsafeCell,
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs::{self, File, OpenOptions},
     io::{self, Read, Write},
     ops::Deref,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
 };
 
 use bytemuck::{Pod, Zeroable};
-use memmap2::MmapMut;
-use parking_lot::Mutex;
+use bytemuck_derive::*;
+use memmap2::{MmapMut, MmapOptions};
+use parking_lot::{Mutex, RwLock};
+use rand::Rng;
+
+use crate::{helpers, storage::N_LANES};
+
+mod failpoint;
+mod names;
+mod nonblocking;
+mod replication;
+#[cfg(feature = "failpoints")]
+pub use failpoint::FailAction;
+pub use failpoint::Failpoint;
+use failpoint::Failpoints;
+use names::FileNames;
+pub use nonblocking::{AsyncHandle, AsyncSubstructure};
+pub use replication::{ChangeLog, Delta};
+
+// file holding the seed all entropy in a landfill is derived from
+const ROOT_SEED_NAME: &str = "_entropy";
+// prefix of the files of temporary branches
+const TEMP_PREFIX: &str = "_tmp";
+// identifies files holding a `FormatHeader`, "LFMT"
+const FORMAT_MAGIC: u32 = 0x544d_464c;
+// identifies static files starting with a `StaticHeader`, "LFST"
+const STATIC_MAGIC: u32 = 0x5453_464c;
+const STATIC_HEADER_SIZE: usize = std::mem::size_of::<StaticHeader>();
+
+// Precedes the value in a static file, recording what was stored
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct StaticHeader {
+    magic: u32,
+    size: u32,
+    tag: u64,
+}
 
 /// A guard around a landfill that can only be created from this module
 pub struct GuardedLandfill {
     guarded: Landfill,
+    format_version: Option<u32>,
 }
 
 impl GuardedLandfill {
@@ -22,6 +61,23 @@ impl GuardedLandfill {
     pub fn inner(self) -> Landfill {
         self.guarded
     }
+
+    /// The version of the format the data of the structure is stored in
+    ///
+    /// `None` for structures without a format
+    pub fn format_version(&self) -> Option<u32> {
+        self.format_version
+    }
+
+    // Write the format header of `S`, or check the existing one
+    pub(crate) fn check_format_of<S: Substructure>(
+        &mut self,
+    ) -> io::Result<()> {
+        if let Some(format) = S::format() {
+            self.format_version = Some(self.guarded.check_format::<S>(format)?);
+        }
+        Ok(())
+    }
 }
 
 impl Deref for GuardedLandfill {
@@ -37,6 +93,182 @@ pub trait Substructure: Sized {
     fn init(landfill: GuardedLandfill) -> io::Result<Self>;
     /// Flush all data to disk
     fn flush(&self) -> io::Result<()>;
+
+    /// The format of the data in the structure, if it has one
+    ///
+    /// The header is written when the structure is first created, and opening
+    /// data written in another format fails with `InvalidData`
+    fn format() -> Option<FormatHeader> {
+        None
+    }
+
+    /// Returns true if data stored in `version` of the format can be opened
+    ///
+    /// Defaults to only the version of `format`. Structures reading older
+    /// versions find the stored one with `GuardedLandfill::format_version`
+    fn reads_version(version: u32) -> bool {
+        Self::format().is_some_and(|format| format.version() == version)
+    }
+
+    /// The format version of data written before format headers existed
+    ///
+    /// Defaults to the version of `format`. `None` rejects such data with
+    /// `InvalidData`
+    fn legacy_version() -> Option<u32> {
+        Self::format().map(|format| format.version())
+    }
+
+    /// The branches below the structure holding its data
+    ///
+    /// Data in these, or in the file of the structure itself, is taken to be
+    /// written before format headers if there is no header. Defaults to
+    /// `None`, for every branch below the structure, which includes siblings
+    /// sharing its name as a prefix. That only matters for structures whose
+    /// `legacy_version` differs from the current one
+    fn data_branches() -> Option<&'static [&'static str]> {
+        None
+    }
+
+    /// Flush and close the structure, dropping its mappings
+    ///
+    /// Defaults to a flush. Structures can also release unused space and
+    /// record that they were shut down cleanly
+    fn close(self) -> io::Result<()> {
+        self.flush()
+    }
+
+    /// Returns the number of bytes the structure occupies on disk
+    ///
+    /// Defaults to 0, for structures without files of their own. Structures
+    /// keeping their landfill branch can use `Landfill::size_on_disk`
+    fn size_on_disk(&self) -> u64 {
+        0
+    }
+
+    /// Check the stored data for corruption
+    ///
+    /// Defaults to a report with nothing checked. Errors are reserved for
+    /// failures to read the data, problems found are listed in the report
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::default())
+    }
+}
+
+/// A datastructure taking tuning parameters when it is first created
+///
+/// The config is persisted along with the structure, so it is reopened
+/// with the config it was created with. `Substructure::init` of such
+/// structures can find it with `Landfill::stored_config`
+pub trait ConfigurableSubstructure: Substructure {
+    /// The tuning parameters of the structure
+    type Config: Pod;
+
+    /// Initialize a datastructure of this type with `config`
+    fn init_with_config(
+        landfill: GuardedLandfill,
+        config: Self::Config,
+    ) -> io::Result<Self>;
+}
+
+/// A header describing the format of the data in a substructure
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Zeroable, Pod)]
+pub struct FormatHeader {
+    magic: u32,
+    version: u32,
+    element_size: u64,
+    kind: [u8; 16],
+}
+
+impl FormatHeader {
+    /// A header for structures of `kind`, holding elements of `element_size`
+    ///
+    /// Kinds are truncated to 16 bytes
+    pub fn new(kind: &str, element_size: usize, version: u32) -> Self {
+        let mut kind_bytes = [0; 16];
+        let len = kind.len().min(kind_bytes.len());
+        kind_bytes[..len].copy_from_slice(&kind.as_bytes()[..len]);
+
+        FormatHeader {
+            magic: FORMAT_MAGIC,
+            version,
+            element_size: element_size as u64,
+            kind: kind_bytes,
+        }
+    }
+
+    /// The kind of structure
+    pub fn kind(&self) -> &str {
+        let len = self.kind.iter().position(|b| *b == 0).unwrap_or(16);
+        std::str::from_utf8(&self.kind[..len]).unwrap_or("?")
+    }
+
+    /// The size in bytes of the elements of the structure
+    pub fn element_size(&self) -> u64 {
+        self.element_size
+    }
+
+    /// The version of the format
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+impl std::fmt::Display for FormatHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} v{} of {} byte elements",
+            self.kind(),
+            self.version,
+            self.element_size
+        )
+    }
+}
+
+/// The result of checking a structure for corruption
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// The number of items checked
+    pub checked: u64,
+    /// Descriptions of the problems found
+    pub problems: Vec<String>,
+}
+
+impl VerifyReport {
+    /// Combine the reports of several structures into one
+    pub fn merged<I>(reports: I) -> Self
+    where
+        I: IntoIterator<Item = VerifyReport>,
+    {
+        reports
+            .into_iter()
+            .fold(Self::default(), |mut all, report| {
+                all.checked += report.checked;
+                all.problems.extend(report.problems);
+                all
+            })
+    }
+
+    /// Returns true if no problems were found
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+type WriteObserver = dyn Fn(&str, u64, u64) + Send + Sync;
+
+// Callbacks registered with `Landfill::on_write`, by id
+#[derive(Default)]
+struct WriteObservers {
+    callbacks: RwLock<Vec<(u64, Box<WriteObserver>)>>,
+    next_id: AtomicU64,
+}
+
+impl std::fmt::Debug for WriteObservers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WriteObservers({})", self.callbacks.read().len())
+    }
 }
 
 #[derive(Debug)]
@@ -44,6 +276,47 @@ struct LandfillInner {
     dir_path: Option<PathBuf>,
     reserved_names: Mutex<HashSet<String>>,
     self_destruct_sequence_initiated: Mutex<bool>,
+    entropy_seed: Option<[u8; 32]>,
+    root_seed: Mutex<Option<[u8; 32]>>,
+    failpoints: Failpoints,
+    observers: WriteObservers,
+    read_only: bool,
+    verify_on_read: AtomicBool,
+    temp_branches: AtomicU64,
+    names: Arc<FileNames>,
+    // top-level structures opened so far, with how to verify them
+    opened: Mutex<Vec<(String, Verifier)>>,
+    // branches of all structures opened so far, with the branch each was
+    // opened from
+    structures: Mutex<HashMap<String, String>>,
+}
+
+// Opens a structure of a known type from a landfill and verifies it
+type Verifier = fn(&Landfill, &str) -> io::Result<VerifyReport>;
+
+fn verify_as<S: Substructure>(
+    lf: &Landfill,
+    name: &str,
+) -> io::Result<VerifyReport> {
+    lf.substructure::<S, _>(name)?.verify()
+}
+
+// Removes the files of a temporary branch once the last handle to it drops
+#[derive(Debug)]
+struct TempBranch {
+    inner: Arc<LandfillInner>,
+    name: String,
+}
+
+impl Drop for TempBranch {
+    fn drop(&mut self) {
+        let branch = Landfill {
+            inner: self.inner.clone(),
+            name_prefix: self.name.clone(),
+            temp: None,
+        };
+        let _ = branch.remove_tree();
+    }
 }
 
 /// The datastructure representing an on-disk data dump
@@ -61,6 +334,8 @@ struct LandfillInner {
 pub struct Landfill {
     inner: Arc<LandfillInner>,
     name_prefix: String,
+    // set for temporary branches and everything branched off them
+    temp: Option<Arc<TempBranch>>,
 }
 
 impl Landfill {
@@ -69,7 +344,25 @@ impl Landfill {
     /// A directory is created if not already there, otherwise all prior
     /// data is ready to be re-requested
     pub fn open<P: AsRef<Path>>(dir_path: P) -> io::Result<Landfill> {
-        let dir_path: PathBuf = dir_path.as_ref().into();
+        Self::open_inner(dir_path.as_ref(), None)
+    }
+
+    /// Opens a Landfill, deriving newly created entropy from `seed`
+    ///
+    /// Entropy already written to disk is kept as is. Useful for
+    /// reproducible tests and benchmarks
+    pub fn open_with_seed<P: AsRef<Path>>(
+        dir_path: P,
+        seed: [u8; 32],
+    ) -> io::Result<Landfill> {
+        Self::open_inner(dir_path.as_ref(), Some(seed))
+    }
+
+    fn open_inner(
+        dir_path: &Path,
+        entropy_seed: Option<[u8; 32]>,
+    ) -> io::Result<Landfill> {
+        let dir_path: PathBuf = dir_path.into();
         if !dir_path.exists() {
             fs::create_dir(&dir_path)?;
         }
@@ -83,46 +376,324 @@ impl Landfill {
             .write(true)
             .open(&lock_file_path)?;
 
+        // nothing can refer to temporary branches of earlier runs
+        let names = Arc::new(FileNames::default());
+        for entry in fs::read_dir(&dir_path)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let branch =
+                names.branch_name(&dir_path, &file_name.to_string_lossy())?;
+            if branch.starts_with(TEMP_PREFIX) {
+                fs::remove_file(entry.path())?;
+            }
+        }
+
         Ok(Landfill {
             inner: Arc::new(LandfillInner {
                 dir_path: Some(dir_path),
                 self_destruct_sequence_initiated: Mutex::new(false),
                 reserved_names: Mutex::new(HashSet::new()),
+                entropy_seed,
+                root_seed: Mutex::new(None),
+                failpoints: Failpoints::default(),
+                observers: WriteObservers::default(),
+                read_only: false,
+                verify_on_read: AtomicBool::new(false),
+                temp_branches: AtomicU64::new(0),
+                names,
+                opened: Mutex::default(),
+                structures: Mutex::default(),
             }),
             name_prefix: String::new(),
+            temp: None,
         })
     }
 
+    /// Opens an existing Landfill for reading, while another process may
+    /// still be writing to it
+    ///
+    /// No lock is taken. Files are mapped copy-on-write and never written,
+    /// writes through the landfill fail with `PermissionDenied` where they
+    /// can fail. Structures see the data as of their newest journal entry
+    /// when opened, `refresh` catches them up with the writer.
+    ///
+    /// Files the writer shrank on `close` are mapped up to their end when
+    /// first read, data appended to them after the writer reopens needs a
+    /// new read-only landfill to be seen
+    pub fn open_read_only<P: AsRef<Path>>(dir_path: P) -> io::Result<Landfill> {
+        let dir_path: PathBuf = dir_path.as_ref().into();
+        if !dir_path.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No landfill at path",
+            ));
+        }
+
+        Ok(Landfill {
+            inner: Arc::new(LandfillInner {
+                dir_path: Some(dir_path),
+                self_destruct_sequence_initiated: Mutex::new(false),
+                reserved_names: Mutex::new(HashSet::new()),
+                entropy_seed: None,
+                root_seed: Mutex::new(None),
+                failpoints: Failpoints::default(),
+                observers: WriteObservers::default(),
+                read_only: true,
+                verify_on_read: AtomicBool::new(false),
+                temp_branches: AtomicU64::new(0),
+                names: Arc::default(),
+                opened: Mutex::default(),
+                structures: Mutex::default(),
+            }),
+            name_prefix: String::new(),
+            temp: None,
+        })
+    }
+
+    /// Returns true if the landfill was opened with `open_read_only`
+    pub fn is_read_only(&self) -> bool {
+        self.inner.read_only
+    }
+
+    // Fails with `PermissionDenied` if the landfill is read-only
+    pub(crate) fn check_writable(&self) -> io::Result<()> {
+        if self.inner.read_only {
+            Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "Landfill opened read-only",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
     /// Create a landfill backed by temporaray directories
     pub fn ephemeral() -> io::Result<Landfill> {
+        Self::ephemeral_inner(None)
+    }
+
+    /// Create an ephemeral landfill, deriving all entropy from `seed`
+    pub fn ephemeral_with_seed(seed: [u8; 32]) -> io::Result<Landfill> {
+        Self::ephemeral_inner(Some(seed))
+    }
+
+    fn ephemeral_inner(entropy_seed: Option<[u8; 32]>) -> io::Result<Landfill> {
         Ok(Landfill {
             inner: Arc::new(LandfillInner {
                 dir_path: None,
                 self_destruct_sequence_initiated: Mutex::new(false),
                 reserved_names: Mutex::new(HashSet::new()),
+                entropy_seed,
+                root_seed: Mutex::new(None),
+                failpoints: Failpoints::default(),
+                observers: WriteObservers::default(),
+                read_only: false,
+                verify_on_read: AtomicBool::new(false),
+                temp_branches: AtomicU64::new(0),
+                names: Arc::default(),
+                opened: Mutex::default(),
+                structures: Mutex::default(),
             }),
             name_prefix: String::new(),
+            temp: None,
         })
     }
 
+    /// Returns the root seed all entropy in this landfill is derived from
+    ///
+    /// The seed is stored in a single file, and created on first use from
+    /// either the seed the landfill was opened with, or random data
+    pub(crate) fn root_seed(&self) -> io::Result<[u8; 32]> {
+        let mut root_seed = self.inner.root_seed.lock();
+
+        if let Some(seed) = *root_seed {
+            return Ok(seed);
+        }
+
+        let root = Landfill {
+            inner: self.inner.clone(),
+            name_prefix: ROOT_SEED_NAME.into(),
+            temp: None,
+        };
+
+        let seed = root.get_static_or_init(|| {
+            self.inner
+                .entropy_seed
+                .unwrap_or_else(|| rand::thread_rng().gen())
+        })?;
+
+        *root_seed = Some(seed);
+        Ok(seed)
+    }
+
+    pub(crate) fn full_name_ref(&self) -> &str {
+        &self.name_prefix
+    }
+
     /// Create a substructure of type `S` with name `N` in the landfill
+    ///
+    /// Names must be non-empty, without path separators or control
+    /// characters, and must not start with `.` or `_`. Other names fail with
+    /// `InvalidInput`, `escape_name` turns any string into a valid name
     pub fn substructure<S, N>(&self, name: N) -> io::Result<S>
     where
         S: Substructure,
         N: Into<String>,
     {
-        let branch = self.branch(name.into());
+        self.substructure_with(name.into(), S::init)
+    }
 
-        if !self.register_name(branch.full_name()) {
+    /// Create a branch for scratch data
+    ///
+    /// The files of the branch are removed once the returned landfill, its
+    /// clones and all substructures created from them are dropped. Files
+    /// left behind by a crash are removed the next time the landfill is
+    /// opened
+    pub fn temp_branch(&self) -> io::Result<Landfill> {
+        self.check_writable()?;
+        let n = self.inner.temp_branches.fetch_add(1, Ordering::Relaxed);
+        let name = format!("{TEMP_PREFIX}{n}");
+
+        Ok(Landfill {
+            inner: self.inner.clone(),
+            name_prefix: name.clone(),
+            temp: Some(Arc::new(TempBranch {
+                inner: self.inner.clone(),
+                name,
+            })),
+        })
+    }
+
+    /// Create a substructure of type `S` with name `N`, configured with
+    /// `config`
+    ///
+    /// The config is persisted when the structure is created. If it already
+    /// exists, the stored config is used instead of `config`
+    pub fn substructure_with_config<S, N>(
+        &self,
+        name: N,
+        config: S::Config,
+    ) -> io::Result<S>
+    where
+        S: ConfigurableSubstructure,
+        N: Into<String>,
+    {
+        self.substructure_with(name.into(), |guarded| {
+            let config =
+                guarded.config_branch().get_static_or_init(|| config)?;
+            S::init_with_config(guarded, config)
+        })
+    }
+
+    // Create a substructure like `substructure`, initializing it with `init`
+    pub(crate) fn substructure_with<S, F>(
+        &self,
+        name: String,
+        init: F,
+    ) -> io::Result<S>
+    where
+        S: Substructure,
+        F: FnOnce(GuardedLandfill) -> io::Result<S>,
+    {
+        let mut guarded = self.guarded_branch(name.clone())?;
+        guarded.check_format_of::<S>()?;
+
+        let structure = init(guarded)?;
+        self.record_opened::<S>(name);
+        Ok(structure)
+    }
+
+    /// Check every structure opened from this landfill for corruption
+    ///
+    /// Each structure opened directly from the landfill, rather than from a
+    /// branch or another structure, is opened once more from a read-only view
+    /// of the files and verified. Problems are prefixed with the name of the
+    /// structure they were found in. Ephemeral landfills have no files to
+    /// reopen, and fail with `Unsupported`
+    pub fn verify(&self) -> io::Result<VerifyReport> {
+        let Some(dir_path) = self.inner.dir_path.clone() else {
             return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Attempt at mapping the same substructure twice",
+                io::ErrorKind::Unsupported,
+                "Ephemeral landfills can not be verified",
             ));
+        };
+
+        let view = Landfill {
+            inner: Arc::new(LandfillInner {
+                dir_path: Some(dir_path),
+                self_destruct_sequence_initiated: Mutex::new(false),
+                reserved_names: Mutex::new(HashSet::new()),
+                entropy_seed: None,
+                root_seed: Mutex::new(None),
+                failpoints: Failpoints::default(),
+                observers: WriteObservers::default(),
+                read_only: true,
+                verify_on_read: AtomicBool::new(false),
+                temp_branches: AtomicU64::new(0),
+                names: self.inner.names.clone(),
+                opened: Mutex::default(),
+                structures: Mutex::default(),
+            }),
+            name_prefix: String::new(),
+            temp: None,
+        };
+
+        let opened = self.inner.opened.lock().clone();
+        let mut reports = vec![];
+        for (name, verifier) in opened {
+            let mut report = verifier(&view, &name)?;
+            for problem in &mut report.problems {
+                *problem = format!("{name}: {problem}");
+            }
+            reports.push(report);
+        }
+        Ok(VerifyReport::merged(reports))
+    }
+
+    // Remember top-level structures, so `verify` can reach them
+    fn record_opened<S: Substructure>(&self, name: String) {
+        if self.name_prefix.is_empty() {
+            self.inner.opened.lock().push((name, verify_as::<S>));
         }
+    }
 
-        let guarded = GuardedLandfill { guarded: branch };
+    /// The config this branch was created with by `substructure_with_config`
+    ///
+    /// Returns `None` if it was created without one
+    pub fn stored_config<C: Pod>(&self) -> io::Result<Option<C>> {
+        let branch = self.config_branch();
+        if branch.file_exists() {
+            branch.get_static_or_init(C::zeroed).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
 
-        S::init(guarded)
+    fn config_branch(&self) -> Landfill {
+        let branch = self.branch("config".into());
+        branch.reserve_name();
+        branch
+    }
+
+    // Reserve the branch `name` for a new substructure
+    fn guarded_branch(&self, name: String) -> io::Result<GuardedLandfill> {
+        Self::check_name(&name)?;
+        let branch = self.branch(name);
+
+        if !self.register_name(branch.full_name()) {
+            return Err(io::Error::other(
+                "Attempt at mapping the same substructure twice",
+            ));
+        }
+        self.inner
+            .structures
+            .lock()
+            .insert(branch.full_name(), self.full_name());
+
+        Ok(GuardedLandfill {
+            guarded: branch,
+            format_version: None,
+        })
     }
 
     pub(crate) fn branch(&self, mut name: String) -> Self {
@@ -133,9 +704,123 @@ impl Landfill {
         Landfill {
             inner: self.inner.clone(),
             name_prefix: name,
+            temp: self.temp.clone(),
+        }
+    }
+
+    // Write the format header of this branch, or check the existing one,
+    // returning the version the data is stored in
+    fn check_format<S: Substructure>(
+        &self,
+        format: FormatHeader,
+    ) -> io::Result<u32> {
+        let header = self.branch("format".into());
+        header.reserve_name();
+
+        let invalid = |msg: String| {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {msg}", self.full_name()),
+            ))
+        };
+
+        // data without a header was written before format headers
+        let mut new = format;
+        if !header.file_exists() && self.holds_data(S::data_branches()) {
+            match S::legacy_version() {
+                Some(version) => new.version = version,
+                None => {
+                    return invalid(format!(
+                        "holds data written before format headers, but was \
+                         opened as {format}"
+                    ))
+                }
+            }
+        }
+
+        let stored = header.get_static_or_init(|| new)?;
+        if stored.magic != FORMAT_MAGIC {
+            return invalid("Invalid format header".into());
+        }
+        let same_kind = FormatHeader {
+            version: format.version,
+            ..stored
+        } == format;
+        if !same_kind || !S::reads_version(stored.version) {
+            return invalid(format!(
+                "holds {stored}, but was opened as {format}"
+            ));
+        }
+        Ok(stored.version)
+    }
+
+    /// Make `point` fail with `action` every time it is hit
+    ///
+    /// Applies to all structures in the landfill, until cleared
+    #[cfg(feature = "failpoints")]
+    pub fn set_failpoint(&self, point: Failpoint, action: FailAction) {
+        self.inner.failpoints.set(point, Some(action))
+    }
+
+    /// Stop `point` from failing
+    #[cfg(feature = "failpoints")]
+    pub fn clear_failpoint(&self, point: Failpoint) {
+        self.inner.failpoints.set(point, None)
+    }
+
+    /// Check checksums on every read of a journal entry or framed record
+    ///
+    /// Off by default. When on, `try_current` on journals and reads of
+    /// framed records fail with `InvalidData` on a checksum mismatch rather
+    /// than trusting data verified when it was first loaded. Reads that
+    /// cannot fail treat corrupted data as missing
+    pub fn set_verify_on_read(&self, on: bool) {
+        self.inner.verify_on_read.store(on, Ordering::Relaxed)
+    }
+
+    /// Returns true if reads are verified, see `set_verify_on_read`
+    pub fn verifies_reads(&self) -> bool {
+        self.inner.verify_on_read.load(Ordering::Relaxed)
+    }
+
+    /// Register a callback to run after every successful write
+    ///
+    /// It is called with the name of the branch written to, and the offset
+    /// and length of the written bytes within it. Writes through the
+    /// `AppendOnly`, `AppendLog`, `RandomAccess` and `Journal` layers are
+    /// reported, and so are the structures built on them and newly created
+    /// static files, as are updates of atomic cells. Callbacks run on the
+    /// writing thread and must not register further callbacks
+    pub fn on_write<F>(&self, observer: F)
+    where
+        F: Fn(&str, u64, u64) + Send + Sync + 'static,
+    {
+        self.add_observer(observer);
+    }
+
+    // Register a write observer, returning an id to remove it by
+    pub(crate) fn add_observer<F>(&self, observer: F) -> u64
+    where
+        F: Fn(&str, u64, u64) + Send + Sync + 'static,
+    {
+        let observers = &self.inner.observers;
+        let id = observers.next_id.fetch_add(1, Ordering::Relaxed);
+        observers.callbacks.write().push((id, Box::new(observer)));
+        id
+    }
+
+    // Report a write of `len` bytes at `offset` in this branch
+    pub(crate) fn notify_write(&self, offset: u64, len: u64) {
+        for (_, observer) in self.inner.observers.callbacks.read().iter() {
+            observer(&self.name_prefix, offset, len)
         }
     }
 
+    // Fails if a failpoint is set for `point`
+    pub(crate) fn failpoint(&self, point: Failpoint) -> io::Result<()> {
+        self.inner.failpoints.hit(point)
+    }
+
     /// Mark this landfill for destruction
     ///
     /// Data will be deleted as soon as the last reference to this landfill
@@ -148,44 +833,69 @@ impl Landfill {
         self.name_prefix.clone()
     }
 
-    fn active_path(&self) -> Option<PathBuf> {
-        self.inner.dir_path.as_ref().map(|path| {
-            let name = self.full_name();
-            let mut path = path.clone();
-            path.push(name);
-            path
-        })
-    }
-
     /// Reads a static file into type `T` if it exists
     ///
     /// Otherwise it calls the `init` closure to create and write a new
-    /// file containing the result.
+    /// file containing the result. Read-only landfills fail with `NotFound`
+    /// instead. Files holding a value of another size fail with
+    /// `InvalidData`.
     pub fn get_static_or_init<Init, T>(&self, init: Init) -> io::Result<T>
     where
         Init: Fn() -> T,
         T: Zeroable + Pod,
     {
-        if let Some(path) = self.active_path() {
-            if path.exists() {
-                let t = T::zeroed();
-                let t_slice = &mut [t];
-                let byte_slice: &mut [u8] = bytemuck::cast_slice_mut(t_slice);
+        self.get_static_or_init_tagged(0, init)
+    }
 
-                let mut file = OpenOptions::new().read(true).open(&path)?;
+    /// Like `get_static_or_init`, storing `tag` along with the value
+    ///
+    /// Tags tell apart types of the same size, reading a file with another
+    /// tag fails with `InvalidData`. Files written before tags were stored
+    /// are accepted under any tag
+    pub fn get_static_or_init_tagged<Init, T>(
+        &self,
+        tag: u64,
+        init: Init,
+    ) -> io::Result<T>
+    where
+        Init: Fn() -> T,
+        T: Zeroable + Pod,
+    {
+        if let Some(path) = self.active_path()? {
+            if path.exists() {
+                let mut bytes = vec![];
+                OpenOptions::new()
+                    .read(true)
+                    .open(&path)?
+                    .read_to_end(&mut bytes)?;
 
-                file.read_exact(byte_slice)?;
-                Ok(t)
+                let value = self.static_value::<T>(&bytes, tag)?;
+                Ok(bytemuck::pod_read_unaligned(value))
+            } else if self.inner.read_only {
+                Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} does not exist", self.full_name()),
+                ))
             } else {
                 let t = init();
-                let t_slice = &[t];
-                let byte_slice: &[u8] = bytemuck::cast_slice(t_slice);
+                let header = StaticHeader {
+                    magic: STATIC_MAGIC,
+                    size: std::mem::size_of::<T>() as u32,
+                    tag,
+                };
+
+                let mut bytes = bytemuck::bytes_of(&header).to_vec();
+                bytes.extend_from_slice(bytemuck::bytes_of(&t));
 
-                let mut file =
-                    OpenOptions::new().write(true).create(true).open(&path)?;
+                let mut file = OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(&path)?;
 
-                file.write_all(byte_slice)?;
+                file.write_all(&bytes)?;
                 file.flush()?;
+                self.notify_write(0, bytes.len() as u64);
                 Ok(t)
             }
         } else {
@@ -194,6 +904,279 @@ impl Landfill {
         }
     }
 
+    // The bytes of the `T` stored in a static file, after checking that
+    // the file holds one
+    fn static_value<'a, T>(
+        &self,
+        bytes: &'a [u8],
+        tag: u64,
+    ) -> io::Result<&'a [u8]>
+    where
+        T: Pod,
+    {
+        let size = std::mem::size_of::<T>();
+        let mismatch = |msg: String| {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Static file {}: {msg}", self.full_name()),
+            ))
+        };
+
+        let header: Option<StaticHeader> = bytes
+            .get(..STATIC_HEADER_SIZE)
+            .map(bytemuck::pod_read_unaligned)
+            .filter(|header: &StaticHeader| {
+                header.magic == STATIC_MAGIC
+                    && header.size as usize == bytes.len() - STATIC_HEADER_SIZE
+            });
+
+        let Some(header) = header else {
+            // files written before static headers hold just the value
+            if bytes.len() == size {
+                return Ok(bytes);
+            }
+            return mismatch(format!(
+                "holds {} bytes without a header, but was read as {size}",
+                bytes.len()
+            ));
+        };
+
+        if header.size as usize != size {
+            return mismatch(format!(
+                "holds {} bytes, but was read as {size}",
+                header.size
+            ));
+        }
+        if header.tag != tag {
+            return mismatch(format!(
+                "holds tag {}, but was read as {tag}",
+                header.tag
+            ));
+        }
+        Ok(&bytes[STATIC_HEADER_SIZE..])
+    }
+
+    /// Remove the files of the structure stored directly in this branch
+    ///
+    /// That is the file named after the branch, its lanes and its format
+    /// and config headers. Branches whose names merely start with the same
+    /// characters are left alone, so nested substructures have to be
+    /// removed through their own branches. Any mappings of these files must
+    /// already have been dropped
+    pub(crate) fn remove_files(&self) -> io::Result<()> {
+        let name = self.full_name();
+        let own: HashSet<String> = (0..N_LANES)
+            .map(|lane| format!("{lane:02x}"))
+            .chain(["format".into(), "config".into()])
+            .map(|suffix| format!("{name}_{suffix}"))
+            .chain([name.clone()])
+            .collect();
+
+        self.remove_matching(|file_name| own.contains(file_name))
+    }
+
+    /// Remove the files of this branch and of everything nested inside it
+    ///
+    /// Matches file names by prefix, so only use this for branches no
+    /// sibling name can start with
+    pub(crate) fn remove_tree(&self) -> io::Result<()> {
+        let name = self.full_name();
+        let prefix = format!("{name}_");
+
+        self.remove_matching(|file_name| {
+            file_name == name || file_name.starts_with(&prefix)
+        })
+    }
+
+    // Remove the files whose branch names match
+    fn remove_matching<F>(&self, matches: F) -> io::Result<()>
+    where
+        F: Fn(&str) -> bool,
+    {
+        if self.inner.read_only {
+            return Ok(());
+        }
+        if let Some(dir_path) = self.inner.dir_path.as_ref() {
+            for entry in fs::read_dir(dir_path)? {
+                let entry = entry?;
+                let file_name = entry.file_name();
+                let file_name = self
+                    .inner
+                    .names
+                    .branch_name(dir_path, &file_name.to_string_lossy())?;
+
+                if matches(&file_name) {
+                    fs::remove_file(entry.path())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the total size of the files belonging to this branch
+    ///
+    /// These are the files of the branch itself, and of the structures
+    /// opened within it so far. Ephemeral landfills are not backed by files,
+    /// and always return 0
+    pub fn size_on_disk(&self) -> u64 {
+        let name = self.full_name();
+        let reserved = self.inner.reserved_names.lock().clone();
+        self.files_matching(|file_name| {
+            // the root landfill owns every file but the lock
+            if name.is_empty() {
+                file_name != "_lock"
+            } else {
+                file_name == name
+                    || (reserved.contains(file_name) && self.owns(file_name))
+            }
+        })
+        .map(|files| files.iter().map(|(_, len)| len).sum())
+        .unwrap_or(0)
+    }
+
+    // Returns true if the branch `name` belongs to a structure opened within
+    // this branch, rather than to a sibling whose name starts with its own
+    //
+    // Each branch belongs to the structure with the longest name that holds
+    // it, which belongs to the branch it was opened from
+    fn owns(&self, name: &str) -> bool {
+        let own = self.full_name();
+        let structures = self.inner.structures.lock();
+        let mut branch = name;
+        loop {
+            if branch == own {
+                return true;
+            }
+            let holder = structures
+                .iter()
+                .filter(|(structure, _)| is_within(branch, structure))
+                .max_by_key(|(structure, _)| structure.len());
+            match holder {
+                Some((structure, _)) if *structure == own => return true,
+                Some((_, parent)) if parent.len() < branch.len() => {
+                    branch = parent
+                }
+                _ => return false,
+            }
+        }
+    }
+
+    // Returns true if the branch has a non-empty file of its own, or one
+    // within `branches`, or within any branch below it for `None`
+    fn holds_data(&self, branches: Option<&[&str]>) -> bool {
+        let name = self.full_name();
+        let prefix = format!("{name}_");
+        self.files_matching(|file_name| {
+            let Some(rest) = file_name.strip_prefix(&prefix) else {
+                return file_name == name;
+            };
+            branches.is_none_or(|branches| {
+                branches.iter().any(|branch| is_within(rest, branch))
+            })
+        })
+        .is_ok_and(|files| files.iter().any(|(_, len)| *len > 0))
+    }
+
+    // The branch names and sizes of the files in the directory for which
+    // `matches` returns true
+    fn files_matching<F>(&self, matches: F) -> io::Result<Vec<(String, u64)>>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let Some(dir_path) = self.inner.dir_path.as_ref() else {
+            return Ok(vec![]);
+        };
+
+        let mut files = vec![];
+        for entry in fs::read_dir(dir_path)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            let file_name = self
+                .inner
+                .names
+                .branch_name(dir_path, &file_name)
+                .unwrap_or_else(|_| file_name.into_owned());
+            if matches(&file_name) {
+                files.push((file_name, entry.metadata()?.len()));
+            }
+        }
+        Ok(files)
+    }
+
+    /// Reserve the name of this branch, without mapping any file
+    ///
+    /// Returns false if the name was already reserved
+    pub(crate) fn reserve_name(&self) -> bool {
+        self.register_name(self.full_name())
+    }
+
+    /// Returns true if a file for this branch exists on disk
+    pub(crate) fn file_exists(&self) -> bool {
+        self.active_path()
+            .ok()
+            .flatten()
+            .map(|path| path.exists())
+            .unwrap_or(false)
+    }
+
+    /// Create an empty marker file for this branch
+    pub(crate) fn create_marker(&self) -> io::Result<()> {
+        if self.inner.read_only {
+            return Ok(());
+        }
+        if let Some(path) = self.active_path()? {
+            File::create(path)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Remove the marker file of this branch
+    ///
+    /// Returns true if the marker existed. Read-only landfills leave it be
+    pub(crate) fn take_marker(&self) -> io::Result<bool> {
+        if self.inner.read_only {
+            return Ok(self.file_exists());
+        }
+        match self.active_path()? {
+            Some(path) => match fs::remove_file(path) {
+                Ok(()) => Ok(true),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+                Err(e) => Err(e),
+            },
+            None => Ok(false),
+        }
+    }
+
+    /// Shrink the file of this branch to `len` bytes, if it exists
+    ///
+    /// Any mappings of the file must already have been dropped
+    pub(crate) fn truncate_file(&self, len: u64) -> io::Result<()> {
+        if self.inner.read_only {
+            return Ok(());
+        }
+        if let Some(path) = self.active_path()? {
+            match OpenOptions::new().write(true).open(path) {
+                Ok(file) => file.set_len(len)?,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Open the file of this branch for reading, without mapping it
+    pub(crate) fn open_file_read(&self) -> io::Result<Option<File>> {
+        match self.active_path()? {
+            Some(path) => match OpenOptions::new().read(true).open(path) {
+                Ok(file) => Ok(Some(file)),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e),
+            },
+            None => Ok(None),
+        }
+    }
+
     fn register_name(&self, name: String) -> bool {
         let mut names = self.inner.reserved_names.lock();
 
@@ -204,12 +1187,18 @@ impl Landfill {
     ///
     /// Returns `None` if the file has already been mapped
     pub fn map_file_create(&self, size: u64) -> io::Result<Option<MappedFile>> {
+        // the whole file is mapped, so it has to fit in the address space
+        let map_size = helpers::usize_from(size)?;
+
         if !self.register_name(self.full_name()) {
-            if let Some(path) = self.active_path() {
+            if self.inner.read_only {
+                self.map_read_only(map_size).map(Some)
+            } else if let Some(path) = self.active_path()? {
                 let file = OpenOptions::new()
                     .read(true)
                     .write(true)
                     .create(true)
+                    .truncate(false)
                     .open(path)?;
 
                 file.set_len(size)?;
@@ -222,7 +1211,7 @@ impl Landfill {
                     _fill: self.clone(),
                 }))
             } else {
-                let map = UnsafeCell::new(MmapMut::map_anon(size as usize)?);
+                let map = UnsafeCell::new(MmapMut::map_anon(map_size)?);
 
                 Ok(Some(MappedFile {
                     _file: None,
@@ -241,12 +1230,22 @@ impl Landfill {
         &self,
         size: u64,
     ) -> io::Result<Option<MappedFile>> {
+        helpers::usize_from(size)?;
+
         let full_name = self.full_name();
         if !self.register_name(full_name) {
             return Ok(None);
         }
 
-        if let Some(path) = self.active_path() {
+        if self.inner.read_only {
+            return match self.map_read_only(helpers::usize_from(size)?) {
+                Ok(mapped) => Ok(Some(mapped)),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e),
+            };
+        }
+
+        if let Some(path) = self.active_path()? {
             if path.exists() {
                 match OpenOptions::new().read(true).write(true).open(&path) {
                     Ok(file) => {
@@ -272,6 +1271,27 @@ impl Landfill {
         }
     }
 
+    // Map the existing file of this branch copy-on-write, leaving the file
+    // untouched
+    //
+    // The file may be shorter than `size` if the writer shrank it on close,
+    // in which case only the bytes up to its end are mapped, since touching
+    // pages past the end of a file faults
+    fn map_read_only(&self, size: usize) -> io::Result<MappedFile> {
+        let path = self.active_path()?.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "Landfill is ephemeral")
+        })?;
+        let file = OpenOptions::new().read(true).open(path)?;
+        let len = size.min(helpers::usize_from(file.metadata()?.len())?);
+        let map = unsafe { MmapOptions::new().len(len).map_copy(&file)? };
+
+        Ok(MappedFile {
+            _file: Some(file),
+            map: UnsafeCell::new(map),
+            _fill: self.clone(),
+        })
+    }
+
     /// This function will remove all data written into this landfill as the last
     /// reference goes out of scope
     pub fn self_destruct(&self) {
@@ -281,6 +1301,10 @@ impl Landfill {
 
 impl Drop for LandfillInner {
     fn drop(&mut self) {
+        if self.read_only {
+            // the lock and the data belong to the writer
+            return;
+        }
         if let Some(dir_path) = self.dir_path.as_ref() {
             // non-volatile paths comes with with lockfiles
             let mut lock_file_path = dir_path.clone();
@@ -295,6 +1319,20 @@ impl Drop for LandfillInner {
     }
 }
 
+fn cast_error(e: bytemuck::PodCastError) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("Cannot view mapping as values: {e:?}"),
+    )
+}
+
+// Returns true if `branch` is `outer` or a branch below it
+fn is_within(branch: &str, outer: &str) -> bool {
+    branch
+        .strip_prefix(outer)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('_'))
+}
+
 /// A file with a corresponding memory map of the entire contents of the file
 pub struct MappedFile {
     map: UnsafeCell<MmapMut>,
@@ -304,7 +1342,7 @@ pub struct MappedFile {
 
 impl AsRef<[u8]> for MappedFile {
     fn as_ref(&self) -> &[u8] {
-        unsafe { &(*self.map.get())[..] }
+        unsafe { &(&*self.map.get())[..] }
     }
 }
 
@@ -318,8 +1356,88 @@ impl MappedFile {
         unsafe { &mut *self.map.get() }
     }
 
+    /// A pointer to the first mapped byte
+    ///
+    /// Taken without creating a reference to the bytes, so it may be used
+    /// for atomics shared between threads
+    pub(crate) fn as_ptr(&self) -> *const u8 {
+        unsafe { (*self.map.get()).as_ptr() }
+    }
+
+    /// The length of the mapping in bytes
+    pub fn len(&self) -> usize {
+        self.as_ref().len()
+    }
+
+    /// Returns true if the mapping is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// View the mapped bytes as a slice of `T`
+    ///
+    /// Trailing bytes that do not make up a whole `T` are left out. Fails
+    /// with `InvalidInput` for zero-sized or misaligned `T`
+    pub fn as_slice_of<T: Pod>(&self) -> io::Result<&[T]> {
+        let len = Self::whole_elements::<T>(self.len())?;
+        bytemuck::try_cast_slice(&self.as_ref()[..len]).map_err(cast_error)
+    }
+
+    /// View the mapped bytes as a mutable slice of `T`
+    ///
+    /// See `as_slice_of`
+    ///
+    /// # Safety
+    /// You must manually guarantee that this slice never aliases
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn as_mut_slice_of<T: Pod>(&self) -> io::Result<&mut [T]> {
+        let len = Self::whole_elements::<T>(self.len())?;
+        let bytes = unsafe { &mut self.bytes_mut()[..len] };
+        bytemuck::try_cast_slice_mut(bytes).map_err(cast_error)
+    }
+
+    // The number of bytes making up whole elements of `T`
+    fn whole_elements<T>(len: usize) -> io::Result<usize> {
+        match std::mem::size_of::<T>() {
+            0 => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Cannot view a mapping as zero-sized values",
+            )),
+            size => Ok(len / size * size),
+        }
+    }
+
+    /// The landfill branch the file belongs to
+    pub(crate) fn landfill(&self) -> &Landfill {
+        &self._fill
+    }
+
     /// Flushes the file to the backing disk, blocks until done
     pub fn flush(&self) -> io::Result<()> {
         unsafe { (*self.map.get()).flush() }
     }
+
+    /// Hint that `len` bytes starting at `offset` will be read soon
+    ///
+    /// Only has an effect on unix
+    pub fn prefetch(&self, offset: usize, len: usize) -> io::Result<()> {
+        #[cfg(unix)]
+        unsafe {
+            (*self.map.get()).advise_range(
+                memmap2::Advice::WillNeed,
+                offset,
+                len,
+            )
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = (offset, len);
+            Ok(())
+        }
+    }
+
+    /// Flushes `len` bytes starting at `offset`, blocks until done
+    pub fn flush_range(&self, offset: usize, len: usize) -> io::Result<()> {
+        unsafe { (*self.map.get()).flush_range(offset, len) }
+    }
 }
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use parking_lot::{MappedMutexGuard, Mutex, MutexGuard};
+
+use super::Landfill;
+
+// file mapping hashed file names to the branches they belong to
+pub(super) const NAMES_FILE: &str = "_names";
+// prefix of hashed file names
+const HASHED_PREFIX: &str = "_h";
+
+// The hashed names read from the names file
+#[derive(Debug, Default)]
+struct Hashed {
+    // hashed file names by branch name
+    files: HashMap<String, String>,
+    // length of the complete lines of the names file
+    len: u64,
+}
+
+// Maps branch names to the names of their files
+#[derive(Debug, Default)]
+pub(super) struct FileNames {
+    // longest branch name used as a file name as is, 0 for no limit
+    limit: AtomicUsize,
+    // loaded on first use
+    hashed: Mutex<Option<Hashed>>,
+}
+
+impl FileNames {
+    fn loaded(&self, dir: &Path) -> io::Result<MappedMutexGuard<'_, Hashed>> {
+        let mut hashed = self.hashed.lock();
+        if hashed.is_none() {
+            *hashed = Some(load(dir)?);
+        }
+        Ok(MutexGuard::map(hashed, |hashed| {
+            hashed.get_or_insert_with(Hashed::default)
+        }))
+    }
+
+    // Forget the loaded names, to pick up names written by someone else
+    pub(super) fn reload(&self) {
+        *self.hashed.lock() = None;
+    }
+
+    // The name of the file of `branch`, if it was hashed
+    pub(super) fn lookup(
+        &self,
+        dir: &Path,
+        branch: &str,
+    ) -> io::Result<Option<String>> {
+        Ok(self.loaded(dir)?.files.get(branch).cloned())
+    }
+
+    // The name of the file of `branch`
+    //
+    // Names over the limit are hashed and appended to the names file, unless
+    // `read_only`. Returns the offset and length of the appended line along
+    // with the name
+    fn file_name(
+        &self,
+        dir: &Path,
+        branch: &str,
+        read_only: bool,
+    ) -> io::Result<(String, Option<(u64, u64)>)> {
+        let mut hashed = self.loaded(dir)?;
+        if let Some(file) = hashed.files.get(branch) {
+            return Ok((file.clone(), None));
+        }
+
+        let limit = self.limit.load(Ordering::Relaxed);
+        if read_only {
+            // the writer may have hashed the name since the names were read
+            let len = fs::metadata(dir.join(NAMES_FILE))
+                .map(|metadata| metadata.len())
+                .unwrap_or(0);
+            if len != hashed.len {
+                *hashed = load(dir)?;
+                if let Some(file) = hashed.files.get(branch) {
+                    return Ok((file.clone(), None));
+                }
+            }
+        }
+        // files created before the limit was set keep their names
+        if limit == 0 || branch.len() <= limit || dir.join(branch).exists() {
+            return Ok((branch.into(), None));
+        }
+
+        let file = hashed_name(branch);
+        if read_only {
+            return Ok((file, None));
+        }
+        if hashed.files.values().any(|other| *other == file) {
+            return Err(io::Error::other(format!(
+                "Hashed file name {file} of {branch} is already taken"
+            )));
+        }
+
+        let line = format!("{file} {branch}\n");
+        let mut names = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(NAMES_FILE))?;
+        // drop a line cut short by a crash
+        let offset = hashed.len;
+        names.set_len(offset)?;
+        names.write_all(line.as_bytes())?;
+        names.sync_all()?;
+
+        hashed.files.insert(branch.into(), file.clone());
+        hashed.len = offset + line.len() as u64;
+        Ok((file, Some((offset, line.len() as u64))))
+    }
+
+    // The name of the branch `file` belongs to
+    pub(super) fn branch_name(
+        &self,
+        dir: &Path,
+        file: &str,
+    ) -> io::Result<String> {
+        if !file.starts_with(HASHED_PREFIX) {
+            return Ok(file.into());
+        }
+        let hashed = self.loaded(dir)?;
+        Ok(hashed
+            .files
+            .iter()
+            .find(|(_, hashed)| *hashed == file)
+            .map(|(branch, _)| branch.clone())
+            .unwrap_or_else(|| file.into()))
+    }
+}
+
+fn hashed_name(branch: &str) -> String {
+    format!("{HASHED_PREFIX}{:016x}", seahash::hash(branch.as_bytes()))
+}
+
+fn load(dir: &Path) -> io::Result<Hashed> {
+    let bytes = match fs::read(dir.join(NAMES_FILE)) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Ok(Hashed::default())
+        }
+        Err(e) => return Err(e),
+    };
+    let invalid =
+        || io::Error::new(io::ErrorKind::InvalidData, "Invalid names file");
+
+    // a line cut short by a crash is hashed again on next use
+    let complete = match bytes.iter().rposition(|b| *b == b'\n') {
+        Some(end) => &bytes[..=end],
+        None => &[],
+    };
+    let text = std::str::from_utf8(complete).map_err(|_| invalid())?;
+
+    let mut files = HashMap::new();
+    for line in text.lines() {
+        let (file, branch) = line.split_once(' ').ok_or_else(invalid)?;
+        files.insert(branch.into(), file.into());
+    }
+
+    Ok(Hashed {
+        files,
+        len: complete.len() as u64,
+    })
+}
+
+// Returns true if `c` at `index` of a substructure name must be escaped
+fn needs_escape(index: usize, c: char) -> bool {
+    matches!(c, '/' | '\\' | '%')
+        || c.is_control()
+        || (index == 0 && matches!(c, '.' | '_'))
+}
+
+impl Landfill {
+    /// Escape `name` into a valid substructure name
+    ///
+    /// Characters that are not allowed are replaced with `%` and the hex
+    /// codes of their bytes, and so is `%` itself. The empty name becomes
+    /// `%`. Distinct names stay distinct
+    pub fn escape_name(name: &str) -> String {
+        if name.is_empty() {
+            return "%".into();
+        }
+        let mut escaped = String::with_capacity(name.len());
+        for (index, c) in name.char_indices() {
+            if needs_escape(index, c) {
+                let mut buf = [0; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    escaped.push_str(&format!("%{b:02x}"));
+                }
+            } else {
+                escaped.push(c);
+            }
+        }
+        escaped
+    }
+
+    // Fails with `InvalidInput` unless `name` is safe to use in file names
+    //
+    // Names must be non-empty, without path separators or control
+    // characters, and must not start with `.` or `_`, which are reserved
+    // for the files of the landfill itself
+    pub(super) fn check_name(name: &str) -> io::Result<()> {
+        let reason = if name.is_empty() {
+            "empty"
+        } else if name.contains(['/', '\\']) {
+            "contains a path separator"
+        } else if name.contains(|c: char| c.is_control()) {
+            "contains a control character"
+        } else if name.starts_with(['.', '_']) {
+            "starts with a reserved character"
+        } else {
+            return Ok(());
+        };
+
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid substructure name {name:?}: {reason}"),
+        ))
+    }
+
+    /// Store branches with names longer than `len` bytes in files with
+    /// short hashed names
+    ///
+    /// Deeply nested branches get file names that may exceed the limits of
+    /// the filesystem, commonly 255 bytes. Hashed names are 18 bytes long,
+    /// and recorded in the `_names` file of the landfill. A branch keeps the
+    /// file it was created with, hashed or not, whatever the limit is when
+    /// it is opened again. `None` turns hashing off
+    pub fn set_name_limit(&self, len: Option<usize>) {
+        self.inner
+            .names
+            .limit
+            .store(len.unwrap_or(0), Ordering::Relaxed)
+    }
+
+    // The path of the file of this branch, `None` if ephemeral
+    pub(super) fn active_path(&self) -> io::Result<Option<PathBuf>> {
+        let Some(dir_path) = self.inner.dir_path.as_ref() else {
+            return Ok(None);
+        };
+        let (file, appended) = self.inner.names.file_name(
+            dir_path,
+            &self.name_prefix,
+            self.inner.read_only,
+        )?;
+
+        if let Some((offset, len)) = appended {
+            let names = Landfill {
+                inner: self.inner.clone(),
+                name_prefix: NAMES_FILE.into(),
+                temp: None,
+            };
+            names.notify_write(offset, len);
+        }
+        Ok(Some(dir_path.join(file)))
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::ops::Deref;
+use std::panic;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+
+use parking_lot::{Condvar, Mutex};
+
+use super::{GuardedLandfill, Landfill, Substructure};
+
+/// A datastructure that can be constructed and flushed asynchronously
+///
+/// Implemented by `AsyncHandle` for every `Substructure`, and open to
+/// structures with async-native initialization of their own
+pub trait AsyncSubstructure: Sized {
+    /// Initialize a datastructure of this type, backed by `landfill`
+    fn init(
+        landfill: GuardedLandfill,
+    ) -> impl Future<Output = io::Result<Self>> + Send;
+    /// Flush all data to disk
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send;
+}
+
+/// A handle to a substructure for use from async code
+///
+/// Opening, flushing and closing run on a bounded pool of worker threads,
+/// so they never block the executor. Reads and writes of mapped data do not
+/// block on io, and are available directly through `Deref`. Anything else
+/// that might block can be moved off the executor with `run`
+pub struct AsyncHandle<S>(Arc<S>);
+
+impl<S> Clone for AsyncHandle<S> {
+    fn clone(&self) -> Self {
+        AsyncHandle(self.0.clone())
+    }
+}
+
+impl<S> Deref for AsyncHandle<S> {
+    type Target = S;
+    fn deref(&self) -> &S {
+        &self.0
+    }
+}
+
+impl<S> AsyncHandle<S>
+where
+    S: Substructure + Send + Sync + 'static,
+{
+    /// Run `f` with the structure on a worker thread
+    ///
+    /// Dropping the returned future before `f` has started cancels it
+    pub async fn run<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&S) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let structure = self.0.clone();
+        blocking(move || f(&structure)).await
+    }
+
+    /// Close the structure, see `Substructure::close`
+    ///
+    /// Fails if other clones of the handle are still alive
+    pub async fn close(self) -> io::Result<()> {
+        match Arc::try_unwrap(self.0) {
+            Ok(structure) => blocking(move || structure.close()).await,
+            Err(_) => Err(io::Error::other(
+                "Cannot close a structure with other handles alive",
+            )),
+        }
+    }
+
+    /// Unwrap the structure, if this is the only handle to it
+    pub fn try_unwrap(self) -> Result<S, Self> {
+        Arc::try_unwrap(self.0).map_err(AsyncHandle)
+    }
+}
+
+impl<S> AsyncSubstructure for AsyncHandle<S>
+where
+    S: Substructure + Send + Sync + 'static,
+{
+    fn init(
+        landfill: GuardedLandfill,
+    ) -> impl Future<Output = io::Result<Self>> + Send {
+        let opened = blocking(move || {
+            let mut landfill = landfill;
+            landfill.check_format_of::<S>()?;
+            S::init(landfill)
+        });
+        async move { Ok(AsyncHandle(Arc::new(opened.await?))) }
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send {
+        let structure = self.0.clone();
+        blocking(move || structure.flush())
+    }
+}
+
+impl Landfill {
+    /// Create a substructure of type `S` with name `N` asynchronously
+    ///
+    /// Use `AsyncHandle<S>` to open any `Substructure` without blocking
+    pub async fn substructure_async<S, N>(&self, name: N) -> io::Result<S>
+    where
+        S: AsyncSubstructure,
+        N: Into<String>,
+    {
+        let branch = self.guarded_branch(name.into())?;
+        S::init(branch).await
+    }
+}
+
+// upper bound on the threads running blocking work
+const MAX_WORKERS: usize = 16;
+// idle workers exit after this long without work
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+type Job = Box<dyn FnOnce() + Send>;
+
+#[derive(Default)]
+struct PoolState {
+    jobs: VecDeque<Job>,
+    workers: usize,
+    idle: usize,
+}
+
+// The threads running blocking work for all landfills in the process
+#[derive(Default)]
+struct Pool {
+    state: Mutex<PoolState>,
+    available: Condvar,
+}
+
+fn pool() -> &'static Pool {
+    static POOL: OnceLock<Pool> = OnceLock::new();
+    POOL.get_or_init(Pool::default)
+}
+
+impl Pool {
+    // Queue `job`, starting another worker unless enough are idle
+    fn submit(&'static self, job: Job) {
+        let mut state = self.state.lock();
+        state.jobs.push_back(job);
+
+        if state.jobs.len() > state.idle && state.workers < MAX_WORKERS {
+            state.workers += 1;
+            thread::spawn(move || self.work());
+        } else {
+            self.available.notify_one();
+        }
+    }
+
+    fn work(&self) {
+        let mut state = self.state.lock();
+        loop {
+            if let Some(job) = state.jobs.pop_front() {
+                drop(state);
+                job();
+                state = self.state.lock();
+                continue;
+            }
+
+            state.idle += 1;
+            let timeout = self.available.wait_for(&mut state, IDLE_TIMEOUT);
+            state.idle -= 1;
+
+            if timeout.timed_out() && state.jobs.is_empty() {
+                state.workers -= 1;
+                return;
+            }
+        }
+    }
+}
+
+struct BlockingState<R> {
+    result: Option<thread::Result<R>>,
+    waker: Option<Waker>,
+    // set when the future is dropped, so a queued closure is skipped
+    cancelled: bool,
+}
+
+// A future resolving to the result of a closure run on the worker pool
+//
+// Dropping it before the closure has started cancels the closure, a
+// closure that is already running finishes in the background
+struct Blocking<R>(Arc<Mutex<BlockingState<R>>>);
+
+fn blocking<F, R>(f: F) -> Blocking<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let state = Arc::new(Mutex::new(BlockingState {
+        result: None,
+        waker: None,
+        cancelled: false,
+    }));
+
+    let shared = state.clone();
+    pool().submit(Box::new(move || {
+        if shared.lock().cancelled {
+            return;
+        }
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(f));
+        let mut state = shared.lock();
+        state.result = Some(result);
+        if let Some(waker) = state.waker.take() {
+            waker.wake()
+        }
+    }));
+
+    Blocking(state)
+}
+
+impl<R> Future for Blocking<R> {
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<R> {
+        let mut state = self.0.lock();
+        match state.result.take() {
+            Some(Ok(res)) => Poll::Ready(res),
+            // propagate panics to the awaiting task
+            Some(Err(panic)) => panic::resume_unwind(panic),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<R> Drop for Blocking<R> {
+    fn drop(&mut self) {
+        self.0.lock().cancelled = true;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn bounded_and_cancellable() {
+        // occupy every worker until released
+        let (release, released) = mpsc::channel::<()>();
+        let released = Arc::new(Mutex::new(released));
+        let busy: Vec<_> = (0..MAX_WORKERS)
+            .map(|_| {
+                let released = released.clone();
+                blocking(move || {
+                    let _ = released.lock().recv();
+                })
+            })
+            .collect();
+        assert!(pool().state.lock().workers <= MAX_WORKERS);
+
+        let ran = Arc::new(AtomicBool::new(false));
+        let flag = ran.clone();
+        let cancelled = blocking(move || flag.store(true, Ordering::SeqCst));
+        drop(cancelled);
+
+        let done = blocking(|| 5);
+        drop(release);
+        drop(busy);
+
+        let mut done = std::pin::pin!(done);
+        let mut cx = Context::from_waker(Waker::noop());
+        let five = loop {
+            if let Poll::Ready(five) = done.as_mut().poll(&mut cx) {
+                break five;
+            }
+            thread::yield_now();
+        };
+        assert_eq!(five, 5);
+        assert!(!ran.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::hash_map::{Entry, HashMap};
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+
+use parking_lot::Mutex;
+
+use super::names::{FileNames, NAMES_FILE};
+use super::{Landfill, LandfillInner};
+use crate::storage::DiskBytes;
+
+// A range of a branch written on the leader, not yet shipped
+struct Change {
+    branch: String,
+    offset: u64,
+    len: u64,
+}
+
+/// A change to a single file of a landfill, shipped from leader to follower
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Delta {
+    file: String,
+    offset: u64,
+    bytes: Vec<u8>,
+}
+
+impl Delta {
+    /// The name of the changed file
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    /// The offset of the change in the file
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The bytes written at the offset
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Encode the delta for shipping
+    pub fn write_to<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let name_len = u16::try_from(self.file.len()).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "File name too long")
+        })?;
+        let len = u32::try_from(self.bytes.len()).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Delta too large")
+        })?;
+
+        w.write_all(&name_len.to_le_bytes())?;
+        w.write_all(self.file.as_bytes())?;
+        w.write_all(&self.offset.to_le_bytes())?;
+        w.write_all(&len.to_le_bytes())?;
+        w.write_all(&self.bytes)
+    }
+
+    /// Decode a delta encoded with `write_to`
+    ///
+    /// Returns `None` if the input ends before the next delta
+    pub fn read_from<R: Read>(mut r: R) -> io::Result<Option<Delta>> {
+        let mut name_len = [0; 2];
+        match r.read_exact(&mut name_len) {
+            Ok(()) => (),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                return Ok(None)
+            }
+            Err(e) => return Err(e),
+        }
+
+        let mut file = vec![0; u16::from_le_bytes(name_len) as usize];
+        r.read_exact(&mut file)?;
+        let file = String::from_utf8(file).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, "Invalid file name")
+        })?;
+
+        let mut offset = [0; 8];
+        r.read_exact(&mut offset)?;
+        let mut len = [0; 4];
+        r.read_exact(&mut len)?;
+
+        let mut bytes = vec![0; u32::from_le_bytes(len) as usize];
+        r.read_exact(&mut bytes)?;
+
+        Ok(Some(Delta {
+            file,
+            offset: u64::from_le_bytes(offset),
+            bytes,
+        }))
+    }
+}
+
+/// Records the writes to a landfill, to ship them to a follower
+///
+/// Writes reported through `Landfill::on_write` are queued as ranges, and
+/// `ship` reads their current bytes from the leader's files. Markers of
+/// clean shutdowns are not shipped, so a follower recovers as if the leader
+/// had crashed
+pub struct ChangeLog {
+    dir_path: PathBuf,
+    names: Arc<FileNames>,
+    pending: Arc<Mutex<VecDeque<Change>>>,
+    // the landfill observed, to stop observing it once dropped
+    landfill: Weak<LandfillInner>,
+    observer: u64,
+}
+
+impl Drop for ChangeLog {
+    fn drop(&mut self) {
+        if let Some(inner) = self.landfill.upgrade() {
+            inner
+                .observers
+                .callbacks
+                .write()
+                .retain(|(id, _)| *id != self.observer);
+        }
+    }
+}
+
+impl ChangeLog {
+    /// Start recording the writes to `landfill`
+    ///
+    /// Files already in the landfill are queued in full, so the first `ship`
+    /// brings an empty follower up to date. Recording stops when the change
+    /// log is dropped
+    pub fn attach(landfill: &Landfill) -> io::Result<ChangeLog> {
+        let dir_path = landfill.inner.dir_path.clone().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Ephemeral landfills cannot be replicated",
+            )
+        })?;
+        let pending = Arc::new(Mutex::new(VecDeque::new()));
+
+        // the observer must not keep the landfill alive
+        let queue = pending.clone();
+        let observer = landfill.add_observer(move |branch, offset, len| {
+            push_change(&mut queue.lock(), branch, offset, len)
+        });
+
+        let mut existing = vec![];
+        for entry in fs::read_dir(&dir_path)? {
+            let entry = entry?;
+            let len = entry.metadata()?.len();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name != "_lock" && len > 0 {
+                existing.push(Change {
+                    branch: landfill
+                        .inner
+                        .names
+                        .branch_name(&dir_path, &name)?,
+                    offset: 0,
+                    len,
+                });
+            }
+        }
+        pending.lock().extend(existing);
+
+        Ok(ChangeLog {
+            dir_path,
+            names: landfill.inner.names.clone(),
+            pending,
+            landfill: Arc::downgrade(&landfill.inner),
+            observer,
+        })
+    }
+
+    /// The number of changes waiting to be shipped
+    pub fn pending(&self) -> usize {
+        self.pending.lock().len()
+    }
+
+    /// Take the queued changes, reading the bytes they cover
+    ///
+    /// On failure, the changes not yet read are kept for the next call
+    pub fn ship(&self) -> io::Result<Vec<Delta>> {
+        let mut changes = mem::take(&mut *self.pending.lock());
+        let mut deltas = vec![];
+
+        while let Some(change) = changes.pop_front() {
+            if let Err(e) = self.read_change(&change, &mut deltas) {
+                changes.push_front(change);
+                let mut pending = self.pending.lock();
+                while let Some(change) = changes.pop_back() {
+                    pending.push_front(change);
+                }
+                return Err(e);
+            }
+        }
+
+        Ok(deltas)
+    }
+
+    fn read_change(
+        &self,
+        change: &Change,
+        deltas: &mut Vec<Delta>,
+    ) -> io::Result<()> {
+        let file = self.file_name(&change.branch)?;
+        if self.dir_path.join(&file).is_file() {
+            return self.read_range(file, change.offset, change.len, deltas);
+        }
+
+        // otherwise the branch is split into lanes
+        let end = change.offset + change.len;
+        let mut pos = change.offset;
+        while pos < end {
+            let (lane_nr, lane_offset) = DiskBytes::lane_nr_and_ofs(pos);
+            let in_lane =
+                (DiskBytes::lane_size(lane_nr) - lane_offset).min(end - pos);
+            let file =
+                self.file_name(&format!("{}_{:02x}", change.branch, lane_nr))?;
+            self.read_range(file, lane_offset, in_lane, deltas)?;
+            pos += in_lane;
+        }
+        Ok(())
+    }
+
+    // The name of the file of `branch`, which may be hashed
+    fn file_name(&self, branch: &str) -> io::Result<String> {
+        Ok(self
+            .names
+            .lookup(&self.dir_path, branch)?
+            .unwrap_or_else(|| branch.into()))
+    }
+
+    fn read_range(
+        &self,
+        file: String,
+        offset: u64,
+        len: u64,
+        deltas: &mut Vec<Delta>,
+    ) -> io::Result<()> {
+        let mut handle = match File::open(self.dir_path.join(&file)) {
+            Ok(handle) => handle,
+            // removed by the leader since, such as after a compaction
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        handle.seek(SeekFrom::Start(offset))?;
+        let mut bytes = vec![];
+        handle.take(len).read_to_end(&mut bytes)?;
+
+        if !bytes.is_empty() {
+            deltas.push(Delta {
+                file,
+                offset,
+                bytes,
+            });
+        }
+        Ok(())
+    }
+}
+
+// Queue a change, merging it with the previous one if they overlap or touch
+fn push_change(
+    pending: &mut VecDeque<Change>,
+    branch: &str,
+    offset: u64,
+    len: u64,
+) {
+    if let Some(last) = pending.back_mut() {
+        let end = offset + len;
+        let last_end = last.offset + last.len;
+        if last.branch == branch && offset <= last_end && end >= last.offset {
+            last.offset = last.offset.min(offset);
+            last.len = last_end.max(end) - last.offset;
+            return;
+        }
+    }
+
+    pending.push_back(Change {
+        branch: branch.into(),
+        offset,
+        len,
+    });
+}
+
+// Returns true if `file` names a plain file of the landfill directory
+fn is_data_file(file: &str) -> bool {
+    file != "_lock" && Path::new(file).file_name().is_some_and(|n| n == file)
+}
+
+impl Landfill {
+    /// Apply deltas shipped from a leader's `ChangeLog`
+    ///
+    /// No structures may be open in the follower while deltas are applied.
+    /// Once opened, they see the data as of the time the deltas were shipped
+    pub fn apply_deltas(&self, deltas: &[Delta]) -> io::Result<()> {
+        self.check_writable()?;
+        let dir_path = self.inner.dir_path.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Ephemeral landfills cannot follow",
+            )
+        })?;
+
+        let mut files: HashMap<&str, File> = HashMap::new();
+
+        for delta in deltas {
+            if !is_data_file(&delta.file) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid file name in delta: {}", delta.file),
+                ));
+            }
+
+            let file = match files.entry(&delta.file) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(
+                    OpenOptions::new()
+                        .write(true)
+                        .create(true)
+                        .truncate(false)
+                        .open(dir_path.join(&delta.file))?,
+                ),
+            };
+
+            file.seek(SeekFrom::Start(delta.offset))?;
+            file.write_all(&delta.bytes)?;
+        }
+
+        for file in files.values() {
+            file.sync_all()?;
+        }
+        if files.contains_key(NAMES_FILE) {
+            self.inner.names.reload();
+        }
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+use std::io;
+
 use bytemuck::{Pod, Zeroable};
 
 // Helper function to test if a value is all zeroes,
@@ -10,3 +12,14 @@ pub(crate) fn is_all_zeroes<T: Zeroable + Pod>(t: &[T]) -> bool {
     let zero_bytes: &[u8] = bytemuck::cast_slice(zero);
     bytes_input == zero_bytes
 }
+
+// Convert an on-disk offset or size to `usize`, which is only 32 bits wide
+// on some targets
+pub(crate) fn usize_from(n: u64) -> io::Result<usize> {
+    usize::try_from(n).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Offset exceeds the address space of this platform",
+        )
+    })
+}
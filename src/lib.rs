@@ -15,6 +15,12 @@ mod structures;
 pub use structures::*;
 
 mod disk;
-pub use disk::{GuardedLandfill, Landfill, MappedFile, Substructure};
+pub use disk::{
+    AsyncHandle, AsyncSubstructure, ChangeLog, ConfigurableSubstructure, Delta,
+    FormatHeader, GuardedLandfill, Landfill, MappedFile, Substructure,
+    VerifyReport,
+};
+#[cfg(feature = "failpoints")]
+pub use disk::{FailAction, Failpoint};
 
 mod helpers;
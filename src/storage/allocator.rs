@@ -0,0 +1,213 @@
+use std::io;
+
+use parking_lot::RwLock;
+
+use crate::{
+    AppendOnly, GuardedLandfill, Journal, RandomAccess, Substructure,
+    VerifyReport,
+};
+
+// blocks are never smaller than the free list link stored in them
+const MIN_BLOCK: usize = 8;
+// blocks are aligned to their size, up to this limit
+const MAX_ALIGN: usize = 4096;
+const CLASSES: usize = 32;
+
+/// Allocates reusable regions of bytes on disk
+///
+/// Space is handed out in power-of-two size classes. Freed blocks are kept
+/// in one free list per class, linked through the blocks themselves, with
+/// the list heads persisted in a journal. Space that is not on a free list
+/// is taken from an `AppendOnly` store, so the files still only grow.
+///
+/// The size class of every allocated block is recorded as well, so blocks
+/// cannot be freed twice, and reads and writes are bounded by the block.
+pub struct Allocator {
+    space: AppendOnly,
+    // offset + 1 of the first free block of each class, 0 for none
+    heads: Journal<[u64; CLASSES]>,
+    // class + 1 of the allocated block at each multiple of `MIN_BLOCK`,
+    // 0 for free blocks and offsets inside blocks
+    classes: RandomAccess<u8>,
+    // reads and writes copy through this lock, since blocks can be reused
+    lock: RwLock<()>,
+}
+
+impl Substructure for Allocator {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(Allocator {
+            space: lf.substructure("space")?,
+            heads: lf.substructure("heads")?,
+            classes: lf.substructure("classes")?,
+            lock: RwLock::new(()),
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.space.flush()?;
+        self.heads.flush()?;
+        self.classes.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.space.close()?;
+        self.heads.close()?;
+        self.classes.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.space.size_on_disk()
+            + self.heads.size_on_disk()
+            + self.classes.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.space.verify()?,
+            self.heads.verify()?,
+            self.classes.verify()?,
+        ]))
+    }
+}
+
+impl Allocator {
+    /// Allocate `len` bytes aligned to `align`, returning their offset
+    ///
+    /// Blocks are aligned to their size class, up to 4096 bytes. Asking for
+    /// a larger alignment than the class of `len` provides is an error.
+    pub fn alloc(&self, len: usize, align: usize) -> io::Result<u64> {
+        let class = Self::class(len)?;
+        let size = 1 << class;
+
+        if !align.is_power_of_two() || align > size.min(MAX_ALIGN) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Alignment not supported for allocation size",
+            ));
+        }
+
+        let _lock = self.lock.write();
+        let reused = self.heads.modify(|heads| {
+            let ofs = heads[class].checked_sub(1)?;
+            let next = self.space.read(ofs, 8)?;
+            heads[class] = u64::from_le_bytes(next.try_into().ok()?);
+            Some(ofs)
+        });
+
+        let ofs = match reused {
+            Some(ofs) => ofs,
+            None => self.space.reserve(size, size.min(MAX_ALIGN))?.0,
+        };
+        // a crash before this leaks the block rather than handing it out twice
+        self.classes
+            .with_mut(Self::slot(ofs), |slot| *slot = class as u8 + 1)?;
+        Ok(ofs)
+    }
+
+    /// Return `len` bytes at `ofs` to the allocator for reuse
+    ///
+    /// `len` must be the length the region was allocated with, freeing a
+    /// block that is not allocated fails with `InvalidInput`
+    pub fn free(&self, ofs: u64, len: usize) -> io::Result<()> {
+        let class = Self::class(len)?;
+
+        let _lock = self.lock.write();
+        if self.block_class(ofs) != Some(class) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Not an allocated block",
+            ));
+        }
+
+        // a crash after this leaks the block rather than freeing it twice
+        self.classes.with_mut(Self::slot(ofs), |slot| *slot = 0)?;
+        self.heads.modify(|heads| {
+            let link = unsafe { self.space.request_write(ofs, MIN_BLOCK)? };
+            link.copy_from_slice(&heads[class].to_le_bytes());
+            self.space.written(ofs, MIN_BLOCK);
+            heads[class] = ofs + 1;
+            Ok(())
+        })
+    }
+
+    /// Read a copy of the first `len` bytes of the block at `ofs`
+    pub fn read(&self, ofs: u64, len: usize) -> io::Result<Vec<u8>> {
+        let _lock = self.lock.read();
+        self.range(ofs, len)?;
+        Ok(self
+            .space
+            .try_read(ofs, len as u32)?
+            .ok_or_else(invalid)?
+            .to_vec())
+    }
+
+    /// Write `bytes` to the start of the block at `ofs`
+    ///
+    /// Fails with `InvalidInput` unless a block at `ofs` is allocated, and
+    /// large enough to hold `bytes`
+    pub fn write(&self, ofs: u64, bytes: &[u8]) -> io::Result<()> {
+        let _lock = self.lock.write();
+        self.range(ofs, bytes.len())?;
+        self.space.write_at(ofs, bytes)?;
+        self.space.written(ofs, bytes.len());
+        Ok(())
+    }
+
+    /// The number of free blocks of each size class, smallest first
+    pub fn free_blocks(&self) -> Vec<(usize, usize)> {
+        let _lock = self.lock.read();
+        let heads = self.heads.current();
+
+        (Self::class(0).unwrap_or(0)..CLASSES)
+            .map(|class| {
+                let mut count = 0;
+                let mut head = heads[class];
+                while let Some(ofs) = head.checked_sub(1) {
+                    let Some(next) = self.space.read(ofs, 8) else {
+                        break;
+                    };
+                    head = u64::from_le_bytes(next.try_into().unwrap());
+                    count += 1;
+                }
+                (1 << class, count)
+            })
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    fn class(len: usize) -> io::Result<usize> {
+        let size = len.max(MIN_BLOCK).checked_next_power_of_two();
+        match size.map(|size| size.trailing_zeros() as usize) {
+            Some(class) if class < CLASSES => Ok(class),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Allocation too large",
+            )),
+        }
+    }
+
+    // The index into `classes` of the block at `ofs`
+    fn slot(ofs: u64) -> usize {
+        (ofs / MIN_BLOCK as u64) as usize
+    }
+
+    // The size class of the allocated block at `ofs`, if any
+    fn block_class(&self, ofs: u64) -> Option<usize> {
+        if !ofs.is_multiple_of(MIN_BLOCK as u64) {
+            return None;
+        }
+        let class = *self.classes.get(Self::slot(ofs))?;
+        Some(class as usize - 1)
+    }
+
+    fn range(&self, ofs: u64, len: usize) -> io::Result<()> {
+        match self.block_class(ofs) {
+            Some(class) if len <= 1 << class => Ok(()),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+fn invalid() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "Invalid offset or length")
+}
@@ -0,0 +1,252 @@
+use std::io;
+use std::marker::PhantomData;
+use std::mem;
+use std::time::{Duration, Instant};
+
+use bytemuck::Pod;
+use parking_lot::{Condvar, Mutex};
+
+use super::bytes::DiskBytes;
+use crate::disk::Failpoint;
+use crate::{
+    FormatHeader, GuardedLandfill, Journal, Snapshot, SnapshotView,
+    Substructure, VerifyReport,
+};
+
+/// A typed, append-only log of `T`
+///
+/// Values are assigned sequential indices as they are appended, and like
+/// `AppendOnly`, never move in memory once written, so references to them
+/// can be handed out while new values are concurrently appended.
+///
+/// Values are packed into the lanes so that they never cross a lane boundary,
+/// and the number of values written is persisted in a journal.
+pub struct AppendLog<T> {
+    bytes: DiskBytes,
+    len: Journal<u64>,
+    // wakes up watchers on every push
+    pushed: (Mutex<()>, Condvar),
+    _marker: PhantomData<T>,
+}
+
+/// A blocking iterator over values pushed to an `AppendLog`, returned by
+/// `AppendLog::watch`
+pub struct Watch<'a, T> {
+    log: &'a AppendLog<T>,
+    next: u64,
+}
+
+impl<T> Substructure for AppendLog<T> {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        if mem::size_of::<T>() == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Zero sized types cannot be stored in an AppendLog",
+            ));
+        }
+
+        Ok(AppendLog {
+            bytes: lf.substructure("bytes")?,
+            len: lf.substructure("len")?,
+            pushed: (Mutex::new(()), Condvar::new()),
+            _marker: PhantomData,
+        })
+    }
+
+    fn format() -> Option<FormatHeader> {
+        Some(FormatHeader::new("append_log", mem::size_of::<T>(), 1))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.bytes.flush()?;
+        self.len.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.bytes.close()?;
+        self.len.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.bytes.size_on_disk() + self.len.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.bytes.verify()?,
+            self.len.verify()?,
+        ]))
+    }
+}
+
+impl<T> AppendLog<T>
+where
+    T: Pod,
+{
+    /// Append a value to the log, returning its index
+    pub fn push(&self, t: T) -> io::Result<u64> {
+        let t_size = mem::size_of::<T>();
+
+        self.len
+            .try_update(|len| {
+                let index = *len;
+                let offset = Self::offset_of(index)
+                    .ok_or_else(|| io::Error::other("AppendLog is full"))?;
+
+                self.bytes.write_at(offset, bytemuck::bytes_of(&t))?;
+                self.bytes.landfill().failpoint(Failpoint::DataWritten)?;
+
+                *len += 1;
+                Ok((index, offset))
+            })
+            .map(|(index, offset)| {
+                self.bytes.written(offset, t_size);
+                index
+            })
+            .inspect(|_| {
+                let _lock = self.pushed.0.lock();
+                self.pushed.1.notify_all();
+            })
+    }
+
+    /// Get a reference to the value at `index`
+    ///
+    /// Returns `None` if no value has been appended at this index
+    pub fn get(&self, index: u64) -> Option<&T> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let offset = Self::offset_of(index)?;
+        let bytes = self.bytes.read(offset, mem::size_of::<T>() as u32)?;
+        Some(bytemuck::from_bytes(bytes))
+    }
+
+    /// The number of values in the log
+    pub fn len(&self) -> u64 {
+        self.len.current()
+    }
+
+    /// Returns true if no values have been appended
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over all values in the log, in the order they were appended
+    ///
+    /// Values appended after the iterator was created are not included
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        (0..self.len()).filter_map(|index| self.get(index))
+    }
+
+    /// Watch for values pushed from now on
+    ///
+    /// Iterating the returned `Watch` blocks until the next value is pushed
+    /// by another thread, so the log can be followed without polling
+    pub fn watch(&self) -> Watch<'_, T> {
+        self.watch_from(self.len())
+    }
+
+    /// Watch for values starting at `index`
+    ///
+    /// Values already in the log are yielded right away
+    pub fn watch_from(&self, index: u64) -> Watch<'_, T> {
+        Watch {
+            log: self,
+            next: index,
+        }
+    }
+
+    fn offset_of(index: u64) -> Option<u64> {
+        DiskBytes::packed_offset(index, mem::size_of::<T>() as u64)
+    }
+}
+
+impl<'a, T> Watch<'a, T>
+where
+    T: Pod,
+{
+    /// The index of the next value to be yielded
+    pub fn position(&self) -> u64 {
+        self.next
+    }
+
+    /// Get the next value if it has already been pushed
+    pub fn try_next(&mut self) -> Option<&'a T> {
+        let t = self.log.get(self.next)?;
+        self.next += 1;
+        Some(t)
+    }
+
+    /// Wait up to `timeout` for the next value
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<&'a T> {
+        self.wait_until(Some(Instant::now() + timeout))
+    }
+
+    fn wait_until(&mut self, deadline: Option<Instant>) -> Option<&'a T> {
+        let (lock, pushed) = &self.log.pushed;
+        let mut guard = lock.lock();
+        loop {
+            if let Some(t) = self.try_next() {
+                return Some(t);
+            }
+            match deadline {
+                Some(deadline) => {
+                    if pushed.wait_until(&mut guard, deadline).timed_out() {
+                        return self.try_next();
+                    }
+                }
+                None => pushed.wait(&mut guard),
+            }
+        }
+    }
+}
+
+impl<'a, T> Iterator for Watch<'a, T>
+where
+    T: Pod,
+{
+    type Item = &'a T;
+
+    /// Blocks until the next value is pushed
+    fn next(&mut self) -> Option<&'a T> {
+        self.wait_until(None)
+    }
+}
+
+impl<T> Snapshot for AppendLog<T> {
+    fn epoch(&self) -> u64 {
+        self.len.current()
+    }
+}
+
+impl<'a, T> SnapshotView<'a, AppendLog<T>>
+where
+    T: Pod,
+{
+    /// Get a reference to the value at `index`, if it was appended before
+    /// the snapshot
+    pub fn get(&self, index: u64) -> Option<&'a T> {
+        if index < self.epoch {
+            self.inner.get(index)
+        } else {
+            None
+        }
+    }
+
+    /// The number of values in the log as of the snapshot
+    pub fn len(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns true if the log was empty as of the snapshot
+    pub fn is_empty(&self) -> bool {
+        self.epoch == 0
+    }
+
+    /// Iterate over all values appended before the snapshot
+    pub fn iter(&self) -> impl Iterator<Item = &'a T> + 'a {
+        let inner = self.inner;
+        (0..self.epoch).filter_map(move |index| inner.get(index))
+    }
+}
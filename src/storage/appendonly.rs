@@ -1,7 +1,54 @@
-use std::io;
+use std::collections::HashMap;
+use std::io::{self, IoSlice, Read};
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use bytemuck_derive::*;
 
 use super::bytes::DiskBytes;
-use crate::{GuardedLandfill, Journal, Substructure};
+use super::group::GroupCommit;
+use super::throttle::Throttle;
+use crate::{
+    ConfigurableSubstructure, Entropy, GuardedLandfill, Journal, Landfill,
+    Snapshot, SnapshotView, Substructure, Tag, VerifyReport,
+};
+
+// size of the chunks read once the length hint has been exhausted
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+// how far ahead sequential reads are prefetched
+const READAHEAD_WINDOW: u64 = 256 * 1024;
+// reads starting at most this far past the previous one count as sequential
+const READAHEAD_GAP: u64 = 4096;
+
+/// A handle to a slice of bytes written into an `AppendOnly` store
+///
+/// The record carries the tag of the store that issued it, so handing it to
+/// another store results in an error rather than garbage data.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Zeroable, Pod, Debug)]
+pub struct Record {
+    offset: u64,
+    length: u32,
+    tag: Tag,
+}
+
+impl Record {
+    /// The offset of the record in the store
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The length of the record in bytes
+    pub fn len(&self) -> u32 {
+        self.length
+    }
+
+    /// Returns true if the record is zero bytes long
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+}
 
 /// AppendOnly
 /// Since the collection can only grow, and written bytes never move in memory,
@@ -10,18 +57,160 @@ use crate::{GuardedLandfill, Journal, Substructure};
 pub struct AppendOnly {
     bytes: DiskBytes,
     journal: Journal<u64>,
+    entropy: Entropy,
+    generation: Journal<u64>,
+    landfill: Landfill,
+    closed_cleanly: bool,
+    commit: GroupCommit,
+    throttle: Throttle,
+    // end of the last read, and how far reads have been prefetched
+    last_read: AtomicU64,
+    prefetched: AtomicU64,
+}
+
+/// Durability settings of an `AppendOnly` store
+///
+/// Persisted when the store is created with `substructure_with_config`.
+/// Changes made later through the setters are not persisted
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Zeroable, Pod)]
+pub struct AppendOnlyConfig {
+    /// The commit window in microseconds, see `AppendOnly::set_commit_window`
+    pub commit_window_micros: u64,
+    /// The dirty byte limit, zero for none, see `AppendOnly::set_dirty_limit`
+    pub dirty_limit: u64,
+}
+
+/// A mapping from old to new offsets, as returned by `AppendOnly::compact`
+#[derive(Debug, Default)]
+pub struct RelocationMap(HashMap<u64, u64>);
+
+impl RelocationMap {
+    /// Returns the new offset of the record previously at `old_offset`
+    ///
+    /// Returns `None` if the record was not kept
+    pub fn get(&self, old_offset: u64) -> Option<u64> {
+        self.0.get(&old_offset).copied()
+    }
+
+    /// Iterate over all `(old, new)` offset pairs
+    pub fn iter(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.0.iter().map(|(old, new)| (*old, *new))
+    }
+
+    /// The number of relocated records
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if no records were relocated
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// Generation 0 lives directly in the landfill, for compatibility with stores
+// that were never compacted
+fn generation_landfill(lf: &Landfill, generation: u64) -> Landfill {
+    if generation == 0 {
+        lf.clone()
+    } else {
+        lf.branch(format!("gen{generation}"))
+    }
+}
+
+fn remove_generation(lf: &Landfill, generation: u64) -> io::Result<()> {
+    let lf = generation_landfill(lf, generation);
+    lf.branch("bytes".into()).remove_files()?;
+    lf.branch("journal".into()).remove_files()
+}
+
+impl ConfigurableSubstructure for AppendOnly {
+    type Config = AppendOnlyConfig;
+
+    fn init_with_config(
+        lf: GuardedLandfill,
+        config: AppendOnlyConfig,
+    ) -> io::Result<AppendOnly> {
+        let generation: Journal<u64> = lf.substructure("generation")?;
+        let current = generation.current();
+
+        // clean up after compactions that were interrupted either before or
+        // after the switch to the new generation
+        let compacting = lf.branch("compacting".into());
+        compacting.reserve_name();
+        if compacting.file_exists() {
+            if current > 0 {
+                remove_generation(&lf, current - 1)?;
+            }
+            remove_generation(&lf, current + 1)?;
+            compacting.take_marker()?;
+        }
+
+        let gen_lf = generation_landfill(&lf, current);
+        let bytes = gen_lf.substructure("bytes")?;
+        // stores from before journal sequence numbers keep their writehead
+        let journal = Journal::open_migrating(&gen_lf, "journal")?;
+        let entropy = lf.substructure("entropy")?;
+
+        // the marker is only valid until the store is written to again
+        let marker = lf.branch("closed".into());
+        marker.reserve_name();
+        let closed_cleanly = marker.take_marker()?;
+
+        let ao = AppendOnly {
+            bytes,
+            journal,
+            entropy,
+            generation,
+            landfill: lf.inner(),
+            closed_cleanly,
+            commit: GroupCommit::default(),
+            throttle: Throttle::default(),
+            last_read: AtomicU64::new(0),
+            prefetched: AtomicU64::new(0),
+        };
+
+        ao.set_commit_window(Duration::from_micros(
+            config.commit_window_micros,
+        ));
+        if config.dirty_limit > 0 {
+            ao.set_dirty_limit(Some(config.dirty_limit));
+        }
+        Ok(ao)
+    }
 }
 
 impl Substructure for AppendOnly {
     fn init(lf: GuardedLandfill) -> io::Result<AppendOnly> {
-        let bytes = lf.substructure("bytes")?;
-        let journal = lf.substructure("journal")?;
-
-        Ok(AppendOnly { bytes, journal })
+        let config = lf.stored_config()?.unwrap_or_default();
+        Self::init_with_config(lf, config)
     }
 
     fn flush(&self) -> io::Result<()> {
-        self.bytes.flush()
+        self.throttle.flushed(|| self.bytes.flush())
+    }
+
+    /// Flushes everything, shrinks the last lane file to the blocks in use
+    /// and leaves a marker that the store was closed cleanly
+    fn close(self) -> io::Result<()> {
+        let used = self.writehead();
+        self.journal.close()?;
+        self.generation.close()?;
+        self.entropy.close()?;
+        self.bytes.close_at(used)?;
+        self.landfill.branch("closed".into()).create_marker()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.landfill.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.journal.verify()?,
+            self.generation.verify()?,
+        ]))
     }
 }
 
@@ -32,30 +221,553 @@ impl AppendOnly {
         bytes: &[u8],
         alignment: usize,
     ) -> io::Result<u64> {
-        let len = bytes.len();
+        let (write_offset, slice) = self.reserve(bytes.len(), alignment)?;
+        slice.copy_from_slice(bytes);
+        self.written(write_offset, bytes.len());
+        Ok(write_offset)
+    }
+
+    /// Write a slice of bytes into the store returning their offset
+    pub fn write(&self, bytes: &[u8]) -> io::Result<u64> {
+        self.write_aligned(bytes, 1)
+    }
+
+    /// Write a slice of bytes, failing with `WouldBlock` instead of flushing
+    /// if the dirty limit has been reached
+    pub fn try_write(&self, bytes: &[u8]) -> io::Result<u64> {
+        if self.throttle.exceeded() {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "Dirty limit reached",
+            ));
+        }
+        self.write(bytes)
+    }
+
+    /// Write a slice of bytes and wait until it is flushed to disk
+    ///
+    /// See `sync` for how flushes of concurrent writers are shared
+    pub fn write_durable(&self, bytes: &[u8]) -> io::Result<u64> {
+        let offset = self.write(bytes)?;
+        self.sync()?;
+        Ok(offset)
+    }
+
+    /// Flush all data written so far, along with the writehead
+    ///
+    /// Concurrent calls are grouped: threads arriving while a flush is in
+    /// progress share the next one, so many writers cost few flushes
+    pub fn sync(&self) -> io::Result<()> {
+        self.commit.commit(|| {
+            self.throttle.flushed(|| {
+                self.bytes.flush()?;
+                self.journal.flush()
+            })
+        })
+    }
+
+    /// Limit how many written bytes may wait to be flushed
+    ///
+    /// Once the limit is reached, the next write first syncs the store, so
+    /// dirty pages are flushed in bounded steps rather than piling up. `None`,
+    /// the default, disables the limit
+    pub fn set_dirty_limit(&self, limit: Option<u64>) {
+        self.throttle.set_limit(limit)
+    }
+
+    /// Catch up with data written by another process since the store was
+    /// opened, for stores in a read-only landfill
+    ///
+    /// Compactions by the writer are not picked up, the store has to be
+    /// reopened after those
+    pub fn refresh(&self) {
+        self.journal.refresh()
+    }
+
+    /// The number of bytes written since the last flush
+    pub fn dirty_bytes(&self) -> u64 {
+        self.throttle.dirty()
+    }
+
+    /// Set how long a sync waits for other writers before flushing
+    ///
+    /// Defaults to zero. A small window trades latency for fewer flushes
+    /// when many threads write durably at once
+    pub fn set_commit_window(&self, window: Duration) {
+        self.commit.set_window(window)
+    }
+
+    /// The number of flushes performed by `sync` since the store was opened
+    pub fn syncs(&self) -> u64 {
+        self.commit.syncs()
+    }
+
+    /// Write a set of slices contiguously into the store, returning the offset
+    /// of the first byte
+    pub fn write_all_vectored(&self, bufs: &[IoSlice]) -> io::Result<u64> {
+        let len = bufs.iter().map(|buf| buf.len()).sum();
+        let (write_offset, slice) = self.reserve(len, 1)?;
+
+        let mut pos = 0;
+        for buf in bufs {
+            slice[pos..][..buf.len()].copy_from_slice(buf);
+            pos += buf.len();
+        }
+
+        self.written(write_offset, len);
+        Ok(write_offset)
+    }
+
+    /// Write a batch of byte slices into the store, returning their offsets
+    ///
+    /// Space for the whole batch is reserved with a single journal update
+    pub fn write_batch(&self, batch: &[&[u8]]) -> io::Result<Vec<u64>> {
+        self.landfill.check_writable()?;
+        self.backpressure()?;
+        let offsets = self.journal.update(|writehead| {
+            let mut offsets = Vec::with_capacity(batch.len());
+            for bytes in batch {
+                let res = DiskBytes::find_space_for(*writehead, bytes.len(), 1);
+                *writehead = res + bytes.len() as u64;
+                offsets.push(res);
+            }
+            offsets
+        });
+
+        for (bytes, offset) in batch.iter().zip(&offsets) {
+            self.bytes.write_at(*offset, bytes)?;
+            self.written(*offset, bytes.len());
+        }
+
+        Ok(offsets)
+    }
+
+    /// Stream the contents of `reader` into the store
+    ///
+    /// Space is reserved for `len_hint` bytes up front and read into directly,
+    /// data beyond the hint is read in chunks. Since records cannot span lanes,
+    /// the data is returned as a list of records in order.
+    ///
+    /// If the reader ends before `len_hint` bytes have been read, the remaining
+    /// reserved space is left unused.
+    pub fn write_from_reader<R: Read>(
+        &self,
+        mut reader: R,
+        len_hint: u64,
+    ) -> io::Result<Vec<Record>> {
+        self.landfill.check_writable()?;
+        self.backpressure()?;
+        let mut records = vec![];
+        let mut remaining_hint = len_hint;
+
+        // read directly into reserved space while the hint lasts
+        while remaining_hint > 0 {
+            let (offset, len) = self.reserve_in_lane(remaining_hint);
+            let slice = unsafe { self.bytes.request_write(offset, len)? };
+
+            let read = read_full(&mut reader, slice)?;
+            if read > 0 {
+                self.written(offset, read);
+                records.push(self.record(offset, read)?);
+            }
+            if read < len {
+                return Ok(records);
+            }
+            remaining_hint -= len as u64;
+        }
+
+        let mut buf = vec![0u8; READ_CHUNK_SIZE];
+
+        loop {
+            let read = read_full(&mut reader, &mut buf)?;
+            let mut chunk = &buf[..read];
 
-        let write_offset = self.journal.update(|writehead| {
+            while !chunk.is_empty() {
+                let (offset, len) = self.reserve_in_lane(chunk.len() as u64);
+                let slice = unsafe { self.bytes.request_write(offset, len)? };
+                slice.copy_from_slice(&chunk[..len]);
+                self.written(offset, len);
+                records.push(self.record(offset, len)?);
+                chunk = &chunk[len..];
+            }
+
+            if read < READ_CHUNK_SIZE {
+                return Ok(records);
+            }
+        }
+    }
+
+    // Report bytes written into reserved space to the landfill observers,
+    // and count them as dirty
+    pub(crate) fn written(&self, offset: u64, len: usize) {
+        self.throttle.add(len as u64);
+        self.bytes.written(offset, len)
+    }
+
+    // Sync before writing more if the dirty limit has been reached
+    fn backpressure(&self) -> io::Result<()> {
+        if self.throttle.exceeded() {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    // Reserve `len` bytes of space aligned to `alignment`
+    //
+    // The returned slice is freshly allocated and cannot alias any other
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) fn reserve(
+        &self,
+        len: usize,
+        alignment: usize,
+    ) -> io::Result<(u64, &mut [u8])> {
+        self.backpressure()?;
+        self.journal.try_update(|writehead| {
             let res = DiskBytes::find_space_for(*writehead, len, alignment);
+            let slice = unsafe { self.bytes.request_write(res, len)? };
             *writehead = res + len as u64;
-            res
-        });
+            Ok((res, slice))
+        })
+    }
 
-        let slice = unsafe { self.bytes.request_write(write_offset, len)? };
+    // Grow the reserved region at `offset`, of which the first `len` bytes
+    // are written, to `new_len` bytes
+    //
+    // Takes the slice of the region as returned by `reserve` or an earlier
+    // `grow`, so that no other reference to it is live while it is requested
+    // again. The region is extended in place if nothing has been reserved
+    // after it and its lane has room, otherwise it is moved to freshly
+    // reserved space. Returns the offset and slice of the grown region
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) fn grow<'a>(
+        &'a self,
+        offset: u64,
+        region: &'a mut [u8],
+        len: usize,
+        new_len: usize,
+    ) -> io::Result<(u64, &'a mut [u8])> {
+        self.backpressure()?;
+        self.journal.try_update(|writehead| {
+            let end = offset + region.len() as u64;
+            let in_place = *writehead == end
+                && DiskBytes::lane_remaining(offset) >= new_len as u64;
 
-        slice.copy_from_slice(bytes);
+            if in_place {
+                // `region` is not used again, the new slice covers it
+                let slice =
+                    unsafe { self.bytes.request_write(offset, new_len)? };
+                *writehead = offset + new_len as u64;
+                return Ok((offset, slice));
+            }
 
-        Ok(write_offset)
+            let res = DiskBytes::find_space_for(*writehead, new_len, 1);
+            let slice = unsafe { self.bytes.request_write(res, new_len)? };
+            slice[..len].copy_from_slice(&region[..len]);
+            *writehead = res + new_len as u64;
+            Ok((res, slice))
+        })
     }
 
-    /// Write a slice of bytes into the store returning their offset
-    pub fn write(&self, bytes: &[u8]) -> io::Result<u64> {
-        self.write_aligned(bytes, 1)
+    pub(crate) fn landfill(&self) -> &Landfill {
+        &self.landfill
+    }
+
+    pub(crate) fn writehead(&self) -> u64 {
+        self.journal.current()
+    }
+
+    // Returns true if the store was closed with `Substructure::close` last
+    // time, so nothing was written past the journaled writehead
+    pub(crate) fn closed_cleanly(&self) -> bool {
+        self.closed_cleanly
+    }
+
+    // Move the writehead forward to `to`, if it is not already past it
+    pub(crate) fn advance_writehead(&self, to: u64) {
+        self.journal.update(|writehead| {
+            if to > *writehead {
+                *writehead = to
+            }
+        })
+    }
+
+    // Copy `bytes` into already reserved space at `offset`
+    //
+    // The caller must make sure nothing else references the range
+    pub(crate) fn write_at(&self, offset: u64, bytes: &[u8]) -> io::Result<()> {
+        self.bytes.write_at(offset, bytes)
+    }
+
+    // Mutable access to already reserved bytes
+    //
+    // The caller must make sure nothing else references the range
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn request_write(
+        &self,
+        offset: u64,
+        len: usize,
+    ) -> io::Result<&mut [u8]> {
+        self.bytes.request_write(offset, len)
+    }
+
+    pub(crate) fn read(&self, offset: u64, len: u32) -> Option<&[u8]> {
+        self.bytes.read(offset, len)
+    }
+
+    pub(crate) fn try_read(
+        &self,
+        offset: u64,
+        len: u32,
+    ) -> io::Result<Option<&[u8]>> {
+        self.bytes.try_read(offset, len)
+    }
+
+    // Reserve up to `len` bytes without crossing into the next lane
+    fn reserve_in_lane(&self, len: u64) -> (u64, usize) {
+        self.journal.update(|writehead| {
+            let offset = *writehead;
+            let len = len
+                .min(DiskBytes::lane_remaining(offset))
+                .min(u32::MAX as u64);
+            *writehead = offset + len;
+            (offset, len as usize)
+        })
+    }
+
+    fn record(&self, offset: u64, len: usize) -> io::Result<Record> {
+        Ok(Record {
+            offset,
+            length: record_len(len)?,
+            tag: self.entropy.tag(),
+        })
+    }
+
+    /// Copy all live records into a fresh set of files, and remove the old ones
+    ///
+    /// Since the store has no knowledge of record boundaries, the candidate
+    /// records are passed in as `(offset, len)` pairs, and only the ones for
+    /// which `is_live` returns true are kept. Alignment of the original
+    /// offsets is preserved up to 16 bytes.
+    ///
+    /// Returns a map from old to new offsets of the kept records.
+    pub fn compact<I, F>(
+        &mut self,
+        records: I,
+        is_live: F,
+    ) -> io::Result<RelocationMap>
+    where
+        I: IntoIterator<Item = (u64, u32)>,
+        F: Fn(u64, u32) -> bool,
+    {
+        let current = self.generation.current();
+        let next = current + 1;
+
+        let compacting = self.landfill.branch("compacting".into());
+        compacting.create_marker()?;
+
+        let gen_lf = generation_landfill(&self.landfill, next);
+        let bytes: DiskBytes = gen_lf.substructure("bytes")?;
+        let journal: Journal<u64> = gen_lf.substructure("journal")?;
+
+        let mut relocations = HashMap::new();
+
+        for (offset, len) in records {
+            if relocations.contains_key(&offset) || !is_live(offset, len) {
+                continue;
+            }
+
+            let data = self.bytes.try_read(offset, len)?.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "Invalid record")
+            })?;
+
+            let alignment = 1 << offset.trailing_zeros().min(4);
+            let new_offset = journal.update(|writehead| {
+                let res = DiskBytes::find_space_for(
+                    *writehead,
+                    len as usize,
+                    alignment,
+                );
+                *writehead = res + len as u64;
+                res
+            });
+
+            unsafe { bytes.request_write(new_offset, len as usize)? }
+                .copy_from_slice(data);
+
+            relocations.insert(offset, new_offset);
+        }
+
+        bytes.flush()?;
+        journal.flush()?;
+
+        // switch over to the new generation
+        self.generation.update(|g| *g = next);
+        self.generation.flush()?;
+
+        drop(mem::replace(&mut self.bytes, bytes));
+        drop(mem::replace(&mut self.journal, journal));
+
+        remove_generation(&self.landfill, current)?;
+        compacting.take_marker()?;
+
+        Ok(RelocationMap(relocations))
+    }
+
+    /// Hint that the data in `offset..offset + len` will be read soon
+    ///
+    /// Consecutive reads with `get` are prefetched automatically, this is
+    /// for access patterns the store cannot predict
+    pub fn prefetch(&self, offset: u64, len: u64) -> io::Result<()> {
+        let end = offset.saturating_add(len).min(self.writehead());
+        if offset >= end {
+            return Ok(());
+        }
+        self.bytes.prefetch(offset, end - offset)
+    }
+
+    // Prefetch the next window of data when reads look sequential
+    fn read_ahead(&self, offset: u64, len: u32) {
+        let end = offset.saturating_add(len as u64);
+        let last = self.last_read.swap(end, Ordering::Relaxed);
+
+        if offset < last || offset - last > READAHEAD_GAP {
+            return;
+        }
+
+        let prefetched = self.prefetched.load(Ordering::Relaxed);
+        if end + READAHEAD_WINDOW / 2 > prefetched {
+            let from = prefetched.max(end);
+            let to = end.saturating_add(READAHEAD_WINDOW);
+            self.prefetched.store(to, Ordering::Relaxed);
+            // only a hint, failures just mean no readahead
+            let _ = self.prefetch(from, to - from);
+        }
     }
 
     /// Get a reference to the data at offset and length
+    ///
+    /// The data is read from the mapping of its lane, which is mapped on
+    /// first access. The returned reference lives as long as the store, so
+    /// it can not point into a cache that evicts entries, use `get_cold` to
+    /// read rarely accessed data without mapping it
     pub fn get(&self, offset: u64, len: u32) -> &[u8] {
+        self.read_ahead(offset, len);
         self.bytes
             .read(offset, len)
             .expect("Fatal Error: invalid offset or length!")
     }
+
+    /// Get a reference to the data at offset and length
+    ///
+    /// Unlike `get`, this returns an error if the requested range has not
+    /// been written to, rather than panicking.
+    pub fn try_get(&self, offset: u64, len: u32) -> io::Result<&[u8]> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid offset or length",
+            )
+        };
+
+        let end = offset.checked_add(len as u64).ok_or_else(invalid)?;
+        if end > self.writehead() {
+            return Err(invalid());
+        }
+
+        self.read_ahead(offset, len);
+        self.bytes.try_read(offset, len)?.ok_or_else(invalid)
+    }
+
+    /// Read a copy of the data at offset and length
+    ///
+    /// Unlike `get`, this does not require the data to be mapped into memory.
+    /// Data that has not been accessed since the store was opened is read
+    /// directly from disk through a small cache, which bounds address space
+    /// and memory usage when serving rarely accessed data.
+    pub fn get_cold(&self, offset: u64, len: u32) -> io::Result<Vec<u8>> {
+        let end = offset.checked_add(len as u64);
+        if end.map(|end| end > self.writehead()).unwrap_or(true) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid offset or length",
+            ));
+        }
+
+        let mut buf = vec![0; len as usize];
+        self.bytes.read_cold(offset, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Write a slice of bytes into the store returning a `Record` handle
+    pub fn write_record(&self, bytes: &[u8]) -> io::Result<Record> {
+        record_len(bytes.len())?;
+        let offset = self.write(bytes)?;
+        self.record(offset, bytes.len())
+    }
+
+    /// Get a reference to the data referred to by `record`
+    ///
+    /// Errors if the record was issued by another store, or points outside
+    /// of the written data
+    pub fn get_record(&self, record: Record) -> io::Result<&[u8]> {
+        if record.tag != self.entropy.tag() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Record does not belong to this store",
+            ));
+        }
+
+        self.try_get(record.offset, record.length)
+    }
+}
+
+impl Snapshot for AppendOnly {
+    fn epoch(&self) -> u64 {
+        self.writehead()
+    }
+}
+
+impl<'a> SnapshotView<'a, AppendOnly> {
+    /// Get a reference to data written before the snapshot
+    ///
+    /// Errors if any of the range was written after the snapshot
+    pub fn get(&self, offset: u64, len: u32) -> io::Result<&'a [u8]> {
+        let end = offset.checked_add(len as u64);
+        if end.is_none_or(|end| end > self.epoch) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Range written after the snapshot",
+            ));
+        }
+        self.inner.try_get(offset, len)
+    }
+
+    /// The writehead of the store as of the snapshot
+    pub fn writehead(&self) -> u64 {
+        self.epoch
+    }
+}
+
+// Read from `reader` until `buf` is full or the reader is exhausted
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
+
+// The length of a record of `len` bytes, which has to fit the `Record`
+fn record_len(len: usize) -> io::Result<u32> {
+    u32::try_from(len).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Records must be shorter than 4 GiB",
+        )
+    })
 }
@@ -0,0 +1,193 @@
+use std::io;
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use bytemuck::Pod;
+
+use super::bytes::DiskBytes;
+use crate::{FormatHeader, GuardedLandfill, Substructure, VerifyReport};
+
+/// Integer types that can be stored in an `AtomicArray`
+pub trait AtomicCell: Pod + sealed::Sealed {
+    /// The atomic counterpart of the integer
+    type Atomic;
+
+    #[doc(hidden)]
+    fn load(atomic: &Self::Atomic, ordering: Ordering) -> Self;
+    #[doc(hidden)]
+    fn store(atomic: &Self::Atomic, value: Self, ordering: Ordering);
+    #[doc(hidden)]
+    fn fetch_add(
+        atomic: &Self::Atomic,
+        value: Self,
+        ordering: Ordering,
+    ) -> Self;
+    #[doc(hidden)]
+    fn compare_exchange(
+        atomic: &Self::Atomic,
+        current: Self,
+        new: Self,
+    ) -> Result<Self, Self>;
+}
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for u32 {}
+    impl Sealed for u64 {}
+}
+
+macro_rules! atomic_cell {
+    ($int:ty, $atomic:ty) => {
+        impl AtomicCell for $int {
+            type Atomic = $atomic;
+
+            fn load(atomic: &$atomic, ordering: Ordering) -> Self {
+                atomic.load(ordering)
+            }
+
+            fn store(atomic: &$atomic, value: Self, ordering: Ordering) {
+                atomic.store(value, ordering)
+            }
+
+            fn fetch_add(
+                atomic: &$atomic,
+                value: Self,
+                ordering: Ordering,
+            ) -> Self {
+                atomic.fetch_add(value, ordering)
+            }
+
+            fn compare_exchange(
+                atomic: &$atomic,
+                current: Self,
+                new: Self,
+            ) -> Result<Self, Self> {
+                atomic.compare_exchange(
+                    current,
+                    new,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                )
+            }
+        }
+    };
+}
+
+atomic_cell!(u32, AtomicU32);
+atomic_cell!(u64, AtomicU64);
+
+/// An unbounded array of atomic integers on disk
+///
+/// Cells are accessed through atomic operations directly on the mapped
+/// memory, without taking any locks. Unwritten cells read as zero
+pub struct AtomicArray<T> {
+    bytes: DiskBytes,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Substructure for AtomicArray<T> {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(AtomicArray {
+            bytes: lf.substructure("cells")?,
+            _marker: PhantomData,
+        })
+    }
+
+    fn format() -> Option<FormatHeader> {
+        Some(FormatHeader::new("atomic_array", mem::size_of::<T>(), 1))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.bytes.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.bytes.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.bytes.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        self.bytes.verify()
+    }
+}
+
+impl<T> AtomicArray<T>
+where
+    T: AtomicCell,
+{
+    /// Load the value of a cell
+    pub fn load(&self, index: usize) -> T {
+        match self.existing_cell(index) {
+            Some(atomic) => T::load(atomic, Ordering::Acquire),
+            None => T::zeroed(),
+        }
+    }
+
+    /// Store a value in a cell
+    pub fn store(&self, index: usize, value: T) -> io::Result<()> {
+        T::store(self.cell(index)?, value, Ordering::Release);
+        self.written(index);
+        Ok(())
+    }
+
+    /// Add to the value of a cell, returning the previous value
+    ///
+    /// Wraps around on overflow
+    pub fn fetch_add(&self, index: usize, value: T) -> io::Result<T> {
+        let previous = T::fetch_add(self.cell(index)?, value, Ordering::AcqRel);
+        self.written(index);
+        Ok(previous)
+    }
+
+    /// Store `new` in a cell if it holds `current`
+    ///
+    /// Returns the previous value, as `Err` if it did not match `current`
+    pub fn compare_exchange(
+        &self,
+        index: usize,
+        current: T,
+        new: T,
+    ) -> io::Result<Result<T, T>> {
+        let result = T::compare_exchange(self.cell(index)?, current, new);
+        if result.is_ok() {
+            self.written(index);
+        }
+        Ok(result)
+    }
+
+    // Report an update of the cell at `index` to the landfill observers
+    fn written(&self, index: usize) {
+        let size = mem::size_of::<T>();
+        self.bytes.written((index * size) as u64, size)
+    }
+
+    // The cell at `index`, `None` if its lane was never written
+    fn existing_cell(&self, index: usize) -> Option<&T::Atomic> {
+        let size = mem::size_of::<T>();
+        let offset = (index * size) as u64;
+        let ptr = self.bytes.cell_ptr(offset, size, false).ok()??;
+        Some(self.atomic(ptr))
+    }
+
+    fn cell(&self, index: usize) -> io::Result<&T::Atomic> {
+        let size = mem::size_of::<T>();
+        let offset = (index * size) as u64;
+        let ptr = self
+            .bytes
+            .cell_ptr(offset, size, true)?
+            .expect("Lane is created");
+        Ok(self.atomic(ptr))
+    }
+
+    // The atomic at `ptr`, which points into the lanes of `self`
+    fn atomic(&self, ptr: *const u8) -> &T::Atomic {
+        // Lanes are page aligned and cells are a power of two in size, so
+        // every cell is aligned for its atomic type
+        debug_assert_eq!(ptr as usize % mem::align_of::<T::Atomic>(), 0);
+        unsafe { &*(ptr as *const T::Atomic) }
+    }
+}
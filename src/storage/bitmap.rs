@@ -0,0 +1,103 @@
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::bytes::DiskBytes;
+use crate::{GuardedLandfill, Substructure, VerifyReport};
+
+const WORD_SIZE: u64 = 8;
+const WORD_BITS: usize = 64;
+
+/// An unbounded set of bits on disk, all initially unset
+pub(crate) struct Bitmap {
+    bytes: DiskBytes,
+}
+
+impl Substructure for Bitmap {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(Bitmap {
+            bytes: lf.substructure("bits")?,
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.bytes.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.bytes.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.bytes.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        self.bytes.verify()
+    }
+}
+
+impl Bitmap {
+    /// Returns whether the bit at `index` is set
+    pub fn get(&self, index: usize) -> bool {
+        let offset = (index / WORD_BITS) as u64 * WORD_SIZE;
+        match self.bytes.cell_ptr(offset, WORD_SIZE as usize, false) {
+            Ok(Some(ptr)) => {
+                self.atomic(ptr).load(Ordering::Acquire) & Self::mask(index)
+                    != 0
+            }
+            _ => false,
+        }
+    }
+
+    /// Set the bit at `index`, returning its previous value
+    pub fn set(&self, index: usize) -> io::Result<bool> {
+        let word = self.word(index)?;
+        let mask = Self::mask(index);
+        let was_set = word.fetch_or(mask, Ordering::AcqRel) & mask != 0;
+        if !was_set {
+            self.written(index);
+        }
+        Ok(was_set)
+    }
+
+    /// Clear the bit at `index`, returning its previous value
+    pub fn clear(&self, index: usize) -> io::Result<bool> {
+        if !self.get(index) {
+            return Ok(false);
+        }
+        let word = self.word(index)?;
+        let mask = Self::mask(index);
+        let was_set = word.fetch_and(!mask, Ordering::AcqRel) & mask != 0;
+        if was_set {
+            self.written(index);
+        }
+        Ok(was_set)
+    }
+
+    // Report a change of the word holding `index` to the landfill observers
+    fn written(&self, index: usize) {
+        let offset = (index / WORD_BITS) as u64 * WORD_SIZE;
+        self.bytes.written(offset, WORD_SIZE as usize)
+    }
+
+    fn word(&self, index: usize) -> io::Result<&AtomicU64> {
+        let offset = (index / WORD_BITS) as u64 * WORD_SIZE;
+        let ptr = self
+            .bytes
+            .cell_ptr(offset, WORD_SIZE as usize, true)?
+            .expect("Lane is created");
+        Ok(self.atomic(ptr))
+    }
+
+    fn mask(index: usize) -> u64 {
+        1 << (index % WORD_BITS)
+    }
+
+    // The word at `ptr`, which points into the lanes of `self`
+    fn atomic(&self, ptr: *const u8) -> &AtomicU64 {
+        // Lanes are page aligned and words never straddle lanes, so every
+        // word is aligned for an `AtomicU64`
+        debug_assert_eq!(ptr as usize % WORD_SIZE as usize, 0);
+        unsafe { &*(ptr as *const AtomicU64) }
+    }
+}
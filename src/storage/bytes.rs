@@ -1,43 +1,39 @@
 use std::io;
-use std::mem;
 use std::sync::OnceLock;
 
+use super::cold::{ColdCache, BLOCK_SIZE};
+use crate::disk::Failpoint;
+use crate::helpers;
 use crate::{GuardedLandfill, Landfill, MappedFile, Substructure};
 
-const N_LANES: usize = 32;
+pub(crate) const N_LANES: usize = 32;
 const FIRST_FILE_SIZE: u64 = 4096;
 
 pub(crate) struct DiskBytes {
     landfill: Landfill,
     lanes: [OnceLock<MappedFile>; N_LANES],
+    // lanes that existed on disk when opened, these are mapped lazily
+    on_disk: [bool; N_LANES],
+    cold: ColdCache,
 }
 
 impl Substructure for DiskBytes {
     fn init(lf: GuardedLandfill) -> Result<Self, io::Error> {
         const LOCK: OnceLock<MappedFile> = OnceLock::new();
         let lanes = [LOCK; N_LANES];
+        let mut on_disk = [false; N_LANES];
 
-        for (i, lane) in lanes.iter().enumerate() {
+        for (i, exists) in on_disk.iter_mut().enumerate() {
             let lf_inner = lf.branch(format!("{:02x}", i));
-
-            if let Some(lane_file) =
-                lf_inner.map_file_existing(Self::lane_size(i))?
-            {
-                // `OnceLock::set` returns the value you tried to set, had it
-                // already been initialized
-                //
-                // This is however always the first time this `OnceLock` is touched,
-                // due to being created just above, thus this will never error.
-
-                if lane.set(lane_file).is_err() {
-                    unreachable!()
-                }
-            }
+            lf_inner.reserve_name();
+            *exists = lf_inner.file_exists();
         }
 
         Ok(DiskBytes {
             landfill: lf.inner(),
             lanes,
+            on_disk,
+            cold: ColdCache::new(),
         })
     }
 
@@ -50,9 +46,43 @@ impl Substructure for DiskBytes {
 
         Ok(())
     }
+
+    fn size_on_disk(&self) -> u64 {
+        self.landfill.size_on_disk()
+    }
 }
 
 impl DiskBytes {
+    /// Report a completed write to the observers of the landfill
+    pub fn written(&self, offset: u64, len: usize) {
+        self.landfill.notify_write(offset, len as u64)
+    }
+
+    /// The landfill branch the lanes are stored in
+    pub fn landfill(&self) -> &Landfill {
+        &self.landfill
+    }
+
+    /// Flush and drop all mappings, then shrink the lane containing `used`
+    /// to the blocks below it
+    pub fn close_at(self, used: u64) -> io::Result<()> {
+        self.flush()?;
+        let landfill = self.landfill.clone();
+        drop(self);
+
+        let (lane_nr, inner_offset) = Self::lane_nr_and_ofs(used);
+        let len = inner_offset.next_multiple_of(BLOCK_SIZE);
+        landfill
+            .branch(format!("{:02x}", lane_nr))
+            .truncate_file(len)
+    }
+
+    /// Returns the number of bytes left in the lane containing `offset`
+    pub fn lane_remaining(offset: u64) -> u64 {
+        let (lane_nr, inner_offset) = Self::lane_nr_and_ofs(offset);
+        Self::lane_size(lane_nr) - inner_offset
+    }
+
     pub fn find_space_for(offset: u64, len: usize, alignment: usize) -> u64 {
         let (lane_nr, inner_offset) = Self::lane_nr_and_ofs(offset);
         let lane_size = Self::lane_size(lane_nr);
@@ -71,61 +101,235 @@ impl DiskBytes {
         }
     }
 
+    #[allow(clippy::mut_from_ref)]
     pub unsafe fn request_write(
         &self,
         offset: u64,
         len: usize,
     ) -> io::Result<&mut [u8]> {
+        self.landfill.check_writable()?;
         let (lane_nr, offset) = Self::lane_nr_and_ofs(offset);
         let lane_size = Self::lane_size(lane_nr);
 
         if offset + len as u64 > lane_size {
-            Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Cannot write between lanes",
-            ))
+            Err(io::Error::other("Cannot write between lanes"))
         } else {
-            let mut lane_initialized = self.lanes[lane_nr].get();
+            let lane = self.created_lane(lane_nr)?;
+            let offset = helpers::usize_from(offset)?;
+            Ok(&mut lane.bytes_mut()[offset..][..len])
+        }
+    }
 
-            // Make sure the lane is initialized
-            while lane_initialized.is_none() {
-                let lf = self.landfill.branch(format!("{:02x}", lane_nr));
-                if let Some(lane_file) = lf.map_file_create(lane_size)? {
-                    // Since we got the file from the landfill, we can be sure
-                    // that no other thread has been able to progress here
-                    //
-                    // Initializing here will thus always succeed, and we can ignore
-                    // the `Result` of setting the once lock
-                    let _ = self.lanes[lane_nr].set(lane_file);
-                    lane_initialized =
-                        Some(self.lanes[lane_nr].get().expect("Just set above"))
-                } else {
-                    // spin
-                    lane_initialized = self.lanes[lane_nr].get();
-                }
+    /// A pointer to the `len` bytes at `offset`, for atomic cells
+    ///
+    /// Unlike `request_write`, no reference to the bytes is created, so the
+    /// same cell can be used from several threads at once. The lane is
+    /// created if `create` is set, otherwise `None` is returned for lanes
+    /// that were never written
+    pub fn cell_ptr(
+        &self,
+        offset: u64,
+        len: usize,
+        create: bool,
+    ) -> io::Result<Option<*const u8>> {
+        let (lane_nr, offset) = Self::lane_nr_and_ofs(offset);
+
+        if offset + len as u64 > Self::lane_size(lane_nr) {
+            return Err(io::Error::other("Cannot access cells between lanes"));
+        }
+
+        let lane = if create {
+            self.landfill.check_writable()?;
+            self.created_lane(lane_nr)?
+        } else {
+            match self.mapped_lane(lane_nr)? {
+                Some(lane) => lane,
+                None => return Ok(None),
             }
+        };
+
+        // lanes of read-only landfills may be mapped shorter than their size
+        let offset = helpers::usize_from(offset)?;
+        if offset + len > lane.len() {
+            return Ok(None);
+        }
+        Ok(Some(unsafe { lane.as_ptr().add(offset) }))
+    }
 
-            let lane_initialized = lane_initialized
-                .expect("Above logic will always assure an initialized lane");
+    // Returns the mapping of a lane, creating its file if needed
+    fn created_lane(&self, lane_nr: usize) -> io::Result<&MappedFile> {
+        let mut lane_initialized = self.lanes[lane_nr].get();
 
-            return Ok(
-                &mut lane_initialized.bytes_mut()[offset as usize..][..len]
-            );
+        // Make sure the lane is initialized
+        while lane_initialized.is_none() {
+            self.landfill.failpoint(Failpoint::LaneCreation)?;
+            let lf = self.landfill.branch(format!("{:02x}", lane_nr));
+            if let Some(lane_file) =
+                lf.map_file_create(Self::lane_size(lane_nr))?
+            {
+                // Since we got the file from the landfill, we can be sure
+                // that no other thread has been able to progress here
+                //
+                // Initializing here will thus always succeed, and we can ignore
+                // the `Result` of setting the once lock
+                let _ = self.lanes[lane_nr].set(lane_file);
+                lane_initialized =
+                    Some(self.lanes[lane_nr].get().expect("Just set above"))
+            } else {
+                // spin
+                lane_initialized = self.lanes[lane_nr].get();
+            }
+        }
+
+        Ok(lane_initialized
+            .expect("Above logic will always assure an initialized lane"))
+    }
+
+    /// Copy `bytes` to `offset`, splitting the copy at lane boundaries
+    ///
+    /// Like `request_write`, nothing may reference the range while it is
+    /// written. This holds for space the caller has just reserved, or guards
+    /// with a lock of its own
+    pub fn write_at(&self, offset: u64, bytes: &[u8]) -> io::Result<()> {
+        let mut written = 0;
+
+        while written < bytes.len() {
+            let pos = offset + written as u64;
+            let in_lane = Self::lane_remaining(pos)
+                .min((bytes.len() - written) as u64)
+                as usize;
+
+            let dst = unsafe { self.request_write(pos, in_lane)? };
+            dst.copy_from_slice(&bytes[written..][..in_lane]);
+            written += in_lane;
         }
+        Ok(())
     }
 
     pub fn read(&self, offset: u64, len: u32) -> Option<&[u8]> {
+        self.try_read(offset, len).ok().flatten()
+    }
+
+    /// Like `read`, but fails if an existing lane could not be mapped
+    pub fn try_read(&self, offset: u64, len: u32) -> io::Result<Option<&[u8]>> {
         let (lane, offset) = Self::lane_nr_and_ofs(offset);
         let lane_size = Self::lane_size(lane);
 
         if offset + len as u64 > lane_size {
             // We cannot read in lane boundaries
-            None
-        } else if let Some(lane) = self.lanes[lane].get() {
-            let lane_bytes = lane.as_ref();
-            Some(&lane_bytes[offset as usize..offset as usize + len as usize])
+            Ok(None)
+        } else if let Some(lane) = self.mapped_lane(lane)? {
+            let offset = helpers::usize_from(offset)?;
+            Ok(lane.as_ref().get(offset..offset + len as usize))
         } else {
-            None
+            Ok(None)
+        }
+    }
+
+    /// Hint that the bytes in `offset..offset + len` will be read soon
+    ///
+    /// Lanes that were never written are skipped
+    pub fn prefetch(&self, offset: u64, len: u64) -> io::Result<()> {
+        let end = offset.saturating_add(len);
+        let mut pos = offset;
+
+        while pos < end {
+            let (lane_nr, lane_offset) = Self::lane_nr_and_ofs(pos);
+            if lane_nr >= N_LANES {
+                break;
+            }
+            let in_lane =
+                (Self::lane_size(lane_nr) - lane_offset).min(end - pos);
+
+            if let Some(lane) = self.mapped_lane(lane_nr)? {
+                // only the mapped part of lanes shortened on close
+                let start = helpers::usize_from(lane_offset)?.min(lane.len());
+                let len = helpers::usize_from(in_lane)?.min(lane.len() - start);
+                if len > 0 {
+                    lane.prefetch(start, len)?;
+                }
+            }
+            pos += in_lane;
+        }
+        Ok(())
+    }
+
+    /// Copy the bytes at `offset` into `buf`
+    ///
+    /// If the lane is not already mapped, the bytes are read from the file
+    /// through a small block cache, without mapping the lane into memory
+    pub fn read_cold(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        let (lane_nr, lane_offset) = Self::lane_nr_and_ofs(offset);
+
+        if lane_offset + buf.len() as u64 > Self::lane_size(lane_nr) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Cannot read between lanes",
+            ));
+        }
+
+        match self.lanes.get(lane_nr).and_then(|lane| lane.get()) {
+            Some(lane) => {
+                let lane_offset = helpers::usize_from(lane_offset)?;
+                let bytes = lane
+                    .as_ref()
+                    .get(lane_offset..lane_offset + buf.len())
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "Cannot read past the end of the lane file",
+                        )
+                    })?;
+                buf.copy_from_slice(bytes);
+                Ok(())
+            }
+            None if self.lane_on_disk(lane_nr) => {
+                let lf = self.landfill.branch(format!("{:02x}", lane_nr));
+                self.cold.read(&lf, lane_nr, offset, buf)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Cannot read unwritten lane",
+            )),
+        }
+    }
+
+    // Returns the mapping of a lane, mapping lanes that exist on disk on
+    // first access
+    fn mapped_lane(&self, lane_nr: usize) -> io::Result<Option<&MappedFile>> {
+        let Some(lane) = self.lanes.get(lane_nr) else {
+            return Ok(None);
+        };
+
+        if let Some(mapped) = lane.get() {
+            return Ok(Some(mapped));
+        }
+
+        if self.lane_on_disk(lane_nr) {
+            let lf = self.landfill.branch(format!("{:02x}", lane_nr));
+            // if another thread mapped the lane concurrently, ours is dropped
+            if let Some(lane_file) =
+                lf.map_file_create(Self::lane_size(lane_nr))?
+            {
+                let _ = lane.set(lane_file);
+            }
+        }
+
+        Ok(lane.get())
+    }
+
+    // Returns true if the lane has a file on disk
+    //
+    // Read-only landfills check for lanes created by the writer since the
+    // store was opened
+    fn lane_on_disk(&self, lane_nr: usize) -> bool {
+        match self.on_disk.get(lane_nr) {
+            Some(true) => true,
+            Some(false) if self.landfill.is_read_only() => self
+                .landfill
+                .branch(format!("{:02x}", lane_nr))
+                .file_exists(),
+            _ => false,
         }
     }
 
@@ -146,17 +350,54 @@ impl DiskBytes {
         }
     }
 
-    fn lane_nr_and_ofs(offset: u64) -> (usize, u64) {
-        let usize_bits = mem::size_of::<usize>() * 8;
+    pub fn lane_nr_and_ofs(offset: u64) -> (usize, u64) {
         let i = offset / FIRST_FILE_SIZE + 1;
-        let lane_nr = usize_bits - i.leading_zeros() as usize - 1;
+        let lane_nr = (u64::BITS - i.leading_zeros() - 1) as usize;
         let offset = offset - (2u64.pow(lane_nr as u32) - 1) * FIRST_FILE_SIZE;
         (lane_nr, offset)
     }
 
-    fn lane_size(lane: usize) -> u64 {
+    pub fn lane_size(lane: usize) -> u64 {
         FIRST_FILE_SIZE * 2u64.pow(lane as u32)
     }
+
+    /// The global offset of element `index` of elements of `size` bytes,
+    /// packed so that no element crosses a lane boundary
+    ///
+    /// Each lane holds as many whole elements as fit into it. Returns None
+    /// if the element is beyond the last lane
+    pub fn packed_offset(mut index: u64, size: u64) -> Option<u64> {
+        if FIRST_FILE_SIZE.is_multiple_of(size) {
+            // elements fill every lane exactly
+            let offset = index.checked_mul(size)?;
+            let last = N_LANES - 1;
+            return (offset < Self::lane_start(last) + Self::lane_size(last))
+                .then_some(offset);
+        }
+
+        for lane in 0..N_LANES {
+            let per_lane = Self::lane_size(lane) / size;
+            if index < per_lane {
+                return Some(Self::lane_start(lane) + index * size);
+            }
+            index -= per_lane;
+        }
+        None
+    }
+
+    /// One past the global offset of the last byte of the highest lane on
+    /// disk, or 0 if no lane exists
+    pub fn end_on_disk(&self) -> u64 {
+        (0..N_LANES)
+            .rev()
+            .find(|lane| self.lane_on_disk(*lane))
+            .map_or(0, |lane| Self::lane_start(lane + 1))
+    }
+
+    /// The global offset of the first byte of `lane`
+    pub fn lane_start(lane: usize) -> u64 {
+        (2u64.pow(lane as u32) - 1) * FIRST_FILE_SIZE
+    }
 }
 
 unsafe impl Send for DiskBytes {}
@@ -175,6 +416,14 @@ mod test {
                 DiskBytes::lane_nr_and_ofs_slow_but_obviously_correct(i),
             );
         }
+
+        // offsets past 4 GiB, which do not fit a 32-bit usize
+        for i in (0..64).map(|shift| (1u64 << 32) * shift + 12345) {
+            assert_eq!(
+                DiskBytes::lane_nr_and_ofs(i),
+                DiskBytes::lane_nr_and_ofs_slow_but_obviously_correct(i),
+            );
+        }
     }
 
     #[test]
@@ -194,6 +443,33 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn write_at_across_lanes() -> io::Result<()> {
+        let lf = Landfill::ephemeral()?;
+        let db: DiskBytes = lf.substructure("diskbytes")?;
+
+        // starts 10 bytes before the end of lane 0, and ends in lane 2
+        let bytes: Vec<u8> = (0..10_000).map(|i| i as u8).collect();
+        let offset = FIRST_FILE_SIZE - 10;
+        db.write_at(offset, &bytes)?;
+
+        assert_eq!(db.read(offset, 10).unwrap(), &bytes[..10]);
+        let lane_1 = DiskBytes::lane_size(1);
+        assert_eq!(
+            db.read(FIRST_FILE_SIZE, lane_1 as u32).unwrap(),
+            &bytes[10..][..lane_1 as usize]
+        );
+        let rest = &bytes[10 + lane_1 as usize..];
+        assert_eq!(
+            db.read(DiskBytes::lane_start(2), rest.len() as u32)
+                .unwrap(),
+            rest
+        );
+
+        db.write_at(0, &[])?;
+        Ok(())
+    }
+
     #[test]
     fn find_space() -> io::Result<()> {
         let lf = Landfill::ephemeral()?;
@@ -0,0 +1,86 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::OnceLock;
+
+use parking_lot::Mutex;
+
+use super::bytes::{DiskBytes, N_LANES};
+use crate::Landfill;
+
+// Lanes start at multiples of the block size, so blocks never cross lanes
+pub(crate) const BLOCK_SIZE: u64 = 4096;
+const CACHED_BLOCKS: usize = 16;
+
+/// A small cache of blocks read from lane files that are not mapped
+pub(crate) struct ColdCache {
+    files: [OnceLock<File>; N_LANES],
+    // most recently used blocks first
+    blocks: Mutex<VecDeque<(u64, Box<[u8]>)>>,
+}
+
+impl ColdCache {
+    pub fn new() -> Self {
+        const FILE: OnceLock<File> = OnceLock::new();
+        ColdCache {
+            files: [FILE; N_LANES],
+            blocks: Mutex::new(VecDeque::with_capacity(CACHED_BLOCKS)),
+        }
+    }
+
+    /// Read `buf.len()` bytes at the global `offset` of lane `lane_nr`
+    pub fn read(
+        &self,
+        lf: &Landfill,
+        lane_nr: usize,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> io::Result<()> {
+        let lane_start = DiskBytes::lane_start(lane_nr);
+        let mut blocks = self.blocks.lock();
+
+        let mut pos = 0;
+        while pos < buf.len() {
+            let global = offset + pos as u64;
+            let block_offset = global - (global % BLOCK_SIZE);
+            let in_block = (global - block_offset) as usize;
+
+            let cached =
+                blocks.iter().position(|(ofs, _)| *ofs == block_offset);
+            let block = match cached {
+                Some(i) => blocks.remove(i).expect("index is in range"),
+                None => {
+                    let file = self.file(lf, lane_nr)?;
+                    let mut block =
+                        vec![0; BLOCK_SIZE as usize].into_boxed_slice();
+                    let mut file_ref = file;
+                    file_ref
+                        .seek(SeekFrom::Start(block_offset - lane_start))?;
+                    file_ref.read_exact(&mut block)?;
+                    (block_offset, block)
+                }
+            };
+
+            let n = (buf.len() - pos).min(BLOCK_SIZE as usize - in_block);
+            buf[pos..pos + n].copy_from_slice(&block.1[in_block..][..n]);
+            pos += n;
+
+            blocks.push_front(block);
+            blocks.truncate(CACHED_BLOCKS);
+        }
+
+        Ok(())
+    }
+
+    fn file(&self, lf: &Landfill, lane_nr: usize) -> io::Result<&File> {
+        if let Some(file) = self.files[lane_nr].get() {
+            return Ok(file);
+        }
+
+        let file = lf.open_file_read()?.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "Lane file missing")
+        })?;
+        let _ = self.files[lane_nr].set(file);
+        Ok(self.files[lane_nr].get().expect("Just set above"))
+    }
+}
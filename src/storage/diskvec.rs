@@ -0,0 +1,128 @@
+use std::io;
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+
+use super::randomaccess::{RandomAccessGuard, RandomAccessWriteGuard};
+use crate::{
+    FormatHeader, GuardedLandfill, Journal, RandomAccess, Substructure,
+    VerifyReport,
+};
+
+/// A typed, growable list of `T` on disk
+///
+/// Unlike `AppendLog`, elements can be modified and popped after being
+/// pushed. The length is persisted in a journal, and only grows once an
+/// element has been written, so a crash never exposes a half-pushed element.
+pub struct DiskVec<T> {
+    values: RandomAccess<T>,
+    len: Journal<u64>,
+}
+
+impl<T> Substructure for DiskVec<T> {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(DiskVec {
+            values: lf.substructure("values")?,
+            len: lf.substructure("len")?,
+        })
+    }
+
+    fn format() -> Option<FormatHeader> {
+        Some(FormatHeader::new("disk_vec", mem::size_of::<T>(), 1))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.values.flush()?;
+        self.len.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.values.close()?;
+        self.len.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.values.size_on_disk() + self.len.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.values.verify()?,
+            self.len.verify()?,
+        ]))
+    }
+}
+
+impl<T> DiskVec<T>
+where
+    T: Zeroable + Pod,
+{
+    /// Append a value to the end of the list, returning its index
+    pub fn push(&self, value: T) -> io::Result<usize> {
+        self.len.try_update(|len| {
+            let index = *len as usize;
+            *self.values.get_mut(index)? = value;
+            *len += 1;
+            Ok(index)
+        })
+    }
+
+    /// Remove the last value of the list and return it
+    pub fn pop(&self) -> Option<T> {
+        self.len.modify(|len| {
+            let index = len.checked_sub(1)?;
+            let value = *self.values.get_raw(index as usize)?;
+            *len = index;
+            Some(value)
+        })
+    }
+
+    /// Get a reference to the value at `index`
+    ///
+    /// Returns None if `index` is out of bounds
+    pub fn get(&self, index: usize) -> Option<RandomAccessGuard<'_, T>> {
+        if index < self.len() {
+            self.values.get_raw(index)
+        } else {
+            None
+        }
+    }
+
+    /// Get a mutable reference to the value at `index`
+    pub fn get_mut(
+        &self,
+        index: usize,
+    ) -> io::Result<RandomAccessWriteGuard<'_, T>> {
+        if index < self.len() {
+            self.values.get_mut(index)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Index out of bounds",
+            ))
+        }
+    }
+
+    /// Set the value at `index`
+    pub fn set(&self, index: usize, value: T) -> io::Result<()> {
+        *self.get_mut(index)? = value;
+        Ok(())
+    }
+
+    /// The number of values in the list
+    pub fn len(&self) -> usize {
+        self.len.current() as usize
+    }
+
+    /// Returns true if the list is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over copies of all values in the list, in order
+    ///
+    /// Values pushed after the iterator was created are not included
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        (0..self.len()).map_while(|index| self.get(index).map(|v| *v))
+    }
+}
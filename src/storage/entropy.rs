@@ -9,11 +9,14 @@ use bytemuck_derive::*;
 use rand::Rng;
 use seahash::SeaHasher;
 
-/// A once-initialized file carrying pseudorandom data
+/// A persistent set of pseudorandom data
 ///
 /// This can be used to have a persistant source of entropy, that will be
 /// the same each time the database is opened, but differ between databases
 ///
+/// The entropy of each branch is derived from a single root seed per
+/// landfill, keyed by the branch name
+///
 /// Useful for DOS-resistant hashmaps etc
 #[repr(C)]
 #[derive(Clone, Copy, Zeroable, Pod, Debug)]
@@ -22,15 +25,26 @@ pub struct Entropy([u64; 4]);
 /// A Tag that can be used to loosely identify this specific instantiation of
 /// entropy.
 #[repr(C)]
-#[derive(Clone, Copy, PartialEq, Eq, Zeroable, Pod)]
+#[derive(Clone, Copy, PartialEq, Eq, Zeroable, Pod, Debug)]
 pub struct Tag(u32);
 
 impl Substructure for Entropy {
     fn init(lf: GuardedLandfill) -> io::Result<Self> {
-        lf.get_static_or_init(|| {
-            let mut rng = rand::thread_rng();
-            Entropy(rng.gen())
-        })
+        if lf.file_exists() {
+            // entropy files from before root seeds were introduced
+            return lf.get_static_or_init(|| {
+                unreachable!("only called when the file is missing")
+            });
+        }
+
+        let root = Entropy::from_seed(lf.root_seed()?);
+        let name = lf.full_name_ref();
+        Ok(Entropy([
+            root.checksum(&(name, 0u8)),
+            root.checksum(&(name, 1u8)),
+            root.checksum(&(name, 2u8)),
+            root.checksum(&(name, 3u8)),
+        ]))
     }
 
     fn flush(&self) -> io::Result<()> {
@@ -39,6 +53,25 @@ impl Substructure for Entropy {
 }
 
 impl Entropy {
+    /// Create an entropy set from a fixed seed
+    ///
+    /// The same seed gives the same entropy on every platform
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        let mut words = [0u64; 4];
+        for (word, chunk) in words.iter_mut().zip(seed.chunks_exact(8)) {
+            *word = u64::from_le_bytes(chunk.try_into().expect("8 bytes"));
+        }
+        Entropy(words)
+    }
+
+    /// Create an entropy set from the thread-local random number generator
+    ///
+    /// Unlike entropy opened as a substructure, it is not derived from the
+    /// root seed of any landfill
+    pub fn random() -> Self {
+        Entropy(rand::thread_rng().gen())
+    }
+
     /// Calculate a checksum of value `T` specific to this entropy set
     pub fn checksum<T: Hash>(&self, t: &T) -> u64 {
         let mut hasher =
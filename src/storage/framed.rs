@@ -0,0 +1,284 @@
+use std::hash::Hasher;
+use std::io;
+use std::mem;
+
+use bytemuck_derive::*;
+use seahash::SeaHasher;
+
+use super::bytes::DiskBytes;
+use crate::{AppendOnly, GuardedLandfill, Substructure, VerifyReport};
+
+const FRAME_MAGIC: u32 = 0x454d_5246;
+const FRAME_ALIGNMENT: usize = 8;
+const HEADER_SIZE: usize = mem::size_of::<FrameHeader>();
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct FrameHeader {
+    magic: u32,
+    len: u32,
+    checksum: u64,
+}
+
+impl FrameHeader {
+    fn checksum(payload: &[u8]) -> u64 {
+        let mut hasher = SeaHasher::new();
+        hasher.write_u32(payload.len() as u32);
+        hasher.write(payload);
+        hasher.finish()
+    }
+
+    // Fails with `InvalidInput` for payloads too long for the header
+    fn new(payload: &[u8]) -> io::Result<Self> {
+        let len = u32::try_from(payload.len()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Frames must be shorter than 4 GiB",
+            )
+        })?;
+        Ok(FrameHeader {
+            magic: FRAME_MAGIC,
+            len,
+            checksum: Self::checksum(payload),
+        })
+    }
+}
+
+/// An `AppendOnly` store where each record is framed with a small header
+///
+/// The header holds a magic number, the length and a checksum of the record,
+/// which makes the store self-describing. Records can be iterated without
+/// any external index, and on opening, valid records written past the last
+/// journaled writehead are recovered.
+pub struct FramedAppendOnly {
+    data: AppendOnly,
+}
+
+impl Substructure for FramedAppendOnly {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        let framed = FramedAppendOnly {
+            data: lf.substructure("data")?,
+        };
+
+        // unless closed cleanly, the journal might lag behind the data
+        // actually written, find the true end of valid frames
+        if !framed.data.closed_cleanly() {
+            let mut end = framed.data.writehead();
+            while let Some((offset, payload)) = framed.frame_after(end) {
+                end = offset + (HEADER_SIZE + payload.len()) as u64;
+            }
+            framed.data.advance_writehead(end);
+        }
+
+        Ok(framed)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.data.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.data.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.data.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        self.data.verify()
+    }
+}
+
+impl FramedAppendOnly {
+    /// Write a framed record into the store, returning its offset
+    pub fn write(&self, bytes: &[u8]) -> io::Result<u64> {
+        let header = FrameHeader::new(bytes)?;
+        let (offset, slice) = self
+            .data
+            .reserve(HEADER_SIZE + bytes.len(), FRAME_ALIGNMENT)?;
+
+        slice[HEADER_SIZE..].copy_from_slice(bytes);
+        slice[..HEADER_SIZE].copy_from_slice(bytemuck::bytes_of(&header));
+        self.data.written(offset, slice.len());
+
+        Ok(offset)
+    }
+
+    /// Get the record at `offset`
+    ///
+    /// Returns `None` if there is no valid frame at this offset
+    pub fn get(&self, offset: u64) -> Option<&[u8]> {
+        let header_bytes = self.data.read(offset, HEADER_SIZE as u32)?;
+        let header: FrameHeader = bytemuck::pod_read_unaligned(header_bytes);
+
+        if header.magic != FRAME_MAGIC {
+            return None;
+        }
+
+        let payload =
+            self.data.read(offset + HEADER_SIZE as u64, header.len)?;
+
+        if FrameHeader::checksum(payload) == header.checksum {
+            Some(payload)
+        } else {
+            None
+        }
+    }
+
+    /// Get the record at `offset`
+    ///
+    /// Fails with `InvalidInput` if the offset is past the written data, and
+    /// with `InvalidData` if the frame is corrupted
+    pub fn try_get(&self, offset: u64) -> io::Result<&[u8]> {
+        let writehead = self.data.writehead();
+        let corrupted = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Corrupted record at offset {offset}"),
+            )
+        };
+
+        let past_written = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Offset past the written records",
+            )
+        };
+        offset
+            .checked_add(HEADER_SIZE as u64)
+            .filter(|end| *end <= writehead)
+            .ok_or_else(past_written)?;
+        let header_bytes = self
+            .data
+            .try_read(offset, HEADER_SIZE as u32)?
+            .ok_or_else(past_written)?;
+        let header: FrameHeader = bytemuck::pod_read_unaligned(header_bytes);
+
+        if header.magic != FRAME_MAGIC {
+            return Err(corrupted());
+        }
+
+        let start = offset + HEADER_SIZE as u64;
+        if start + header.len as u64 > writehead {
+            return Err(corrupted());
+        }
+
+        let payload = self
+            .data
+            .try_read(start, header.len)?
+            .ok_or_else(corrupted)?;
+        if FrameHeader::checksum(payload) == header.checksum {
+            Ok(payload)
+        } else {
+            Err(corrupted())
+        }
+    }
+
+    // Get the record at `offset` without verifying its checksum, for
+    // records that have been verified before
+    //
+    // The checksum is verified anyway if the landfill verifies reads
+    pub(crate) fn get_trusted(&self, offset: u64) -> io::Result<&[u8]> {
+        if self.data.landfill().verifies_reads() {
+            return self.try_get(offset);
+        }
+
+        let unreadable =
+            || io::Error::new(io::ErrorKind::InvalidInput, "Unreadable record");
+        let header_bytes = self
+            .data
+            .try_read(offset, HEADER_SIZE as u32)?
+            .ok_or_else(unreadable)?;
+        let header: FrameHeader = bytemuck::pod_read_unaligned(header_bytes);
+        self.data
+            .try_read(offset + HEADER_SIZE as u64, header.len)?
+            .ok_or_else(unreadable)
+    }
+
+    /// Iterate over all valid records in the store, with their offsets
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &[u8])> {
+        self.iter_from(0)
+    }
+
+    /// Iterate over all valid records written at or after `offset`
+    ///
+    /// Corrupted records are skipped, iteration picks up again at the next
+    /// valid record after them
+    pub fn iter_from(&self, offset: u64) -> impl Iterator<Item = (u64, &[u8])> {
+        let end = self.data.writehead();
+        let mut pos = offset;
+        std::iter::from_fn(move || {
+            let (offset, payload) = self.next_frame(pos, end)?;
+            pos = offset + (HEADER_SIZE + payload.len()) as u64;
+            Some((offset, payload))
+        })
+    }
+
+    // The offset just past the frame at `offset` holding `payload`
+    pub(crate) fn end_of(offset: u64, payload: &[u8]) -> u64 {
+        offset + (HEADER_SIZE + payload.len()) as u64
+    }
+
+    // Find the first valid frame at or after `pos` that ends by `end`
+    //
+    // Every aligned offset is tried in turn, so that a corrupted frame, whose
+    // length cannot be trusted, only hides itself. Between valid frames this
+    // only steps over padding and the unused tails of lanes, lanes that were
+    // never written are skipped as a whole
+    fn next_frame(&self, pos: u64, end: u64) -> Option<(u64, &[u8])> {
+        let mut candidate = pos;
+        loop {
+            candidate = DiskBytes::find_space_for(
+                candidate,
+                HEADER_SIZE,
+                FRAME_ALIGNMENT,
+            );
+            if candidate + HEADER_SIZE as u64 > end {
+                return None;
+            }
+
+            if self.data.read(candidate, HEADER_SIZE as u32).is_none() {
+                candidate += DiskBytes::lane_remaining(candidate);
+                continue;
+            }
+
+            if let Some(payload) = self.get(candidate) {
+                return (Self::end_of(candidate, payload) <= end)
+                    .then_some((candidate, payload));
+            }
+        }
+    }
+
+    // Find the frame that a write at `pos` would have produced
+    //
+    // Frames that do not fit into the remainder of a lane are placed in a
+    // later lane, so each lane is tried in order, checking that the frame
+    // found is where the writer would have put it.
+    fn frame_after(&self, pos: u64) -> Option<(u64, &[u8])> {
+        let mut lane_pos = pos;
+        loop {
+            let candidate = DiskBytes::find_space_for(
+                lane_pos,
+                HEADER_SIZE,
+                FRAME_ALIGNMENT,
+            );
+
+            // stop at the first lane that has not been written
+            self.data.read(candidate, HEADER_SIZE as u32)?;
+
+            if let Some(payload) = self.get(candidate) {
+                let expected = DiskBytes::find_space_for(
+                    pos,
+                    HEADER_SIZE + payload.len(),
+                    FRAME_ALIGNMENT,
+                );
+                if expected == candidate {
+                    return Some((candidate, payload));
+                }
+            }
+
+            lane_pos += DiskBytes::lane_remaining(lane_pos);
+        }
+    }
+}
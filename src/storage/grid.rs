@@ -0,0 +1,200 @@
+use std::io;
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+use parking_lot::RwLock;
+
+use crate::{
+    FormatHeader, GuardedLandfill, Journal, RandomAccess, Substructure,
+    VerifyReport,
+};
+
+// number of lock shards shared by the tiles
+const TILE_LOCKS: usize = 64;
+
+// the number of columns, and the rows and columns of each tile, 0 while
+// the shape is not set
+type Shape = [u64; 3];
+
+const COLS: usize = 0;
+const TILE_ROWS: usize = 1;
+const TILE_COLS: usize = 2;
+
+/// A two dimensional array of `T` on disk, with a fixed number of columns
+///
+/// Cells are grouped into rectangular tiles, stored one after the other,
+/// each tile in row-major order. Cells close to each other thus tend to
+/// share pages, and each tile can be read or updated as a whole under its
+/// own lock, while other tiles are accessed concurrently.
+///
+/// The shape must be set with `set_shape` before use, and is persisted.
+pub struct Grid<T> {
+    values: RandomAccess<T>,
+    shape: Journal<Shape>,
+    locks: Box<[RwLock<()>]>,
+}
+
+impl<T> Substructure for Grid<T> {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(Grid {
+            values: lf.substructure("values")?,
+            shape: lf.substructure("shape")?,
+            locks: (0..TILE_LOCKS).map(|_| RwLock::new(())).collect(),
+        })
+    }
+
+    fn format() -> Option<FormatHeader> {
+        Some(FormatHeader::new("grid", mem::size_of::<T>(), 1))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.values.flush()?;
+        self.shape.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.values.close()?;
+        self.shape.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.values.size_on_disk() + self.shape.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.values.verify()?,
+            self.shape.verify()?,
+        ]))
+    }
+}
+
+impl<T> Grid<T>
+where
+    T: Zeroable + Pod,
+{
+    /// Set the number of columns, and the size of the tiles
+    ///
+    /// The number of rows is unbounded. The shape cannot be changed once
+    /// set, setting it again to the same values is a no-op
+    pub fn set_shape(
+        &self,
+        cols: u64,
+        tile_rows: u64,
+        tile_cols: u64,
+    ) -> io::Result<()> {
+        if cols == 0 || tile_rows == 0 || tile_cols == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Grid dimensions must be non-zero",
+            ));
+        }
+
+        let new = [cols, tile_rows, tile_cols];
+        self.shape.update(|shape| {
+            if shape[COLS] == 0 {
+                *shape = new;
+            }
+        });
+
+        if self.shape.current() != new {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "Grid already has a different shape",
+            ));
+        }
+        Ok(())
+    }
+
+    /// The number of columns, 0 if the shape is not set
+    pub fn cols(&self) -> u64 {
+        self.shape.current()[COLS]
+    }
+
+    /// The number of rows and columns of each tile
+    pub fn tile_shape(&self) -> (u64, u64) {
+        let shape = self.shape.current();
+        (shape[TILE_ROWS], shape[TILE_COLS])
+    }
+
+    /// Get a copy of the cell at `row` and `col`
+    ///
+    /// Cells never written to are zeroed. Returns `None` if the column is
+    /// out of bounds
+    pub fn get(&self, row: u64, col: u64) -> Option<T> {
+        let (tile, index) = self.locate(row, col).ok()?;
+        let _lock = self.lock(tile).read();
+        Some(
+            self.values
+                .get_raw(index)
+                .map(|v| *v)
+                .unwrap_or_else(T::zeroed),
+        )
+    }
+
+    /// Set the cell at `row` and `col`
+    pub fn set(&self, row: u64, col: u64, value: T) -> io::Result<()> {
+        self.update(row, col, |cell| *cell = value)
+    }
+
+    /// Update the cell at `row` and `col` under the lock of its tile
+    pub fn update<F, R>(&self, row: u64, col: u64, f: F) -> io::Result<R>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        let (tile, index) = self.locate(row, col)?;
+        let _lock = self.lock(tile).write();
+        let mut cell = self.values.get_mut(index)?;
+        Ok(f(&mut cell))
+    }
+
+    /// Get a copy of the tile containing the cell at `row` and `col`, in
+    /// row-major order
+    ///
+    /// Columns of edge tiles past the last column of the grid are included
+    pub fn tile(&self, row: u64, col: u64) -> io::Result<Vec<T>> {
+        let (tile, _) = self.locate(row, col)?;
+        let (tile_rows, tile_cols) = self.tile_shape();
+        let len = (tile_rows * tile_cols) as usize;
+        let start = tile as usize * len;
+
+        let _lock = self.lock(tile).read();
+        Ok((start..start + len)
+            .map(|i| {
+                self.values.get_raw(i).map(|v| *v).unwrap_or_else(T::zeroed)
+            })
+            .collect())
+    }
+
+    // The tile of a cell, and its index in the values
+    fn locate(&self, row: u64, col: u64) -> io::Result<(u64, usize)> {
+        let [cols, tile_rows, tile_cols] = self.shape.current();
+        if col >= cols {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Column out of bounds",
+            ));
+        }
+
+        let tiles_per_row = cols.div_ceil(tile_cols);
+        let tile = (row / tile_rows)
+            .checked_mul(tiles_per_row)
+            .and_then(|t| t.checked_add(col / tile_cols));
+        let index = tile.and_then(|tile| {
+            let within = (row % tile_rows) * tile_cols + col % tile_cols;
+            tile.checked_mul(tile_rows * tile_cols)?.checked_add(within)
+        });
+
+        match (tile, index.and_then(|i| usize::try_from(i).ok())) {
+            (Some(tile), Some(index)) => Ok((tile, index)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Row out of bounds",
+            )),
+        }
+    }
+
+    fn lock(&self, tile: u64) -> &RwLock<()> {
+        &self.locks[tile as usize % TILE_LOCKS]
+    }
+}
@@ -0,0 +1,74 @@
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use parking_lot::{Condvar, Mutex, MutexGuard};
+
+#[derive(Default)]
+struct CommitState {
+    // tickets handed out to committers, and the last one made durable
+    requested: u64,
+    synced: u64,
+    leading: bool,
+    window: Duration,
+    syncs: u64,
+}
+
+/// Batches concurrent commits into a single flush
+///
+/// The first committer becomes the leader, waits for the commit window and
+/// flushes on behalf of everyone who committed before the flush started
+#[derive(Default)]
+pub(crate) struct GroupCommit {
+    state: Mutex<CommitState>,
+    synced: Condvar,
+}
+
+impl GroupCommit {
+    pub fn set_window(&self, window: Duration) {
+        self.state.lock().window = window;
+    }
+
+    pub fn syncs(&self) -> u64 {
+        self.state.lock().syncs
+    }
+
+    /// Returns once everything written before the call has been flushed
+    pub fn commit<F>(&self, flush: F) -> io::Result<()>
+    where
+        F: Fn() -> io::Result<()>,
+    {
+        let mut state = self.state.lock();
+        state.requested += 1;
+        let ticket = state.requested;
+
+        loop {
+            if state.synced >= ticket {
+                return Ok(());
+            }
+
+            if !state.leading {
+                state.leading = true;
+
+                let window = state.window;
+                if !window.is_zero() {
+                    MutexGuard::unlocked(&mut state, || thread::sleep(window));
+                }
+
+                let target = state.requested;
+                let res = MutexGuard::unlocked(&mut state, &flush);
+
+                state.leading = false;
+                if res.is_ok() {
+                    state.synced = target;
+                    state.syncs += 1;
+                }
+                // on failure, one of the waiters takes over and retries
+                self.synced.notify_all();
+                return res;
+            }
+
+            self.synced.wait(&mut state);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::ops::{Deref, DerefMut};
+
+use bytemuck::{Pod, Zeroable};
+use zerocopy::{FromBytes, FromZeros, Immutable, IntoBytes};
+
+/// A wrapper storing `zerocopy` types wherever a `Pod` type is expected
+///
+/// `FromBytes`, `IntoBytes` and `Immutable` together give the same guarantees
+/// as `Pod`, so `RandomAccess<ZeroCopy<T>>`, `Journal<ZeroCopy<T>>` and the
+/// maps work with types that only derive the `zerocopy` traits
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ZeroCopy<T>(pub T);
+
+impl<T> ZeroCopy<T> {
+    /// Unwrap the inner value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+// SAFETY: `FromBytes` implies every bit pattern, including all zeroes, is a
+// valid `T`
+unsafe impl<T> Zeroable for ZeroCopy<T> where T: FromBytes {}
+
+// SAFETY: `IntoBytes` rules out padding, `Immutable` rules out interior
+// mutability, and `ZeroCopy` is a transparent wrapper
+unsafe impl<T> Pod for ZeroCopy<T> where
+    T: FromBytes + IntoBytes + Immutable + Copy + 'static
+{
+}
+
+impl<T: FromZeros> Default for ZeroCopy<T> {
+    fn default() -> Self {
+        ZeroCopy(T::new_zeroed())
+    }
+}
+
+impl<T> From<T> for ZeroCopy<T> {
+    fn from(t: T) -> Self {
+        ZeroCopy(t)
+    }
+}
+
+impl<T> Deref for ZeroCopy<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for ZeroCopy<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
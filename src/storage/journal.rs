@@ -1,5 +1,8 @@
+use std::cmp::Ordering;
+use std::convert::Infallible;
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
+use std::ops::Range;
 use std::{io, mem};
 
 use bytemuck::{Pod, Zeroable};
@@ -7,15 +10,30 @@ use bytemuck_derive::*;
 use parking_lot::Mutex;
 use seahash::SeaHasher;
 
-use crate::{GuardedLandfill, MappedFile, Substructure};
+use crate::disk::Failpoint;
+use crate::helpers;
+use crate::{
+    FormatHeader, GuardedLandfill, Landfill, MappedFile, Substructure,
+    VerifyReport,
+};
 
 // journal is one page maximum
 const JOURNAL_SIZE: usize = 4096;
 
+// entries carry sequence numbers from this version of the format on, data
+// without a format header predates them
+const SEQUENCE_VERSION: u32 = 2;
+const LEGACY_VERSION: u32 = 1;
+
+// Compares values in the order they were written, for migrating journals
+// from before sequence numbers
+type LegacyOrder<T> = fn(&T, &T) -> Ordering;
+
 #[derive(Clone, Copy, Zeroable, Pod)]
 #[repr(C, packed)]
 struct JournalEntry<T> {
     checksum: u64,
+    sequence: u64,
     value: T,
 }
 
@@ -24,20 +42,25 @@ where
     T: Hash + Pod,
 {
     #[inline(always)]
-    fn checksum(value: &T) -> u64 {
+    fn checksum(sequence: u64, value: &T) -> u64 {
         let mut hasher = SeaHasher::new();
+        sequence.hash(&mut hasher);
         value.hash(&mut hasher);
         hasher.finish()
     }
 
-    fn new(value: T) -> Self {
-        let checksum = Self::checksum(&value);
-        JournalEntry { value, checksum }
+    fn new(sequence: u64, value: T) -> Self {
+        let checksum = Self::checksum(sequence, &value);
+        JournalEntry {
+            checksum,
+            sequence,
+            value,
+        }
     }
 
     fn get(&self) -> Option<T> {
         let value = self.value;
-        if Self::checksum(&value) == self.checksum {
+        if Self::checksum(self.sequence, &value) == self.checksum {
             Some(value)
         } else {
             None
@@ -45,9 +68,42 @@ where
     }
 }
 
+// The entry layout before sequence numbers were introduced, only used to
+// migrate journals written in the old format
+#[derive(Clone, Copy, Zeroable, Pod)]
+#[repr(C, packed)]
+struct LegacyJournalEntry<T> {
+    checksum: u64,
+    value: T,
+}
+
+impl<T> LegacyJournalEntry<T>
+where
+    T: Hash + Pod,
+{
+    fn is_valid(&self) -> bool {
+        let value = self.value;
+        let mut hasher = SeaHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish() == self.checksum
+    }
+}
+
+// The latest entry written to a register
+#[derive(Clone, Copy)]
+struct Head {
+    index: usize,
+    sequence: u64,
+}
+
+// The page is split evenly between a number of registers, each rotating
+// through its own set of entries
 struct JournalInner<T> {
     mapping: MappedFile,
-    latest_entry_index: usize,
+    entries_per_register: usize,
+    heads: Vec<Option<Head>>,
+    // bytes written since the last flush
+    dirty: Option<Range<usize>>,
     _marker: PhantomData<T>,
 }
 
@@ -55,11 +111,60 @@ struct JournalInner<T> {
 ///
 /// Useful for keeping track of writeheads into other collections, specifically
 /// `AppendOnly`
+///
+/// Values whose ordering does not follow their age can be journaled as well,
+/// using `modify` and `set`, since recovery is based on the sequence of
+/// writes rather than on the values themselves.
+///
+/// Journals written before entries carried sequence numbers only know the
+/// order of their values. `Landfill::substructure` fails with `InvalidData`
+/// on such data, `Journal::open_migrating` rewrites it instead.
 pub struct Journal<T>(Mutex<JournalInner<T>>);
 
 impl<T> Journal<T>
 where
-    T: Pod + Clone + Hash + Ord + Default,
+    T: Pod + Hash + Default,
+{
+    /// Returns the current value of the journal
+    pub fn current(&self) -> T {
+        self.0.lock().current(0)
+    }
+
+    /// Takes a closure with mutable access to the guarded value
+    ///
+    /// Unlike `update`, no ordering is enforced between the old and new value
+    pub fn modify<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        self.0.lock().modify(0, f)
+    }
+
+    /// Returns the current value of the journal
+    ///
+    /// If the landfill verifies reads, the entry is checked against its
+    /// checksum, failing with `InvalidData` if it has been corrupted
+    pub fn try_current(&self) -> io::Result<T> {
+        self.0.lock().try_current(0)
+    }
+
+    /// Set the value of the journal
+    pub fn set(&self, value: T) {
+        self.modify(|v| *v = value)
+    }
+
+    /// Re-read the newest entry from the file
+    ///
+    /// Picks up entries written by another process, for journals opened
+    /// through `Landfill::open_read_only`
+    pub fn refresh(&self) {
+        self.0.lock().load_heads()
+    }
+}
+
+impl<T> Journal<T>
+where
+    T: Pod + Hash + Ord + Default,
 {
     /// Takes a closure with mutable access to the guarded value
     ///
@@ -71,79 +176,578 @@ where
     where
         F: FnOnce(&mut T) -> R,
     {
-        self.0.lock().update(f)
+        self.0.lock().update(0, f)
+    }
+
+    /// Apply a batch of updates in order, writing a single journal entry
+    ///
+    /// PANICKING
+    ///
+    /// Like `update`, this method panics if any update makes the value
+    /// compare less than before it.
+    pub fn update_many<I, F, R>(&self, updates: I) -> Vec<R>
+    where
+        I: IntoIterator<Item = F>,
+        F: FnOnce(&mut T) -> R,
+    {
+        self.0.lock().update_many(0, updates)
+    }
+
+    /// Takes a fallible closure with mutable access to the guarded value
+    ///
+    /// If the closure returns an error, the journal is left untouched.
+    ///
+    /// PANICKING
+    ///
+    /// Like `update`, this method panics if the updated value compares less
+    /// than the old one.
+    pub fn try_update<F, R, E>(&self, f: F) -> Result<R, E>
+    where
+        F: FnOnce(&mut T) -> Result<R, E>,
+    {
+        self.0.lock().try_update(0, f)
+    }
+
+    /// Takes a closure with mutable access to the guarded value
+    ///
+    /// Instead of panicking, this returns an error if the updated value
+    /// compares less than the old one, leaving the journal untouched.
+    pub fn checked_update<F, R>(&self, f: F) -> io::Result<R>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        self.0.lock().checked_update(0, f)
+    }
+}
+
+impl<T> Journal<T>
+where
+    T: Pod + Hash + Ord + Default,
+{
+    /// Open the journal `name` like `Landfill::substructure`, migrating data
+    /// written before entries carried sequence numbers
+    ///
+    /// Values could only grow back then, so the greatest valid value is the
+    /// newest, and becomes the first entry of the migrated journal
+    pub fn open_migrating<N: Into<String>>(
+        lf: &Landfill,
+        name: N,
+    ) -> io::Result<Self> {
+        lf.substructure_with(name.into(), |lf| {
+            let legacy = lf.format_version() == Some(LEGACY_VERSION);
+            let order = legacy.then_some(T::cmp as LegacyOrder<T>);
+            Ok(Journal(Mutex::new(JournalInner::init(
+                lf, 1, legacy, order,
+            )?)))
+        })
     }
 }
 
 impl<T> Substructure for Journal<T>
 where
-    T: Zeroable + Pod + Default + Hash + Ord,
+    T: Zeroable + Pod + Default + Hash,
 {
     fn init(lf: GuardedLandfill) -> io::Result<Self> {
-        if let Some(mapping) = lf.map_file_create(JOURNAL_SIZE as u64)? {
-            let journal_entry_slice = unsafe { mapping.bytes_mut() };
-            let journal_entries: &mut [JournalEntry<T>] =
-                bytemuck::cast_slice_mut(journal_entry_slice);
-
-            let mut latest_entry_index = 0;
-            let mut candidate = T::default();
-
-            for (i, entry) in journal_entries.iter().enumerate() {
-                if let Some(val) = entry.get() {
-                    if val > candidate {
-                        latest_entry_index = i;
-                        candidate = val;
-                    }
-                }
-            }
+        let legacy = lf.format_version() == Some(LEGACY_VERSION);
+        Ok(Journal(Mutex::new(JournalInner::init(
+            lf, 1, legacy, None,
+        )?)))
+    }
 
-            Ok(Journal(Mutex::new(JournalInner {
-                mapping,
-                latest_entry_index,
-                _marker: PhantomData,
-            })))
-        } else {
-            Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Attempt at mapping the same file twice",
-            ))
-        }
+    fn format() -> Option<FormatHeader> {
+        Some(FormatHeader::new(
+            "journal",
+            mem::size_of::<T>(),
+            SEQUENCE_VERSION,
+        ))
+    }
+
+    fn reads_version(version: u32) -> bool {
+        version == LEGACY_VERSION || version == SEQUENCE_VERSION
+    }
+
+    fn legacy_version() -> Option<u32> {
+        Some(LEGACY_VERSION)
+    }
+
+    // the entries are kept in the file of the journal itself
+    fn data_branches() -> Option<&'static [&'static str]> {
+        Some(&[])
     }
 
     fn flush(&self) -> io::Result<()> {
         self.0.lock().flush()
     }
+
+    fn size_on_disk(&self) -> u64 {
+        self.0.lock().mapping.landfill().size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(self.0.lock().verify())
+    }
 }
 
-impl<T> JournalInner<T>
+/// A set of `N` independent journals sharing a single page
+///
+/// Each journal behaves like a `Journal<T>`, for structures that need to keep
+/// track of several values, without needing a separate file for each one.
+pub struct JournalArray<T, const N: usize>(Mutex<JournalInner<T>>);
+
+impl<T, const N: usize> JournalArray<T, N>
 where
-    T: Pod + Clone + Hash + Ord,
+    T: Pod + Hash + Default,
 {
-    fn update<F, R>(&mut self, f: F) -> R
+    /// Returns the current value of journal `i`
+    pub fn current(&self, i: usize) -> T {
+        assert!(i < N, "JournalArray index out of bounds");
+        self.0.lock().current(i)
+    }
+
+    /// Returns the current value of journal `i`, see `Journal::try_current`
+    pub fn try_current(&self, i: usize) -> io::Result<T> {
+        assert!(i < N, "JournalArray index out of bounds");
+        self.0.lock().try_current(i)
+    }
+
+    /// Takes a closure with mutable access to the value of journal `i`
+    ///
+    /// Unlike `update`, no ordering is enforced between the old and new value
+    pub fn modify<F, R>(&self, i: usize, f: F) -> R
     where
         F: FnOnce(&mut T) -> R,
     {
-        let entries: &mut [JournalEntry<T>] =
-            bytemuck::cast_slice_mut(unsafe { self.mapping.bytes_mut() });
-        let entry = &mut entries[self.latest_entry_index];
+        assert!(i < N, "JournalArray index out of bounds");
+        self.0.lock().modify(i, f)
+    }
 
-        let mut value = entry.value;
-        let old_value = entry.value;
+    /// Set the value of journal `i`
+    pub fn set(&self, i: usize, value: T) {
+        self.modify(i, |v| *v = value)
+    }
 
-        let max_entry =
-            JOURNAL_SIZE / (mem::size_of::<T>() + mem::size_of::<u64>());
-        let next_entry = (self.latest_entry_index + 1) % max_entry;
+    /// Re-read the newest entries of all journals from the file
+    ///
+    /// See `Journal::refresh`
+    pub fn refresh(&self) {
+        self.0.lock().load_heads()
+    }
+}
 
-        let res = f(&mut value);
+impl<T, const N: usize> JournalArray<T, N>
+where
+    T: Pod + Hash + Ord + Default,
+{
+    /// Takes a closure with mutable access to the value of journal `i`
+    ///
+    /// PANICKING
+    ///
+    /// This method will panic if `i` is out of bounds, or if the updated value
+    /// compares less as the old one.
+    pub fn update<F, R>(&self, i: usize, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        assert!(i < N, "JournalArray index out of bounds");
+        self.0.lock().update(i, f)
+    }
 
-        assert!(value >= old_value, "Journal updates must be incremental");
+    /// Apply a batch of updates to journal `i` in order, writing a single
+    /// journal entry
+    ///
+    /// PANICKING
+    ///
+    /// Like `update`, this method panics if `i` is out of bounds or if any
+    /// update makes the value compare less than before it.
+    pub fn update_many<I, F, R>(&self, i: usize, updates: I) -> Vec<R>
+    where
+        I: IntoIterator<Item = F>,
+        F: FnOnce(&mut T) -> R,
+    {
+        assert!(i < N, "JournalArray index out of bounds");
+        self.0.lock().update_many(i, updates)
+    }
 
-        entries[next_entry] = JournalEntry::new(value);
-        self.latest_entry_index = next_entry;
-        res
+    /// Takes a fallible closure with mutable access to the value of journal
+    /// `i`, leaving it untouched if the closure errors
+    ///
+    /// PANICKING
+    ///
+    /// Like `update`, this method panics if `i` is out of bounds or the
+    /// updated value compares less than the old one.
+    pub fn try_update<F, R, E>(&self, i: usize, f: F) -> Result<R, E>
+    where
+        F: FnOnce(&mut T) -> Result<R, E>,
+    {
+        assert!(i < N, "JournalArray index out of bounds");
+        self.0.lock().try_update(i, f)
+    }
+
+    /// Takes a closure with mutable access to the value of journal `i`
+    ///
+    /// Returns an error if the updated value compares less than the old one.
+    pub fn checked_update<F, R>(&self, i: usize, f: F) -> io::Result<R>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        assert!(i < N, "JournalArray index out of bounds");
+        self.0.lock().checked_update(i, f)
+    }
+}
+
+impl<T, const N: usize> Substructure for JournalArray<T, N>
+where
+    T: Zeroable + Pod + Default + Hash,
+{
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(JournalArray(Mutex::new(JournalInner::init(
+            lf, N, false, None,
+        )?)))
+    }
+
+    fn format() -> Option<FormatHeader> {
+        Some(FormatHeader::new(
+            "journal_array",
+            mem::size_of::<[T; N]>(),
+            1,
+        ))
     }
 
     fn flush(&self) -> io::Result<()> {
-        self.mapping.flush()
+        self.0.lock().flush()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.0.lock().mapping.landfill().size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(self.0.lock().verify())
+    }
+}
+
+impl<T> JournalInner<T>
+where
+    T: Pod + Hash + Default,
+{
+    // `legacy` data may predate sequence numbers, and is migrated if
+    // `order` is given
+    fn init(
+        lf: GuardedLandfill,
+        registers: usize,
+        legacy: bool,
+        order: Option<LegacyOrder<T>>,
+    ) -> io::Result<Self> {
+        let entry_size = mem::size_of::<JournalEntry<T>>();
+        let entries_per_register = JOURNAL_SIZE / entry_size / registers.max(1);
+
+        // each register needs at least two entries to rotate between
+        if entries_per_register < 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Journal values too large to fit in one page",
+            ));
+        }
+
+        if let Some(mapping) = lf.map_file_create(JOURNAL_SIZE as u64)? {
+            let mut inner = JournalInner {
+                mapping,
+                entries_per_register,
+                heads: vec![None; registers],
+                dirty: None,
+                _marker: PhantomData,
+            };
+
+            inner.load_heads();
+
+            if legacy && inner.heads.iter().all(Option::is_none) {
+                inner.migrate_legacy(order)?;
+            }
+
+            Ok(inner)
+        } else {
+            Err(io::Error::other("Attempt at mapping the same file twice"))
+        }
+    }
+
+    // Find the newest valid entry of each register, which is the one with
+    // the highest sequence number, regardless of its value
+    fn load_heads(&mut self) {
+        for register in 0..self.heads.len() {
+            let mut head: Option<Head> = None;
+
+            for (index, entry) in self.entries(register).iter().enumerate() {
+                if entry.get().is_some()
+                    && head.is_none_or(|h| entry.sequence > h.sequence)
+                {
+                    head = Some(Head {
+                        index,
+                        sequence: entry.sequence,
+                    });
+                }
+            }
+
+            self.heads[register] = head;
+        }
+    }
+
+    // Rewrite a journal from before entries carried sequence numbers
+    //
+    // Values could only grow back then, so the greatest valid value of each
+    // register under `order` is its newest, and becomes its first entry in
+    // the new layout
+    fn migrate_legacy(
+        &mut self,
+        order: Option<LegacyOrder<T>>,
+    ) -> io::Result<()> {
+        let registers = self.heads.len();
+        let entry_size = mem::size_of::<LegacyJournalEntry<T>>();
+        let entries_per_register = JOURNAL_SIZE / entry_size / registers.max(1);
+
+        let Ok(entries) = self.mapping.as_slice_of::<LegacyJournalEntry<T>>()
+        else {
+            return Ok(());
+        };
+        let valid: Vec<Vec<T>> = entries
+            .chunks(entries_per_register)
+            .take(registers)
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter(|entry| entry.is_valid())
+                    .map(|entry| entry.value)
+                    .collect()
+            })
+            .collect();
+
+        if valid.iter().all(Vec::is_empty) {
+            return Ok(());
+        }
+        let Some(order) = order else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Journal written before sequence numbers, open it with \
+                 Journal::open_migrating",
+            ));
+        };
+        let newest: Vec<Option<T>> = valid
+            .into_iter()
+            .map(|values| values.into_iter().max_by(order))
+            .collect();
+
+        unsafe { self.mapping.bytes_mut() }.fill(0);
+        for (register, value) in newest.into_iter().enumerate() {
+            if let Some(value) = value {
+                self.entries(register)[0] = JournalEntry::new(1, value);
+                self.heads[register] = Some(Head {
+                    index: 0,
+                    sequence: 1,
+                });
+            }
+        }
+
+        // the whole page was rewritten
+        self.dirty = Some(0..JOURNAL_SIZE);
+        self.mapping.landfill().notify_write(0, JOURNAL_SIZE as u64);
+        Ok(())
+    }
+
+    // The entries belonging to `register`
+    fn entries(&mut self, register: usize) -> &mut [JournalEntry<T>] {
+        let entry_size = mem::size_of::<JournalEntry<T>>();
+        let start = register * self.entries_per_register * entry_size;
+        let len = self.entries_per_register * entry_size;
+
+        let bytes = unsafe { self.mapping.bytes_mut() };
+        bytemuck::cast_slice_mut(&mut bytes[start..][..len])
+    }
+
+    fn modify<F, R>(&mut self, register: usize, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        match self.apply(register, |value| Ok::<_, Infallible>(f(value))) {
+            Ok(res) => res,
+            Err(never) => match never {},
+        }
+    }
+
+    // Run the update, writing a new entry unless the closure errors
+    fn apply<F, R, E>(&mut self, register: usize, f: F) -> Result<R, E>
+    where
+        F: FnOnce(&mut T) -> Result<R, E>,
+    {
+        let head = self.heads[register];
+        let max_entry = self.entries_per_register;
+        let mut value = self.current(register);
+
+        let next = match head {
+            Some(head) => Head {
+                index: (head.index + 1) % max_entry,
+                sequence: head.sequence + 1,
+            },
+            None => Head {
+                index: 0,
+                sequence: 1,
+            },
+        };
+
+        let res = f(&mut value)?;
+
+        self.entries(register)[next.index] =
+            JournalEntry::new(next.sequence, value);
+        self.heads[register] = Some(next);
+        self.mark_written(register, next.index);
+        Ok(res)
+    }
+
+    // Record the entry as dirty, and report the write
+    fn mark_written(&mut self, register: usize, index: usize) {
+        let entry_size = mem::size_of::<JournalEntry<T>>();
+        let start = (register * self.entries_per_register + index) * entry_size;
+        let end = start + entry_size;
+
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => dirty.start.min(start)..dirty.end.max(end),
+            None => start..end,
+        });
+        self.mapping
+            .landfill()
+            .notify_write(start as u64, entry_size as u64);
+    }
+
+    fn current(&mut self, register: usize) -> T {
+        match self.heads[register] {
+            Some(head) => self.entries(register)[head.index].value,
+            None => T::default(),
+        }
+    }
+
+    fn try_current(&mut self, register: usize) -> io::Result<T> {
+        let Some(head) = self.heads[register] else {
+            return Ok(T::default());
+        };
+        let entry = self.entries(register)[head.index];
+
+        if !self.mapping.landfill().verifies_reads() {
+            return Ok(entry.value);
+        }
+
+        let sequence = entry.sequence;
+        match entry.get() {
+            Some(value) if sequence == head.sequence => Ok(value),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Corrupted journal entry in {}",
+                    self.mapping.landfill().full_name_ref()
+                ),
+            )),
+        }
+    }
+
+    // Only the entries written since the last flush are flushed
+    fn flush(&mut self) -> io::Result<()> {
+        self.mapping.landfill().failpoint(Failpoint::JournalFlush)?;
+        match self.dirty.take() {
+            Some(dirty) => {
+                let res = self.mapping.flush_range(dirty.start, dirty.len());
+                if res.is_err() {
+                    self.dirty = Some(dirty);
+                }
+                res
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Check the checksums of all entries ever written
+    //
+    // A crash can leave a single torn entry behind, which is harmless but
+    // reported all the same
+    fn verify(&mut self) -> VerifyReport {
+        let mut report = VerifyReport::default();
+
+        for register in 0..self.heads.len() {
+            for (index, entry) in self.entries(register).iter().enumerate() {
+                if helpers::is_all_zeroes(std::slice::from_ref(entry)) {
+                    continue;
+                }
+                report.checked += 1;
+                if entry.get().is_none() {
+                    report.problems.push(format!(
+                        "Journal register {register} entry {index} has an \
+                         invalid checksum"
+                    ));
+                }
+            }
+        }
+        report
+    }
+}
+
+impl<T> JournalInner<T>
+where
+    T: Pod + Hash + Ord + Default,
+{
+    fn update<F, R>(&mut self, register: usize, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        match self.try_update(register, |value| Ok::<_, Infallible>(f(value))) {
+            Ok(res) => res,
+            Err(never) => match never {},
+        }
+    }
+
+    fn update_many<I, F, R>(&mut self, register: usize, updates: I) -> Vec<R>
+    where
+        I: IntoIterator<Item = F>,
+        F: FnOnce(&mut T) -> R,
+    {
+        self.update(register, |value| {
+            updates
+                .into_iter()
+                .map(|f| {
+                    let old_value = *value;
+                    let res = f(value);
+                    assert!(
+                        *value >= old_value,
+                        "Journal updates must be incremental"
+                    );
+                    res
+                })
+                .collect()
+        })
+    }
+
+    fn try_update<F, R, E>(&mut self, register: usize, f: F) -> Result<R, E>
+    where
+        F: FnOnce(&mut T) -> Result<R, E>,
+    {
+        self.apply(register, |value| {
+            let old_value = *value;
+            let res = f(value)?;
+            assert!(*value >= old_value, "Journal updates must be incremental");
+            Ok(res)
+        })
+    }
+
+    fn checked_update<F, R>(&mut self, register: usize, f: F) -> io::Result<R>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        self.apply(register, |value| {
+            let old_value = *value;
+            let res = f(value);
+            if *value < old_value {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Journal updates must be incremental",
+                ))
+            } else {
+                Ok(res)
+            }
+        })
     }
 }
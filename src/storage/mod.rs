@@ -1,10 +1,45 @@
+mod allocator;
+mod appendlog;
 mod appendonly;
+mod atomicarray;
+mod bitmap;
 mod bytes;
+mod cold;
+mod diskvec;
 mod entropy;
+mod framed;
+mod grid;
+mod group;
+#[cfg(feature = "zerocopy")]
+mod interop;
 mod journal;
+mod optionarray;
 mod randomaccess;
+mod register;
+mod ringlog;
+mod snapshot;
+mod throttle;
+mod wal;
 
-pub use appendonly::AppendOnly;
+pub(crate) use bytes::{DiskBytes, N_LANES};
+
+pub use allocator::Allocator;
+pub use appendlog::{AppendLog, Watch};
+pub use appendonly::{AppendOnly, AppendOnlyConfig, Record, RelocationMap};
+pub use atomicarray::{AtomicArray, AtomicCell};
+pub use diskvec::DiskVec;
 pub use entropy::{Entropy, Tag};
-pub use journal::Journal;
-pub use randomaccess::RandomAccess;
+pub use framed::FramedAppendOnly;
+pub use grid::Grid;
+#[cfg(feature = "zerocopy")]
+pub use interop::ZeroCopy;
+pub use journal::{Journal, JournalArray};
+pub use optionarray::OptionArray;
+pub use randomaccess::{
+    RandomAccess, RandomAccessGuard, RandomAccessSliceGuard,
+    RandomAccessWriteGuard,
+};
+pub use register::Register;
+pub use ringlog::RingLog;
+pub use snapshot::{Snapshot, SnapshotView};
+pub use wal::WriteAheadLog;
@@ -0,0 +1,113 @@
+use std::io;
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+
+use super::bitmap::Bitmap;
+use super::randomaccess::{RandomAccessGuard, RandomAccessWriteGuard};
+use crate::{
+    FormatHeader, GuardedLandfill, RandomAccess, Substructure, VerifyReport,
+};
+
+/// An unbounded array of optional `T` on disk
+///
+/// Unlike `RandomAccess`, a bitmap alongside the values records which
+/// elements have been written, so an all-zero value is a valid element
+pub struct OptionArray<T> {
+    values: RandomAccess<T>,
+    valid: Bitmap,
+}
+
+impl<T> Substructure for OptionArray<T> {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(OptionArray {
+            values: lf.substructure("values")?,
+            valid: lf.substructure("valid")?,
+        })
+    }
+
+    fn format() -> Option<FormatHeader> {
+        Some(FormatHeader::new("option_array", mem::size_of::<T>(), 1))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.values.flush()?;
+        self.valid.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.values.close()?;
+        self.valid.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.values.size_on_disk() + self.valid.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.values.verify()?,
+            self.valid.verify()?,
+        ]))
+    }
+}
+
+impl<T> OptionArray<T>
+where
+    T: Zeroable + Pod,
+{
+    /// Get a reference to an element in the array
+    ///
+    /// Returns None if the element has never been written or was taken
+    pub fn get(&self, index: usize) -> Option<RandomAccessGuard<'_, T>> {
+        let guard = self.values.get_raw(index)?;
+        if self.valid.get(index) {
+            Some(guard)
+        } else {
+            None
+        }
+    }
+
+    /// Get a mutable reference to an element of the array, marking it as
+    /// written
+    ///
+    /// Elements that were never written start out as `Zeroable::zeroed()`
+    pub fn get_mut(
+        &self,
+        index: usize,
+    ) -> io::Result<RandomAccessWriteGuard<'_, T>> {
+        let guard = self.values.get_mut(index)?;
+        self.valid.set(index)?;
+        Ok(guard)
+    }
+
+    /// Set the element at `index` to `value`
+    pub fn set(&self, index: usize, value: T) -> io::Result<()> {
+        *self.get_mut(index)? = value;
+        Ok(())
+    }
+
+    /// Clear an element of the array, returning its previous value
+    pub fn take(&self, index: usize) -> io::Result<Option<T>> {
+        if !self.valid.get(index) {
+            return Ok(None);
+        }
+
+        let mut guard = self.values.get_mut(index)?;
+        if self.valid.clear(index)? {
+            Ok(Some(mem::replace(&mut *guard, T::zeroed())))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns one past the highest index ever written to
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if no element has ever been written to
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
@@ -1,16 +1,25 @@
 use std::io;
 use std::marker::PhantomData;
 use std::mem;
-use std::ops::Deref;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use bytemuck::{Pod, Zeroable};
-use parking_lot::{RwLock, RwLockReadGuard};
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use super::bytes::DiskBytes;
 use crate::helpers;
-use crate::{GuardedLandfill, Substructure};
+use crate::{
+    FormatHeader, GuardedLandfill, Journal, Substructure, VerifyReport,
+};
 
-const N_LOCKS: usize = 256;
+// the format version packing elements per lane, earlier versions place them
+// at `index * size`, possibly across lanes
+const PACKED_VERSION: u32 = 2;
+
+// minimum number of lock shards, scaled up with available parallelism
+const MIN_LOCKS: usize = 256;
+const LOCKS_PER_THREAD: usize = 64;
 
 /// An unbounded array of `T` on disk
 ///
@@ -20,13 +29,21 @@ const N_LOCKS: usize = 256;
 /// the array
 pub struct RandomAccess<T> {
     bytes: DiskBytes,
-    locks: [RwLock<()>; N_LOCKS],
+    // always a power of two in length
+    locks: Box<[RwLock<()>]>,
+    // one past the highest index ever written, cached from the journal
+    len: AtomicU64,
+    len_journal: Journal<u64>,
+    // elements are packed per lane, rather than placed at `index * size`
+    packed: bool,
     _marker: PhantomData<T>,
 }
 
+/// A read guard for an element of a `RandomAccess` array
 pub struct RandomAccessGuard<'a, T> {
     item: &'a T,
-    _guard: RwLockReadGuard<'a, ()>,
+    // None for elements read without locking
+    _guard: Option<RwLockReadGuard<'a, ()>>,
 }
 
 impl<'a, T> Deref for RandomAccessGuard<'a, T> {
@@ -36,22 +53,189 @@ impl<'a, T> Deref for RandomAccessGuard<'a, T> {
     }
 }
 
+/// A read guard for a contiguous run of elements of a `RandomAccess` array
+pub struct RandomAccessSliceGuard<'a, T> {
+    items: &'a [T],
+    _guards: Vec<RwLockReadGuard<'a, ()>>,
+}
+
+impl<'a, T> Deref for RandomAccessSliceGuard<'a, T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        self.items
+    }
+}
+
+/// A write guard for an element of a `RandomAccess` array
+///
+/// The write is reported to the landfill observers when the guard is dropped
+pub struct RandomAccessWriteGuard<'a, T> {
+    item: &'a mut T,
+    bytes: &'a DiskBytes,
+    offset: u64,
+    _guard: RwLockWriteGuard<'a, ()>,
+}
+
+impl<'a, T> Deref for RandomAccessWriteGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.item
+    }
+}
+
+impl<'a, T> DerefMut for RandomAccessWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.item
+    }
+}
+
+impl<'a, T> Drop for RandomAccessWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        self.bytes.written(self.offset, mem::size_of::<T>())
+    }
+}
+
 impl<T> Substructure for RandomAccess<T> {
     fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        // evenly dividing elements are laid out the same in both versions
+        let packed = lf.format_version() == Some(PACKED_VERSION);
         let bytes = lf.substructure("array")?;
+        let len_journal: Journal<u64> = lf.substructure("len")?;
 
-        const MUTEX: RwLock<()> = RwLock::new(());
-        let locks = [MUTEX; N_LOCKS];
+        let n_locks = std::thread::available_parallelism()
+            .map(|n| n.get() * LOCKS_PER_THREAD)
+            .unwrap_or(0)
+            .max(MIN_LOCKS)
+            .next_power_of_two();
+        let locks = (0..n_locks).map(|_| RwLock::new(())).collect();
 
-        Ok(RandomAccess {
+        let array = RandomAccess {
             bytes,
             locks,
+            len: AtomicU64::new(len_journal.current()),
+            len_journal,
+            packed,
             _marker: PhantomData,
-        })
+        };
+
+        // arrays written before the length was journaled get it from a scan
+        if array.is_empty() && array.bytes.end_on_disk() > 0 {
+            let len = array.scan_len() as u64;
+            array.len.store(len, Ordering::Release);
+            if len > 0 && !lf.is_read_only() {
+                array.len_journal.set(len);
+            }
+        }
+
+        Ok(array)
+    }
+
+    fn format() -> Option<FormatHeader> {
+        Some(FormatHeader::new(
+            "random_access",
+            mem::size_of::<T>(),
+            PACKED_VERSION,
+        ))
+    }
+
+    fn reads_version(version: u32) -> bool {
+        version == 1 || version == PACKED_VERSION
+    }
+
+    fn legacy_version() -> Option<u32> {
+        Some(1)
+    }
+
+    fn data_branches() -> Option<&'static [&'static str]> {
+        Some(&["array"])
     }
 
     fn flush(&self) -> io::Result<()> {
-        self.bytes.flush()
+        self.bytes.flush()?;
+        self.len_journal.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.bytes.close()?;
+        self.len_journal.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.bytes.size_on_disk() + self.len_journal.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.bytes.verify()?,
+            self.len_journal.verify()?,
+        ]))
+    }
+}
+
+impl<T> RandomAccess<T> {
+    /// Returns one past the highest index ever written to
+    ///
+    /// All elements at or above this index are uninitialized
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire) as usize
+    }
+
+    /// Returns true if no element has ever been written to
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The byte offset of the element at `index`
+    fn offset_of(&self, index: usize) -> Option<u64> {
+        let t_size = mem::size_of::<T>() as u64;
+        if self.packed {
+            DiskBytes::packed_offset(index as u64, t_size)
+        } else {
+            (index as u64).checked_mul(t_size)
+        }
+    }
+
+    fn write_offset_of(&self, index: usize) -> io::Result<u64> {
+        self.offset_of(index).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Index out of range")
+        })
+    }
+
+    // One past the highest element on disk that is not all zeroes
+    fn scan_len(&self) -> usize {
+        let t_size = mem::size_of::<T>();
+        let end = self.bytes.end_on_disk();
+        if t_size == 0 {
+            return 0;
+        }
+        (0..)
+            .map_while(|index| {
+                let offset = self.offset_of(index)?;
+                (offset + t_size as u64 <= end).then_some((index, offset))
+            })
+            .filter(|(_, offset)| {
+                self.bytes
+                    .read(*offset, t_size as u32)
+                    .is_some_and(|bytes| bytes.iter().any(|b| *b != 0))
+            })
+            .last()
+            .map_or(0, |(index, _)| index + 1)
+    }
+
+    // The number of elements that are not all zeroes
+    pub(crate) fn count_nonzero(&self) -> usize {
+        let t_size = mem::size_of::<T>();
+        (0..self.len.load(Ordering::Acquire) as usize)
+            .filter(|index| {
+                let Some(offset) = self.offset_of(*index) else {
+                    return false;
+                };
+                let _guard = self.locks[index & (self.locks.len() - 1)].read();
+                self.bytes
+                    .read(offset, t_size as u32)
+                    .is_some_and(|bytes| bytes.iter().any(|b| *b != 0))
+            })
+            .count()
     }
 }
 
@@ -63,26 +247,160 @@ where
     ///
     /// Returns None if the element is uninitialized
     /// or equal to `Zeroable::zeroed()`.
-    pub fn get(&self, index: usize) -> Option<RandomAccessGuard<T>> {
-        let t_size = mem::size_of::<T>();
-        let byte_offset = (index * t_size) as u64;
-
-        let guard = self.locks[index % N_LOCKS].read();
-
-        if let Some(slice) = self.bytes.read(byte_offset, t_size as u32) {
-            let cast: &[T] = bytemuck::cast_slice(slice);
-            debug_assert_eq!(cast.len(), 1);
-            if !helpers::is_all_zeroes(cast) {
-                Some(RandomAccessGuard {
-                    item: &cast[0],
-                    _guard: guard,
-                })
-            } else {
-                None
-            }
+    pub fn get(&self, index: usize) -> Option<RandomAccessGuard<'_, T>> {
+        let guard = self.get_raw(index)?;
+        if helpers::is_all_zeroes(std::slice::from_ref(guard.item)) {
+            None
         } else {
+            Some(guard)
+        }
+    }
+
+    /// Get a contiguous run of `len` elements starting at `start`
+    ///
+    /// Returns None if the run is not fully contained in one written lane.
+    /// Uninitialized elements are included as `Zeroable::zeroed()`
+    pub fn get_slice(
+        &self,
+        start: usize,
+        len: usize,
+    ) -> Option<RandomAccessSliceGuard<'_, T>> {
+        let t_size = mem::size_of::<T>();
+        let byte_offset = self.offset_of(start)?;
+        let byte_len = u32::try_from(len * t_size).ok()?;
+
+        // the run must not continue into the next lane
+        if DiskBytes::lane_remaining(byte_offset) / (t_size as u64) < len as u64
+        {
+            return None;
+        }
+
+        let shards = self.shards_for_run(start, len);
+
+        let guards = shards
+            .iter()
+            .map(|shard| self.locks[*shard].read())
+            .collect();
+
+        let slice = self.bytes.read(byte_offset, byte_len)?;
+        Some(RandomAccessSliceGuard {
+            items: bytemuck::cast_slice(slice),
+            _guards: guards,
+        })
+    }
+
+    // Like `get`, but also returns elements equal to `Zeroable::zeroed()`
+    pub(crate) fn get_raw(
+        &self,
+        index: usize,
+    ) -> Option<RandomAccessGuard<'_, T>> {
+        let t_size = mem::size_of::<T>();
+        let byte_offset = self.offset_of(index)?;
+
+        let guard = self.lock(index).read();
+
+        let slice = self.bytes.read(byte_offset, t_size as u32)?;
+        let cast: &[T] = bytemuck::cast_slice(slice);
+        Some(RandomAccessGuard {
+            item: &cast[0],
+            _guard: Some(guard),
+        })
+    }
+
+    /// Like `get`, but without taking the lock of the element
+    ///
+    /// # Safety
+    /// The caller must guarantee that no element is written concurrently
+    pub(crate) unsafe fn get_unlocked(
+        &self,
+        index: usize,
+    ) -> Option<RandomAccessGuard<'_, T>> {
+        let t_size = mem::size_of::<T>();
+        let byte_offset = self.offset_of(index)?;
+
+        let slice = self.bytes.read(byte_offset, t_size as u32)?;
+        let cast: &[T] = bytemuck::cast_slice(slice);
+        if helpers::is_all_zeroes(cast) {
             None
+        } else {
+            Some(RandomAccessGuard {
+                item: &cast[0],
+                _guard: None,
+            })
+        }
+    }
+
+    /// Get a mutable reference to an element of the array
+    ///
+    /// Will grow the array as neccesary to be able to index the position.
+    /// The element stays locked for as long as the guard is held.
+    ///
+    /// Elements share a fixed set of locks, so the lock taken also covers
+    /// other, unrelated indices. While the guard is held, any other access
+    /// to the array through `get`, `get_mut` or `with_mut` can deadlock,
+    /// even for a different index, and so can holding guards from two
+    /// threads that then wait on each other. Use `with_mut_many` to modify
+    /// several elements at once
+    pub fn get_mut(
+        &self,
+        index: usize,
+    ) -> io::Result<RandomAccessWriteGuard<'_, T>> {
+        let t_size = mem::size_of::<T>();
+        let byte_offset = self.write_offset_of(index)?;
+
+        let guard = self.lock(index).write();
+
+        let slice = unsafe { self.bytes.request_write(byte_offset, t_size)? };
+
+        let t_slice = bytemuck::cast_slice_mut(slice);
+        assert!(t_slice.len() == 1);
+
+        self.mark_written(index);
+
+        Ok(RandomAccessWriteGuard {
+            item: &mut t_slice[0],
+            bytes: &self.bytes,
+            offset: byte_offset,
+            _guard: guard,
+        })
+    }
+
+    /// Get the element at `index`, initializing it with `init` if it is
+    /// uninitialized
+    ///
+    /// `init` runs under the write lock of the element, so it is called at
+    /// most once even if several threads race to initialize the same index
+    pub fn get_or_init<F>(
+        &self,
+        index: usize,
+        init: F,
+    ) -> io::Result<RandomAccessGuard<'_, T>>
+    where
+        F: FnOnce() -> T,
+    {
+        if let Some(guard) = self.get(index) {
+            return Ok(guard);
         }
+
+        let t_size = mem::size_of::<T>();
+        let byte_offset = self.write_offset_of(index)?;
+
+        let guard = self.lock(index).write();
+
+        let slice = unsafe { self.bytes.request_write(byte_offset, t_size)? };
+        let t_slice: &mut [T] = bytemuck::cast_slice_mut(slice);
+
+        self.mark_written(index);
+
+        if helpers::is_all_zeroes(t_slice) {
+            t_slice[0] = init();
+            self.bytes.written(byte_offset, t_size);
+        }
+
+        Ok(RandomAccessGuard {
+            item: &t_slice[0],
+            _guard: Some(RwLockWriteGuard::downgrade(guard)),
+        })
     }
 
     /// Run a closure with mutable access to an element of the array
@@ -92,22 +410,178 @@ where
     where
         F: FnMut(&mut T) -> R,
     {
-        let t_size = mem::size_of::<T>();
-        let byte_offset = (index * t_size) as u64;
+        let mut guard = self.get_mut(index)?;
+        Ok(closure(&mut guard))
+    }
 
-        let guard = self.locks[index % N_LOCKS].write();
+    /// Run a closure with mutable access to several elements of the array
+    ///
+    /// The references are passed in the same order as `indices`. All shard
+    /// locks are taken in a canonical order before running the closure, so
+    /// concurrent calls cannot deadlock. Duplicate indices are rejected
+    pub fn with_mut_many<F, R>(
+        &self,
+        indices: &[usize],
+        closure: F,
+    ) -> io::Result<R>
+    where
+        F: FnOnce(&mut [&mut T]) -> R,
+    {
+        let mut sorted = indices.to_vec();
+        sorted.sort_unstable();
+        if sorted.windows(2).any(|pair| pair[0] == pair[1]) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Duplicate index in with_mut_many",
+            ));
+        }
 
-        let slice = unsafe { self.bytes.request_write(byte_offset, t_size)? };
+        let mut shards: Vec<usize> =
+            sorted.iter().map(|i| i & (self.locks.len() - 1)).collect();
+        shards.sort_unstable();
+        shards.dedup();
 
-        let t_slice = bytemuck::cast_slice_mut(slice);
-        assert!(t_slice.len() == 1);
-        let t = &mut t_slice[0];
+        let _guards: Vec<_> = shards
+            .iter()
+            .map(|shard| self.locks[*shard].write())
+            .collect();
 
-        let res = closure(t);
+        let t_size = mem::size_of::<T>();
+        let mut offsets = Vec::with_capacity(indices.len());
+        let mut refs = Vec::with_capacity(indices.len());
+
+        for index in indices {
+            let byte_offset = self.write_offset_of(*index)?;
+            // the indices are distinct, so the slices never alias
+            let slice =
+                unsafe { self.bytes.request_write(byte_offset, t_size)? };
+            let t_slice: &mut [T] = bytemuck::cast_slice_mut(slice);
+            offsets.push(byte_offset);
+            refs.push(&mut t_slice[0]);
+        }
 
-        // just to be explicit, it's not neccesary to manually drop this
-        drop(guard);
+        if let Some(max) = sorted.last() {
+            self.mark_written(*max);
+        }
 
+        let res = closure(&mut refs);
+        for offset in offsets {
+            self.bytes.written(offset, t_size);
+        }
         Ok(res)
     }
+
+    /// Replace the element at `index` with `new` if it currently equals
+    /// `expected`
+    ///
+    /// Elements are compared bytewise, and uninitialized elements are equal to
+    /// `Zeroable::zeroed()`. Returns true if the swap took place
+    pub fn compare_and_swap(
+        &self,
+        index: usize,
+        expected: T,
+        new: T,
+    ) -> io::Result<bool> {
+        let mut slot = self.get_mut(index)?;
+        if bytemuck::bytes_of(&*slot) == bytemuck::bytes_of(&expected) {
+            *slot = new;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Clear an element of the array, returning its previous value
+    ///
+    /// Returns None if the element was uninitialized
+    pub fn take(&self, index: usize) -> Option<T> {
+        let t_size = mem::size_of::<T>();
+        let byte_offset = self.offset_of(index)?;
+
+        let _guard = self.lock(index).write();
+
+        // don't grow the array just to clear an element
+        self.bytes.read(byte_offset, t_size as u32)?;
+
+        let slice =
+            unsafe { self.bytes.request_write(byte_offset, t_size).ok()? };
+        let t_slice: &mut [T] = bytemuck::cast_slice_mut(slice);
+
+        if helpers::is_all_zeroes(t_slice) {
+            None
+        } else {
+            let taken = mem::replace(&mut t_slice[0], T::zeroed());
+            self.bytes.written(byte_offset, t_size);
+            Some(taken)
+        }
+    }
+
+    /// Write a run of elements starting at `start`
+    ///
+    /// All shard locks covering the run are taken up front, in order, and the
+    /// elements are copied in one pass
+    pub fn write_slice(&self, start: usize, values: &[T]) -> io::Result<()> {
+        if values.is_empty() {
+            return Ok(());
+        }
+
+        let shards = self.shards_for_run(start, values.len());
+
+        let _guards: Vec<_> = shards
+            .iter()
+            .map(|shard| self.locks[*shard].write())
+            .collect();
+
+        let t_size = mem::size_of::<T>();
+        let mut written = 0;
+
+        // copy as many elements as fit into each lane at once, unpacked
+        // elements may also be split between lanes
+        while written < values.len() {
+            let offset = self.write_offset_of(start + written)?;
+            let fits = if self.packed {
+                DiskBytes::lane_remaining(offset) as usize / t_size
+            } else {
+                values.len() - written
+            };
+            let run = &values[written..][..fits.min(values.len() - written)];
+
+            let src: &[u8] = bytemuck::cast_slice(run);
+            self.bytes.write_at(offset, src)?;
+            self.bytes.written(offset, src.len());
+            written += run.len();
+        }
+
+        self.mark_written(start + values.len() - 1);
+
+        Ok(())
+    }
+
+    // The lock shards guarding a run of elements, in locking order
+    fn shards_for_run(&self, start: usize, len: usize) -> Vec<usize> {
+        let mut shards: Vec<usize> = (start..start + len)
+            .take(self.locks.len())
+            .map(|i| i & (self.locks.len() - 1))
+            .collect();
+        shards.sort_unstable();
+        shards
+    }
+
+    // The lock shard guarding `index`
+    fn lock(&self, index: usize) -> &RwLock<()> {
+        &self.locks[index & (self.locks.len() - 1)]
+    }
+
+    // Record that `index` has been written, growing the length if needed
+    fn mark_written(&self, index: usize) {
+        let new_len = index as u64 + 1;
+        if new_len > self.len.load(Ordering::Acquire) {
+            self.len_journal.update(|len| {
+                if new_len > *len {
+                    *len = new_len;
+                }
+                self.len.store(*len, Ordering::Release);
+            });
+        }
+    }
 }
@@ -0,0 +1,168 @@
+use std::hash::Hasher;
+use std::io;
+use std::mem;
+
+use bytemuck::Pod;
+use bytemuck_derive::*;
+use parking_lot::Mutex;
+use seahash::SeaHasher;
+
+use crate::{FormatHeader, GuardedLandfill, MappedFile, Substructure};
+
+#[derive(Clone, Copy, Zeroable, Pod)]
+#[repr(C, packed)]
+struct RegisterSlot<T> {
+    sequence: u64,
+    checksum: u64,
+    value: T,
+}
+
+impl<T> RegisterSlot<T>
+where
+    T: Pod,
+{
+    fn checksum(sequence: u64, value: &T) -> u64 {
+        let mut hasher = SeaHasher::new();
+        hasher.write_u64(sequence);
+        hasher.write(bytemuck::bytes_of(value));
+        hasher.finish()
+    }
+
+    fn new(sequence: u64, value: T) -> Self {
+        RegisterSlot {
+            sequence,
+            checksum: Self::checksum(sequence, &value),
+            value,
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        let value = self.value;
+        Self::checksum(self.sequence, &value) == self.checksum
+    }
+}
+
+struct RegisterInner<T> {
+    mapping: MappedFile,
+    // index of the slot holding the current value, if any
+    current: Option<usize>,
+    sequence: u64,
+    value: T,
+}
+
+/// A crash-resistant register holding a single value of `T`
+///
+/// Unlike `Journal`, the value can be set to anything, not just values
+/// comparing greater than the last one. The register keeps two checksummed
+/// slots and alternates between them, so a half-finished write never
+/// clobbers the last valid value.
+///
+/// Useful for configuration blobs and epoch markers.
+pub struct Register<T>(Mutex<RegisterInner<T>>);
+
+impl<T> Substructure for Register<T>
+where
+    T: Pod,
+{
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        let size = mem::size_of::<RegisterSlot<T>>() * 2;
+
+        if let Some(mapping) = lf.map_file_create(size as u64)? {
+            let slots: &[RegisterSlot<T>] = mapping.as_slice_of()?;
+
+            let mut current = None;
+            let mut sequence = 0;
+            let mut value = T::zeroed();
+
+            for (i, slot) in slots.iter().enumerate() {
+                if slot.is_valid()
+                    && (current.is_none() || slot.sequence > sequence)
+                {
+                    current = Some(i);
+                    sequence = slot.sequence;
+                    value = slot.value;
+                }
+            }
+
+            Ok(Register(Mutex::new(RegisterInner {
+                mapping,
+                current,
+                sequence,
+                value,
+            })))
+        } else {
+            Err(io::Error::other("Attempt at mapping the same file twice"))
+        }
+    }
+
+    fn format() -> Option<FormatHeader> {
+        Some(FormatHeader::new("register", mem::size_of::<T>(), 1))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.0.lock().mapping.flush()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.0.lock().mapping.landfill().size_on_disk()
+    }
+}
+
+impl<T> Register<T>
+where
+    T: Pod + PartialEq,
+{
+    /// Returns the current value of the register
+    ///
+    /// A register that was never set holds `Zeroable::zeroed()`
+    pub fn get(&self) -> T {
+        self.0.lock().value
+    }
+
+    /// Set the value of the register
+    pub fn set(&self, value: T) {
+        self.0.lock().write(value)
+    }
+
+    /// Set the register to `new` if its current value equals `expected`
+    ///
+    /// Like `compare_exchange` on atomics, this returns `Ok` with the
+    /// previous value if the swap happened, and `Err` with the current value
+    /// if it did not.
+    pub fn compare_and_swap(&self, expected: T, new: T) -> Result<T, T> {
+        let mut inner = self.0.lock();
+        let previous = inner.value;
+
+        if previous == expected {
+            inner.write(new);
+            Ok(previous)
+        } else {
+            Err(previous)
+        }
+    }
+}
+
+impl<T> RegisterInner<T>
+where
+    T: Pod,
+{
+    fn write(&mut self, value: T) {
+        let slots: &mut [RegisterSlot<T>] =
+            unsafe { self.mapping.as_mut_slice_of() }
+                .expect("checked when the register was opened");
+
+        let next = match self.current {
+            Some(i) => (i + 1) % 2,
+            None => 0,
+        };
+
+        self.sequence += 1;
+        slots[next] = RegisterSlot::new(self.sequence, value);
+        let size = mem::size_of::<RegisterSlot<T>>();
+        self.mapping
+            .landfill()
+            .notify_write((next * size) as u64, size as u64);
+        self.current = Some(next);
+        self.value = value;
+    }
+}
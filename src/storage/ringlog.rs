@@ -0,0 +1,122 @@
+use std::io;
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    FormatHeader, GuardedLandfill, Journal, RandomAccess, Substructure,
+    VerifyReport,
+};
+
+/// A log keeping only the last `N` values pushed
+///
+/// Values are stored in a circular array of `N + 1` slots, with the number
+/// of values ever pushed kept in a journal. The spare slot is the one being
+/// written, so an interrupted push never damages the values retained.
+pub struct RingLog<T, const N: usize> {
+    slots: RandomAccess<T>,
+    pushed: Journal<u64>,
+}
+
+impl<T, const N: usize> Substructure for RingLog<T, N> {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        if N == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "RingLog capacity must be at least 1",
+            ));
+        }
+
+        Ok(RingLog {
+            slots: lf.substructure("slots")?,
+            pushed: lf.substructure("pushed")?,
+        })
+    }
+
+    fn format() -> Option<FormatHeader> {
+        Some(FormatHeader::new("ring_log", mem::size_of::<T>(), 1))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.slots.flush()?;
+        self.pushed.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.slots.close()?;
+        self.pushed.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.slots.size_on_disk() + self.pushed.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.slots.verify()?,
+            self.pushed.verify()?,
+        ]))
+    }
+}
+
+impl<T, const N: usize> RingLog<T, N>
+where
+    T: Zeroable + Pod,
+{
+    /// Push a value, dropping the oldest value if the log is full
+    ///
+    /// Returns the sequence number of the value
+    pub fn push(&self, value: T) -> io::Result<u64> {
+        self.pushed.try_update(|pushed| {
+            let seq = *pushed;
+            *self.slots.get_mut(Self::slot(seq))? = value;
+            *pushed += 1;
+            Ok(seq)
+        })
+    }
+
+    /// Get the value with sequence number `seq`, if still retained
+    pub fn get(&self, seq: u64) -> Option<T> {
+        if seq >= self.pushed() {
+            return None;
+        }
+
+        let value = *self.slots.get_raw(Self::slot(seq))?;
+        // the slot is reused by the push of `seq + N + 1`
+        (self.pushed() <= seq + N as u64).then_some(value)
+    }
+
+    /// Iterate over the retained values from oldest to newest, with their
+    /// sequence numbers
+    ///
+    /// Values dropped while iterating are skipped
+    pub fn iter(&self) -> impl Iterator<Item = (u64, T)> + '_ {
+        let pushed = self.pushed();
+        let oldest = pushed.saturating_sub(N as u64);
+        (oldest..pushed).filter_map(|seq| Some((seq, self.get(seq)?)))
+    }
+
+    /// The most recently pushed value
+    pub fn last(&self) -> Option<T> {
+        self.get(self.pushed().checked_sub(1)?)
+    }
+
+    /// The number of values retained
+    pub fn len(&self) -> usize {
+        (self.pushed() as usize).min(N)
+    }
+
+    /// Returns true if nothing has been pushed
+    pub fn is_empty(&self) -> bool {
+        self.pushed() == 0
+    }
+
+    /// The number of values ever pushed
+    pub fn pushed(&self) -> u64 {
+        self.pushed.current()
+    }
+
+    fn slot(seq: u64) -> usize {
+        (seq % (N as u64 + 1)) as usize
+    }
+}
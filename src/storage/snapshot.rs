@@ -0,0 +1,41 @@
+/// Append-only structures that can serve reads as of an earlier point
+///
+/// The epoch of a structure is the journaled value marking how much has
+/// been written, such as a length or writehead. Since written data never
+/// changes, pinning the epoch is all it takes to read a consistent view
+/// while writers continue.
+pub trait Snapshot: Sized {
+    /// The current epoch of the structure
+    fn epoch(&self) -> u64;
+
+    /// Pin the current epoch, returning a view of the structure as of now
+    fn snapshot(&self) -> SnapshotView<'_, Self> {
+        SnapshotView {
+            inner: self,
+            epoch: self.epoch(),
+        }
+    }
+}
+
+/// A view of a structure pinned at an epoch
+///
+/// Reads through the view ignore everything written after the epoch
+pub struct SnapshotView<'a, S> {
+    pub(crate) inner: &'a S,
+    pub(crate) epoch: u64,
+}
+
+impl<S> SnapshotView<'_, S> {
+    /// The epoch the view is pinned at
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+}
+
+impl<S> Clone for SnapshotView<'_, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> Copy for SnapshotView<'_, S> {}
@@ -0,0 +1,47 @@
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counts bytes written since the last flush, against an optional limit
+#[derive(Default)]
+pub(crate) struct Throttle {
+    dirty: AtomicU64,
+    // zero means unlimited
+    limit: AtomicU64,
+}
+
+impl Throttle {
+    pub fn set_limit(&self, limit: Option<u64>) {
+        self.limit.store(limit.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    pub fn dirty(&self) -> u64 {
+        self.dirty.load(Ordering::Acquire)
+    }
+
+    pub fn add(&self, len: u64) {
+        self.dirty.fetch_add(len, Ordering::AcqRel);
+    }
+
+    /// Returns true if a limit is set and the dirty bytes have reached it
+    pub fn exceeded(&self) -> bool {
+        let limit = self.limit.load(Ordering::Relaxed);
+        limit != 0 && self.dirty() >= limit
+    }
+
+    /// Run `flush`, and on success forget the bytes that were dirty before
+    /// it started
+    pub fn flushed<F>(&self, flush: F) -> io::Result<()>
+    where
+        F: FnOnce() -> io::Result<()>,
+    {
+        let before = self.dirty();
+        flush()?;
+        // concurrent flushes may both count the same bytes
+        let _ = self.dirty.fetch_update(
+            Ordering::AcqRel,
+            Ordering::Acquire,
+            |dirty| Some(dirty.saturating_sub(before)),
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use std::io;
+
+use parking_lot::Mutex;
+
+use crate::{
+    AppendLog, FramedAppendOnly, GuardedLandfill, Journal, Snapshot,
+    SnapshotView, Substructure, VerifyReport,
+};
+
+const SEQ_SIZE: usize = 8;
+
+/// A write-ahead log of checksummed byte entries
+///
+/// Entries are assigned sequential numbers starting at 0, and can be
+/// replayed from any sequence number to rebuild in-memory state after a
+/// crash. A checkpoint records up to which entry the state has been made
+/// durable elsewhere, and is where replays are expected to start.
+pub struct WriteAheadLog {
+    frames: FramedAppendOnly,
+    // offset of the frame of each entry, indexed by sequence number
+    offsets: AppendLog<u64>,
+    // sequence number of the first entry not covered by a checkpoint
+    checkpoint: Journal<u64>,
+    // keeps entries in the same order in the frames and the offsets
+    append: Mutex<()>,
+}
+
+impl Substructure for WriteAheadLog {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        let wal = WriteAheadLog {
+            frames: lf.substructure("frames")?,
+            offsets: lf.substructure("offsets")?,
+            checkpoint: lf.substructure("checkpoint")?,
+            append: Mutex::new(()),
+        };
+
+        // index frames that were written but not yet recorded
+        let resume = match wal.offsets.len() {
+            0 => 0,
+            n => {
+                let offset = *wal.offsets.get(n - 1).expect("within length");
+                let frame = wal.frames.get(offset).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "Corrupt WAL")
+                })?;
+                FramedAppendOnly::end_of(offset, frame)
+            }
+        };
+        let unindexed = wal
+            .frames
+            .iter_from(resume)
+            .map(|(offset, _)| offset)
+            .collect::<Vec<_>>();
+        for offset in unindexed {
+            wal.offsets.push(offset)?;
+        }
+
+        Ok(wal)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.frames.flush()?;
+        self.offsets.flush()?;
+        self.checkpoint.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.frames.close()?;
+        self.offsets.close()?;
+        self.checkpoint.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.frames.size_on_disk()
+            + self.offsets.size_on_disk()
+            + self.checkpoint.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.frames.verify()?,
+            self.offsets.verify()?,
+            self.checkpoint.verify()?,
+        ]))
+    }
+}
+
+impl WriteAheadLog {
+    /// Append an entry to the log, returning its sequence number
+    pub fn append(&self, entry: &[u8]) -> io::Result<u64> {
+        let _append = self.append.lock();
+        let seq = self.offsets.len();
+
+        let mut frame = Vec::with_capacity(SEQ_SIZE + entry.len());
+        frame.extend_from_slice(&seq.to_le_bytes());
+        frame.extend_from_slice(entry);
+
+        let offset = self.frames.write(&frame)?;
+        self.offsets.push(offset)
+    }
+
+    /// Get the entry with sequence number `seq`
+    pub fn get(&self, seq: u64) -> Option<&[u8]> {
+        let offset = *self.offsets.get(seq)?;
+        Self::entry_of(seq, self.frames.get(offset)?)
+    }
+
+    /// Iterate over all entries from sequence number `from` onwards
+    ///
+    /// Stops at the first entry that fails its checksum
+    pub fn replay(&self, from: u64) -> impl Iterator<Item = (u64, &[u8])> {
+        (from..self.len()).map_while(|seq| Some((seq, self.get(seq)?)))
+    }
+
+    /// Iterate over all entries not covered by the last checkpoint
+    pub fn replay_from_checkpoint(&self) -> impl Iterator<Item = (u64, &[u8])> {
+        self.replay(self.checkpointed())
+    }
+
+    /// Record that all entries up to and including `seq` are durable
+    /// elsewhere and no longer need to be replayed
+    ///
+    /// Checkpoints can only move forward
+    pub fn checkpoint(&self, seq: u64) -> io::Result<()> {
+        if seq >= self.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Checkpoint past the end of the log",
+            ));
+        }
+
+        self.checkpoint.update(|checkpoint| {
+            *checkpoint = (*checkpoint).max(seq + 1);
+        });
+        self.checkpoint.flush()
+    }
+
+    /// The sequence number of the first entry after the last checkpoint
+    pub fn checkpointed(&self) -> u64 {
+        self.checkpoint.current()
+    }
+
+    /// The number of entries in the log
+    pub fn len(&self) -> u64 {
+        self.offsets.len()
+    }
+
+    /// Returns true if no entries have been appended
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn entry_of(seq: u64, frame: &[u8]) -> Option<&[u8]> {
+        let (frame_seq, entry) = frame.split_at_checked(SEQ_SIZE)?;
+        (frame_seq == seq.to_le_bytes()).then_some(entry)
+    }
+}
+
+impl Snapshot for WriteAheadLog {
+    fn epoch(&self) -> u64 {
+        self.len()
+    }
+}
+
+impl<'a> SnapshotView<'a, WriteAheadLog> {
+    /// Iterate over the entries from sequence number `from` onwards that
+    /// were appended before the snapshot
+    pub fn replay(&self, from: u64) -> impl Iterator<Item = (u64, &'a [u8])> {
+        let inner = self.inner;
+        (from..self.epoch).map_while(move |seq| Some((seq, inner.get(seq)?)))
+    }
+
+    /// The number of entries in the log as of the snapshot
+    pub fn len(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns true if the log was empty as of the snapshot
+    pub fn is_empty(&self) -> bool {
+        self.epoch == 0
+    }
+}
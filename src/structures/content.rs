@@ -1,61 +1,458 @@
+use std::fmt;
 use std::hash::Hash;
-use std::io;
+use std::io::{self, Write};
 use std::marker::PhantomData;
+use std::mem;
+use std::str::FromStr;
 
-use bytemuck_derive::*;
+use bytemuck::{Pod, Zeroable};
+use bytemuck_derive::{Pod, Zeroable};
 use digest::Digest;
 
-use crate::{AppendOnly, GuardedLandfill, SmashMap, Substructure};
+use super::smash::generation_name;
+use crate::{
+    AppendOnly, AtomicArray, FormatHeader, GuardedLandfill, Journal, Landfill,
+    RandomAccess, SmashMap, Substructure, VerifyReport,
+};
 
+// An entry of the index, for ids of `N` bytes
+//
+// Packed, so that ids of any width leave no padding
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct Entry<const N: usize> {
+    ofs: u64,
+    len: u32,
+    tag: [u8; 4],
+    // index into the reference counts
+    serial: u64,
+    // digest of the stored bytes, so probes never have to rehash payloads
+    id: [u8; N],
+}
+
+// all fields are plain data, and packing leaves no padding bytes
+unsafe impl<const N: usize> Zeroable for Entry<N> {}
+unsafe impl<const N: usize> Pod for Entry<N> {}
+
+impl<const N: usize> Entry<N> {
+    fn serial(&self) -> usize {
+        self.serial as usize
+    }
+
+    fn id(&self) -> ContentId<N> {
+        ContentId(self.id)
+    }
+}
+
+// An entry of the index written before format headers, which kept no
+// id, serial or reference count
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
-struct Entry {
+struct LegacyEntry {
     ofs: u64,
     len: u32,
     tag: u32,
 }
 
-#[repr(C)]
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Pod, Zeroable)]
-pub struct ContentId([u8; 32]);
+// the version of the index layout
+const FORMAT_VERSION: u32 = 1;
+// the version of stores written before format headers
+const LEGACY_VERSION: u32 = 0;
 
-impl ContentId {
+// Reference count states, tracked counts are stored offset by one
+const UNTRACKED: u32 = 0;
+const DEAD: u32 = u32::MAX;
+
+// the largest alignment kept when content is copied, that of a page
+const MAX_ALIGNMENT: usize = 4096;
+
+// The alignment content stored at `ofs` is copied with, which is at least
+// the one it was inserted with
+fn alignment_of(ofs: u64) -> usize {
+    1 << ofs.trailing_zeros().min(MAX_ALIGNMENT.trailing_zeros())
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "Content not found")
+}
+
+/// The id of a piece of content, its digest truncated to `N` bytes
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ContentId<const N: usize = 32>([u8; N]);
+
+impl<const N: usize> ContentId<N> {
     fn from_bytes<D: Digest>(bytes: &[u8]) -> Self {
-        let mut hash_bytes = [0u8; 32];
-        let digest = D::digest(bytes);
-        hash_bytes.copy_from_slice(digest.as_ref());
-        ContentId(hash_bytes)
+        Self::from_digest(D::digest(bytes).as_ref())
+    }
+
+    fn from_digest(digest: &[u8]) -> Self {
+        let mut id = [0u8; N];
+        id.copy_from_slice(&digest[..N]);
+        ContentId(id)
+    }
+
+    /// The bytes of the id
+    pub fn as_bytes(&self) -> &[u8; N] {
+        &self.0
+    }
+
+    /// Parse an id from its hexadecimal representation
+    pub fn from_hex(hex: &str) -> io::Result<Self> {
+        let invalid = || {
+            io::Error::new(io::ErrorKind::InvalidInput, "Invalid content id")
+        };
+
+        if hex.len() != N * 2 {
+            return Err(invalid());
+        }
+
+        let mut id = [0u8; N];
+        let mut nibbles =
+            hex.chars().map(|c| c.to_digit(16).ok_or_else(invalid));
+        for byte in &mut id {
+            let (high, low) = (nibbles.next(), nibbles.next());
+            match (high, low) {
+                (Some(high), Some(low)) => *byte = (high? << 4 | low?) as u8,
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(ContentId(id))
     }
 }
 
-/// A storage for content-adressable byte-slices
-pub struct Content<D> {
+impl<const N: usize> fmt::Display for ContentId<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<const N: usize> fmt::Debug for ContentId<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ContentId({self})")
+    }
+}
+
+impl<const N: usize> FromStr for ContentId<N> {
+    type Err = io::Error;
+
+    fn from_str(hex: &str) -> io::Result<Self> {
+        Self::from_hex(hex)
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for ContentId<N> {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+// ids are serialized as hex strings
+#[cfg(feature = "serde")]
+impl<const N: usize> serde::Serialize for ContentId<N> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const N: usize> serde::Deserialize<'de> for ContentId<N> {
+    fn deserialize<De: serde::Deserializer<'de>>(
+        deserializer: De,
+    ) -> Result<Self, De::Error> {
+        let hex = <std::borrow::Cow<str>>::deserialize(deserializer)?;
+        Self::from_hex(&hex).map_err(serde::de::Error::custom)
+    }
+}
+
+// cells of the persisted deduplication statistics
+const STAT_DEDUPLICATED: usize = 0;
+const STAT_WRITTEN: usize = 1;
+const STAT_LOGICAL_BYTES: usize = 2;
+const STAT_PHYSICAL_BYTES: usize = 3;
+
+/// Deduplication statistics of a `Content` store
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DedupStats {
+    /// Number of inserts of content that was already stored
+    pub deduplicated: u64,
+    /// Number of inserts that stored new content
+    pub written: u64,
+    /// Total bytes passed to all inserts
+    pub logical_bytes: u64,
+    /// Total bytes of content stored
+    pub physical_bytes: u64,
+}
+
+// initial space reserved by a `ContentWriter`
+const STREAM_INITIAL_CAPACITY: usize = 64 * 1024;
+
+// The parts of a store holding its content, replaced as a whole when
+// compacting
+struct Generation<const N: usize> {
     data: AppendOnly,
-    index: SmashMap<ContentId, Entry>,
+    index: SmashMap<ContentId<N>, Entry<N>>,
+    refs: AtomicArray<u32>,
+    serials: Journal<u64>,
+}
+
+impl<const N: usize> Generation<N> {
+    fn open(lf: &Landfill, generation: u64) -> io::Result<Self> {
+        Ok(Generation {
+            data: lf.substructure(generation_name("data", generation))?,
+            index: lf.substructure(generation_name("index", generation))?,
+            refs: lf.substructure(generation_name("refs", generation))?,
+            serials: lf.substructure(generation_name("serials", generation))?,
+        })
+    }
+
+    // Store `bytes` under `id` with a reference count of `refs`, unless
+    // the id is already present
+    fn copy_in(
+        &self,
+        id: ContentId<N>,
+        bytes: &[u8],
+        alignment: usize,
+        refs: u32,
+    ) -> io::Result<()> {
+        self.index.insert(
+            &id,
+            |search, entry| {
+                if search.matches_tag(entry.tag) && entry.id() == id {
+                    search.halt()
+                } else {
+                    search.proceed()
+                }
+            },
+            |search| {
+                let ofs = self.data.write_aligned(bytes, alignment)?;
+                let serial = self.serials.update(|next| {
+                    *next += 1;
+                    *next - 1
+                });
+                self.refs.store(serial as usize, refs)?;
+
+                Ok(Entry {
+                    ofs,
+                    len: bytes.len() as u32,
+                    tag: search.tag_bytes(),
+                    serial,
+                    id: id.0,
+                })
+            },
+        )
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.data.flush()?;
+        self.index.flush()?;
+        self.refs.flush()?;
+        self.serials.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.data.close()?;
+        self.index.close()?;
+        self.refs.close()?;
+        self.serials.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.data.size_on_disk()
+            + self.index.size_on_disk()
+            + self.refs.size_on_disk()
+            + self.serials.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.data.verify()?,
+            self.index.verify()?,
+            self.refs.verify()?,
+            self.serials.verify()?,
+        ]))
+    }
+}
+
+fn remove_generation(lf: &Landfill, generation: u64) -> io::Result<()> {
+    for name in ["data", "index", "refs", "serials"] {
+        lf.branch(generation_name(name, generation)).remove_tree()?;
+    }
+    Ok(())
+}
+
+// Rebuild a store written before format headers as generation 1, digesting
+// the payloads to recover their ids
+fn migrate_legacy<D: Digest, const N: usize>(
+    lf: &Landfill,
+    generation: &Journal<u64>,
+    stats: &AtomicArray<u64>,
+) -> io::Result<Generation<N>> {
+    lf.check_writable()?;
+
+    let compacting = lf.branch("compacting".into());
+    compacting.create_marker()?;
+
+    let data: AppendOnly = lf.substructure("data")?;
+    let slots: RandomAccess<LegacyEntry> =
+        lf.branch("index".into()).substructure("slots")?;
+    let migrated = Generation::<N>::open(lf, 1)?;
+
+    let (mut entries, mut physical) = (0, 0);
+    for slot in 0..slots.len() {
+        let Some(entry) = slots.get(slot).map(|entry| *entry) else {
+            continue;
+        };
+        let bytes = data.try_get(entry.ofs, entry.len)?;
+        let id = ContentId::<N>::from_bytes::<D>(bytes);
+        let alignment = alignment_of(entry.ofs);
+        migrated.copy_in(id, bytes, alignment, UNTRACKED)?;
+        entries += 1;
+        physical += entry.len as u64;
+    }
+    migrated.flush()?;
+    data.close()?;
+    slots.close()?;
+
+    generation.update(|g| *g = 1);
+    generation.flush()?;
+    remove_generation(lf, 0)?;
+    compacting.take_marker()?;
+
+    stats.store(STAT_WRITTEN, entries)?;
+    stats.store(STAT_PHYSICAL_BYTES, physical)?;
+    Ok(migrated)
+}
+
+/// A storage for content-adressable byte-slices
+///
+/// Content is identified by its digest under `D`, truncated to `N` bytes.
+/// `N` can be no larger than the output size of `D`
+pub struct Content<D, const N: usize = 32> {
+    current: Generation<N>,
+    // the current generation, changed by compacting
+    generation: Journal<u64>,
+    stats: AtomicArray<u64>,
+    landfill: Landfill,
     _marker: PhantomData<D>,
 }
 
-impl<D> Substructure for Content<D> {
+impl<D, const N: usize> Substructure for Content<D, N>
+where
+    D: Digest,
+{
     fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        if N > <D as Digest>::output_size() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Content id wider than the digest",
+            ));
+        }
+
+        let generation: Journal<u64> = lf.substructure("generation")?;
+        let current = generation.current();
+
+        // clean up after an interrupted compaction, or the generation left
+        // over after switching to a new one
+        let compacting = lf.branch("compacting".into());
+        compacting.reserve_name();
+        if compacting.file_exists() {
+            if current > 0 {
+                remove_generation(&lf, current - 1)?;
+            }
+            remove_generation(&lf, current + 1)?;
+            compacting.take_marker()?;
+        }
+
+        let stats: AtomicArray<u64> = lf.substructure("stats")?;
+
+        // stores from before format headers have no generations yet, and
+        // are migrated to the second one
+        let current =
+            if lf.format_version() == Some(LEGACY_VERSION) && current == 0 {
+                migrate_legacy::<D, N>(&lf, &generation, &stats)?
+            } else {
+                Generation::open(&lf, current)?
+            };
+
         Ok(Content {
-            data: lf.substructure("data")?,
-            index: lf.substructure("index")?,
+            current,
+            generation,
+            stats,
+            landfill: lf.inner(),
             _marker: PhantomData,
         })
     }
 
+    fn format() -> Option<FormatHeader> {
+        Some(FormatHeader::new(
+            "content",
+            mem::size_of::<Entry<N>>(),
+            FORMAT_VERSION,
+        ))
+    }
+
+    fn reads_version(version: u32) -> bool {
+        version == LEGACY_VERSION || version == FORMAT_VERSION
+    }
+
+    // indexes written before the format header are migrated when opened
+    fn legacy_version() -> Option<u32> {
+        Some(LEGACY_VERSION)
+    }
+
+    fn data_branches() -> Option<&'static [&'static str]> {
+        Some(&["data", "index"])
+    }
+
     fn flush(&self) -> io::Result<()> {
-        self.data.flush()?;
-        self.index.flush()
+        self.current.flush()?;
+        self.generation.flush()?;
+        self.stats.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.current.close()?;
+        self.generation.close()?;
+        self.stats.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.current.size_on_disk()
+            + self.generation.size_on_disk()
+            + self.stats.size_on_disk()
+    }
+
+    /// Checks the digest of every stored item, along with the structures
+    /// it is made of
+    fn verify(&self) -> io::Result<VerifyReport> {
+        let mut report = VerifyReport::merged([
+            self.current.verify()?,
+            self.generation.verify()?,
+            self.stats.verify()?,
+        ]);
+
+        report.checked += self.current.index.len() as u64;
+        for id in self.corrupted() {
+            report
+                .problems
+                .push(format!("Content {id} does not match its digest"));
+        }
+        Ok(report)
     }
 }
 
-impl<D> Content<D>
+impl<D, const N: usize> Content<D, N>
 where
     D: Digest,
 {
     /// Insert bytes into the Content store, returning the content id
-    pub fn insert(&self, bytes: &[u8]) -> io::Result<ContentId> {
+    pub fn insert(&self, bytes: &[u8]) -> io::Result<ContentId<N>> {
         self.insert_aligned(bytes, 1)
     }
 
@@ -65,58 +462,290 @@ where
         &self,
         bytes: &[u8],
         alignment: usize,
-    ) -> io::Result<ContentId> {
+    ) -> io::Result<ContentId<N>> {
         let id = ContentId::from_bytes::<D>(bytes);
 
-        self.index.insert(
-            &id,
-            |search, entry| {
-                let search_tag = search.tag_u32();
+        self.index_with(id, bytes.len() as u32, || {
+            self.current.data.write_aligned(bytes, alignment)
+        })?;
+        Ok(id)
+    }
 
-                if search_tag == entry.tag {
-                    let stored = self.data.get(entry.ofs, entry.len);
-                    let stored_id = ContentId::from_bytes::<D>(stored);
+    /// Start inserting content of unknown length
+    ///
+    /// The returned writer hashes the bytes as they are written, and
+    /// `ContentWriter::finish` inserts them and returns the content id
+    pub fn insert_streaming(&self) -> ContentWriter<'_, D, N> {
+        ContentWriter {
+            content: self,
+            hasher: D::new(),
+            offset: 0,
+            buf: &mut [],
+            len: 0,
+        }
+    }
 
-                    if id == stored_id {
-                        search.halt()
-                    } else {
-                        search.proceed()
-                    }
+    // Index content of length `len` under `id`, calling `write` to store
+    // the bytes if they are not already present
+    fn index_with<F>(
+        &self,
+        id: ContentId<N>,
+        len: u32,
+        mut write: F,
+    ) -> io::Result<()>
+    where
+        F: FnMut() -> io::Result<u64>,
+    {
+        let mut written = false;
+
+        self.current.index.insert(
+            &id,
+            |search, entry| {
+                if search.matches_tag(entry.tag) && entry.id() == id {
+                    // inserting collected content brings it back to life
+                    let _ = self.current.refs.compare_exchange(
+                        entry.serial(),
+                        DEAD,
+                        0,
+                    );
+                    search.halt()
                 } else {
                     search.proceed()
                 }
             },
             |search| {
-                let ofs = self.data.write_aligned(bytes, alignment)?;
+                let ofs = write()?;
+                written = true;
+                let serial = self.current.serials.update(|next| {
+                    *next += 1;
+                    *next - 1
+                });
 
                 Ok(Entry {
                     ofs,
-                    len: bytes.len() as u32,
-                    tag: search.tag_u32(),
+                    len,
+                    tag: search.tag_bytes(),
+                    serial,
+                    id: id.0,
                 })
             },
         )?;
-        Ok(id)
+
+        let len = len as u64;
+        if written {
+            self.stats.fetch_add(STAT_WRITTEN, 1)?;
+            self.stats.fetch_add(STAT_PHYSICAL_BYTES, len)?;
+        } else {
+            self.stats.fetch_add(STAT_DEDUPLICATED, 1)?;
+        }
+        self.stats.fetch_add(STAT_LOGICAL_BYTES, len)?;
+        Ok(())
+    }
+
+    /// Returns deduplication statistics of all inserts into the store
+    pub fn dedup_stats(&self) -> DedupStats {
+        DedupStats {
+            deduplicated: self.stats.load(STAT_DEDUPLICATED),
+            written: self.stats.load(STAT_WRITTEN),
+            logical_bytes: self.stats.load(STAT_LOGICAL_BYTES),
+            physical_bytes: self.stats.load(STAT_PHYSICAL_BYTES),
+        }
     }
 
     /// Gets the value corresponding to the key, if any
-    pub fn get(&self, id: ContentId) -> Option<&[u8]> {
-        let mut result = None;
-        self.index.get(&id, |search, entry| {
-            let search_tag = search.tag_u32();
+    pub fn get(&self, id: ContentId<N>) -> Option<&[u8]> {
+        self.find(id)
+            .map(|entry| self.current.data.get(entry.ofs, entry.len))
+    }
 
-            if search_tag == entry.tag {
-                let stored = self.data.get(entry.ofs, entry.len);
+    /// Hint that the content with this id will be read soon
+    ///
+    /// Reading content in insertion order is prefetched automatically
+    pub fn prefetch(&self, id: ContentId<N>) -> io::Result<()> {
+        match self.find(id) {
+            Some(entry) => {
+                self.current.data.prefetch(entry.ofs, entry.len as u64)
+            }
+            None => Ok(()),
+        }
+    }
 
-                let stored_id = ContentId::from_bytes::<D>(stored);
+    /// Returns true if content with this id is stored
+    ///
+    /// Only the index is probed, the stored bytes are never read
+    pub fn contains(&self, id: ContentId<N>) -> bool {
+        self.find(id).is_some()
+    }
 
-                if stored_id == id {
-                    // found it!
-                    result = Some(stored);
-                    search.halt()
-                } else {
-                    search.proceed()
+    /// Returns the length of the content stored under this id, if any
+    pub fn size(&self, id: ContentId<N>) -> Option<u32> {
+        self.find(id).map(|entry| entry.len)
+    }
+
+    /// Re-digest all stored content, returning the ids of entries whose
+    /// bytes no longer match their id
+    ///
+    /// Entries whose bytes cannot be read at all are reported as well.
+    /// `Substructure::verify` includes these in its report
+    pub fn corrupted(&self) -> Vec<ContentId<N>> {
+        self.current
+            .index
+            .values()
+            .filter(|entry| {
+                match self.current.data.try_get(entry.ofs, entry.len) {
+                    Ok(stored) => {
+                        ContentId::<N>::from_bytes::<D>(stored) != entry.id()
+                    }
+                    Err(_) => true,
                 }
+            })
+            .map(|entry| entry.id())
+            .collect()
+    }
+
+    /// Returns the ids of all content stored in `other` but not in `self`
+    pub fn missing_from(&self, other: &Content<D, N>) -> Vec<ContentId<N>> {
+        other
+            .live_entries()
+            .filter(|entry| !self.contains(entry.id()))
+            .map(|entry| entry.id())
+            .collect()
+    }
+
+    /// Copy all content stored in `other` but not in `self` into `self`,
+    /// returning the number of items copied
+    pub fn sync_from(&self, other: &Content<D, N>) -> io::Result<usize> {
+        let mut copied = 0;
+        for entry in other.live_entries() {
+            if !self.contains(entry.id()) {
+                let bytes = other.current.data.try_get(entry.ofs, entry.len)?;
+                self.insert(bytes)?;
+                copied += 1;
+            }
+        }
+        Ok(copied)
+    }
+
+    fn live_entries(&self) -> impl Iterator<Item = Entry<N>> + '_ {
+        self.current
+            .index
+            .values()
+            .filter(|entry| self.current.refs.load(entry.serial()) != DEAD)
+    }
+
+    /// Take a reference to the content stored under this id
+    ///
+    /// Content that has been acquired at least once is reference counted,
+    /// and is marked dead by `collect` once all references are released.
+    /// Content that was never acquired is never collected
+    pub fn acquire(&self, id: ContentId<N>) -> io::Result<()> {
+        let serial = self.find(id).ok_or_else(not_found)?.serial();
+
+        let mut refs = self.current.refs.load(serial);
+        loop {
+            let new = match refs {
+                DEAD => return Err(not_found()),
+                UNTRACKED => 2,
+                n if n == DEAD - 1 => {
+                    return Err(io::Error::other("Too many references"))
+                }
+                n => n + 1,
+            };
+            match self.current.refs.compare_exchange(serial, refs, new)? {
+                Ok(_) => return Ok(()),
+                Err(actual) => refs = actual,
+            }
+        }
+    }
+
+    /// Release a reference taken with `acquire`
+    pub fn release(&self, id: ContentId<N>) -> io::Result<()> {
+        let serial = self.find(id).ok_or_else(not_found)?.serial();
+
+        let mut refs = self.current.refs.load(serial);
+        loop {
+            if refs == DEAD {
+                return Err(not_found());
+            } else if refs <= 1 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Content has no references to release",
+                ));
+            }
+            match self.current.refs.compare_exchange(serial, refs, refs - 1)? {
+                Ok(_) => return Ok(()),
+                Err(actual) => refs = actual,
+            }
+        }
+    }
+
+    /// Mark all acquired content without remaining references as dead
+    ///
+    /// Dead content is no longer returned by lookups, unless it is inserted
+    /// again, and its space is reclaimed by `compact`. Returns the number
+    /// of entries marked
+    pub fn collect(&self) -> io::Result<usize> {
+        let mut marked = 0;
+        for serial in 0..self.current.serials.current() as usize {
+            if self.current.refs.compare_exchange(serial, 1, DEAD)?.is_ok() {
+                marked += 1;
+            }
+        }
+        Ok(marked)
+    }
+
+    /// Rebuild the store without the content marked dead by `collect`
+    ///
+    /// Live content is copied into a fresh set of files, which replace the
+    /// old ones once complete, reclaiming the bytes, index slots and
+    /// reference counts of dead content. Returns the number of entries
+    /// removed
+    pub fn compact(&mut self) -> io::Result<usize> {
+        let generation = self.generation.current();
+        let next = generation + 1;
+
+        let compacting = self.landfill.branch("compacting".into());
+        compacting.create_marker()?;
+
+        let compacted = Generation::<N>::open(&self.landfill, next)?;
+
+        let mut removed = 0;
+        let mut physical = 0;
+        for entry in self.current.index.values() {
+            let refs = self.current.refs.load(entry.serial());
+            if refs == DEAD {
+                removed += 1;
+                continue;
+            }
+
+            let bytes = self.current.data.try_get(entry.ofs, entry.len)?;
+            let alignment = alignment_of(entry.ofs);
+            compacted.copy_in(entry.id(), bytes, alignment, refs)?;
+            physical += entry.len as u64;
+        }
+        compacted.flush()?;
+
+        // switch over to the new generation
+        self.generation.update(|g| *g = next);
+        self.generation.flush()?;
+
+        drop(mem::replace(&mut self.current, compacted));
+        remove_generation(&self.landfill, generation)?;
+        compacting.take_marker()?;
+
+        self.stats.store(STAT_PHYSICAL_BYTES, physical)?;
+        Ok(removed)
+    }
+
+    fn find(&self, id: ContentId<N>) -> Option<Entry<N>> {
+        let mut result = None;
+        self.current.index.get(&id, |search, entry| {
+            if search.matches_tag(entry.tag)
+                && entry.id() == id
+                && self.current.refs.load(entry.serial()) != DEAD
+            {
+                result = Some(*entry);
+                search.halt()
             } else {
                 search.proceed()
             }
@@ -124,3 +753,79 @@ where
         result
     }
 }
+
+/// A writer streaming bytes into a `Content` store
+///
+/// Bytes are copied into space reserved in the store and hashed
+/// incrementally. If the content was already stored, the space written is
+/// left unused
+pub struct ContentWriter<'a, D, const N: usize = 32> {
+    content: &'a Content<D, N>,
+    hasher: D,
+    offset: u64,
+    // reserved space, of which the first `len` bytes are written
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<D, const N: usize> ContentWriter<'_, D, N>
+where
+    D: Digest,
+{
+    /// Insert the written bytes into the store, returning the content id
+    pub fn finish(mut self) -> io::Result<ContentId<N>> {
+        if self.buf.is_empty() {
+            // nothing was written, reserve an empty slice to point to
+            self.offset = self.content.current.data.write(&[])?;
+        } else {
+            self.content.current.data.written(self.offset, self.len);
+        }
+
+        let id = ContentId::from_digest(self.hasher.finalize().as_ref());
+
+        let offset = self.offset;
+        self.content
+            .index_with(id, self.len as u32, || Ok(offset))?;
+        Ok(id)
+    }
+}
+
+impl<D, const N: usize> Write for ContentWriter<'_, D, N>
+where
+    D: Digest,
+{
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let needed = self.len + bytes.len();
+
+        if needed > u32::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Content too large",
+            ));
+        }
+
+        if needed > self.buf.len() {
+            let capacity = needed
+                .next_power_of_two()
+                .clamp(STREAM_INITIAL_CAPACITY, u32::MAX as usize);
+            let region = std::mem::take(&mut self.buf);
+            let (offset, buf) = self.content.current.data.grow(
+                self.offset,
+                region,
+                self.len,
+                capacity,
+            )?;
+            self.offset = offset;
+            self.buf = buf;
+        }
+
+        self.buf[self.len..needed].copy_from_slice(bytes);
+        self.hasher.update(bytes);
+        self.len = needed;
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+use std::cell::Cell;
+use std::hash::Hash;
+use std::io;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{GuardedLandfill, SmashMap, Substructure, VerifyReport};
+
+// A key and value stored together in a slot of the index
+//
+// Packed so that the slot has no padding bytes, the `occupied` marker keeps
+// an all-zero key and value distinct from an empty slot
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+struct Slot<K, V> {
+    occupied: u8,
+    key: K,
+    value: V,
+}
+
+// Safety: the struct is packed, so it has no padding, and all fields are Pod
+unsafe impl<K: Pod, V: Pod> Zeroable for Slot<K, V> {}
+unsafe impl<K: Pod, V: Pod> Pod for Slot<K, V> {}
+
+/// A map storing fixed-size keys inline next to their values
+///
+/// Unlike a bare `SmashMap`, keys are compared exactly, so lookups have no
+/// false positives. Each key can be set only once
+pub struct InlineMap<K, V> {
+    index: SmashMap<K, Slot<K, V>>,
+}
+
+impl<K, V> Substructure for InlineMap<K, V> {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(InlineMap {
+            index: lf.substructure("index")?,
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.index.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.index.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.index.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        self.index.verify()
+    }
+}
+
+impl<K, V> InlineMap<K, V>
+where
+    K: Hash + Pod + Eq,
+    V: Pod,
+{
+    /// Insert a key-value pair into the map
+    ///
+    /// If the key is already present, the map is left unchanged and the
+    /// existing value is returned
+    pub fn insert(&self, key: K, value: V) -> io::Result<Option<V>> {
+        let existing = Cell::new(None);
+
+        self.index.insert(
+            &key,
+            |search, slot| {
+                if { slot.key } == key {
+                    existing.set(Some(slot.value));
+                    search.halt()
+                } else {
+                    search.proceed()
+                }
+            },
+            |_| {
+                Ok(Slot {
+                    occupied: 1,
+                    key,
+                    value,
+                })
+            },
+        )?;
+
+        Ok(existing.get())
+    }
+
+    /// Gets the value corresponding to the key, if any
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.index
+            .get_first(key, |_, slot| { slot.key } == *key)
+            .map(|slot| slot.value)
+    }
+
+    /// Returns true if the key is present in the map
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the number of entries in the map
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Returns true if the map has no entries
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+}
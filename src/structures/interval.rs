@@ -0,0 +1,153 @@
+use std::io;
+use std::ops::Range;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    GuardedLandfill, Journal, RandomAccess, SkipList, Substructure,
+    VerifyReport,
+};
+
+// class, start and id of an interval
+type Key = [u64; 3];
+
+const CLASSES: u32 = u64::BITS + 1;
+
+/// An index of values by half-open `u64` ranges, supporting overlap queries
+///
+/// Intervals are binned by length class, the smallest power of two at least
+/// as large as their length, and kept ordered by start within each class.
+/// An overlap query thus only has to look at intervals starting at most one
+/// class length before the queried range, in each class in use.
+pub struct IntervalTree<V> {
+    // maps each interval to its end
+    intervals: SkipList<Key, u64>,
+    values: RandomAccess<V>,
+    ids: Journal<u64>,
+    // bit set for each length class in use
+    classes: Journal<u128>,
+}
+
+impl<V> Substructure for IntervalTree<V> {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(IntervalTree {
+            intervals: lf.substructure("intervals")?,
+            values: lf.substructure("values")?,
+            ids: lf.substructure("ids")?,
+            classes: lf.substructure("classes")?,
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.intervals.flush()?;
+        self.values.flush()?;
+        self.ids.flush()?;
+        self.classes.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.intervals.close()?;
+        self.values.close()?;
+        self.ids.close()?;
+        self.classes.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.intervals.size_on_disk()
+            + self.values.size_on_disk()
+            + self.ids.size_on_disk()
+            + self.classes.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.intervals.verify()?,
+            self.values.verify()?,
+            self.ids.verify()?,
+            self.classes.verify()?,
+        ]))
+    }
+}
+
+impl<V> IntervalTree<V>
+where
+    V: Zeroable + Pod,
+{
+    /// Insert a value for a non-empty range
+    pub fn insert(&self, range: Range<u64>, v: V) -> io::Result<()> {
+        if range.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Cannot index an empty range",
+            ));
+        }
+
+        let id = self.ids.update(|ids| {
+            *ids += 1;
+            *ids - 1
+        });
+        *self.values.get_mut(id as usize)? = v;
+
+        let class = Self::class(&range);
+        self.classes.update(|classes| *classes |= 1 << class);
+        self.intervals
+            .insert([class as u64, range.start, id], range.end)?;
+        Ok(())
+    }
+
+    /// Iterate over all intervals containing `point`
+    pub fn stab(
+        &self,
+        point: u64,
+    ) -> impl Iterator<Item = (Range<u64>, V)> + '_ {
+        self.overlapping(point..point.saturating_add(1))
+    }
+
+    /// Iterate over all intervals overlapping `range`
+    ///
+    /// Intervals are grouped by length class, and ordered by start within
+    /// each group
+    pub fn overlapping(
+        &self,
+        range: Range<u64>,
+    ) -> impl Iterator<Item = (Range<u64>, V)> + '_ {
+        let classes = self.classes.current();
+        let Range { start, end } = range;
+
+        (0..CLASSES)
+            .filter(move |class| !range.is_empty() && classes & 1 << class != 0)
+            .flat_map(move |class| {
+                // intervals of this class starting earlier end before `start`
+                let lower = start.saturating_sub(Self::max_len(class) - 1);
+                self.intervals
+                    .iter_from(&[class as u64, lower, 0])
+                    .take_while(move |([c, s, _], _)| {
+                        *c == class as u64 && *s < end
+                    })
+            })
+            .filter(move |(_, e)| *e > start)
+            .filter_map(|([_, s, id], e)| {
+                let v = self.values.get_raw(id as usize)?;
+                Some((s..e, *v))
+            })
+    }
+
+    /// The number of intervals in the index
+    pub fn len(&self) -> usize {
+        self.intervals.len()
+    }
+
+    /// Returns true if the index is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn class(range: &Range<u64>) -> u32 {
+        let len = range.end - range.start;
+        u64::BITS - (len - 1).leading_zeros()
+    }
+
+    fn max_len(class: u32) -> u64 {
+        1u64.checked_shl(class).unwrap_or(u64::MAX)
+    }
+}
@@ -0,0 +1,225 @@
+use std::cell::Cell;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::{io, mem};
+
+use bytemuck::{Pod, Zeroable};
+use bytemuck_derive::*;
+
+use crate::{
+    AppendOnly, AtomicArray, GuardedLandfill, Journal, SmashMap, Substructure,
+    VerifyReport,
+};
+
+// cell of the counters holding the number of live keys
+const LEN: usize = 0;
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Entry {
+    k_ofs: u64,
+    // index of the head of the key
+    slot: u32,
+    tag: [u8; 4],
+}
+
+/// A map from keys to values supporting updates and removals
+///
+/// Every value written is appended to the data region, and each key has a
+/// head pointing to its current value. Removing a key clears its head, which
+/// acts as a tombstone, leaving the key in the index to be reused when it is
+/// inserted again
+pub struct KVMap<K, V> {
+    data: AppendOnly,
+    index: SmashMap<K, Entry>,
+    // offset + 1 of the current value of each key, 0 for removed keys
+    heads: AtomicArray<u64>,
+    slots: Journal<u64>,
+    counters: AtomicArray<u64>,
+    _marker: PhantomData<V>,
+}
+
+impl<K, V> Substructure for KVMap<K, V> {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(KVMap {
+            data: lf.substructure("data")?,
+            index: lf.substructure("index")?,
+            heads: lf.substructure("heads")?,
+            slots: lf.substructure("slots")?,
+            counters: lf.substructure("counters")?,
+            _marker: PhantomData,
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.data.flush()?;
+        self.index.flush()?;
+        self.heads.flush()?;
+        self.slots.flush()?;
+        self.counters.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.data.close()?;
+        self.index.close()?;
+        self.heads.close()?;
+        self.slots.close()?;
+        self.counters.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.data.size_on_disk()
+            + self.index.size_on_disk()
+            + self.heads.size_on_disk()
+            + self.slots.size_on_disk()
+            + self.counters.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.data.verify()?,
+            self.index.verify()?,
+            self.heads.verify()?,
+            self.slots.verify()?,
+            self.counters.verify()?,
+        ]))
+    }
+}
+
+impl<K, V> KVMap<K, V>
+where
+    K: Hash + Zeroable + Pod + PartialEq + Eq,
+    V: Zeroable + Pod,
+{
+    /// Insert a key-value pair into the map, returning the previous value
+    pub fn insert(&self, k: K, v: V) -> io::Result<Option<V>> {
+        let v_ofs = self
+            .data
+            .write_aligned(bytemuck::bytes_of(&v), mem::align_of::<V>())?;
+        let slot = self.slot_or_insert(k)?;
+
+        let mut head = self.heads.load(slot);
+        loop {
+            match self.heads.compare_exchange(slot, head, v_ofs + 1)? {
+                Ok(_) => break,
+                Err(actual) => head = actual,
+            }
+        }
+
+        if head == 0 {
+            self.counters.fetch_add(LEN, 1)?;
+        }
+        Ok(self.value_at(head))
+    }
+
+    /// Remove a key from the map, returning its value
+    pub fn remove(&self, k: &K) -> io::Result<Option<V>> {
+        let Some(slot) = self.slot(k) else {
+            return Ok(None);
+        };
+
+        let mut head = self.heads.load(slot);
+        while head != 0 {
+            match self.heads.compare_exchange(slot, head, 0)? {
+                Ok(_) => {
+                    self.counters.fetch_add(LEN, u64::MAX)?;
+                    break;
+                }
+                Err(actual) => head = actual,
+            }
+        }
+        Ok(self.value_at(head))
+    }
+
+    /// Get the current value of a key
+    pub fn get(&self, k: &K) -> Option<V> {
+        self.value_at(self.heads.load(self.slot(k)?))
+    }
+
+    /// Returns true if the key is set
+    pub fn contains_key(&self, k: &K) -> bool {
+        self.get(k).is_some()
+    }
+
+    /// Returns the number of keys set
+    pub fn len(&self) -> usize {
+        self.counters.load(LEN) as usize
+    }
+
+    /// Returns true if no key is set
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over copies of all key-value pairs, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (K, V)> + '_ {
+        self.index.values().filter_map(|entry| {
+            let v = self.value_at(self.heads.load(entry.slot as usize))?;
+            Some((self.entry_key(&entry), v))
+        })
+    }
+
+    // Find the head slot of a key, if it was ever inserted
+    fn slot(&self, k: &K) -> Option<usize> {
+        let mut result = None;
+        self.index.get(k, |search, entry| {
+            if search.matches_tag(entry.tag) && *k == self.entry_key(entry) {
+                result = Some(entry.slot as usize);
+                search.halt()
+            } else {
+                search.proceed()
+            }
+        });
+        result
+    }
+
+    // Find the head slot of a key, adding the key to the index if needed
+    fn slot_or_insert(&self, k: K) -> io::Result<usize> {
+        let existing = Cell::new(None);
+
+        self.index.insert(
+            &k,
+            |search, entry| {
+                if search.matches_tag(entry.tag) && k == self.entry_key(entry) {
+                    existing.set(Some(entry.slot));
+                    search.halt()
+                } else {
+                    search.proceed()
+                }
+            },
+            |search| {
+                let k_ofs = self.data.write_aligned(
+                    bytemuck::bytes_of(&k),
+                    mem::align_of::<K>(),
+                )?;
+                let slot = self.slots.try_update(|slots| {
+                    let slot = u32::try_from(*slots).map_err(|_| {
+                        io::Error::other("KVMap key limit reached")
+                    })?;
+                    *slots += 1;
+                    Ok::<_, io::Error>(slot)
+                })?;
+                existing.set(Some(slot));
+
+                Ok(Entry {
+                    k_ofs,
+                    slot,
+                    tag: search.tag_bytes(),
+                })
+            },
+        )?;
+
+        Ok(existing.get().expect("set by either closure") as usize)
+    }
+
+    fn entry_key(&self, entry: &Entry) -> K {
+        let key_bytes = self.data.get(entry.k_ofs, mem::size_of::<K>() as u32);
+        bytemuck::pod_read_unaligned(key_bytes)
+    }
+
+    fn value_at(&self, head: u64) -> Option<V> {
+        let v_ofs = head.checked_sub(1)?;
+        let v_bytes = self.data.get(v_ofs, mem::size_of::<V>() as u32);
+        Some(bytemuck::pod_read_unaligned(v_bytes))
+    }
+}
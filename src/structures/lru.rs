@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::{io, mem};
+
+use bytemuck::{Pod, Zeroable};
+use parking_lot::Mutex;
+
+use crate::{
+    GuardedLandfill, Journal, RandomAccess, Substructure, VerifyReport,
+};
+
+const DEFAULT_BUDGET: u64 = 1 << 20;
+
+// slot 0 is the sentinel of the recency list, its next slot is the most
+// recently used and its previous slot the least recently used
+const SENTINEL: usize = 0;
+const PREV: usize = 0;
+const NEXT: usize = 1;
+
+// head of the free list, number of slots ever allocated and byte budget
+const FREE: usize = 0;
+const ALLOCATED: usize = 1;
+const BUDGET: usize = 2;
+
+/// A cache of keys and values on disk with a bounded size
+///
+/// Entries live in a fixed set of slots, and once the byte budget is used
+/// up, inserting evicts the least recently used entry and reuses its slot.
+/// Slots freed by removals are kept in a free list. The recency order is
+/// kept on disk, while the key lookup table is rebuilt in memory on open.
+///
+/// As a cache, entries being written while crashing may be lost.
+pub struct LruCache<K, V> {
+    keys: RandomAccess<K>,
+    values: RandomAccess<V>,
+    links: RandomAccess<[u64; 2]>,
+    meta: Journal<[u64; 3]>,
+    slots: Mutex<HashMap<K, usize>>,
+}
+
+impl<K, V> Substructure for LruCache<K, V>
+where
+    K: Hash + Eq + Zeroable + Pod,
+    V: Zeroable + Pod,
+{
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        let cache = LruCache {
+            keys: lf.substructure("keys")?,
+            values: lf.substructure("values")?,
+            links: lf.substructure("links")?,
+            meta: lf.substructure("meta")?,
+            slots: Mutex::new(HashMap::new()),
+        };
+
+        // rebuild the lookup table from the recency list, starting over if
+        // it was left inconsistent
+        let allocated = cache.meta.current()[ALLOCATED] as usize;
+        let mut slots = HashMap::new();
+        let mut slot = cache.link(SENTINEL, NEXT);
+        while slot != SENTINEL {
+            if slot > allocated || slots.len() >= allocated {
+                slots.clear();
+                cache.links.get_mut(SENTINEL)?.fill(0);
+                cache.meta.modify(|meta| {
+                    meta[FREE] = 0;
+                    meta[ALLOCATED] = 0;
+                });
+                break;
+            }
+            slots.insert(cache.key(slot), slot);
+            slot = cache.link(slot, NEXT);
+        }
+        *cache.slots.lock() = slots;
+
+        Ok(cache)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.keys.flush()?;
+        self.values.flush()?;
+        self.links.flush()?;
+        self.meta.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.keys.close()?;
+        self.values.close()?;
+        self.links.close()?;
+        self.meta.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.keys.size_on_disk()
+            + self.values.size_on_disk()
+            + self.links.size_on_disk()
+            + self.meta.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.keys.verify()?,
+            self.values.verify()?,
+            self.links.verify()?,
+            self.meta.verify()?,
+        ]))
+    }
+}
+
+impl<K, V> LruCache<K, V>
+where
+    K: Hash + Eq + Zeroable + Pod,
+    V: Zeroable + Pod,
+{
+    /// Get the value of a key, marking it as recently used
+    pub fn get(&self, k: &K) -> io::Result<Option<V>> {
+        let slots = self.slots.lock();
+        let Some(slot) = slots.get(k).copied() else {
+            return Ok(None);
+        };
+
+        self.unlink(slot)?;
+        self.push_front(slot)?;
+        Ok(Some(self.value(slot)))
+    }
+
+    /// Returns true if the key is cached, without marking it as used
+    pub fn contains_key(&self, k: &K) -> bool {
+        self.slots.lock().contains_key(k)
+    }
+
+    /// Insert a key-value pair, returning the previous value of the key
+    ///
+    /// Evicts the least recently used entry if the cache is full
+    pub fn insert(&self, k: K, v: V) -> io::Result<Option<V>> {
+        let mut slots = self.slots.lock();
+
+        if let Some(slot) = slots.get(&k).copied() {
+            let old = mem::replace(&mut *self.values.get_mut(slot)?, v);
+            self.unlink(slot)?;
+            self.push_front(slot)?;
+            return Ok(Some(old));
+        }
+
+        let slot = if slots.len() < self.capacity() {
+            self.allocate()?
+        } else {
+            let lru = self.link(SENTINEL, PREV);
+            slots.remove(&self.key(lru));
+            self.unlink(lru)?;
+            lru
+        };
+
+        *self.keys.get_mut(slot)? = k;
+        *self.values.get_mut(slot)? = v;
+        self.push_front(slot)?;
+        slots.insert(k, slot);
+        Ok(None)
+    }
+
+    /// Remove a key from the cache, returning its value
+    pub fn remove(&self, k: &K) -> io::Result<Option<V>> {
+        let mut slots = self.slots.lock();
+        let Some(slot) = slots.remove(k) else {
+            return Ok(None);
+        };
+
+        self.unlink(slot)?;
+        self.free(slot)?;
+        Ok(Some(self.value(slot)))
+    }
+
+    /// Set the number of bytes the cache may use, evicting entries if needed
+    pub fn set_budget(&self, bytes: u64) -> io::Result<()> {
+        let mut slots = self.slots.lock();
+        self.meta.modify(|meta| meta[BUDGET] = bytes.max(1));
+
+        while slots.len() > self.capacity() {
+            let lru = self.link(SENTINEL, PREV);
+            slots.remove(&self.key(lru));
+            self.unlink(lru)?;
+            self.free(lru)?;
+        }
+        Ok(())
+    }
+
+    /// The maximum number of entries within the byte budget
+    pub fn capacity(&self) -> usize {
+        let budget = match self.meta.current()[BUDGET] {
+            0 => DEFAULT_BUDGET,
+            budget => budget,
+        };
+        let slot_size = mem::size_of::<K>()
+            + mem::size_of::<V>()
+            + mem::size_of::<[u64; 2]>();
+        ((budget / slot_size as u64) as usize).max(1)
+    }
+
+    /// Returns the number of cached entries
+    pub fn len(&self) -> usize {
+        self.slots.lock().len()
+    }
+
+    /// Returns true if the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Take a slot from the free list, or a fresh one
+    fn allocate(&self) -> io::Result<usize> {
+        let meta = self.meta.current();
+        if meta[FREE] != 0 {
+            let slot = meta[FREE] as usize;
+            let next_free = self.link(slot, NEXT);
+            self.meta.modify(|meta| meta[FREE] = next_free as u64);
+            Ok(slot)
+        } else {
+            // slots are numbered from 1, after the sentinel
+            Ok(self.meta.modify(|meta| {
+                meta[ALLOCATED] += 1;
+                meta[ALLOCATED] as usize
+            }))
+        }
+    }
+
+    // Put an unlinked slot on the free list
+    fn free(&self, slot: usize) -> io::Result<()> {
+        let free = self.meta.current()[FREE];
+        self.links.get_mut(slot)?[NEXT] = free;
+        self.meta.modify(|meta| meta[FREE] = slot as u64);
+        Ok(())
+    }
+
+    fn push_front(&self, slot: usize) -> io::Result<()> {
+        let first = self.link(SENTINEL, NEXT);
+        *self.links.get_mut(slot)? = [SENTINEL as u64, first as u64];
+        self.links.get_mut(first)?[PREV] = slot as u64;
+        self.links.get_mut(SENTINEL)?[NEXT] = slot as u64;
+        Ok(())
+    }
+
+    fn unlink(&self, slot: usize) -> io::Result<()> {
+        let (prev, next) = (self.link(slot, PREV), self.link(slot, NEXT));
+        self.links.get_mut(prev)?[NEXT] = next as u64;
+        self.links.get_mut(next)?[PREV] = prev as u64;
+        Ok(())
+    }
+
+    fn link(&self, slot: usize, direction: usize) -> usize {
+        self.links
+            .get_raw(slot)
+            .map(|links| links[direction] as usize)
+            .unwrap_or(SENTINEL)
+    }
+
+    fn key(&self, slot: usize) -> K {
+        self.keys
+            .get_raw(slot)
+            .map(|k| *k)
+            .unwrap_or_else(K::zeroed)
+    }
+
+    fn value(&self, slot: usize) -> V {
+        self.values
+            .get_raw(slot)
+            .map(|v| *v)
+            .unwrap_or_else(V::zeroed)
+    }
+}
@@ -0,0 +1,351 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bytemuck::Pod;
+use parking_lot::{Mutex, RwLock};
+
+use crate::{
+    FramedAppendOnly, GuardedLandfill, Journal, Substructure, VerifyReport,
+};
+
+// maximum number of sorted runs before they are merged
+const MAX_RUNS: usize = 8;
+const DEFAULT_MEMTABLE_LIMIT: usize = 4096;
+
+// offset in the write-ahead log where the memtable starts, the number of
+// runs and the offsets of the runs, newest first
+type Manifest = [u64; 2 + MAX_RUNS];
+
+const WAL_START: usize = 0;
+const N_RUNS: usize = 1;
+const RUNS: usize = 2;
+
+// record flags
+const TOMBSTONE: u8 = 0;
+const LIVE: u8 = 1;
+
+struct Memtable<K, V> {
+    // None marks a removed key
+    entries: BTreeMap<K, Option<V>>,
+    // end of the last write-ahead log frame applied to the memtable
+    wal_end: u64,
+}
+
+/// A log-structured merge tree of keys and values
+///
+/// Writes go to a write-ahead log and an in-memory table, which is written
+/// out as a sorted run once it grows past a limit. Lookups check the
+/// memtable and then each run, newest first. Once there are too many runs
+/// they are merged into one, which can also be done ahead of time with
+/// `compact`, for example from a background thread.
+///
+/// The tree only grows. Merged runs and the part of the write-ahead log that
+/// was written out as a run are no longer read, but their space is not
+/// reclaimed, so the size on disk follows the total amount of data written
+/// rather than the number of keys.
+pub struct LsmTree<K, V> {
+    wal: FramedAppendOnly,
+    runs: FramedAppendOnly,
+    manifest: Journal<Manifest>,
+    memtable: RwLock<Memtable<K, V>>,
+    memtable_limit: AtomicUsize,
+    // only one merge can be in progress at a time
+    merging: Mutex<()>,
+}
+
+impl<K, V> Substructure for LsmTree<K, V>
+where
+    K: Pod + Ord,
+    V: Pod,
+{
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        let wal: FramedAppendOnly = lf.substructure("wal")?;
+        let runs: FramedAppendOnly = lf.substructure("runs")?;
+        let manifest: Journal<Manifest> = lf.substructure("manifest")?;
+
+        let current = manifest.current();
+        for run in Self::run_offsets_of(&current) {
+            if runs.get(run).is_none() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Corrupt LSM run",
+                ));
+            }
+        }
+
+        // replay the writes that had not yet been written out as a run
+        let mut memtable = Memtable {
+            entries: BTreeMap::new(),
+            wal_end: current[WAL_START],
+        };
+        for (offset, record) in wal.iter_from(current[WAL_START]) {
+            let (k, v) = Self::decode(record)?;
+            memtable.entries.insert(k, v);
+            memtable.wal_end = FramedAppendOnly::end_of(offset, record);
+        }
+
+        Ok(LsmTree {
+            wal,
+            runs,
+            manifest,
+            memtable: RwLock::new(memtable),
+            memtable_limit: AtomicUsize::new(DEFAULT_MEMTABLE_LIMIT),
+            merging: Mutex::new(()),
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.wal.flush()?;
+        self.runs.flush()?;
+        self.manifest.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.wal.close()?;
+        self.runs.close()?;
+        self.manifest.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.wal.size_on_disk()
+            + self.runs.size_on_disk()
+            + self.manifest.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.wal.verify()?,
+            self.runs.verify()?,
+            self.manifest.verify()?,
+        ]))
+    }
+}
+
+impl<K, V> LsmTree<K, V>
+where
+    K: Pod + Ord,
+    V: Pod,
+{
+    /// Set a key to a value
+    pub fn insert(&self, k: K, v: V) -> io::Result<()> {
+        self.write(k, Some(v))
+    }
+
+    /// Remove a key
+    pub fn remove(&self, k: K) -> io::Result<()> {
+        self.write(k, None)
+    }
+
+    /// Get the current value of a key
+    ///
+    /// Fails with `InvalidData` if a run on disk is corrupted
+    pub fn get(&self, k: &K) -> io::Result<Option<V>> {
+        if let Some(v) = self.memtable.read().entries.get(k) {
+            return Ok(*v);
+        }
+
+        for run in self.run_offsets() {
+            if let Some(v) = self.search_run(run, k)? {
+                return Ok(v);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Set the number of keys the memtable holds before it is written out
+    pub fn set_memtable_limit(&self, limit: usize) {
+        self.memtable_limit.store(limit.max(1), Ordering::Relaxed);
+    }
+
+    /// Returns the number of sorted runs on disk
+    pub fn runs(&self) -> usize {
+        self.manifest.current()[N_RUNS] as usize
+    }
+
+    /// Write the memtable out as a sorted run
+    pub fn flush_memtable(&self) -> io::Result<()> {
+        let mut memtable = self.memtable.write();
+        if memtable.entries.is_empty() {
+            return Ok(());
+        }
+
+        if self.runs() == MAX_RUNS {
+            self.compact()?;
+        }
+
+        let mut payload =
+            Vec::with_capacity(memtable.entries.len() * Self::record_size());
+        for (k, v) in &memtable.entries {
+            Self::encode(k, *v, &mut payload);
+        }
+        let run = self.runs.write(&payload)?;
+
+        let wal_end = memtable.wal_end;
+        self.manifest.modify(|manifest| {
+            let n_runs = manifest[N_RUNS] as usize;
+            manifest.copy_within(RUNS..RUNS + n_runs, RUNS + 1);
+            manifest[RUNS] = run;
+            manifest[N_RUNS] += 1;
+            manifest[WAL_START] = wal_end;
+        });
+
+        memtable.entries.clear();
+        Ok(())
+    }
+
+    /// Merge all sorted runs into one, dropping removed keys
+    ///
+    /// Runs written while the merge is in progress are kept as is
+    pub fn compact(&self) -> io::Result<()> {
+        let _merging = self.merging.lock();
+
+        let merged_runs = self.run_offsets();
+        if merged_runs.len() < 2 {
+            return Ok(());
+        }
+
+        let runs = merged_runs
+            .iter()
+            .map(|run| self.runs.get_trusted(*run))
+            .collect::<io::Result<Vec<&[u8]>>>()?;
+        let mut positions = vec![0; runs.len()];
+        let record_size = Self::record_size();
+        let mut payload = vec![];
+
+        loop {
+            // the smallest key at the heads of the runs, newest run first
+            let mut smallest: Option<(K, &[u8])> = None;
+            for (run, pos) in runs.iter().zip(&positions) {
+                if let Some(record) = run.get(*pos..*pos + record_size) {
+                    let k = Self::key_of(record);
+                    if smallest.is_none_or(|(smallest, _)| k < smallest) {
+                        smallest = Some((k, record));
+                    }
+                }
+            }
+
+            let Some((k, record)) = smallest else { break };
+
+            for (run, pos) in runs.iter().zip(&mut positions) {
+                if let Some(head) = run.get(*pos..*pos + record_size) {
+                    if Self::key_of(head) == k {
+                        *pos += record_size;
+                    }
+                }
+            }
+
+            // this is the oldest data, so removals no longer need recording
+            if record[record_size - 1] == LIVE {
+                payload.extend_from_slice(record);
+            }
+        }
+
+        let merged = match payload.is_empty() {
+            true => None,
+            false => Some(self.runs.write(&payload)?),
+        };
+
+        self.manifest.modify(|manifest| {
+            // runs can only have been added in front of the merged ones
+            let n_runs = manifest[N_RUNS] as usize;
+            let kept = n_runs - merged_runs.len();
+            manifest[RUNS + kept..].fill(0);
+            if let Some(merged) = merged {
+                manifest[RUNS + kept] = merged;
+            }
+            manifest[N_RUNS] = (kept + merged.is_some() as usize) as u64;
+        });
+
+        Ok(())
+    }
+
+    fn write(&self, k: K, v: Option<V>) -> io::Result<()> {
+        let mut record = Vec::with_capacity(Self::record_size());
+        Self::encode(&k, v, &mut record);
+
+        let full = {
+            let mut memtable = self.memtable.write();
+            let offset = self.wal.write(&record)?;
+            memtable.wal_end = FramedAppendOnly::end_of(offset, &record);
+            memtable.entries.insert(k, v);
+            memtable.entries.len()
+                >= self.memtable_limit.load(Ordering::Relaxed)
+        };
+
+        if full {
+            self.flush_memtable()?;
+        }
+        Ok(())
+    }
+
+    // Binary search a run, returning `Some(None)` for removed keys
+    fn search_run(&self, run: u64, k: &K) -> io::Result<Option<Option<V>>> {
+        let records = self.runs.get_trusted(run)?;
+        let record_size = Self::record_size();
+
+        let (mut low, mut high) = (0, records.len() / record_size);
+        while low < high {
+            let mid = (low + high) / 2;
+            let record = &records[mid * record_size..][..record_size];
+            match Self::key_of(record).cmp(k) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => {
+                    return Ok(Some(Self::decode(record)?.1))
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn run_offsets(&self) -> Vec<u64> {
+        Self::run_offsets_of(&self.manifest.current())
+    }
+
+    fn run_offsets_of(manifest: &Manifest) -> Vec<u64> {
+        let n_runs = (manifest[N_RUNS] as usize).min(MAX_RUNS);
+        manifest[RUNS..RUNS + n_runs].to_vec()
+    }
+
+    // records are the key, the value and a flag byte
+    fn record_size() -> usize {
+        mem::size_of::<K>() + mem::size_of::<V>() + 1
+    }
+
+    fn encode(k: &K, v: Option<V>, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(bytemuck::bytes_of(k));
+        match v {
+            Some(v) => {
+                buf.extend_from_slice(bytemuck::bytes_of(&v));
+                buf.push(LIVE);
+            }
+            None => {
+                buf.extend_from_slice(bytemuck::bytes_of(&V::zeroed()));
+                buf.push(TOMBSTONE);
+            }
+        }
+    }
+
+    fn key_of(record: &[u8]) -> K {
+        bytemuck::pod_read_unaligned(&record[..mem::size_of::<K>()])
+    }
+
+    fn decode(record: &[u8]) -> io::Result<(K, Option<V>)> {
+        if record.len() != Self::record_size() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid LSM record",
+            ));
+        }
+
+        let k = Self::key_of(record);
+        let v_bytes = &record[mem::size_of::<K>()..record.len() - 1];
+        let v = match record[record.len() - 1] {
+            TOMBSTONE => None,
+            _ => Some(bytemuck::pod_read_unaligned(v_bytes)),
+        };
+        Ok((k, v))
+    }
+}
@@ -0,0 +1,309 @@
+use std::io;
+use std::marker::PhantomData;
+
+use digest::{Digest, Output};
+use parking_lot::Mutex;
+
+use crate::{
+    AppendLog, AppendOnly, GuardedLandfill, RandomAccess, Substructure,
+    VerifyReport,
+};
+
+// node hashes are stored in pieces of this size
+const PIECE: usize = 32;
+
+// domain separation between leaves and inner nodes
+const LEAF: u8 = 0;
+const NODE: u8 = 1;
+
+type Piece = [u8; PIECE];
+
+/// An authenticated append-only log, stored as a Merkle mountain range
+///
+/// Leaves are hashed into a forest of perfect binary trees, one per set bit
+/// of the number of leaves. The root commits to every leaf, and a proof that
+/// a leaf is part of the log only takes a logarithmic number of hashes.
+///
+/// Nodes are numbered in the order they are written, children before their
+/// parent. The data of each leaf is kept as well, and the log only grows
+/// once a leaf and all its new parents have been written.
+pub struct MerkleMountainRange<D> {
+    data: AppendOnly,
+    // offset and length of the data of each leaf
+    leaves: AppendLog<[u64; 2]>,
+    nodes: RandomAccess<Piece>,
+    writer: Mutex<()>,
+    _marker: PhantomData<D>,
+}
+
+/// A proof that a leaf is part of a `MerkleMountainRange`
+#[derive(Clone, Debug)]
+pub struct MerkleProof<D: Digest> {
+    position: u64,
+    leaves: u64,
+    // siblings on the path from the leaf to its peak, bottom up
+    siblings: Vec<Output<D>>,
+    peaks: Vec<Output<D>>,
+}
+
+impl<D> Substructure for MerkleMountainRange<D>
+where
+    D: Digest,
+{
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        if <D as Digest>::output_size() == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Digest has no output",
+            ));
+        }
+
+        Ok(MerkleMountainRange {
+            data: lf.substructure("data")?,
+            leaves: lf.substructure("leaves")?,
+            nodes: lf.substructure("nodes")?,
+            writer: Mutex::new(()),
+            _marker: PhantomData,
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.data.flush()?;
+        self.leaves.flush()?;
+        self.nodes.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.data.close()?;
+        self.leaves.close()?;
+        self.nodes.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.data.size_on_disk()
+            + self.leaves.size_on_disk()
+            + self.nodes.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.data.verify()?,
+            self.leaves.verify()?,
+            self.nodes.verify()?,
+        ]))
+    }
+}
+
+impl<D> MerkleMountainRange<D>
+where
+    D: Digest,
+{
+    /// Append a leaf to the log, returning its position
+    pub fn append(&self, leaf: &[u8]) -> io::Result<u64> {
+        let _writer = self.writer.lock();
+
+        let position = self.leaves.len();
+        let mut node = node_count(position);
+        let mut hash = leaf_hash::<D>(leaf);
+        self.write_node(node, &hash)?;
+
+        // every trailing one of the previous number of leaves is a tree of
+        // the same height to merge with
+        for height in 0..position.trailing_ones() {
+            let left = node + 1 - (2 << height);
+            hash = node_hash::<D>(&self.node(left), &hash);
+            node += 1;
+            self.write_node(node, &hash)?;
+        }
+
+        let offset = self.data.write(leaf)?;
+        self.leaves.push([offset, leaf.len() as u64])
+    }
+
+    /// Get the data of the leaf at `position`
+    pub fn get(&self, position: u64) -> Option<&[u8]> {
+        let [offset, len] = *self.leaves.get(position)?;
+        if len == 0 {
+            return Some(&[]);
+        }
+        self.data.try_get(offset, len as u32).ok()
+    }
+
+    /// The number of leaves in the log
+    pub fn len(&self) -> u64 {
+        self.leaves.len()
+    }
+
+    /// Returns true if the log has no leaves
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The root hash committing to all leaves, `None` for an empty log
+    pub fn root(&self) -> Option<Output<D>> {
+        bag::<D>(&self.peaks(self.len()))
+    }
+
+    /// Prove that the leaf at `position` is part of the log
+    pub fn prove(&self, position: u64) -> io::Result<MerkleProof<D>> {
+        let leaves = self.len();
+        if position >= leaves {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No leaf at position",
+            ));
+        }
+
+        let (mut start, mut height, local) = peak_of(leaves, position);
+        let mut siblings = vec![];
+
+        // walk down from the peak, collecting the other child at each level
+        while height > 0 {
+            let left_size = (1 << height) - 1;
+            if local & (1 << (height - 1)) == 0 {
+                siblings.push(self.node(start + 2 * left_size - 1));
+            } else {
+                siblings.push(self.node(start + left_size - 1));
+                start += left_size;
+            }
+            height -= 1;
+        }
+        siblings.reverse();
+
+        Ok(MerkleProof {
+            position,
+            leaves,
+            siblings,
+            peaks: self.peaks(leaves),
+        })
+    }
+
+    fn peaks(&self, leaves: u64) -> Vec<Output<D>> {
+        peak_positions(leaves)
+            .map(|(start, height)| self.node(start + (2 << height) - 2))
+            .collect()
+    }
+
+    fn pieces() -> u64 {
+        <D as Digest>::output_size().div_ceil(PIECE) as u64
+    }
+
+    fn write_node(&self, node: u64, hash: &Output<D>) -> io::Result<()> {
+        for (i, chunk) in hash.chunks(PIECE).enumerate() {
+            let index = node * Self::pieces() + i as u64;
+            let mut piece = self.nodes.get_mut(index as usize)?;
+            piece[..chunk.len()].copy_from_slice(chunk);
+        }
+        Ok(())
+    }
+
+    fn node(&self, node: u64) -> Output<D> {
+        let mut hash = Output::<D>::default();
+        for (i, chunk) in hash.chunks_mut(PIECE).enumerate() {
+            let index = node * Self::pieces() + i as u64;
+            if let Some(piece) = self.nodes.get_raw(index as usize) {
+                chunk.copy_from_slice(&piece[..chunk.len()]);
+            }
+        }
+        hash
+    }
+}
+
+impl<D> MerkleProof<D>
+where
+    D: Digest,
+{
+    /// The position of the leaf proven
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// The number of leaves in the log the proof was made for
+    pub fn leaves(&self) -> u64 {
+        self.leaves
+    }
+
+    /// Check that `leaf` is at the position of the proof in the log with
+    /// the given root
+    pub fn verify(&self, leaf: &[u8], root: &Output<D>) -> bool {
+        if self.position >= self.leaves {
+            return false;
+        }
+
+        let (_, height, local) = peak_of(self.leaves, self.position);
+        let peak_index = peak_positions(self.leaves)
+            .position(|(_, h)| h == height)
+            .expect("the peak of a leaf is one of the peaks");
+
+        if self.siblings.len() != height as usize
+            || self.peaks.len() != self.leaves.count_ones() as usize
+        {
+            return false;
+        }
+
+        let mut hash = leaf_hash::<D>(leaf);
+        for (level, sibling) in self.siblings.iter().enumerate() {
+            hash = if local & (1 << level) == 0 {
+                node_hash::<D>(&hash, sibling)
+            } else {
+                node_hash::<D>(sibling, &hash)
+            };
+        }
+
+        hash == self.peaks[peak_index]
+            && bag::<D>(&self.peaks).as_ref() == Some(root)
+    }
+}
+
+// The number of nodes in a range of `leaves` leaves
+fn node_count(leaves: u64) -> u64 {
+    2 * leaves - leaves.count_ones() as u64
+}
+
+// The first node and height of each tree in a range, left to right
+fn peak_positions(leaves: u64) -> impl Iterator<Item = (u64, u32)> {
+    let mut start = 0;
+    (0..u64::BITS).rev().filter_map(move |height| {
+        if leaves & (1 << height) == 0 {
+            return None;
+        }
+        let peak = (start, height);
+        start += (2 << height) - 1;
+        Some(peak)
+    })
+}
+
+// The first node and height of the tree holding leaf `position`, and the
+// index of the leaf within it
+fn peak_of(leaves: u64, position: u64) -> (u64, u32, u64) {
+    let mut first_leaf = 0;
+    for (start, height) in peak_positions(leaves) {
+        if position < first_leaf + (1 << height) {
+            return (start, height, position - first_leaf);
+        }
+        first_leaf += 1 << height;
+    }
+    unreachable!("position is below the number of leaves")
+}
+
+// Combine the peaks into a single root, right to left
+fn bag<D: Digest>(peaks: &[Output<D>]) -> Option<Output<D>> {
+    let (last, rest) = peaks.split_last()?;
+    Some(
+        rest.iter()
+            .rev()
+            .fold(last.clone(), |root, peak| node_hash::<D>(peak, &root)),
+    )
+}
+
+fn leaf_hash<D: Digest>(leaf: &[u8]) -> Output<D> {
+    D::new().chain_update([LEAF]).chain_update(leaf).finalize()
+}
+
+fn node_hash<D: Digest>(left: &Output<D>, right: &Output<D>) -> Output<D> {
+    D::new()
+        .chain_update([NODE])
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+}
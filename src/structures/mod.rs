@@ -1,8 +1,60 @@
 mod smash;
-pub use smash::SmashMap;
+pub use smash::{
+    PrehashedAdapter, ProbeStats, SeaHasherAdapter, SmashHasher, SmashMap,
+};
 
 mod oncemap;
-pub use oncemap::OnceMap;
+pub use oncemap::{AlreadySet, OnceMap};
+
+mod onceset;
+pub use onceset::OnceSet;
+
+mod oncemapraw;
+pub use oncemapraw::OnceMapRaw;
+
+#[cfg(feature = "serde")]
+mod serdemap;
+#[cfg(feature = "serde")]
+pub use serdemap::SerdeOnceMap;
 
 mod content;
-pub use content::Content;
+pub use content::{Content, ContentId, ContentWriter, DedupStats};
+
+mod kvmap;
+pub use kvmap::KVMap;
+
+mod multimap;
+pub use multimap::MultiMap;
+
+mod lsm;
+pub use lsm::LsmTree;
+
+mod skiplist;
+pub use skiplist::SkipList;
+
+mod roaring;
+pub use roaring::{BitmapStore, RoaringBitmap};
+
+mod lru;
+pub use lru::LruCache;
+
+mod radix;
+pub use radix::RadixTrie;
+
+mod interval;
+pub use interval::IntervalTree;
+
+mod mmr;
+pub use mmr::{MerkleMountainRange, MerkleProof};
+
+mod secondary;
+pub use secondary::SecondaryIndex;
+
+mod slab;
+pub use slab::{Slab, SlabHandle};
+
+mod sorter;
+pub use sorter::Sorter;
+
+mod inline;
+pub use inline::InlineMap;
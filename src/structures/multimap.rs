@@ -0,0 +1,196 @@
+use std::cell::Cell;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::{io, mem};
+
+use bytemuck::{Pod, Zeroable};
+use bytemuck_derive::*;
+
+use crate::{
+    AppendOnly, AtomicArray, GuardedLandfill, Journal, SmashMap, Substructure,
+    VerifyReport,
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Entry {
+    k_ofs: u64,
+    // index of the head of the chain of the key
+    slot: u32,
+    tag: [u8; 4],
+}
+
+/// A map from keys to any number of values
+///
+/// The values of each key form a chain of nodes in the data region, each
+/// pointing to the value inserted before it, with the newest node of every
+/// chain stored in a head
+pub struct MultiMap<K, V> {
+    data: AppendOnly,
+    index: SmashMap<K, Entry>,
+    // offset + 1 of the newest node of each chain, 0 for empty chains
+    heads: AtomicArray<u64>,
+    slots: Journal<u64>,
+    _marker: PhantomData<V>,
+}
+
+impl<K, V> Substructure for MultiMap<K, V> {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(MultiMap {
+            data: lf.substructure("data")?,
+            index: lf.substructure("index")?,
+            heads: lf.substructure("heads")?,
+            slots: lf.substructure("slots")?,
+            _marker: PhantomData,
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.data.flush()?;
+        self.index.flush()?;
+        self.heads.flush()?;
+        self.slots.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.data.close()?;
+        self.index.close()?;
+        self.heads.close()?;
+        self.slots.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.data.size_on_disk()
+            + self.index.size_on_disk()
+            + self.heads.size_on_disk()
+            + self.slots.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.data.verify()?,
+            self.index.verify()?,
+            self.heads.verify()?,
+            self.slots.verify()?,
+        ]))
+    }
+}
+
+impl<K, V> MultiMap<K, V>
+where
+    K: Hash + Zeroable + Pod + PartialEq + Eq,
+    V: Zeroable + Pod,
+{
+    /// Add a value to the values of a key
+    pub fn insert(&self, k: K, v: V) -> io::Result<()> {
+        let slot = self.slot_or_insert(k)?;
+
+        // nodes are the offset + 1 of the previous node, followed by the value
+        let header = Self::header_size();
+        let (ofs, node) = self
+            .data
+            .reserve(header + mem::size_of::<V>(), Self::node_align())?;
+        node[header..].copy_from_slice(bytemuck::bytes_of(&v));
+
+        let mut head = self.heads.load(slot);
+        loop {
+            node[..8].copy_from_slice(&head.to_le_bytes());
+            match self.heads.compare_exchange(slot, head, ofs + 1)? {
+                Ok(_) => {
+                    self.data.written(ofs, node.len());
+                    return Ok(());
+                }
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    /// Iterate over all values of a key, newest first
+    pub fn get(&self, k: &K) -> impl Iterator<Item = V> + '_ {
+        let mut head = match self.slot(k) {
+            Some(slot) => self.heads.load(slot),
+            None => 0,
+        };
+
+        std::iter::from_fn(move || {
+            let ofs = head.checked_sub(1)?;
+            let node = self
+                .data
+                .get(ofs, (Self::header_size() + mem::size_of::<V>()) as u32);
+            let (prev, value) = node.split_at(Self::header_size());
+            head = u64::from_le_bytes(prev[..8].try_into().ok()?);
+            Some(bytemuck::pod_read_unaligned(value))
+        })
+    }
+
+    /// Returns true if the key has any values
+    pub fn contains_key(&self, k: &K) -> bool {
+        self.get(k).next().is_some()
+    }
+
+    fn header_size() -> usize {
+        mem::size_of::<u64>().max(mem::align_of::<V>())
+    }
+
+    fn node_align() -> usize {
+        mem::align_of::<u64>().max(mem::align_of::<V>())
+    }
+
+    // Find the head slot of a key, if it has any values
+    fn slot(&self, k: &K) -> Option<usize> {
+        let mut result = None;
+        self.index.get(k, |search, entry| {
+            if search.matches_tag(entry.tag) && *k == self.entry_key(entry) {
+                result = Some(entry.slot as usize);
+                search.halt()
+            } else {
+                search.proceed()
+            }
+        });
+        result
+    }
+
+    // Find the head slot of a key, adding the key to the index if needed
+    fn slot_or_insert(&self, k: K) -> io::Result<usize> {
+        let existing = Cell::new(None);
+
+        self.index.insert(
+            &k,
+            |search, entry| {
+                if search.matches_tag(entry.tag) && k == self.entry_key(entry) {
+                    existing.set(Some(entry.slot));
+                    search.halt()
+                } else {
+                    search.proceed()
+                }
+            },
+            |search| {
+                let k_ofs = self.data.write_aligned(
+                    bytemuck::bytes_of(&k),
+                    mem::align_of::<K>(),
+                )?;
+                let slot = self.slots.try_update(|slots| {
+                    let slot = u32::try_from(*slots).map_err(|_| {
+                        io::Error::other("MultiMap key limit reached")
+                    })?;
+                    *slots += 1;
+                    Ok::<_, io::Error>(slot)
+                })?;
+                existing.set(Some(slot));
+
+                Ok(Entry {
+                    k_ofs,
+                    slot,
+                    tag: search.tag_bytes(),
+                })
+            },
+        )?;
+
+        Ok(existing.get().expect("set by either closure") as usize)
+    }
+
+    fn entry_key(&self, entry: &Entry) -> K {
+        let key_bytes = self.data.get(entry.k_ofs, mem::size_of::<K>() as u32);
+        bytemuck::pod_read_unaligned(key_bytes)
+    }
+}
@@ -1,19 +1,49 @@
 use std::borrow::Borrow;
+use std::cell::Cell;
+use std::collections::HashSet;
 use std::hash::Hash;
 use std::marker::PhantomData;
-use std::{io, mem};
+use std::{fmt, io, mem};
 
 use bytemuck::{Pod, Zeroable};
 use bytemuck_derive::*;
 
-use crate::{AppendOnly, GuardedLandfill, SmashMap, Substructure};
+use crate::{
+    storage::DiskBytes, AppendOnly, GuardedLandfill, Journal, SmashMap,
+    Substructure, VerifyReport,
+};
+
+// maximum number of pairs written to the data region with a single
+// reservation
+const BATCH_CHUNK: usize = 4096;
 
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
 struct Entry {
     k_ofs: u64,
     v_ofs_relative: u32,
-    tag: u32,
+    tag: [u8; 4],
+}
+
+/// Returned by `OnceMap::try_insert` when the key was already set
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AlreadySet<V> {
+    /// The value the key is bound to
+    pub existing: V,
+}
+
+impl<V> fmt::Display for AlreadySet<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Key is already set")
+    }
+}
+
+impl<V: fmt::Debug> std::error::Error for AlreadySet<V> {}
+
+impl<V> From<AlreadySet<V>> for io::Error {
+    fn from(_: AlreadySet<V>) -> io::Error {
+        io::Error::new(io::ErrorKind::AlreadyExists, "Key is already set")
+    }
 }
 
 /// A map structure where each key can be set only once
@@ -23,24 +53,53 @@ struct Entry {
 pub struct OnceMap<K, V> {
     data: AppendOnly,
     index: SmashMap<K, Entry>,
+    // 1 once the map has been sealed
+    sealed: Journal<u64>,
     _marker: PhantomData<V>,
 }
 
 impl<K, V> Substructure for OnceMap<K, V> {
     fn init(lf: GuardedLandfill) -> io::Result<Self> {
         let data = lf.substructure("data")?;
-        let index = lf.substructure("index")?;
+        let mut index: SmashMap<K, Entry> = lf.substructure("index")?;
+        let sealed: Journal<u64> = lf.substructure("sealed")?;
+
+        if sealed.current() != 0 {
+            index.freeze();
+        }
 
         Ok(OnceMap {
             data,
             index,
+            sealed,
             _marker: PhantomData,
         })
     }
 
     fn flush(&self) -> io::Result<()> {
         self.data.flush()?;
-        self.index.flush()
+        self.index.flush()?;
+        self.sealed.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.data.close()?;
+        self.index.close()?;
+        self.sealed.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.data.size_on_disk()
+            + self.index.size_on_disk()
+            + self.sealed.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.data.verify()?,
+            self.index.verify()?,
+            self.sealed.verify()?,
+        ]))
     }
 }
 
@@ -50,19 +109,37 @@ where
     V: Zeroable + Pod,
 {
     /// Insert a key-value pair into the map
+    ///
+    /// If the key is already set, the map is left unchanged
     pub fn insert(&self, k: K, v: V) -> io::Result<()> {
+        self.try_insert(k, v).map(|_| ())
+    }
+
+    /// Insert a key-value pair into the map, reporting if the key was
+    /// already set
+    ///
+    /// If the key is already set, the map is left unchanged and the existing
+    /// value is returned in the `AlreadySet` error
+    pub fn try_insert(
+        &self,
+        k: K,
+        v: V,
+    ) -> io::Result<Result<(), AlreadySet<V>>> {
+        if self.is_sealed() {
+            return Err(Self::sealed_error());
+        }
+
+        let existing = Cell::new(None);
+
         self.index.insert(
             &k,
             |search, entry| {
-                let search_tag = search.tag_u32();
-
-                if search_tag == entry.tag {
-                    let key_bytes =
-                        self.data.get(entry.k_ofs, mem::size_of::<K>() as u32);
-                    let key_slice: &[K] = bytemuck::cast_slice(key_bytes);
-
-                    if k == key_slice[0] {
+                if search.matches_tag(entry.tag) {
+                    if k == self.entry_key(entry) {
                         // we already have this key set
+                        existing.set(Some(bytemuck::pod_read_unaligned(
+                            self.entry_value_bytes(entry),
+                        )));
                         search.halt()
                     } else {
                         search.proceed()
@@ -88,39 +165,227 @@ where
                 Ok(Entry {
                     k_ofs,
                     v_ofs_relative,
-                    tag: search.tag_u32(),
+                    tag: search.tag_bytes(),
                 })
             },
-        )
+        )?;
+
+        Ok(match existing.get() {
+            Some(existing) => Err(AlreadySet { existing }),
+            None => Ok(()),
+        })
+    }
+
+    /// Insert many key-value pairs into the map
+    ///
+    /// Keys and values of new pairs are written to the data region in large
+    /// groups, with a single update of the writehead per group. As with
+    /// `insert`, keys that are already set keep their value
+    pub fn insert_batch<I>(&self, pairs: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        if self.is_sealed() {
+            return Err(Self::sealed_error());
+        }
+
+        let mut pairs = pairs.into_iter().peekable();
+        let mut seen = HashSet::new();
+
+        while pairs.peek().is_some() {
+            let chunk: Vec<(K, V)> = pairs
+                .by_ref()
+                .filter(|(k, _)| self.get(k).is_none() && seen.insert(*k))
+                .take(self.chunk_len())
+                .collect();
+
+            self.insert_chunk(&chunk)?;
+        }
+
+        Ok(())
+    }
+
+    // Padding between key and value, size and alignment of a stored pair
+    fn pair_layout() -> (usize, usize, usize) {
+        let k_size = mem::size_of::<K>();
+        let v_size = mem::size_of::<V>();
+        let v_padding = (mem::align_of::<V>() - k_size % mem::align_of::<V>())
+            % mem::align_of::<V>();
+        let pair_align = mem::align_of::<K>().max(mem::align_of::<V>());
+        let pair_size =
+            (k_size + v_padding + v_size).next_multiple_of(pair_align);
+        (v_padding, pair_size, pair_align)
+    }
+
+    // Number of pairs fitting in what is left of the current lane, so a
+    // chunk does not skip the rest of the lane. Once less than a pair is
+    // left the next lane is used, which fits a full chunk
+    fn chunk_len(&self) -> usize {
+        let (_, pair_size, pair_align) = Self::pair_layout();
+        let remaining = DiskBytes::lane_remaining(self.data.writehead())
+            .saturating_sub(pair_align as u64);
+
+        match remaining as usize / pair_size {
+            0 => BATCH_CHUNK,
+            fits => fits.min(BATCH_CHUNK),
+        }
+    }
+
+    // Write all keys and values of the chunk with one reservation, and index
+    // them afterwards
+    fn insert_chunk(&self, chunk: &[(K, V)]) -> io::Result<()> {
+        if chunk.is_empty() {
+            return Ok(());
+        }
+
+        let k_size = mem::size_of::<K>();
+        let v_size = mem::size_of::<V>();
+        let (v_padding, pair_size, pair_align) = Self::pair_layout();
+
+        let (base, bytes) =
+            self.data.reserve(pair_size * chunk.len(), pair_align)?;
+
+        for (i, ((k, v), dst)) in chunk
+            .iter()
+            .zip(bytes.chunks_exact_mut(pair_size))
+            .enumerate()
+        {
+            dst[..k_size].copy_from_slice(bytemuck::bytes_of(k));
+            dst[k_size + v_padding..][..v_size]
+                .copy_from_slice(bytemuck::bytes_of(v));
+
+            let k_ofs = base + (i * pair_size) as u64;
+            let entry = Entry {
+                k_ofs,
+                v_ofs_relative: (k_size + v_padding) as u32,
+                tag: [0; 4],
+            };
+
+            self.index.insert(
+                k,
+                |search, existing| {
+                    if search.matches_tag(existing.tag)
+                        && self.entry_key(existing) == *k
+                    {
+                        // set concurrently since we checked
+                        search.halt()
+                    } else {
+                        search.proceed()
+                    }
+                },
+                |search| {
+                    Ok(Entry {
+                        tag: search.tag_bytes(),
+                        ..entry
+                    })
+                },
+            )?;
+        }
+
+        self.data.written(base, pair_size * chunk.len());
+        Ok(())
     }
 
     /// Gets the value corresponding to the key, if any
+    ///
+    /// Values are always stored aligned for `V`, but as a safeguard, values
+    /// failing the alignment check on read are not returned. `get_copied`
+    /// reads such values regardless of alignment
     pub fn get<O: Borrow<K>>(&self, o: &O) -> Option<&V> {
-        let mut result = None;
-        let k = o.borrow();
-        self.index.get(k, |search, entry| {
-            let search_tag = search.tag_u32();
+        let entry = self.find(o.borrow())?;
+        bytemuck::try_from_bytes(self.entry_value_bytes(&entry)).ok()
+    }
+
+    /// Gets a copy of the value corresponding to the key, if any
+    ///
+    /// Works regardless of the alignment of the stored value
+    pub fn get_copied<O: Borrow<K>>(&self, o: &O) -> Option<V> {
+        let entry = self.find(o.borrow())?;
+        Some(bytemuck::pod_read_unaligned(self.entry_value_bytes(&entry)))
+    }
+
+    fn find(&self, k: &K) -> Option<Entry> {
+        let entry = self.index.get_first(k, |search, entry| {
+            search.matches_tag(entry.tag) && self.entry_key(entry) == *k
+        })?;
+        Some(*entry)
+    }
+
+    /// Returns the number of keys set in the map
+    pub fn len(&self) -> usize {
+        // every first-time insert occupies exactly one index slot
+        self.index.len()
+    }
+
+    /// Returns true if no key has been set
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Iterate over all key-value pairs in the map, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        self.index.values().filter_map(|entry| {
+            let key_bytes =
+                self.data.get(entry.k_ofs, mem::size_of::<K>() as u32);
+            let key = bytemuck::try_from_bytes(key_bytes).ok()?;
+            let value =
+                bytemuck::try_from_bytes(self.entry_value_bytes(&entry))
+                    .ok()?;
+            Some((key, value))
+        })
+    }
+
+    // Keys are compared by copy, so misaligned keys can still be found
+    fn entry_key(&self, entry: &Entry) -> K {
+        let key_bytes = self.data.get(entry.k_ofs, mem::size_of::<K>() as u32);
+        bytemuck::pod_read_unaligned(key_bytes)
+    }
+
+    fn entry_value_bytes(&self, entry: &Entry) -> &[u8] {
+        let v_ofs = entry.k_ofs + entry.v_ofs_relative as u64;
+        self.data.get(v_ofs, mem::size_of::<V>() as u32)
+    }
+
+    /// Permanently mark the map as read-only
+    ///
+    /// All data is flushed to disk, after which inserts fail, also after
+    /// reopening, and lookups no longer take any locks
+    pub fn seal(&mut self) -> io::Result<()> {
+        self.data.flush()?;
+        self.index.flush()?;
+
+        self.sealed.update(|sealed| *sealed = 1);
+        self.sealed.flush()?;
+
+        self.index.freeze();
+        Ok(())
+    }
 
-            if search_tag == entry.tag {
+    /// Returns true if the map has been sealed
+    pub fn is_sealed(&self) -> bool {
+        self.sealed.current() != 0
+    }
+
+    fn sealed_error() -> io::Error {
+        io::Error::new(io::ErrorKind::PermissionDenied, "OnceMap is sealed")
+    }
+
+    /// Rebuild the index of the map with a freshly generated entropy set
+    ///
+    /// All keys are rehashed into a new index, which replaces the old one
+    /// once complete. Keys and values are not copied
+    pub fn rotate_entropy(&mut self) -> io::Result<()> {
+        let data = &self.data;
+        self.index.rehash(
+            |entry| {
                 let key_bytes =
-                    self.data.get(entry.k_ofs, mem::size_of::<K>() as u32);
-                let key_slice: &[K] = bytemuck::cast_slice(key_bytes);
-
-                if &key_slice[0] == k {
-                    // found it!
-                    let v_ofs = entry.k_ofs + entry.v_ofs_relative as u64;
-                    let v_bytes =
-                        self.data.get(v_ofs, mem::size_of::<V>() as u32);
-                    let v_slice: &[V] = bytemuck::cast_slice(v_bytes);
-                    result = Some(&v_slice[0]);
-                    search.halt()
-                } else {
-                    search.proceed()
-                }
-            } else {
-                search.proceed()
-            }
-        });
-        result
+                    data.get(entry.k_ofs, mem::size_of::<K>() as u32);
+                bytemuck::pod_read_unaligned(key_bytes)
+            },
+            |search, entry| Entry {
+                tag: search.tag_bytes(),
+                ..entry
+            },
+        )
     }
 }
@@ -0,0 +1,128 @@
+use std::io::{self, IoSlice};
+
+use bytemuck_derive::*;
+
+use super::smash::SearchPattern;
+use crate::{
+    AppendOnly, GuardedLandfill, SmashMap, Substructure, VerifyReport,
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct RawEntry {
+    // the value is stored directly after the key
+    k_ofs: u64,
+    k_len: u32,
+    v_len: u32,
+    tag: [u8; 4],
+    // always 1, to distinguish empty keys and values from empty slots
+    occupied: u32,
+}
+
+/// A map of byte-slice keys to byte-slice values, where each key can be set
+/// only once
+///
+/// The variable-length counterpart of `OnceMap`, for strings and serialized
+/// data that do not fit a fixed-size type
+pub struct OnceMapRaw {
+    data: AppendOnly,
+    index: SmashMap<[u8], RawEntry>,
+}
+
+impl Substructure for OnceMapRaw {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(OnceMapRaw {
+            data: lf.substructure("data")?,
+            index: lf.substructure("index")?,
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.data.flush()?;
+        self.index.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.data.close()?;
+        self.index.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.data.size_on_disk() + self.index.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.data.verify()?,
+            self.index.verify()?,
+        ]))
+    }
+}
+
+impl OnceMapRaw {
+    /// Insert a key-value pair into the map
+    ///
+    /// If the key is already set, the map is left unchanged
+    pub fn insert(&self, key: &[u8], value: &[u8]) -> io::Result<()> {
+        let k_len = Self::checked_len(key)?;
+        let v_len = Self::checked_len(value)?;
+
+        self.index.insert(
+            key,
+            |search, entry| {
+                if self.entry_key(search, entry) == Some(key) {
+                    // we already have this key set
+                    search.halt()
+                } else {
+                    search.proceed()
+                }
+            },
+            |search| {
+                let k_ofs = self.data.write_all_vectored(&[
+                    IoSlice::new(key),
+                    IoSlice::new(value),
+                ])?;
+
+                Ok(RawEntry {
+                    k_ofs,
+                    k_len,
+                    v_len,
+                    tag: search.tag_bytes(),
+                    occupied: 1,
+                })
+            },
+        )
+    }
+
+    /// Gets the value corresponding to the key, if any
+    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        let entry = *self.index.get_first(key, |search, entry| {
+            self.entry_key(search, entry) == Some(key)
+        })?;
+
+        self.data
+            .read(entry.k_ofs + entry.k_len as u64, entry.v_len)
+    }
+
+    // The key of an entry, if its tag matches
+    fn entry_key(
+        &self,
+        search: &SearchPattern,
+        entry: &RawEntry,
+    ) -> Option<&[u8]> {
+        if search.matches_tag(entry.tag) {
+            self.data.read(entry.k_ofs, entry.k_len)
+        } else {
+            None
+        }
+    }
+
+    fn checked_len(bytes: &[u8]) -> io::Result<u32> {
+        u32::try_from(bytes.len()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Keys and values must be shorter than 4 GiB",
+            )
+        })
+    }
+}
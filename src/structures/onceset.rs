@@ -0,0 +1,106 @@
+use std::hash::Hash;
+use std::io;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{GuardedLandfill, Journal, SmashMap, Substructure, VerifyReport};
+
+/// A set where keys can be inserted, but never removed
+///
+/// Keys are stored directly in the slots of the index, so unlike a `OnceMap`
+/// with dummy values, no data region is needed. Since empty slots are all
+/// zeroes, the zeroed key is tracked separately.
+pub struct OnceSet<K> {
+    index: SmashMap<K, K>,
+    // 1 once the zeroed key has been inserted
+    zeroed: Journal<u64>,
+}
+
+impl<K> Substructure for OnceSet<K> {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(OnceSet {
+            index: lf.substructure("index")?,
+            zeroed: lf.substructure("zeroed")?,
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.index.flush()?;
+        self.zeroed.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.index.close()?;
+        self.zeroed.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.index.size_on_disk() + self.zeroed.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.index.verify()?,
+            self.zeroed.verify()?,
+        ]))
+    }
+}
+
+impl<K> OnceSet<K>
+where
+    K: Hash + Zeroable + Pod + PartialEq + Eq,
+{
+    /// Insert a key into the set, returning true if it was not present
+    pub fn insert(&self, k: K) -> io::Result<bool> {
+        if k == K::zeroed() {
+            return Ok(self.zeroed.update(|zeroed| {
+                let new = *zeroed == 0;
+                *zeroed = 1;
+                new
+            }));
+        }
+
+        let mut new = false;
+        self.index.insert(
+            &k,
+            |search, existing| {
+                if *existing == k {
+                    search.halt()
+                } else {
+                    search.proceed()
+                }
+            },
+            |_| {
+                new = true;
+                Ok(k)
+            },
+        )?;
+        Ok(new)
+    }
+
+    /// Returns true if the set contains the key
+    pub fn contains(&self, k: &K) -> bool {
+        if *k == K::zeroed() {
+            return self.zeroed.current() != 0;
+        }
+        self.index
+            .get_first(k, |_, existing| existing == k)
+            .is_some()
+    }
+
+    /// Returns the number of keys in the set
+    pub fn len(&self) -> usize {
+        self.index.len() + self.zeroed.current() as usize
+    }
+
+    /// Returns true if the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over copies of all keys in the set, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = K> + '_ {
+        let zeroed = (self.zeroed.current() != 0).then(K::zeroed);
+        zeroed.into_iter().chain(self.index.values())
+    }
+}
@@ -0,0 +1,386 @@
+use std::io;
+
+use bytemuck::{Pod, Zeroable};
+use bytemuck_derive::*;
+use parking_lot::Mutex;
+
+use crate::{
+    AppendOnly, AtomicArray, GuardedLandfill, Journal, RandomAccess,
+    Substructure, VerifyReport,
+};
+
+// the root of the trie is node 0, with an empty label
+const ROOT: u64 = 0;
+// cell of the counters holding the number of keys
+const LEN: usize = 0;
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Node {
+    label_ofs: u64,
+    // node + 1 of the first child, children ordered by first label byte
+    child: u64,
+    // node + 1 of the next sibling
+    sibling: u64,
+    label_len: u32,
+    has_value: u32,
+}
+
+// where a link to a node is stored
+#[derive(Clone, Copy)]
+enum Link {
+    Child(u64),
+    Sibling(u64),
+}
+
+/// A map from byte strings to values, stored as a radix trie
+///
+/// Keys sharing a prefix share the nodes of that prefix, which allows
+/// iterating over all keys starting with a given prefix in key order.
+///
+/// Nodes are never modified in a way visible to readers other than by
+/// switching a single link, so lookups proceed concurrently with inserts.
+/// Inserts themselves are serialized.
+pub struct RadixTrie<V> {
+    labels: AppendOnly,
+    nodes: RandomAccess<Node>,
+    values: RandomAccess<V>,
+    allocated: Journal<u64>,
+    counters: AtomicArray<u64>,
+    writer: Mutex<()>,
+}
+
+impl<V> Substructure for RadixTrie<V> {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(RadixTrie {
+            labels: lf.substructure("labels")?,
+            nodes: lf.substructure("nodes")?,
+            values: lf.substructure("values")?,
+            allocated: lf.substructure("allocated")?,
+            counters: lf.substructure("counters")?,
+            writer: Mutex::new(()),
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.labels.flush()?;
+        self.nodes.flush()?;
+        self.values.flush()?;
+        self.allocated.flush()?;
+        self.counters.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.labels.close()?;
+        self.nodes.close()?;
+        self.values.close()?;
+        self.allocated.close()?;
+        self.counters.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.labels.size_on_disk()
+            + self.nodes.size_on_disk()
+            + self.values.size_on_disk()
+            + self.allocated.size_on_disk()
+            + self.counters.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.labels.verify()?,
+            self.nodes.verify()?,
+            self.values.verify()?,
+            self.allocated.verify()?,
+            self.counters.verify()?,
+        ]))
+    }
+}
+
+impl<V> RadixTrie<V>
+where
+    V: Zeroable + Pod,
+{
+    /// Insert a value under `key`, returning the previous value
+    pub fn insert(&self, key: &[u8], v: V) -> io::Result<Option<V>> {
+        let _writer = self.writer.lock();
+
+        let mut node = ROOT;
+        let mut rest = key;
+
+        loop {
+            if rest.is_empty() {
+                return self.set_value(node, v);
+            }
+
+            let (link, found) = self.find_child(node, rest[0]);
+            let Some(child) = found else {
+                let sibling = self.target(link);
+                let leaf = self.new_node(rest, 0, sibling, Some(v))?;
+                self.set_link(link, leaf)?;
+                self.counters.fetch_add(LEN, 1)?;
+                return Ok(None);
+            };
+
+            let label = self.label(child);
+            let common = common_prefix(label, rest);
+
+            if common < label.len() {
+                node = self.split(link, child, common)?;
+            } else {
+                node = child;
+            }
+            rest = &rest[common..];
+        }
+    }
+
+    /// Remove `key`, returning its value
+    pub fn remove(&self, key: &[u8]) -> io::Result<Option<V>> {
+        let _writer = self.writer.lock();
+
+        let Some(node) = self.find(key) else {
+            return Ok(None);
+        };
+        let v = self.value(node);
+        self.nodes.get_mut(node as usize)?.has_value = 0;
+        self.counters.fetch_add(LEN, u64::MAX)?;
+        Ok(v)
+    }
+
+    /// Get the value of `key`
+    pub fn get(&self, key: &[u8]) -> Option<V> {
+        self.value(self.find(key)?)
+    }
+
+    /// Returns true if the trie contains `key`
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.find(key).is_some()
+    }
+
+    /// Returns the number of keys in the trie
+    pub fn len(&self) -> usize {
+        self.counters.load(LEN) as usize
+    }
+
+    /// Returns true if the trie is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over copies of all key-value pairs, in key order
+    pub fn iter(&self) -> impl Iterator<Item = (Vec<u8>, V)> + '_ {
+        self.iter_prefix(&[])
+    }
+
+    /// Iterate over copies of all key-value pairs with keys starting with
+    /// `prefix`, in key order
+    pub fn iter_prefix(
+        &self,
+        prefix: &[u8],
+    ) -> impl Iterator<Item = (Vec<u8>, V)> + '_ {
+        let mut node = Some(ROOT);
+        let mut key = vec![];
+        let mut rest = prefix;
+
+        while let (Some(current), Some(first)) = (node, rest.first()) {
+            node = self.find_child(current, *first).1.and_then(|child| {
+                let label = self.label(child);
+                let common = common_prefix(label, rest);
+                // the prefix either ends within the label, or continues
+                // past it
+                if common == rest.len() || common == label.len() {
+                    key.extend_from_slice(label);
+                    rest = &rest[common..];
+                    Some(child)
+                } else {
+                    None
+                }
+            });
+        }
+
+        // stack of the next node to visit, and the key of its parent
+        let mut stack = vec![];
+        if let Some(node) = node {
+            stack.push((node + 1, key.len()));
+        }
+        let mut started = false;
+
+        std::iter::from_fn(move || {
+            while let Some((next, parent_len)) = stack.pop() {
+                let Some(node) = next.checked_sub(1) else {
+                    continue;
+                };
+                let n = self.node(node);
+
+                key.truncate(parent_len);
+                // the starting node is already part of the key, and its
+                // siblings are not part of the iteration
+                if started {
+                    key.extend_from_slice(self.label(node));
+                    stack.push((n.sibling, parent_len));
+                }
+                started = true;
+                stack.push((n.child, key.len()));
+
+                if n.has_value != 0 {
+                    if let Some(v) = self.value(node) {
+                        return Some((key.clone(), v));
+                    }
+                }
+            }
+            None
+        })
+    }
+
+    // The node holding a value for exactly `key`
+    fn find(&self, key: &[u8]) -> Option<u64> {
+        let mut node = ROOT;
+        let mut rest = key;
+
+        while let Some(first) = rest.first() {
+            let child = self.find_child(node, *first).1?;
+            rest = rest.strip_prefix(self.label(child))?;
+            node = child;
+        }
+
+        (self.node(node).has_value != 0).then_some(node)
+    }
+
+    // The child of `node` with a label starting with `first`, along with
+    // the link pointing to it, or to where it would be linked in
+    fn find_child(&self, node: u64, first: u8) -> (Link, Option<u64>) {
+        let mut link = Link::Child(node);
+
+        while let Some(child) = self.target(link).checked_sub(1) {
+            match self.label(child)[0].cmp(&first) {
+                std::cmp::Ordering::Less => link = Link::Sibling(child),
+                std::cmp::Ordering::Equal => return (link, Some(child)),
+                std::cmp::Ordering::Greater => break,
+            }
+        }
+        (link, None)
+    }
+
+    // Split the label of `child` after `at` bytes, returning the new node
+    // holding the first part
+    //
+    // The child is replaced by copies rather than modified, so concurrent
+    // readers see either the old or the new shape of the trie
+    fn split(&self, link: Link, child: u64, at: usize) -> io::Result<u64> {
+        let n = self.node(child);
+        let v = (n.has_value != 0).then(|| self.value(child)).flatten();
+
+        let tail = self.new_node_at(
+            n.label_ofs + at as u64,
+            n.label_len - at as u32,
+            n.child,
+            0,
+            v,
+        )?;
+        let head = self.new_node_at(
+            n.label_ofs,
+            at as u32,
+            tail + 1,
+            n.sibling,
+            None,
+        )?;
+
+        self.set_link(link, head)?;
+        Ok(head)
+    }
+
+    fn set_value(&self, node: u64, v: V) -> io::Result<Option<V>> {
+        let old = self.value(node);
+        *self.values.get_mut(node as usize)? = v;
+
+        let mut n = self.nodes.get_mut(node as usize)?;
+        if n.has_value != 0 {
+            return Ok(old);
+        }
+        n.has_value = 1;
+        drop(n);
+
+        self.counters.fetch_add(LEN, 1)?;
+        Ok(None)
+    }
+
+    fn new_node(
+        &self,
+        label: &[u8],
+        child: u64,
+        sibling: u64,
+        v: Option<V>,
+    ) -> io::Result<u64> {
+        let label_ofs = self.labels.write(label)?;
+        self.new_node_at(label_ofs, label.len() as u32, child, sibling, v)
+    }
+
+    fn new_node_at(
+        &self,
+        label_ofs: u64,
+        label_len: u32,
+        child: u64,
+        sibling: u64,
+        v: Option<V>,
+    ) -> io::Result<u64> {
+        // nodes are numbered from 1, node 0 being the root
+        let node = self.allocated.update(|allocated| {
+            *allocated += 1;
+            *allocated
+        });
+
+        if let Some(v) = v {
+            *self.values.get_mut(node as usize)? = v;
+        }
+        *self.nodes.get_mut(node as usize)? = Node {
+            label_ofs,
+            child,
+            sibling,
+            label_len,
+            has_value: v.is_some() as u32,
+        };
+        Ok(node)
+    }
+
+    fn target(&self, link: Link) -> u64 {
+        match link {
+            Link::Child(node) => self.node(node).child,
+            Link::Sibling(node) => self.node(node).sibling,
+        }
+    }
+
+    fn set_link(&self, link: Link, to: u64) -> io::Result<()> {
+        match link {
+            Link::Child(node) => {
+                self.nodes.get_mut(node as usize)?.child = to + 1
+            }
+            Link::Sibling(node) => {
+                self.nodes.get_mut(node as usize)?.sibling = to + 1
+            }
+        }
+        Ok(())
+    }
+
+    fn node(&self, node: u64) -> Node {
+        self.nodes
+            .get_raw(node as usize)
+            .map(|n| *n)
+            .unwrap_or_else(Node::zeroed)
+    }
+
+    fn label(&self, node: u64) -> &[u8] {
+        let n = self.node(node);
+        if n.label_len == 0 {
+            return &[];
+        }
+        self.labels.get(n.label_ofs, n.label_len)
+    }
+
+    fn value(&self, node: u64) -> Option<V> {
+        self.values.get_raw(node as usize).map(|v| *v)
+    }
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
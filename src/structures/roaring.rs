@@ -0,0 +1,386 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use crate::{AppendOnly, GuardedLandfill, Record, Substructure, VerifyReport};
+
+// containers with more values than this are stored as bitmaps
+const ARRAY_MAX: usize = 4096;
+const BITMAP_WORDS: usize = 1024;
+
+const KIND_ARRAY: u16 = 0;
+const KIND_BITMAP: u16 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Container {
+    // sorted values
+    Array(Vec<u16>),
+    Bitmap(Box<[u64; BITMAP_WORDS]>),
+}
+
+impl Container {
+    fn insert(&mut self, low: u16) -> bool {
+        match self {
+            Container::Array(values) => match values.binary_search(&low) {
+                Ok(_) => false,
+                Err(pos) => {
+                    values.insert(pos, low);
+                    if values.len() > ARRAY_MAX {
+                        *self = Self::bitmap_of(values.iter().copied());
+                    }
+                    true
+                }
+            },
+            Container::Bitmap(words) => {
+                let (word, mask) = Self::word_and_mask(low);
+                let new = words[word] & mask == 0;
+                words[word] |= mask;
+                new
+            }
+        }
+    }
+
+    fn contains(&self, low: u16) -> bool {
+        match self {
+            Container::Array(values) => values.binary_search(&low).is_ok(),
+            Container::Bitmap(words) => {
+                let (word, mask) = Self::word_and_mask(low);
+                words[word] & mask != 0
+            }
+        }
+    }
+
+    fn len(&self) -> u64 {
+        match self {
+            Container::Array(values) => values.len() as u64,
+            Container::Bitmap(words) => {
+                words.iter().map(|word| word.count_ones() as u64).sum()
+            }
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = u16> + '_> {
+        match self {
+            Container::Array(values) => Box::new(values.iter().copied()),
+            Container::Bitmap(words) => {
+                Box::new(words.iter().enumerate().flat_map(|(i, word)| {
+                    (0..64)
+                        .filter(move |bit| word & (1 << bit) != 0)
+                        .map(move |bit| (i * 64 + bit) as u16)
+                }))
+            }
+        }
+    }
+
+    fn union(&self, other: &Container) -> Container {
+        match (self, other) {
+            (Container::Bitmap(a), Container::Bitmap(b)) => {
+                let mut words = a.clone();
+                for (word, b) in words.iter_mut().zip(b.iter()) {
+                    *word |= b;
+                }
+                Container::Bitmap(words)
+            }
+            (Container::Bitmap(words), other)
+            | (other, Container::Bitmap(words)) => {
+                let mut union = Container::Bitmap(words.clone());
+                for low in other.iter() {
+                    union.insert(low);
+                }
+                union
+            }
+            (Container::Array(a), Container::Array(b)) => {
+                let mut values = Vec::with_capacity(a.len() + b.len());
+                let (mut i, mut j) = (0, 0);
+                while i < a.len() && j < b.len() {
+                    match a[i].cmp(&b[j]) {
+                        std::cmp::Ordering::Less => {
+                            values.push(a[i]);
+                            i += 1;
+                        }
+                        std::cmp::Ordering::Greater => {
+                            values.push(b[j]);
+                            j += 1;
+                        }
+                        std::cmp::Ordering::Equal => {
+                            values.push(a[i]);
+                            i += 1;
+                            j += 1;
+                        }
+                    }
+                }
+                values.extend_from_slice(&a[i..]);
+                values.extend_from_slice(&b[j..]);
+                Self::normalized(values)
+            }
+        }
+    }
+
+    // Returns None if the intersection is empty
+    fn intersection(&self, other: &Container) -> Option<Container> {
+        let intersection = match (self, other) {
+            (Container::Bitmap(a), Container::Bitmap(b)) => {
+                let mut words = a.clone();
+                for (word, b) in words.iter_mut().zip(b.iter()) {
+                    *word &= b;
+                }
+                let container = Container::Bitmap(words);
+                if container.len() as usize <= ARRAY_MAX {
+                    Container::Array(container.iter().collect())
+                } else {
+                    container
+                }
+            }
+            (Container::Array(values), other)
+            | (other, Container::Array(values)) => Container::Array(
+                values
+                    .iter()
+                    .copied()
+                    .filter(|low| other.contains(*low))
+                    .collect(),
+            ),
+        };
+        (intersection.len() > 0).then_some(intersection)
+    }
+
+    fn normalized(values: Vec<u16>) -> Container {
+        if values.len() > ARRAY_MAX {
+            Self::bitmap_of(values.into_iter())
+        } else {
+            Container::Array(values)
+        }
+    }
+
+    fn bitmap_of(values: impl Iterator<Item = u16>) -> Container {
+        let mut words = Box::new([0u64; BITMAP_WORDS]);
+        for low in values {
+            let (word, mask) = Self::word_and_mask(low);
+            words[word] |= mask;
+        }
+        Container::Bitmap(words)
+    }
+
+    fn word_and_mask(low: u16) -> (usize, u64) {
+        (low as usize / 64, 1 << (low % 64))
+    }
+}
+
+/// A compressed set of `u32`, split into containers of 2^16 values
+///
+/// Sparse containers are stored as sorted arrays and dense ones as plain
+/// bitmaps, keeping large sets of scattered ids small while unions and
+/// intersections work a container at a time.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RoaringBitmap {
+    containers: BTreeMap<u16, Container>,
+}
+
+impl RoaringBitmap {
+    /// Create an empty bitmap
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a value to the set, returning true if it was not already present
+    pub fn insert(&mut self, value: u32) -> bool {
+        let (high, low) = Self::split(value);
+        self.containers
+            .entry(high)
+            .or_insert_with(|| Container::Array(vec![]))
+            .insert(low)
+    }
+
+    /// Returns true if the value is in the set
+    pub fn contains(&self, value: u32) -> bool {
+        let (high, low) = Self::split(value);
+        self.containers
+            .get(&high)
+            .is_some_and(|container| container.contains(low))
+    }
+
+    /// Returns the number of values in the set
+    pub fn len(&self) -> u64 {
+        self.containers.values().map(Container::len).sum()
+    }
+
+    /// Returns true if the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.containers.is_empty()
+    }
+
+    /// Iterate over all values in the set, in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.containers.iter().flat_map(|(high, container)| {
+            container
+                .iter()
+                .map(move |low| (*high as u32) << 16 | low as u32)
+        })
+    }
+
+    /// Returns the set of values in either set
+    pub fn union(&self, other: &RoaringBitmap) -> RoaringBitmap {
+        let mut containers = self.containers.clone();
+        for (high, container) in &other.containers {
+            containers
+                .entry(*high)
+                .and_modify(|existing| *existing = existing.union(container))
+                .or_insert_with(|| container.clone());
+        }
+        RoaringBitmap { containers }
+    }
+
+    /// Returns the set of values in both sets
+    pub fn intersection(&self, other: &RoaringBitmap) -> RoaringBitmap {
+        let containers = self
+            .containers
+            .iter()
+            .filter_map(|(high, container)| {
+                let other = other.containers.get(high)?;
+                Some((*high, container.intersection(other)?))
+            })
+            .collect();
+        RoaringBitmap { containers }
+    }
+
+    fn split(value: u32) -> (u16, u16) {
+        ((value >> 16) as u16, value as u16)
+    }
+
+    // Containers are written as their key, kind and number of values,
+    // followed by the values or bitmap words, all little endian
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&(self.containers.len() as u32).to_le_bytes());
+
+        for (high, container) in &self.containers {
+            bytes.extend_from_slice(&high.to_le_bytes());
+            match container {
+                Container::Array(values) => {
+                    bytes.extend_from_slice(&KIND_ARRAY.to_le_bytes());
+                    bytes.extend_from_slice(
+                        &(values.len() as u32).to_le_bytes(),
+                    );
+                    for value in values {
+                        bytes.extend_from_slice(&value.to_le_bytes());
+                    }
+                }
+                Container::Bitmap(words) => {
+                    bytes.extend_from_slice(&KIND_BITMAP.to_le_bytes());
+                    bytes.extend_from_slice(
+                        &(container.len() as u32).to_le_bytes(),
+                    );
+                    for word in words.iter() {
+                        bytes.extend_from_slice(&word.to_le_bytes());
+                    }
+                }
+            }
+        }
+        bytes
+    }
+
+    fn decode(mut bytes: &[u8]) -> io::Result<RoaringBitmap> {
+        fn take<'a>(bytes: &mut &'a [u8], n: usize) -> io::Result<&'a [u8]> {
+            if bytes.len() < n {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Truncated bitmap",
+                ));
+            }
+            let (taken, rest) = bytes.split_at(n);
+            *bytes = rest;
+            Ok(taken)
+        }
+        let u16_of = |b: &[u8]| u16::from_le_bytes([b[0], b[1]]);
+
+        let n_containers =
+            u32::from_le_bytes(take(&mut bytes, 4)?.try_into().unwrap());
+        let mut containers = BTreeMap::new();
+
+        for _ in 0..n_containers {
+            let header = take(&mut bytes, 8)?;
+            let high = u16_of(&header[0..2]);
+            let kind = u16_of(&header[2..4]);
+            let count =
+                u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+
+            let container = match kind {
+                KIND_ARRAY if count <= ARRAY_MAX => Container::Array(
+                    take(&mut bytes, count * 2)?
+                        .chunks(2)
+                        .map(u16_of)
+                        .collect(),
+                ),
+                KIND_BITMAP => {
+                    let mut words = Box::new([0u64; BITMAP_WORDS]);
+                    let raw = take(&mut bytes, BITMAP_WORDS * 8)?;
+                    for (word, raw) in words.iter_mut().zip(raw.chunks(8)) {
+                        *word = u64::from_le_bytes(raw.try_into().unwrap());
+                    }
+                    Container::Bitmap(words)
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Invalid bitmap container",
+                    ))
+                }
+            };
+            containers.insert(high, container);
+        }
+
+        Ok(RoaringBitmap { containers })
+    }
+}
+
+impl FromIterator<u32> for RoaringBitmap {
+    fn from_iter<I: IntoIterator<Item = u32>>(values: I) -> Self {
+        let mut bitmap = RoaringBitmap::new();
+        for value in values {
+            bitmap.insert(value);
+        }
+        bitmap
+    }
+}
+
+/// A store of `RoaringBitmap`s, such as posting lists
+///
+/// Bitmaps are written once into an `AppendOnly` buffer, and read back
+/// through the `Record` returned when writing them
+pub struct BitmapStore {
+    data: AppendOnly,
+}
+
+impl Substructure for BitmapStore {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(BitmapStore {
+            data: lf.substructure("data")?,
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.data.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.data.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.data.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        self.data.verify()
+    }
+}
+
+impl BitmapStore {
+    /// Write a bitmap into the store
+    pub fn insert(&self, bitmap: &RoaringBitmap) -> io::Result<Record> {
+        self.data.write_record(&bitmap.encode())
+    }
+
+    /// Read a bitmap back from the store
+    pub fn get(&self, record: Record) -> io::Result<RoaringBitmap> {
+        RoaringBitmap::decode(self.data.get_record(record)?)
+    }
+}
@@ -0,0 +1,166 @@
+use std::hash::Hash;
+use std::io;
+
+use bytemuck::{Pod, Zeroable};
+use parking_lot::Mutex;
+
+use crate::{
+    AppendLog, FramedAppendOnly, GuardedLandfill, Journal, MultiMap,
+    Substructure, VerifyReport,
+};
+
+/// An index from keys extracted from records to the positions of the records
+///
+/// The index follows a single source, either an `AppendLog`, indexed by the
+/// position of each value, or a `FramedAppendOnly`, indexed by the offset of
+/// each record. How far the source has been indexed is persisted, so each
+/// call only indexes the records added since the last one.
+///
+/// The extraction closure is passed on every call, and must extract the
+/// same keys each time. Records it returns `None` for are not indexed.
+pub struct SecondaryIndex<K> {
+    entries: MultiMap<K, u64>,
+    // number of values, or end of the last frame, indexed so far
+    indexed: Journal<u64>,
+    catching_up: Mutex<()>,
+}
+
+impl<K> Substructure for SecondaryIndex<K> {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(SecondaryIndex {
+            entries: lf.substructure("entries")?,
+            indexed: lf.substructure("indexed")?,
+            catching_up: Mutex::new(()),
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.entries.flush()?;
+        self.indexed.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.entries.close()?;
+        self.indexed.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.entries.size_on_disk() + self.indexed.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.entries.verify()?,
+            self.indexed.verify()?,
+        ]))
+    }
+}
+
+impl<K> SecondaryIndex<K>
+where
+    K: Hash + Zeroable + Pod + PartialEq + Eq,
+{
+    /// Push a value to `log` and index it, returning its position
+    pub fn push<T, F>(
+        &self,
+        log: &AppendLog<T>,
+        t: T,
+        extract: F,
+    ) -> io::Result<u64>
+    where
+        T: Pod,
+        F: FnMut(&T) -> Option<K>,
+    {
+        let index = log.push(t)?;
+        self.index_log(log, extract)?;
+        Ok(index)
+    }
+
+    /// Write a record to `framed` and index it, returning its offset
+    pub fn write<F>(
+        &self,
+        framed: &FramedAppendOnly,
+        bytes: &[u8],
+        extract: F,
+    ) -> io::Result<u64>
+    where
+        F: FnMut(&[u8]) -> Option<K>,
+    {
+        let offset = framed.write(bytes)?;
+        self.index_framed(framed, extract)?;
+        Ok(offset)
+    }
+
+    /// Index all values of `log` not yet indexed, returning how many were
+    pub fn index_log<T, F>(
+        &self,
+        log: &AppendLog<T>,
+        mut extract: F,
+    ) -> io::Result<usize>
+    where
+        T: Pod,
+        F: FnMut(&T) -> Option<K>,
+    {
+        let _catching_up = self.catching_up.lock();
+        let start = self.indexed.current();
+
+        for index in start..log.len() {
+            let t = log.get(index).expect("index below the length of the log");
+            if let Some(k) = extract(t) {
+                self.add(k, index, index == start)?;
+            }
+            self.indexed.update(|indexed| *indexed = index + 1);
+        }
+
+        Ok((log.len().max(start) - start) as usize)
+    }
+
+    /// Index all records of `framed` not yet indexed, returning how many were
+    pub fn index_framed<F>(
+        &self,
+        framed: &FramedAppendOnly,
+        mut extract: F,
+    ) -> io::Result<usize>
+    where
+        F: FnMut(&[u8]) -> Option<K>,
+    {
+        let _catching_up = self.catching_up.lock();
+        let start = self.indexed.current();
+        let mut count = 0;
+
+        for (offset, payload) in framed.iter_from(start) {
+            if let Some(k) = extract(payload) {
+                self.add(k, offset, count == 0)?;
+            }
+            let end = FramedAppendOnly::end_of(offset, payload);
+            self.indexed.update(|indexed| *indexed = end);
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Iterate over the positions of all records with key `k`, newest first
+    pub fn get(&self, k: &K) -> impl Iterator<Item = u64> + '_ {
+        self.entries.get(k)
+    }
+
+    /// Returns true if any record with key `k` has been indexed
+    pub fn contains_key(&self, k: &K) -> bool {
+        self.entries.contains_key(k)
+    }
+
+    /// The number of values, or the end of the last record, indexed so far
+    pub fn indexed(&self) -> u64 {
+        self.indexed.current()
+    }
+
+    fn add(&self, k: K, position: u64, first: bool) -> io::Result<()> {
+        // the first record of a run may have been indexed right before a
+        // crash, without the progress being persisted
+        if first && self.entries.get(&k).any(|p| p == position) {
+            return Ok(());
+        }
+        self.entries.insert(k, position)
+    }
+}
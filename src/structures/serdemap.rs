@@ -0,0 +1,69 @@
+use std::io;
+use std::marker::PhantomData;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{GuardedLandfill, OnceMapRaw, Substructure, VerifyReport};
+
+/// A map of serde-encoded keys and values, where each key can be set only
+/// once
+///
+/// Keys and values are encoded with bincode into a `OnceMapRaw`, so any
+/// serializable type can be stored. Keys are compared by their encoding
+pub struct SerdeOnceMap<K, V> {
+    raw: OnceMapRaw,
+    _marker: PhantomData<fn(K) -> V>,
+}
+
+impl<K, V> Substructure for SerdeOnceMap<K, V> {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(SerdeOnceMap {
+            raw: lf.substructure("raw")?,
+            _marker: PhantomData,
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.raw.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.raw.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.raw.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        self.raw.verify()
+    }
+}
+
+impl<K, V> SerdeOnceMap<K, V>
+where
+    K: Serialize,
+    V: Serialize + DeserializeOwned,
+{
+    /// Insert a key-value pair into the map
+    ///
+    /// If the key is already set, the map is left unchanged
+    pub fn insert(&self, key: &K, value: &V) -> io::Result<()> {
+        self.raw.insert(&encode(key)?, &encode(value)?)
+    }
+
+    /// Gets the value corresponding to the key, if any
+    pub fn get(&self, key: &K) -> io::Result<Option<V>> {
+        match self.raw.get(&encode(key)?) {
+            Some(bytes) => bincode::deserialize(bytes)
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn encode<T: Serialize>(t: &T) -> io::Result<Vec<u8>> {
+    bincode::serialize(t)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
@@ -0,0 +1,232 @@
+use std::io;
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+use rand::Rng;
+
+use crate::{
+    AtomicArray, GuardedLandfill, Journal, RandomAccess, Substructure,
+    VerifyReport,
+};
+
+const MAX_LEVEL: usize = 16;
+// the head of the list is node 0, which carries no key
+const HEAD: usize = 0;
+// cell of the counters holding the number of keys
+const LEN: usize = 0;
+
+// the next node at each level, 0 marking the end of the list
+type Links = [u64; MAX_LEVEL];
+
+/// An ordered map of keys to values, stored as a skip list
+///
+/// Nodes are kept in `RandomAccess` arrays, so inserts only lock the nodes
+/// whose links they change, and inserts in different parts of the list can
+/// proceed concurrently. Nodes are never removed.
+pub struct SkipList<K, V> {
+    keys: RandomAccess<K>,
+    values: RandomAccess<V>,
+    links: RandomAccess<Links>,
+    nodes: Journal<u64>,
+    counters: AtomicArray<u64>,
+}
+
+impl<K, V> Substructure for SkipList<K, V> {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(SkipList {
+            keys: lf.substructure("keys")?,
+            values: lf.substructure("values")?,
+            links: lf.substructure("links")?,
+            nodes: lf.substructure("nodes")?,
+            counters: lf.substructure("counters")?,
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.keys.flush()?;
+        self.values.flush()?;
+        self.links.flush()?;
+        self.nodes.flush()?;
+        self.counters.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.keys.close()?;
+        self.values.close()?;
+        self.links.close()?;
+        self.nodes.close()?;
+        self.counters.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.keys.size_on_disk()
+            + self.values.size_on_disk()
+            + self.links.size_on_disk()
+            + self.nodes.size_on_disk()
+            + self.counters.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.keys.verify()?,
+            self.values.verify()?,
+            self.links.verify()?,
+            self.nodes.verify()?,
+            self.counters.verify()?,
+        ]))
+    }
+}
+
+impl<K, V> SkipList<K, V>
+where
+    K: Zeroable + Pod + Ord,
+    V: Zeroable + Pod,
+{
+    /// Insert a key-value pair into the list, returning the previous value
+    pub fn insert(&self, k: K, v: V) -> io::Result<Option<V>> {
+        let mut preds = self.find_preds(&k);
+        if let Some(existing) = self.found(&preds, &k) {
+            return self.replace_value(existing, v).map(Some);
+        }
+
+        // nodes are numbered from 1, node 0 being the head
+        let node = self.nodes.update(|nodes| {
+            *nodes += 1;
+            *nodes
+        });
+        let node_index = node as usize;
+        *self.keys.get_mut(node_index)? = k;
+        *self.values.get_mut(node_index)? = v;
+
+        let height = Self::random_height();
+        for level in 0..height {
+            loop {
+                let pred = preds[level];
+                let next = self.link(pred, level);
+
+                // a smaller key was inserted after `pred` meanwhile
+                if next != HEAD && self.key(next) < k {
+                    preds = self.find_preds(&k);
+                    continue;
+                }
+
+                if level == 0 {
+                    if let Some(existing) = self.found(&preds, &k) {
+                        // the same key was inserted concurrently, the node
+                        // allocated is left unused
+                        return self.replace_value(existing, v).map(Some);
+                    }
+                }
+
+                let next = next as u64;
+                self.links.get_mut(node_index)?[level] = next;
+
+                // link the node in, unless the predecessor changed meanwhile
+                let mut pred_links = self.links.get_mut(pred)?;
+                if pred_links[level] == next {
+                    pred_links[level] = node;
+                    break;
+                }
+                drop(pred_links);
+                preds = self.find_preds(&k);
+            }
+        }
+
+        self.counters.fetch_add(LEN, 1)?;
+        Ok(None)
+    }
+
+    /// Get the value of a key
+    pub fn get(&self, k: &K) -> Option<V> {
+        let preds = self.find_preds(k);
+        let node = self.found(&preds, k)?;
+        self.values.get_raw(node).map(|v| *v)
+    }
+
+    /// Returns the number of keys in the list
+    pub fn len(&self) -> usize {
+        self.counters.load(LEN) as usize
+    }
+
+    /// Returns true if the list is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over copies of all key-value pairs, in key order
+    pub fn iter(&self) -> impl Iterator<Item = (K, V)> + '_ {
+        self.iter_nodes(self.link(HEAD, 0))
+    }
+
+    /// Iterate over copies of all key-value pairs with keys of at least `k`,
+    /// in key order
+    pub fn iter_from(&self, k: &K) -> impl Iterator<Item = (K, V)> + '_ {
+        let preds = self.find_preds(k);
+        self.iter_nodes(self.link(preds[0], 0))
+    }
+
+    fn iter_nodes(&self, mut node: usize) -> impl Iterator<Item = (K, V)> + '_ {
+        std::iter::from_fn(move || {
+            if node == HEAD {
+                return None;
+            }
+            let k = self.key(node);
+            let v = *self.values.get_raw(node)?;
+            node = self.link(node, 0);
+            Some((k, v))
+        })
+    }
+
+    // The last node with a key less than `k` at every level
+    fn find_preds(&self, k: &K) -> [usize; MAX_LEVEL] {
+        let mut preds = [HEAD; MAX_LEVEL];
+        let mut node = HEAD;
+
+        for level in (0..MAX_LEVEL).rev() {
+            loop {
+                let next = self.link(node, level);
+                if next != HEAD && self.key(next) < *k {
+                    node = next;
+                } else {
+                    break;
+                }
+            }
+            preds[level] = node;
+        }
+        preds
+    }
+
+    // The node holding `k`, given its predecessors
+    fn found(&self, preds: &[usize; MAX_LEVEL], k: &K) -> Option<usize> {
+        let next = self.link(preds[0], 0);
+        (next != HEAD && self.key(next) == *k).then_some(next)
+    }
+
+    fn replace_value(&self, node: usize, v: V) -> io::Result<V> {
+        Ok(mem::replace(&mut *self.values.get_mut(node)?, v))
+    }
+
+    fn link(&self, node: usize, level: usize) -> usize {
+        self.links
+            .get_raw(node)
+            .map(|links| links[level] as usize)
+            .unwrap_or(HEAD)
+    }
+
+    fn key(&self, node: usize) -> K {
+        self.keys
+            .get_raw(node)
+            .map(|k| *k)
+            .unwrap_or_else(K::zeroed)
+    }
+
+    // Geometrically distributed, every level a quarter as likely as the last
+    fn random_height() -> usize {
+        let mut rng = rand::thread_rng();
+        let mut height = 1;
+        while height < MAX_LEVEL && rng.gen_ratio(1, 4) {
+            height += 1;
+        }
+        height
+    }
+}
@@ -0,0 +1,261 @@
+use std::fmt;
+use std::io;
+
+use parking_lot::Mutex;
+
+use crate::{
+    Allocator, AtomicArray, GuardedLandfill, Journal, RandomAccess,
+    Substructure, VerifyReport,
+};
+
+// size classes grow by a factor of four from the smallest
+const SMALLEST_CLASS: usize = 64;
+const LARGEST_CLASS: usize = 1024 * 1024;
+
+// cell of the counters holding the number of live values
+const LEN: usize = 0;
+
+// offset of the block, length + 1 of the value, and generation of the
+// handle
+//
+// free entries have a length of 0, and store the next free entry + 1 in
+// place of the offset
+type Entry = [u64; 3];
+
+const OFFSET: usize = 0;
+const LEN_PLUS_ONE: usize = 1;
+const GENERATION: usize = 2;
+
+/// A stable handle to a value in a `Slab`
+///
+/// Handles of removed values are never valid again, even once their entry
+/// is reused
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SlabHandle(u64);
+
+impl SlabHandle {
+    fn new(entry: usize, generation: u64) -> Self {
+        SlabHandle((entry as u64) << 32 | (generation & u32::MAX as u64))
+    }
+
+    fn entry(&self) -> usize {
+        (self.0 >> 32) as usize
+    }
+
+    fn generation(&self) -> u64 {
+        self.0 & u32::MAX as u64
+    }
+
+    /// The handle as an integer, for storing in other structures
+    pub fn to_u64(self) -> u64 {
+        self.0
+    }
+
+    /// A handle from an integer returned by `to_u64`
+    pub fn from_u64(handle: u64) -> Self {
+        SlabHandle(handle)
+    }
+}
+
+impl fmt::Debug for SlabHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SlabHandle({}@{})", self.entry(), self.generation())
+    }
+}
+
+/// A store of variable-size values that can be updated and removed
+///
+/// Values are kept in blocks of fixed size classes of 64 bytes, 256 bytes,
+/// 1 KiB and so on up to 1 MiB, and the blocks of removed values are reused
+/// by later values of the same class. Values are referred to by handles that
+/// stay the same when the value is updated, even if it moves to a block of
+/// another class.
+pub struct Slab {
+    blocks: Allocator,
+    entries: RandomAccess<Entry>,
+    allocated: Journal<u64>,
+    // next free entry + 1, 0 for none
+    free: Journal<u64>,
+    counters: AtomicArray<u64>,
+    writer: Mutex<()>,
+}
+
+impl Substructure for Slab {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        Ok(Slab {
+            blocks: lf.substructure("blocks")?,
+            entries: lf.substructure("entries")?,
+            allocated: lf.substructure("allocated")?,
+            free: lf.substructure("free")?,
+            counters: lf.substructure("counters")?,
+            writer: Mutex::new(()),
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.blocks.flush()?;
+        self.entries.flush()?;
+        self.allocated.flush()?;
+        self.free.flush()?;
+        self.counters.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.blocks.close()?;
+        self.entries.close()?;
+        self.allocated.close()?;
+        self.free.close()?;
+        self.counters.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.blocks.size_on_disk()
+            + self.entries.size_on_disk()
+            + self.allocated.size_on_disk()
+            + self.free.size_on_disk()
+            + self.counters.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        Ok(VerifyReport::merged([
+            self.blocks.verify()?,
+            self.entries.verify()?,
+            self.allocated.verify()?,
+            self.free.verify()?,
+            self.counters.verify()?,
+        ]))
+    }
+}
+
+impl Slab {
+    /// Store a value, returning its handle
+    pub fn insert(&self, bytes: &[u8]) -> io::Result<SlabHandle> {
+        let class = Self::class(bytes.len())?;
+        let _writer = self.writer.lock();
+
+        let offset = self.blocks.alloc(class, 8)?;
+        self.blocks.write(offset, bytes)?;
+
+        let index = match self.free.current().checked_sub(1) {
+            Some(index) => {
+                let next = self.entry(index as usize)[OFFSET];
+                self.free.set(next);
+                index as usize
+            }
+            None => self.allocated.update(|allocated| {
+                *allocated += 1;
+                *allocated as usize - 1
+            }),
+        };
+
+        let mut entry = self.entries.get_mut(index)?;
+        entry[OFFSET] = offset;
+        entry[LEN_PLUS_ONE] = bytes.len() as u64 + 1;
+        let handle = SlabHandle::new(index, entry[GENERATION]);
+        drop(entry);
+
+        self.counters.fetch_add(LEN, 1)?;
+        Ok(handle)
+    }
+
+    /// Get a copy of the value of `handle`
+    pub fn get(&self, handle: SlabHandle) -> io::Result<Vec<u8>> {
+        let _writer = self.writer.lock();
+        let (offset, len) = self.live(handle)?;
+        self.blocks.read(offset, len)
+    }
+
+    /// Replace the value of `handle`
+    ///
+    /// The value is updated in place if it still fits its block, and moved
+    /// to a block of another class otherwise
+    pub fn update(&self, handle: SlabHandle, bytes: &[u8]) -> io::Result<()> {
+        let new_class = Self::class(bytes.len())?;
+        let _writer = self.writer.lock();
+        let (offset, len) = self.live(handle)?;
+
+        let old_class = Self::class(len)?;
+        let new_offset = if new_class == old_class {
+            offset
+        } else {
+            self.blocks.alloc(new_class, 8)?
+        };
+        self.blocks.write(new_offset, bytes)?;
+
+        let mut entry = self.entries.get_mut(handle.entry())?;
+        entry[OFFSET] = new_offset;
+        entry[LEN_PLUS_ONE] = bytes.len() as u64 + 1;
+        drop(entry);
+
+        if new_offset != offset {
+            self.blocks.free(offset, old_class)?;
+        }
+        Ok(())
+    }
+
+    /// Remove the value of `handle`
+    pub fn remove(&self, handle: SlabHandle) -> io::Result<()> {
+        let _writer = self.writer.lock();
+        let (offset, len) = self.live(handle)?;
+
+        let mut entry = self.entries.get_mut(handle.entry())?;
+        entry[OFFSET] = self.free.current();
+        entry[LEN_PLUS_ONE] = 0;
+        entry[GENERATION] = (entry[GENERATION] + 1) & u32::MAX as u64;
+        drop(entry);
+
+        self.free.set(handle.entry() as u64 + 1);
+        self.blocks.free(offset, Self::class(len)?)?;
+        self.counters.fetch_add(LEN, u64::MAX)?;
+        Ok(())
+    }
+
+    /// Returns true if `handle` refers to a value
+    pub fn contains(&self, handle: SlabHandle) -> bool {
+        let _writer = self.writer.lock();
+        self.live(handle).is_ok()
+    }
+
+    /// The number of values in the slab
+    pub fn len(&self) -> usize {
+        self.counters.load(LEN) as usize
+    }
+
+    /// Returns true if the slab holds no values
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The offset and length of the value of a valid handle
+    fn live(&self, handle: SlabHandle) -> io::Result<(u64, usize)> {
+        let entry = self.entry(handle.entry());
+        match entry[LEN_PLUS_ONE].checked_sub(1) {
+            Some(len) if entry[GENERATION] == handle.generation() => {
+                Ok((entry[OFFSET], len as usize))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "Invalid slab handle",
+            )),
+        }
+    }
+
+    fn entry(&self, index: usize) -> Entry {
+        self.entries.get_raw(index).map(|e| *e).unwrap_or_default()
+    }
+
+    // The block size for a value of `len` bytes
+    fn class(len: usize) -> io::Result<usize> {
+        let mut class = SMALLEST_CLASS;
+        while class < len {
+            class *= 4;
+        }
+        if class > LARGEST_CLASS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Value too large for slab",
+            ));
+        }
+        Ok(class)
+    }
+}
@@ -1,13 +1,20 @@
-use std::hash::Hash;
+use std::hash::{Hash, Hasher};
 use std::io;
 use std::marker::PhantomData;
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use bytemuck::{Pod, Zeroable};
 
 use crate::helpers;
-use crate::{Entropy, GuardedLandfill, RandomAccess, Substructure};
+use crate::{
+    Entropy, GuardedLandfill, Journal, Landfill, RandomAccess,
+    RandomAccessGuard, Substructure, VerifyReport,
+};
 
 const INITIAL_FANOUT: u64 = 1024;
+// default maximum number of slots probed by a single operation
+const DEFAULT_PROBE_LIMIT: u64 = 1 << 24;
 
 /// Low-level on-disk hashmap
 ///
@@ -15,23 +22,258 @@ const INITIAL_FANOUT: u64 = 1024;
 ///
 /// This type should generally not be used directly, but rather be used as a base
 /// to implement other map-like datastructues
-pub struct SmashMap<K, V> {
+///
+/// Tags are stored in a fixed byte order, but keys are hashed through their
+/// `Hash` implementations, and offsets and lengths are stored as native
+/// integers, so maps built on it only move between hosts of the same byte
+/// order
+pub struct SmashMap<K: ?Sized, V, H = SeaHasherAdapter> {
+    current: Generation<V>,
+    // the current generation and its initial fanout, changed by rehashing
+    layout: Journal<[u64; 2]>,
+    stats: ProbeCounters,
+    probe_limit: u64,
+    // set once no more inserts can happen, reads then skip locking
+    frozen: bool,
+    landfill: Landfill,
+    _key: PhantomData<fn(&K)>,
+    _hasher: PhantomData<H>,
+}
+
+/// The hash function used to place keys in a `SmashMap`
+///
+/// Implementations should mix in the entropy of the map, to keep the map
+/// resistant to adversarial keys
+pub trait SmashHasher {
+    /// Hash `key` using the entropy of the map
+    fn hash_key<K: Hash + ?Sized>(entropy: &Entropy, key: &K) -> u64;
+}
+
+/// The default hasher, running keys through a seahash keyed by the entropy
+pub struct SeaHasherAdapter;
+
+impl SmashHasher for SeaHasherAdapter {
+    fn hash_key<K: Hash + ?Sized>(entropy: &Entropy, key: &K) -> u64 {
+        entropy.checksum(&key)
+    }
+}
+
+/// A hasher for keys that already carry a strong hash, such as content ids
+///
+/// Folds the bytes fed to the hasher by the `Hash` implementation of the key
+/// into a single word with xor, and hashes only that word with the entropy,
+/// instead of hashing the whole key again
+pub struct PrehashedAdapter;
+
+impl SmashHasher for PrehashedAdapter {
+    fn hash_key<K: Hash + ?Sized>(entropy: &Entropy, key: &K) -> u64 {
+        #[derive(Default)]
+        struct XorFold {
+            word: [u8; 8],
+            pos: usize,
+        }
+
+        impl Hasher for XorFold {
+            fn write(&mut self, bytes: &[u8]) {
+                for byte in bytes {
+                    self.word[self.pos % 8] ^= byte;
+                    self.pos += 1;
+                }
+            }
+
+            fn finish(&self) -> u64 {
+                u64::from_le_bytes(self.word)
+            }
+        }
+
+        let mut hasher = XorFold::default();
+        key.hash(&mut hasher);
+        entropy.checksum(&hasher.finish())
+    }
+}
+
+// The slots of a map, replaced as a whole when rehashing
+struct Generation<V> {
     slots: RandomAccess<V>,
     entropy: Entropy,
-    _marker: PhantomData<K>,
+    // number of occupied slots
+    count: Journal<u64>,
+    // occupied slots found by scanning a map that was never counted, for
+    // read-only views that cannot record them in `count`
+    scanned: u64,
+    fanout: u64,
+}
+
+impl<V> Generation<V> {
+    fn open(lf: &Landfill, generation: u64, fanout: u64) -> io::Result<Self> {
+        let mut generation = Generation {
+            slots: lf.substructure(generation_name("slots", generation))?,
+            entropy: lf.substructure(generation_name("entropy", generation))?,
+            count: lf.substructure(generation_name("count", generation))?,
+            scanned: 0,
+            fanout,
+        };
+
+        // maps written before occupied slots were counted get their count
+        // from a scan
+        if generation.count.current() == 0 && !generation.slots.is_empty() {
+            let occupied = generation.slots.count_nonzero() as u64;
+            if lf.is_read_only() {
+                generation.scanned = occupied;
+            } else if occupied > 0 {
+                generation.count.set(occupied);
+            }
+        }
+
+        Ok(generation)
+    }
+
+    // Open a new generation, keyed by fresh random entropy which is persisted
+    // along with it, so that it is independent of the root seed
+    fn create(lf: &Landfill, generation: u64, fanout: u64) -> io::Result<Self> {
+        let entropy = lf
+            .branch(generation_name("entropy", generation))
+            .get_static_or_init(Entropy::random)?;
+        Ok(Generation {
+            entropy,
+            ..Generation::open(lf, generation, fanout)?
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.slots.flush()?;
+        self.count.flush()
+    }
+
+    // The number of occupied slots
+    fn occupied(&self) -> u64 {
+        self.count.current().max(self.scanned)
+    }
+}
+
+// Name of a substructure belonging to a specific generation of the map
+pub(crate) fn generation_name(name: &str, generation: u64) -> String {
+    if generation == 0 {
+        name.into()
+    } else {
+        format!("{name}{generation}")
+    }
+}
+
+fn remove_generation(lf: &Landfill, generation: u64) -> io::Result<()> {
+    lf.branch(generation_name("slots", generation))
+        .remove_tree()?;
+    for name in ["entropy", "count"] {
+        lf.branch(generation_name(name, generation))
+            .remove_files()?;
+    }
+    Ok(())
 }
 
-impl<K, V> Substructure for SmashMap<K, V> {
+/// Aggregate probe statistics of a `SmashMap` since it was opened
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProbeStats {
+    /// Number of inserts and gets performed
+    pub operations: u64,
+    /// Average number of slots probed per operation
+    pub average_probes: f64,
+    /// Highest number of slots probed by a single operation
+    pub max_probes: u64,
+    /// Deepest fanout level reached by a single operation
+    pub max_level: u32,
+}
+
+#[derive(Default)]
+struct ProbeCounters {
+    operations: AtomicU64,
+    probes: AtomicU64,
+    max_probes: AtomicU64,
+    max_level: AtomicU64,
+}
+
+impl ProbeCounters {
+    fn record(&self, search: &SearchPattern) {
+        self.operations.fetch_add(1, Ordering::Relaxed);
+        self.probes.fetch_add(search.probes(), Ordering::Relaxed);
+        self.max_probes
+            .fetch_max(search.probes(), Ordering::Relaxed);
+        self.max_level
+            .fetch_max(search.level() as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> ProbeStats {
+        let operations = self.operations.load(Ordering::Relaxed);
+        let probes = self.probes.load(Ordering::Relaxed);
+        ProbeStats {
+            operations,
+            average_probes: match operations {
+                0 => 0.0,
+                n => probes as f64 / n as f64,
+            },
+            max_probes: self.max_probes.load(Ordering::Relaxed),
+            max_level: self.max_level.load(Ordering::Relaxed) as u32,
+        }
+    }
+}
+
+impl<K: ?Sized, V, H> Substructure for SmashMap<K, V, H> {
     fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        let layout: Journal<[u64; 2]> = lf.substructure("layout")?;
+        let [generation, fanout] = layout.current();
+
+        // clean up after an interrupted rehash, or the generation left over
+        // after switching to a new one
+        let rehashing = lf.branch("rehashing".into());
+        rehashing.reserve_name();
+        if rehashing.file_exists() {
+            if generation > 0 {
+                remove_generation(&lf, generation - 1)?;
+            }
+            remove_generation(&lf, generation + 1)?;
+            rehashing.take_marker()?;
+        }
+
+        let fanout = if fanout == 0 { INITIAL_FANOUT } else { fanout };
+
         Ok(SmashMap {
-            slots: lf.substructure("slots")?,
-            entropy: lf.substructure("entropy")?,
-            _marker: PhantomData,
+            current: Generation::open(&lf, generation, fanout)?,
+            layout,
+            stats: ProbeCounters::default(),
+            probe_limit: DEFAULT_PROBE_LIMIT,
+            frozen: false,
+            landfill: lf.inner(),
+            _key: PhantomData,
+            _hasher: PhantomData,
         })
     }
 
     fn flush(&self) -> io::Result<()> {
-        self.slots.flush()
+        self.current.flush()?;
+        self.layout.flush()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.landfill.size_on_disk()
+    }
+
+    /// Checks that the number of occupied slots matches the count of the
+    /// map, which only holds while no inserts are in progress
+    fn verify(&self) -> io::Result<VerifyReport> {
+        let mut report = VerifyReport::merged([
+            self.layout.verify()?,
+            self.current.count.verify()?,
+            self.current.slots.verify()?,
+        ]);
+
+        let occupied = self.current.slots.count_nonzero() as u64;
+        let count = self.current.occupied();
+        report.checked += occupied;
+        if occupied != count {
+            report.problems.push(format!(
+                "SmashMap has {occupied} occupied slots, but counts {count}"
+            ));
+        }
+        Ok(report)
     }
 }
 
@@ -50,6 +292,15 @@ pub struct SearchPattern<'a> {
     offset: u64,
     retries: u64,
     tries_limit: u64,
+    probes: u64,
+    level: u32,
+}
+
+impl<K: ?Sized, V, H> SmashMap<K, V, H> {
+    /// Reject all further inserts, letting reads skip slot locking
+    pub(crate) fn freeze(&mut self) {
+        self.frozen = true;
+    }
 }
 
 impl<'a> SearchPattern<'a> {
@@ -61,37 +312,61 @@ impl<'a> SearchPattern<'a> {
         SearchNext::Halt
     }
 
+    // Tags are the low-order bytes of the hash state, so hosts of either
+    // endianness derive the same tags. On little-endian hosts this matches
+    // the native-endian derivation of earlier versions
     pub fn tag_u8(&self) -> u8 {
-        let slice = &[self.entropy_state];
-        let bytes: &[u8] = bytemuck::cast_slice(slice);
-        bytes[0]
+        self.entropy_state.to_le_bytes()[0]
     }
 
     pub fn tag_u16(&self) -> u16 {
-        let slice = &[self.entropy_state];
-        let bytes: &[u16] = bytemuck::cast_slice(slice);
-        bytes[0]
+        let bytes = self.entropy_state.to_le_bytes();
+        u16::from_le_bytes([bytes[0], bytes[1]])
     }
 
     pub fn tag_u32(&self) -> u32 {
-        let slice = &[self.entropy_state];
-        let bytes: &[u32] = bytemuck::cast_slice(slice);
-        bytes[0]
+        let bytes = self.entropy_state.to_le_bytes();
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    /// The bytes of `tag_u32` in little-endian order, for storing on disk
+    ///
+    /// Stored tags are compared as bytes, so their layout does not depend
+    /// on the byte order of the host
+    pub fn tag_bytes(&self) -> [u8; 4] {
+        self.tag_u32().to_le_bytes()
+    }
+
+    /// Returns true if `tag` was stored for this key by `tag_bytes`
+    ///
+    /// Big-endian hosts also accept the native-endian tag earlier versions
+    /// derived from the high-order bytes, so files they wrote can still be
+    /// read
+    pub fn matches_tag(&self, tag: [u8; 4]) -> bool {
+        tag == self.tag_bytes()
+            || (cfg!(target_endian = "big")
+                && tag == ((self.entropy_state >> 32) as u32).to_ne_bytes())
     }
 
     pub fn tag_u64(&self) -> u64 {
         self.entropy_state
     }
 
-    fn new<K: Hash>(key: &K, entropy_source: &'a Entropy) -> Self {
-        let entropy_state = entropy_source.checksum(key);
+    fn new<K: Hash + ?Sized, H: SmashHasher>(
+        key: &K,
+        entropy_source: &'a Entropy,
+        fanout: u64,
+    ) -> Self {
+        let entropy_state = H::hash_key(entropy_source, key);
         SearchPattern {
             entropy_source,
             entropy_state,
-            fanout: INITIAL_FANOUT,
+            fanout,
             offset: 0,
             retries: 0,
             tries_limit: 1,
+            probes: 1,
+            level: 0,
         }
     }
 
@@ -102,9 +377,21 @@ impl<'a> SearchPattern<'a> {
         with_offset as usize
     }
 
+    /// Returns the number of slots probed so far, including the current one
+    pub fn probes(&self) -> u64 {
+        self.probes
+    }
+
+    /// Returns the fanout level of the current slot, starting at zero
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
     fn calculate_next(&mut self) {
+        self.probes += 1;
         self.retries += 1;
         if self.retries == self.tries_limit {
+            self.level += 1;
             self.offset += self.fanout;
             self.fanout <<= 1;
             self.tries_limit <<= 1;
@@ -115,10 +402,11 @@ impl<'a> SearchPattern<'a> {
     }
 }
 
-impl<K, V> SmashMap<K, V>
+impl<K, V, H> SmashMap<K, V, H>
 where
-    K: Hash,
+    K: Hash + ?Sized,
     V: Zeroable + Pod,
+    H: SmashHasher,
 {
     /// Searches the map for entries and presents them to the consumer,
     /// that may chose to break the process here (for example,
@@ -130,28 +418,53 @@ where
         &self,
         key: &K,
         on_occupied: Occupied,
+        on_empty: Empty,
+    ) -> io::Result<()>
+    where
+        Occupied: Fn(&SearchPattern, &V) -> SearchNext,
+        Empty: FnMut(&SearchPattern) -> io::Result<V>,
+    {
+        self.insert_in(&self.current, key, on_occupied, on_empty)
+    }
+
+    fn insert_in<Occupied, Empty>(
+        &self,
+        generation: &Generation<V>,
+        key: &K,
+        on_occupied: Occupied,
         mut on_empty: Empty,
     ) -> io::Result<()>
     where
         Occupied: Fn(&SearchPattern, &V) -> SearchNext,
         Empty: FnMut(&SearchPattern) -> io::Result<V>,
     {
-        let mut search = SearchPattern::new(key, &self.entropy);
+        if self.frozen {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "SmashMap is frozen",
+            ));
+        }
+
+        let mut search = SearchPattern::new::<K, H>(
+            key,
+            &generation.entropy,
+            generation.fanout,
+        );
         loop {
             let slot = search.get_slot();
 
-            match self.slots.get(slot) {
+            match generation.slots.get(slot) {
                 Some(value) => {
                     if let SearchNext::Halt = on_occupied(&search, &*value) {
                         // consumer signaled that the search is over
-                        return Ok(());
+                        break;
                     }
                 }
                 None => {
                     // Encountered an empty slot
                     let mut finished = false;
 
-                    self.slots.with_mut(slot, |mut_slot| {
+                    generation.slots.with_mut(slot, |mut_slot| {
                         if !helpers::is_all_zeroes(&[*mut_slot]) {
                             // another thread already wrote here before our
                             // write lock cleared
@@ -163,17 +476,59 @@ where
                             }
                         } else {
                             *mut_slot = on_empty(&search)?;
+                            generation.count.update(|count| *count += 1);
                             finished = true;
                         }
                         io::Result::Ok(())
                     })??;
                     if finished {
-                        return Ok(());
+                        break;
                     }
                 }
             }
+            if search.probes() >= self.probe_limit {
+                self.stats.record(&search);
+                return Err(io::Error::other("SmashMap probe limit reached"));
+            }
             search.calculate_next()
         }
+
+        self.stats.record(&search);
+        Ok(())
+    }
+
+    /// Set the maximum number of slots probed by a single operation
+    ///
+    /// Inserts that reach the limit fail with an error, and gets stop
+    /// searching. Protects against spinning forever on corrupted slots
+    pub fn set_probe_limit(&mut self, limit: u64) {
+        self.probe_limit = limit.max(1);
+    }
+
+    /// Returns the number of occupied slots in the map
+    pub fn len(&self) -> usize {
+        self.current.occupied() as usize
+    }
+
+    /// Returns true if no slot in the map is occupied
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the fraction of occupied slots, up to the highest slot used
+    ///
+    /// A low occupancy means entries are spread over many sparse levels
+    pub fn occupancy(&self) -> f64 {
+        match self.current.slots.len() {
+            0 => 0.0,
+            used => self.len() as f64 / used as f64,
+        }
+    }
+
+    /// Iterate over all values in the map, in slot order
+    pub fn values(&self) -> impl Iterator<Item = V> + '_ {
+        (0..self.current.slots.len())
+            .filter_map(|slot| self.slot(slot).map(|v| *v))
     }
 
     /// Search the map and call the provided closure with the results
@@ -182,21 +537,161 @@ where
         K: Hash,
         Occupied: FnMut(&SearchPattern, &V) -> SearchNext,
     {
-        let mut search = SearchPattern::new(key, &self.entropy);
+        let mut search = SearchPattern::new::<K, H>(
+            key,
+            &self.current.entropy,
+            self.current.fanout,
+        );
         loop {
             let slot = search.get_slot();
 
-            match self.slots.get(slot) {
+            match self.slot(slot) {
                 Some(value) => {
                     if let SearchNext::Halt = on_occupied(&search, &*value) {
-                        return;
+                        break;
                     }
                 }
-                None => {
-                    return;
+                None => break,
+            }
+            if search.probes() >= self.probe_limit {
+                break;
+            }
+            search.calculate_next()
+        }
+
+        self.stats.record(&search);
+    }
+
+    /// Search the map and return the first value accepted by `predicate`
+    ///
+    /// The value stays read-locked for as long as the guard is held
+    pub fn get_first<Predicate>(
+        &self,
+        key: &K,
+        mut predicate: Predicate,
+    ) -> Option<RandomAccessGuard<'_, V>>
+    where
+        Predicate: FnMut(&SearchPattern, &V) -> bool,
+    {
+        let mut search = SearchPattern::new::<K, H>(
+            key,
+            &self.current.entropy,
+            self.current.fanout,
+        );
+        let found = loop {
+            let slot = search.get_slot();
+
+            match self.slot(slot) {
+                Some(value) => {
+                    if predicate(&search, &value) {
+                        break Some(value);
+                    }
                 }
+                None => break None,
+            }
+            if search.probes() >= self.probe_limit {
+                break None;
             }
             search.calculate_next()
+        };
+
+        self.stats.record(&search);
+        found
+    }
+
+    /// Rebuild the map into a fresh generation, with an initial fanout sized
+    /// to the current number of entries
+    ///
+    /// The new generation hashes keys with newly generated random entropy,
+    /// so rehashing also rotates the hash keys of the map
+    ///
+    /// Since the map does not store keys, `key_of` must recover the key of
+    /// each value, and `rebuild` is called to produce the value to store in
+    /// the new map, for values carrying tags derived from the search
+    pub fn rehash<KeyOf, Rebuild>(
+        &mut self,
+        mut key_of: KeyOf,
+        mut rebuild: Rebuild,
+    ) -> io::Result<()>
+    where
+        K: Sized,
+        KeyOf: FnMut(&V) -> K,
+        Rebuild: FnMut(&SearchPattern, V) -> V,
+    {
+        let [generation, _] = self.layout.current();
+        let next = generation + 1;
+        let fanout = (self.len() as u64 * 2)
+            .next_power_of_two()
+            .max(INITIAL_FANOUT);
+
+        let rehashing = self.landfill.branch("rehashing".into());
+        rehashing.create_marker()?;
+
+        let rehashed = Generation::create(&self.landfill, next, fanout)?;
+
+        for value in self.values() {
+            let key = key_of(&value);
+            self.insert_in(
+                &rehashed,
+                &key,
+                |search, _| search.proceed(),
+                |search| Ok(rebuild(search, value)),
+            )?;
         }
+        rehashed.flush()?;
+
+        // switch over to the new generation
+        self.layout.update(|layout| *layout = [next, fanout]);
+        self.layout.flush()?;
+
+        drop(mem::replace(&mut self.current, rehashed));
+        remove_generation(&self.landfill, generation)?;
+        rehashing.take_marker()?;
+        Ok(())
+    }
+
+    fn slot(&self, slot: usize) -> Option<RandomAccessGuard<'_, V>> {
+        if self.frozen {
+            // Safety: frozen maps are never written to again, and freezing
+            // takes `&mut self` so no write can still be in progress
+            unsafe { self.current.slots.get_unlocked(slot) }
+        } else {
+            self.current.slots.get(slot)
+        }
+    }
+
+    /// Returns probe statistics of all operations since the map was opened
+    pub fn probe_stats(&self) -> ProbeStats {
+        self.stats.snapshot()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tags_are_little_endian() {
+        let entropy = Entropy::from_seed([7; 32]);
+        let search =
+            SearchPattern::new::<_, SeaHasherAdapter>(&1234u64, &entropy, 16);
+        let state = search.tag_u64();
+
+        assert_eq!(search.tag_u8(), state as u8);
+        assert_eq!(search.tag_u16(), state as u16);
+        assert_eq!(search.tag_u32(), state as u32);
+        assert_eq!(search.tag_bytes(), (state as u32).to_le_bytes());
+        assert!(search.matches_tag(search.tag_bytes()));
+        assert!(!search.matches_tag((!state as u32).to_le_bytes()));
+    }
+
+    #[test]
+    fn stored_tag_bytes() {
+        // single byte keys hash the same on hosts of either byte order
+        let entropy = Entropy::from_seed([7; 32]);
+        let search =
+            SearchPattern::new::<_, SeaHasherAdapter>(&7u8, &entropy, 16);
+
+        assert_eq!(search.tag_bytes(), [97, 54, 128, 33]);
     }
 }
@@ -0,0 +1,199 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bytemuck::Pod;
+use parking_lot::Mutex;
+
+use crate::{AppendOnly, GuardedLandfill, Substructure, VerifyReport};
+
+const DEFAULT_MEMORY_LIMIT: usize = 16 * 1024 * 1024;
+// maximum size of the pieces a run is written in, so that they fit in a lane
+const CHUNK_BYTES: usize = 64 * 1024;
+
+// a piece of a run, written with a single reservation
+#[derive(Clone, Copy)]
+struct Chunk {
+    offset: u64,
+    len: usize,
+}
+
+struct State<T> {
+    buffer: Vec<T>,
+    // chunks of each spilled run, in order
+    runs: Vec<Vec<Chunk>>,
+}
+
+/// Sorts more records than fit in memory
+///
+/// Records are collected in a buffer, which is sorted and spilled to disk
+/// as a run whenever it reaches the memory limit. The sorted records are
+/// then read back by merging all runs, keeping only one record per run in
+/// memory.
+///
+/// The runs are not persisted, and are meant to live in an ephemeral
+/// landfill or a branch created with `Landfill::temp_branch`.
+pub struct Sorter<T> {
+    data: AppendOnly,
+    state: Mutex<State<T>>,
+    memory_limit: AtomicUsize,
+}
+
+impl<T> Substructure for Sorter<T> {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        if mem::size_of::<T>() == 0 || mem::size_of::<T>() > CHUNK_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Unsupported record size for Sorter",
+            ));
+        }
+
+        Ok(Sorter {
+            data: lf.substructure("data")?,
+            state: Mutex::new(State {
+                buffer: vec![],
+                runs: vec![],
+            }),
+            memory_limit: AtomicUsize::new(DEFAULT_MEMORY_LIMIT),
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.data.flush()
+    }
+
+    fn close(self) -> io::Result<()> {
+        self.data.close()
+    }
+
+    fn size_on_disk(&self) -> u64 {
+        self.data.size_on_disk()
+    }
+
+    fn verify(&self) -> io::Result<VerifyReport> {
+        self.data.verify()
+    }
+}
+
+impl<T> Sorter<T>
+where
+    T: Pod + Ord,
+{
+    /// Set the number of bytes of records buffered before spilling a run
+    pub fn set_memory_limit(&self, bytes: usize) {
+        self.memory_limit.store(bytes, Ordering::Relaxed)
+    }
+
+    /// Add a record to be sorted
+    pub fn push(&self, t: T) -> io::Result<()> {
+        let mut state = self.state.lock();
+        state.buffer.push(t);
+
+        let limit = self.memory_limit.load(Ordering::Relaxed);
+        if state.buffer.len() * mem::size_of::<T>() >= limit {
+            self.spill(&mut state)?;
+        }
+        Ok(())
+    }
+
+    /// Add all records of an iterator to be sorted
+    pub fn extend<I>(&self, records: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        records.into_iter().try_for_each(|t| self.push(t))
+    }
+
+    /// The number of records added
+    pub fn len(&self) -> usize {
+        let state = self.state.lock();
+        let spilled: usize = state
+            .runs
+            .iter()
+            .flat_map(|run| run.iter().map(|chunk| chunk.len))
+            .sum();
+        spilled + state.buffer.len()
+    }
+
+    /// Returns true if no records were added
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of runs spilled to disk
+    pub fn runs(&self) -> usize {
+        self.state.lock().runs.len()
+    }
+
+    /// Iterate over copies of all records added so far, in sorted order
+    ///
+    /// Buffered records are spilled as a run first, so the iterator only
+    /// reads from disk
+    pub fn sorted(&self) -> io::Result<impl Iterator<Item = T> + '_> {
+        let mut state = self.state.lock();
+        self.spill(&mut state)?;
+
+        let mut cursors: Vec<_> = state
+            .runs
+            .iter()
+            .map(|run| self.run_iter(run.clone()))
+            .collect();
+        drop(state);
+
+        // the next record of every run, ties going to the earlier run
+        let mut heap = BinaryHeap::new();
+        for (i, cursor) in cursors.iter_mut().enumerate() {
+            if let Some(t) = cursor.next() {
+                heap.push(Reverse((t, i)));
+            }
+        }
+
+        Ok(std::iter::from_fn(move || {
+            let Reverse((t, i)) = heap.pop()?;
+            if let Some(next) = cursors[i].next() {
+                heap.push(Reverse((next, i)));
+            }
+            Some(t)
+        }))
+    }
+
+    // Sort the buffer and write it out as a new run
+    fn spill(&self, state: &mut State<T>) -> io::Result<()> {
+        if state.buffer.is_empty() {
+            return Ok(());
+        }
+
+        state.buffer.sort_unstable();
+
+        let per_chunk = CHUNK_BYTES / mem::size_of::<T>();
+        let run = state
+            .buffer
+            .chunks(per_chunk)
+            .map(|records| {
+                let offset = self.data.write_aligned(
+                    bytemuck::cast_slice(records),
+                    mem::align_of::<T>(),
+                )?;
+                Ok(Chunk {
+                    offset,
+                    len: records.len(),
+                })
+            })
+            .collect::<io::Result<_>>()?;
+
+        state.runs.push(run);
+        state.buffer.clear();
+        Ok(())
+    }
+
+    fn run_iter(&self, run: Vec<Chunk>) -> impl Iterator<Item = T> + '_ {
+        let t_size = mem::size_of::<T>();
+        run.into_iter().flat_map(move |chunk| {
+            let bytes =
+                self.data.get(chunk.offset, (chunk.len * t_size) as u32);
+            bytes.chunks_exact(t_size).map(bytemuck::pod_read_unaligned)
+        })
+    }
+}
@@ -0,0 +1,91 @@
+use std::io;
+
+use landfill::{Allocator, Landfill};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+#[test]
+fn reuses_freed_space() -> io::Result<()> {
+    with_temp_path(|path| {
+        let (a, b) = {
+            let lf = Landfill::open(path)?;
+            let alloc: Allocator = lf.substructure("alloc")?;
+
+            let a = alloc.alloc(100, 8)?;
+            let b = alloc.alloc(100, 8)?;
+            assert_ne!(a, b);
+            assert_eq!(a % 128, 0);
+
+            alloc.write(a, &[1; 100])?;
+            alloc.write(b, &[2; 100])?;
+            alloc.free(a, 100)?;
+            alloc.free(b, 100)?;
+            (a, b)
+        };
+
+        let lf = Landfill::open(path)?;
+        let alloc: Allocator = lf.substructure("alloc")?;
+
+        assert_eq!(alloc.free_blocks(), [(128, 2)]);
+
+        // same size class, most recently freed first
+        assert_eq!(alloc.alloc(65, 1)?, b);
+        assert_eq!(alloc.alloc(128, 64)?, a);
+        assert!(alloc.free_blocks().is_empty());
+
+        let c = alloc.alloc(100, 8)?;
+        assert!(c != a && c != b);
+
+        alloc.write(c, b"hello")?;
+        assert_eq!(alloc.read(c, 5)?, b"hello");
+
+        Ok(())
+    })
+}
+
+#[test]
+fn invalid_requests() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let alloc: Allocator = lf.substructure("alloc")?;
+
+    assert!(alloc.alloc(8, 16).is_err());
+    assert!(alloc.alloc(8, 3).is_err());
+    assert!(alloc.free(1 << 40, 8).is_err());
+    assert!(alloc.read(1 << 40, 8).is_err());
+
+    let small = alloc.alloc(1, 1)?;
+    assert_eq!(alloc.free_blocks(), []);
+    alloc.free(small, 1)?;
+    assert_eq!(alloc.free_blocks(), [(8, 1)]);
+
+    Ok(())
+}
+
+#[test]
+fn blocks_tracked() -> io::Result<()> {
+    with_temp_path(|path| {
+        let block = {
+            let lf = Landfill::open(path)?;
+            let alloc: Allocator = lf.substructure("alloc")?;
+            alloc.alloc(100, 8)?
+        };
+
+        let lf = Landfill::open(path)?;
+        let alloc: Allocator = lf.substructure("alloc")?;
+
+        // bounded by the size class of the block
+        alloc.write(block, &[1; 128])?;
+        assert!(alloc.write(block, &[1; 129]).is_err());
+        assert!(alloc.read(block, 129).is_err());
+        assert!(alloc.write(block + 8, &[1; 8]).is_err());
+
+        assert!(alloc.free(block, 8).is_err());
+        alloc.free(block, 100)?;
+        assert!(alloc.free(block, 100).is_err());
+        assert!(alloc.write(block, &[1; 8]).is_err());
+        assert_eq!(alloc.free_blocks(), [(128, 1)]);
+
+        Ok(())
+    })
+}
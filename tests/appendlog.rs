@@ -0,0 +1,117 @@
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytemuck_derive::*;
+use landfill::{AppendLog, Landfill};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+#[derive(Copy, Clone, Zeroable, Pod, Debug, PartialEq)]
+#[repr(C)]
+struct Event {
+    kind: u32,
+    source: u32,
+    timestamp: u64,
+    // odd size to not divide lanes evenly
+    extra: [u8; 8],
+    more: [u16; 4],
+    last: [u8; 8],
+}
+
+fn event(i: u64) -> Event {
+    Event {
+        kind: i as u32 % 3,
+        source: i as u32,
+        timestamp: i * 1000,
+        extra: [i as u8; 8],
+        more: [i as u16; 4],
+        last: [1; 8],
+    }
+}
+
+const A_LOT: u64 = 10_000;
+
+#[test]
+fn appendlog_push_get() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let log: AppendLog<Event> = lf.substructure("log")?;
+
+    assert!(log.is_empty());
+
+    for i in 0..A_LOT {
+        assert_eq!(log.push(event(i))?, i);
+    }
+
+    assert_eq!(log.len(), A_LOT);
+
+    for i in 0..A_LOT {
+        assert_eq!(log.get(i), Some(&event(i)));
+    }
+    assert_eq!(log.get(A_LOT), None);
+
+    for (i, ev) in log.iter().enumerate() {
+        assert_eq!(ev, &event(i as u64));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn appendlog_reopen() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let log: AppendLog<u64> = lf.substructure("log")?;
+            for i in 0..A_LOT {
+                log.push(i)?;
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let log: AppendLog<u64> = lf.substructure("log")?;
+
+        assert_eq!(log.len(), A_LOT);
+        assert_eq!(log.push(A_LOT)?, A_LOT);
+        assert!(log.iter().copied().eq(0..=A_LOT));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn appendlog_watch() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let log: Arc<AppendLog<Event>> = Arc::new(lf.substructure("log")?);
+
+    log.push(event(0))?;
+
+    let mut watch = log.watch();
+    assert_eq!(watch.position(), 1);
+    assert!(watch.try_next().is_none());
+    assert!(watch.next_timeout(Duration::from_millis(10)).is_none());
+
+    let writer = {
+        let log = log.clone();
+        std::thread::spawn(move || -> io::Result<()> {
+            for i in 1..100 {
+                log.push(event(i))?;
+            }
+            Ok(())
+        })
+    };
+
+    for i in 1..100 {
+        assert_eq!(watch.next(), Some(&event(i)));
+    }
+    writer.join().unwrap()?;
+
+    // watching from an earlier index yields the values already pushed
+    let mut earlier = log.watch_from(98);
+    assert_eq!(earlier.next(), Some(&event(98)));
+    assert_eq!(earlier.try_next(), Some(&event(99)));
+    assert!(earlier.try_next().is_none());
+
+    Ok(())
+}
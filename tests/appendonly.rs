@@ -1,4 +1,6 @@
-use landfill::{AppendOnly, Landfill};
+use std::io::IoSlice;
+
+use landfill::{AppendOnly, Landfill, Substructure};
 
 mod with_temp_path;
 use with_temp_path::with_temp_path;
@@ -98,3 +100,415 @@ fn appendonly_save_restore_skip_files() -> Result<(), std::io::Error> {
         Ok(())
     })
 }
+
+#[test]
+fn appendonly_batch() -> Result<(), std::io::Error> {
+    let lf = Landfill::ephemeral()?;
+    let ao: AppendOnly = lf.substructure("ao")?;
+
+    let msgs: Vec<Vec<u8>> = (0..1024u32)
+        .map(|i| i.to_le_bytes().repeat(i as usize % 7 + 1))
+        .collect();
+    let batch: Vec<&[u8]> = msgs.iter().map(|msg| &msg[..]).collect();
+
+    let offsets = ao.write_batch(&batch)?;
+
+    for (msg, ofs) in msgs.iter().zip(offsets) {
+        assert_eq!(ao.get(ofs, msg.len() as u32), &msg[..]);
+    }
+
+    let ofs = ao.write_all_vectored(&[
+        IoSlice::new(b"hello "),
+        IoSlice::new(b"vectored "),
+        IoSlice::new(b"world"),
+    ])?;
+
+    assert_eq!(ao.get(ofs, 20), b"hello vectored world");
+
+    Ok(())
+}
+
+#[test]
+fn appendonly_records() -> Result<(), std::io::Error> {
+    let lf = Landfill::ephemeral()?;
+    let ao_a: AppendOnly = lf.substructure("a")?;
+    let ao_b: AppendOnly = lf.substructure("b")?;
+
+    let record = ao_a.write_record(b"hello record")?;
+
+    assert_eq!(ao_a.get_record(record)?, b"hello record");
+    assert!(ao_b.get_record(record).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn appendonly_from_reader() -> Result<(), std::io::Error> {
+    let lf = Landfill::ephemeral()?;
+    let ao: AppendOnly = lf.substructure("ao")?;
+
+    let data: Vec<u8> = (0..300_000u32).map(|i| i as u8).collect();
+
+    for hint in [0, 1000, data.len() as u64, 1_000_000] {
+        let records = ao.write_from_reader(&data[..], hint)?;
+
+        let mut read_back = vec![];
+        for record in records {
+            read_back.extend_from_slice(ao.get_record(record)?);
+        }
+        assert_eq!(read_back, data);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn appendonly_compact() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        let msgs: Vec<Vec<u8>> =
+            (0..256u32).map(|i| i.to_le_bytes().to_vec()).collect();
+
+        let mut records = vec![];
+        let relocations;
+
+        {
+            let lf = Landfill::open(path)?;
+            let mut ao: AppendOnly = lf.substructure("ao")?;
+
+            for msg in &msgs {
+                records.push((ao.write(msg)?, msg.len() as u32));
+            }
+
+            // keep every other record
+            let live: Vec<u64> =
+                records.iter().step_by(2).map(|(ofs, _)| *ofs).collect();
+
+            relocations = ao.compact(records.iter().copied(), |ofs, _| {
+                live.contains(&ofs)
+            })?;
+
+            assert_eq!(relocations.len(), msgs.len() / 2);
+        }
+
+        let lf = Landfill::open(path)?;
+        let ao: AppendOnly = lf.substructure("ao")?;
+
+        for (i, (ofs, len)) in records.iter().enumerate() {
+            match relocations.get(*ofs) {
+                Some(new_ofs) => assert_eq!(ao.get(new_ofs, *len), &msgs[i]),
+                None => assert!(i % 2 == 1),
+            }
+        }
+
+        Ok(())
+    })
+}
+
+#[test]
+fn appendonly_compact_keeps_siblings() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        let ofs;
+        {
+            let lf = Landfill::open(path)?;
+            let mut ao: AppendOnly = lf.substructure("ao")?;
+            let sibling: AppendOnly = lf.substructure("ao_bytes_x")?;
+
+            let old = ao.write(b"old")?;
+            ofs = sibling.write(b"sibling")?;
+            sibling.flush()?;
+
+            ao.compact([(old, 3)], |_, _| true)?;
+        }
+
+        let lf = Landfill::open(path)?;
+        let sibling: AppendOnly = lf.substructure("ao_bytes_x")?;
+        assert_eq!(sibling.get(ofs, 7), b"sibling");
+
+        Ok(())
+    })
+}
+
+#[test]
+fn appendonly_legacy_journal() -> Result<(), std::io::Error> {
+    use std::hash::{Hash, Hasher};
+
+    with_temp_path(|path| {
+        // a store written before journal entries carried sequence numbers
+        let mut lane = vec![0; 4096];
+        lane[..5].copy_from_slice(b"hello");
+        std::fs::write(path.join("ao_bytes_00"), lane)?;
+
+        let mut hasher = seahash::SeaHasher::new();
+        5u64.hash(&mut hasher);
+        let mut journal = vec![0; 4096];
+        journal[16..24].copy_from_slice(&hasher.finish().to_ne_bytes());
+        journal[24..32].copy_from_slice(&5u64.to_ne_bytes());
+        std::fs::write(path.join("ao_journal"), journal)?;
+
+        let lf = Landfill::open(path)?;
+        let ao: AppendOnly = lf.substructure("ao")?;
+        assert_eq!(ao.get(0, 5), b"hello");
+        assert!(ao.write(b"world")? >= 5);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn appendonly_try_get() -> Result<(), std::io::Error> {
+    let lf = Landfill::ephemeral()?;
+    let ao: AppendOnly = lf.substructure("ao")?;
+
+    let ofs = ao.write(b"hello")?;
+
+    assert_eq!(ao.try_get(ofs, 5)?, b"hello");
+    assert!(ao.try_get(ofs, 6).is_err());
+    assert!(ao.try_get(ofs + 1_000_000, 5).is_err());
+    assert!(ao.try_get(u64::MAX, 5).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn appendonly_cold_reads() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i / 7) as u8).collect();
+        let mut records = vec![];
+
+        {
+            let lf = Landfill::open(path)?;
+            let ao: AppendOnly = lf.substructure("ao")?;
+            for chunk in data.chunks(3000) {
+                records.push((ao.write(chunk)?, chunk.len() as u32));
+            }
+            // mapped lanes are read from the mapping
+            let (ofs, len) = records[0];
+            assert_eq!(ao.get_cold(ofs, len)?, &data[..len as usize]);
+        }
+
+        let lf = Landfill::open(path)?;
+        let ao: AppendOnly = lf.substructure("ao")?;
+
+        for (i, (ofs, len)) in records.iter().enumerate().rev() {
+            let expected = &data[i * 3000..][..*len as usize];
+            assert_eq!(ao.get_cold(*ofs, *len)?, expected);
+            assert_eq!(ao.get(*ofs, *len), expected);
+        }
+
+        assert!(ao.get_cold(1_000_000, 10).is_err());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn appendonly_close() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i / 7) as u8).collect();
+        let mut records = vec![];
+
+        {
+            let lf = Landfill::open(path)?;
+            let ao: AppendOnly = lf.substructure("ao")?;
+            for chunk in data.chunks(3000) {
+                records.push((ao.write(chunk)?, chunk.len() as u32));
+            }
+            let before = ao.size_on_disk();
+            ao.close()?;
+            assert!(lf.size_on_disk() < before);
+        }
+
+        let lf = Landfill::open(path)?;
+        let ao: AppendOnly = lf.substructure("ao")?;
+
+        for (i, (ofs, len)) in records.iter().enumerate() {
+            let expected = &data[i * 3000..][..*len as usize];
+            assert_eq!(ao.get_cold(*ofs, *len)?, expected);
+            assert_eq!(ao.get(*ofs, *len), expected);
+        }
+
+        // the store keeps growing after being closed
+        let ofs = ao.write(b"more")?;
+        assert_eq!(ao.get(ofs, 4), b"more");
+
+        Ok(())
+    })
+}
+
+#[test]
+fn appendonly_group_commit() -> Result<(), std::io::Error> {
+    const THREADS: usize = 8;
+    const WRITES: usize = 16;
+
+    with_temp_path(|path| {
+        let lf = Landfill::open(path)?;
+        let ao: AppendOnly = lf.substructure("ao")?;
+        ao.set_commit_window(std::time::Duration::from_millis(2));
+
+        let written = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|t| {
+                    let ao = &ao;
+                    s.spawn(move || {
+                        (0..WRITES)
+                            .map(|i| {
+                                let bytes = [t as u8, i as u8];
+                                Ok((ao.write_durable(&bytes)?, bytes))
+                            })
+                            .collect::<std::io::Result<Vec<_>>>()
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<std::io::Result<Vec<_>>>()
+        })?;
+
+        // writers arriving during a flush share the next one
+        assert!(ao.syncs() > 0);
+        assert!(ao.syncs() < (THREADS * WRITES) as u64);
+
+        for (offset, bytes) in written.into_iter().flatten() {
+            assert_eq!(ao.get(offset, 2), bytes);
+        }
+
+        Ok(())
+    })
+}
+
+#[test]
+fn appendonly_prefetch() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        let mut offsets = vec![];
+        {
+            let lf = Landfill::open(path)?;
+            let ao: AppendOnly = lf.substructure("ao")?;
+            for i in 0..10_000u32 {
+                offsets.push(ao.write(&i.to_le_bytes().repeat(16))?);
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let ao: AppendOnly = lf.substructure("ao")?;
+
+        // ranges past the written data are ignored
+        ao.prefetch(0, 1 << 40)?;
+        ao.prefetch(1 << 40, 10)?;
+
+        // sequential reads prefetch ahead of themselves
+        for (i, offset) in offsets.iter().enumerate() {
+            let expected = (i as u32).to_le_bytes().repeat(16);
+            assert_eq!(ao.get(*offset, 64), expected);
+        }
+
+        Ok(())
+    })
+}
+
+#[test]
+fn appendonly_on_write() -> Result<(), std::io::Error> {
+    use std::sync::{Arc, Mutex};
+
+    let lf = Landfill::ephemeral()?;
+    let writes = Arc::new(Mutex::new(vec![]));
+
+    let log = writes.clone();
+    lf.on_write(move |branch, offset, len| {
+        log.lock().unwrap().push((branch.to_owned(), offset, len))
+    });
+
+    let ao: AppendOnly = lf.substructure("ao")?;
+    writes.lock().unwrap().clear();
+
+    let a = ao.write(b"hello")?;
+    let b = ao.write(b"world!")?;
+
+    let writes = writes.lock().unwrap();
+    let data: Vec<_> = writes
+        .iter()
+        .filter(|(branch, ..)| branch == "ao_bytes")
+        .collect();
+
+    assert_eq!(data.len(), 2);
+    assert_eq!(data[0].1, a);
+    assert_eq!(data[0].2, 5);
+    assert_eq!(data[1].1, b);
+    assert_eq!(data[1].2, 6);
+
+    Ok(())
+}
+
+#[test]
+fn appendonly_dirty_limit() -> Result<(), std::io::Error> {
+    let lf = Landfill::ephemeral()?;
+    let ao: AppendOnly = lf.substructure("ao")?;
+    ao.set_dirty_limit(Some(1000));
+
+    for _ in 0..10 {
+        ao.write(&[1; 100])?;
+    }
+    assert_eq!(ao.dirty_bytes(), 1000);
+    assert_eq!(ao.syncs(), 0);
+
+    let err = ao.try_write(&[2; 100]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+
+    // a plain write flushes first
+    let ofs = ao.write(&[2; 100])?;
+    assert_eq!(ao.syncs(), 1);
+    assert_eq!(ao.dirty_bytes(), 100);
+    assert_eq!(ao.get(ofs, 100), &[2; 100]);
+
+    ao.set_dirty_limit(None);
+    for _ in 0..20 {
+        ao.try_write(&[3; 100])?;
+    }
+    assert_eq!(ao.syncs(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn appendonly_with_config() -> Result<(), std::io::Error> {
+    use landfill::AppendOnlyConfig;
+
+    with_temp_path(|path| {
+        let config = AppendOnlyConfig {
+            commit_window_micros: 0,
+            dirty_limit: 1000,
+        };
+
+        {
+            let lf = Landfill::open(path)?;
+            let ao: AppendOnly = lf.substructure_with_config("ao", config)?;
+            for _ in 0..11 {
+                ao.write(&[1; 100])?;
+            }
+            assert_eq!(ao.syncs(), 1);
+            assert_eq!(lf.stored_config::<AppendOnlyConfig>()?, None);
+        }
+
+        // the stored config wins over the one passed on reopen
+        {
+            let lf = Landfill::open(path)?;
+            let ao: AppendOnly =
+                lf.substructure_with_config("ao", AppendOnlyConfig::default())?;
+            for _ in 0..11 {
+                ao.write(&[1; 100])?;
+            }
+            assert_eq!(ao.syncs(), 1);
+        }
+
+        // and is used when opened without one
+        let lf = Landfill::open(path)?;
+        let ao: AppendOnly = lf.substructure("ao")?;
+        for _ in 0..11 {
+            ao.write(&[1; 100])?;
+        }
+        assert_eq!(ao.syncs(), 1);
+
+        Ok(())
+    })
+}
@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use landfill::{AtomicArray, Landfill};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+#[test]
+fn atomic_array_counters() -> Result<(), std::io::Error> {
+    const N_THREADS: usize = 8;
+    const ADDS_PER_THREAD: usize = 10_000;
+
+    let lf = Landfill::ephemeral()?;
+    let counters: Arc<AtomicArray<u64>> =
+        Arc::new(lf.substructure("counters")?);
+
+    let threads: Vec<_> = (0..N_THREADS)
+        .map(|_| {
+            let counters = counters.clone();
+            std::thread::spawn(move || {
+                for i in 0..ADDS_PER_THREAD {
+                    counters.fetch_add(i % 4, 1).unwrap();
+                }
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        thread.join().unwrap()
+    }
+
+    for i in 0..4 {
+        assert_eq!(counters.load(i), (N_THREADS * ADDS_PER_THREAD / 4) as u64);
+    }
+    assert_eq!(counters.load(1_000_000), 0);
+
+    Ok(())
+}
+
+#[test]
+fn atomic_array_persist_restore() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let cells: AtomicArray<u32> = lf.substructure("cells")?;
+            cells.store(5000, 17)?;
+            assert_eq!(cells.fetch_add(5000, 3)?, 17);
+        }
+
+        let lf = Landfill::open(path)?;
+        let cells: AtomicArray<u32> = lf.substructure("cells")?;
+        assert_eq!(cells.load(5000), 20);
+
+        Ok(())
+    })
+}
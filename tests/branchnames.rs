@@ -0,0 +1,43 @@
+use std::io::ErrorKind;
+
+use landfill::{AppendOnly, Landfill};
+
+#[test]
+fn dangerous_names_rejected() -> Result<(), std::io::Error> {
+    let lf = Landfill::ephemeral()?;
+
+    for name in ["", ".", "..", "../up", "a/b", "a\\b", "nul\0", "_lock"] {
+        let err = lf.substructure::<AppendOnly, _>(name).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput, "{name:?}");
+    }
+
+    // underscores and dots within names are fine
+    let _ao: AppendOnly = lf.substructure("a_b.c")?;
+
+    Ok(())
+}
+
+#[test]
+fn escaped_names_accepted() -> Result<(), std::io::Error> {
+    let dir = tempfile::tempdir()?;
+    let lf = Landfill::open(dir.path())?;
+
+    let names = ["", "..", "../up", "a/b", "_lock", "100%", "line\n"];
+    let escaped: Vec<_> =
+        names.iter().map(|n| Landfill::escape_name(n)).collect();
+    assert_eq!(escaped[3], "a%2fb");
+    assert_eq!(escaped[5], "100%25");
+
+    for (i, name) in escaped.iter().enumerate() {
+        let ao: AppendOnly = lf.substructure(name.as_str())?;
+        ao.write(&[i as u8])?;
+    }
+
+    // everything stays inside the landfill directory
+    for entry in std::fs::read_dir(dir.path())? {
+        assert!(entry?.file_type()?.is_file());
+    }
+    assert!(!dir.path().join("..").join("up_00").exists());
+
+    Ok(())
+}
@@ -1,7 +1,12 @@
+use std::fs;
 use std::io;
 
 use blake3::Hasher;
-use landfill::{Content, Landfill};
+use landfill::{Content, ContentId, Landfill, Substructure};
+use sha2::{Digest, Sha512};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
 
 const A_LOT: u64 = 1024;
 
@@ -22,3 +27,472 @@ fn lots_of_content() -> io::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn contains() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let content: Content<Hasher> = lf.substructure("content")?;
+
+    let mut ids = vec![];
+
+    for i in 0u64..A_LOT {
+        ids.push(content.insert(&i.to_le_bytes())?);
+    }
+
+    for id in &ids {
+        assert!(content.contains(*id));
+    }
+
+    let other: Content<Hasher> = lf.substructure("other")?;
+    let missing = other.insert(b"never stored in content")?;
+    assert!(!content.contains(missing));
+
+    Ok(())
+}
+
+#[test]
+fn insert_streaming() -> io::Result<()> {
+    use std::io::Write;
+
+    let lf = Landfill::ephemeral()?;
+    let content: Content<Hasher> = lf.substructure("content")?;
+
+    // large enough to outgrow the initial reservation several times
+    let blob: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+
+    let mut writer = content.insert_streaming();
+    for chunk in blob.chunks(1000) {
+        writer.write_all(chunk)?;
+    }
+    let id = writer.finish()?;
+
+    assert_eq!(content.get(id).unwrap(), &blob[..]);
+    assert!(content.insert(&blob)? == id);
+
+    // interleaved with regular inserts, forcing relocation
+    let mut writer = content.insert_streaming();
+    let mut small = vec![];
+    for (i, chunk) in blob.chunks(100 * 1024).enumerate() {
+        writer.write_all(chunk)?;
+        small.push(content.insert(&i.to_le_bytes())?);
+    }
+    writer.write_all(b"!")?;
+    let id = writer.finish()?;
+
+    let mut expected = blob.clone();
+    expected.push(b'!');
+    assert_eq!(content.get(id).unwrap(), &expected[..]);
+
+    for (i, id) in small.iter().enumerate() {
+        assert_eq!(content.get(*id).unwrap(), i.to_le_bytes());
+    }
+
+    let empty = content.insert_streaming().finish()?;
+    assert_eq!(content.get(empty).unwrap(), b"");
+
+    Ok(())
+}
+
+#[test]
+fn size() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let content: Content<Hasher> = lf.substructure("content")?;
+
+    let mut ids = vec![];
+
+    for i in 0..A_LOT as usize {
+        ids.push(content.insert(&vec![7u8; i])?);
+    }
+
+    for (i, id) in ids.iter().enumerate() {
+        assert_eq!(content.size(*id), Some(i as u32));
+    }
+
+    let other: Content<Hasher> = lf.substructure("other")?;
+    let missing = other.insert(b"never stored in content")?;
+    assert_eq!(content.size(missing), None);
+
+    Ok(())
+}
+
+#[test]
+fn refcounts() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let content: Content<Hasher> = lf.substructure("content")?;
+
+    let kept = content.insert(b"kept")?;
+    let dropped = content.insert(b"dropped")?;
+    let untracked = content.insert(b"untracked")?;
+
+    content.acquire(kept)?;
+    content.acquire(kept)?;
+    content.acquire(dropped)?;
+
+    content.release(kept)?;
+    content.release(dropped)?;
+    assert!(content.release(dropped).is_err());
+
+    assert_eq!(content.collect()?, 1);
+
+    assert!(content.contains(kept));
+    assert!(content.contains(untracked));
+    assert!(!content.contains(dropped));
+    assert!(content.get(dropped).is_none());
+    assert!(content.acquire(dropped).is_err());
+
+    // inserting collected content revives it
+    assert!(content.insert(b"dropped")? == dropped);
+    assert_eq!(content.get(dropped).unwrap(), b"dropped");
+
+    assert_eq!(content.collect()?, 0);
+
+    Ok(())
+}
+
+#[test]
+fn compact() -> io::Result<()> {
+    with_temp_path(|path| {
+        let blob = vec![7u8; 64 * 1024];
+        let kept = {
+            let lf = Landfill::open(path)?;
+            let mut content: Content<Hasher> = lf.substructure("content")?;
+
+            let kept = content.insert(b"kept")?;
+            content.acquire(kept)?;
+            for i in 0u64..64 {
+                let mut bytes = blob.clone();
+                bytes[..8].copy_from_slice(&i.to_le_bytes());
+                let id = content.insert(&bytes)?;
+                content.acquire(id)?;
+                content.release(id)?;
+            }
+            assert_eq!(content.collect()?, 64);
+
+            let before = content.size_on_disk();
+            assert_eq!(content.compact()?, 64);
+            assert!(content.size_on_disk() < before / 4);
+            assert_eq!(content.dedup_stats().physical_bytes, 4);
+            kept
+        };
+
+        let lf = Landfill::open(path)?;
+        let content: Content<Hasher> = lf.substructure("content")?;
+        assert_eq!(content.get(kept).unwrap(), b"kept");
+
+        // reference counts survive compaction
+        content.release(kept)?;
+        assert!(content.release(kept).is_err());
+        assert_eq!(content.collect()?, 1);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn verify() -> io::Result<()> {
+    with_temp_path(|path| {
+        let corrupted = {
+            let lf = Landfill::open(path)?;
+            let content: Content<Hasher> = lf.substructure("content")?;
+
+            for i in 0u64..A_LOT {
+                content.insert(&i.to_le_bytes())?;
+            }
+            assert!(content.corrupted().is_empty());
+
+            content.insert(b"corrupt me")?
+        };
+
+        // flip the stored bytes directly on disk
+        for file in fs::read_dir(path)? {
+            let file = file?.path();
+            let name = file.file_name().unwrap().to_string_lossy().to_string();
+            if name.starts_with("content_data") {
+                let mut bytes = fs::read(&file)?;
+                if let Some(pos) =
+                    bytes.windows(10).position(|window| window == b"corrupt me")
+                {
+                    bytes[pos] ^= 0xff;
+                    fs::write(&file, bytes)?;
+                }
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let content: Content<Hasher> = lf.substructure("content")?;
+
+        let failed = content.corrupted();
+        assert_eq!(failed.len(), 1);
+        assert!(failed[0] == corrupted);
+
+        // also reported through the generic check
+        let report = content.verify()?;
+        assert!(report.checked > A_LOT);
+        assert_eq!(report.problems.len(), 1);
+        assert!(report.problems[0].contains(&corrupted.to_string()));
+
+        // and through the landfill, from a read-only view of the files
+        let report = lf.verify()?;
+        assert_eq!(report.problems.len(), 1);
+        assert!(report.problems[0].starts_with("content: "));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn sync_from() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let local: Content<Hasher> = lf.substructure("local")?;
+    let remote: Content<Hasher> = lf.substructure("remote")?;
+
+    for i in 0u64..A_LOT {
+        remote.insert(&i.to_le_bytes())?;
+        if i % 2 == 0 {
+            local.insert(&i.to_le_bytes())?;
+        }
+    }
+
+    let missing = local.missing_from(&remote);
+    assert_eq!(missing.len(), A_LOT as usize / 2);
+
+    assert_eq!(local.sync_from(&remote)?, A_LOT as usize / 2);
+
+    for id in missing {
+        assert!(local.get(id) == remote.get(id));
+    }
+    assert!(local.missing_from(&remote).is_empty());
+    assert_eq!(local.sync_from(&remote)?, 0);
+
+    Ok(())
+}
+
+#[test]
+fn other_digest_widths() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let wide: Content<Sha512, 64> = lf.substructure("wide")?;
+    let truncated: Content<Sha512, 20> = lf.substructure("truncated")?;
+
+    for i in 0u64..A_LOT {
+        let wide_id = wide.insert(&i.to_le_bytes())?;
+        let truncated_id = truncated.insert(&i.to_le_bytes())?;
+
+        assert_eq!(wide_id.as_bytes()[..], Sha512::digest(i.to_le_bytes())[..]);
+        assert_eq!(wide_id.as_bytes()[..20], truncated_id.as_bytes()[..]);
+    }
+
+    for i in 0u64..A_LOT {
+        let id = truncated.insert(&i.to_le_bytes())?;
+        assert_eq!(truncated.get(id).unwrap(), i.to_le_bytes());
+    }
+
+    let too_wide: io::Result<Content<Hasher, 33>> = lf.substructure("too_wide");
+    assert!(too_wide.is_err());
+
+    Ok(())
+}
+
+#[test]
+fn dedup_stats() -> io::Result<()> {
+    use std::io::Write;
+
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let content: Content<Hasher> = lf.substructure("content")?;
+
+            for i in 0u64..A_LOT {
+                content.insert(&i.to_le_bytes())?;
+                content.insert(&i.to_le_bytes())?;
+            }
+
+            let mut writer = content.insert_streaming();
+            writer.write_all(&0u64.to_le_bytes())?;
+            writer.finish()?;
+        }
+
+        let lf = Landfill::open(path)?;
+        let content: Content<Hasher> = lf.substructure("content")?;
+
+        let stats = content.dedup_stats();
+        assert_eq!(stats.written, A_LOT);
+        assert_eq!(stats.deduplicated, A_LOT + 1);
+        assert_eq!(stats.physical_bytes, A_LOT * 8);
+        assert_eq!(stats.logical_bytes, (A_LOT * 2 + 1) * 8);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn content_id_hex() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let content: Content<Hasher> = lf.substructure("content")?;
+
+    let id = content.insert(b"hello")?;
+    let hex = id.to_string();
+
+    assert_eq!(hex, blake3::hash(b"hello").to_hex().as_str());
+    assert_eq!(hex.parse::<ContentId>()?, id);
+    assert_eq!(ContentId::<32>::from_hex(&hex)?.as_ref(), id.as_bytes());
+
+    assert!(ContentId::<32>::from_hex("abc").is_err());
+    assert!(ContentId::<2>::from_hex("zzzz").is_err());
+    assert!(ContentId::<2>::from_hex("ab\u{e9}").is_err());
+    assert!(ContentId::<2>::from_hex("+a+b").is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn content_id_serde() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let content: Content<Hasher> = lf.substructure("content")?;
+
+    let id = content.insert(b"hello")?;
+    let encoded = bincode::serialize(&id).unwrap();
+    let decoded: ContentId = bincode::deserialize(&encoded).unwrap();
+    assert_eq!(decoded, id);
+
+    Ok(())
+}
+
+#[test]
+fn prefetch() -> io::Result<()> {
+    with_temp_path(|path| {
+        let mut ids = vec![];
+        {
+            let lf = Landfill::open(path)?;
+            let content: Content<Hasher> = lf.substructure("content")?;
+            for i in 0..A_LOT {
+                ids.push(content.insert(&i.to_le_bytes().repeat(100))?);
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let content: Content<Hasher> = lf.substructure("content")?;
+        content.prefetch(ids[0])?;
+
+        for (i, id) in ids.iter().enumerate() {
+            let expected = (i as u64).to_le_bytes().repeat(100);
+            assert_eq!(content.get(*id), Some(&expected[..]));
+        }
+
+        let other: Content<Hasher> = lf.substructure("other")?;
+        content.prefetch(other.insert(b"not in content")?)?;
+
+        Ok(())
+    })
+}
+
+#[test]
+fn index_format() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let content: Content<Sha512, 20> = lf.substructure("content")?;
+            content.insert(b"hello")?;
+        }
+
+        // entries are as wide as the ids
+        let lf = Landfill::open(path)?;
+        let wider: io::Result<Content<Sha512, 32>> = lf.substructure("content");
+        assert_eq!(wider.err().unwrap().kind(), io::ErrorKind::InvalidData);
+
+        Ok(())
+    })
+}
+
+// A journal entry as written before sequence numbers: checksum, then value
+fn legacy_journal_entry(value: u64) -> [u8; 16] {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = seahash::SeaHasher::new();
+    value.hash(&mut hasher);
+
+    let mut entry = [0; 16];
+    entry[..8].copy_from_slice(&hasher.finish().to_ne_bytes());
+    entry[8..].copy_from_slice(&value.to_ne_bytes());
+    entry
+}
+
+#[test]
+fn baseline_layout_migrated() -> io::Result<()> {
+    with_temp_path(|path| {
+        // a store written before format headers, its data holding "hello"
+        // at 0 and "world!" at 8
+        let mut lane = vec![0; 4096];
+        lane[..5].copy_from_slice(b"hello");
+        lane[8..14].copy_from_slice(b"world!");
+        fs::write(path.join("content_data_bytes_00"), lane)?;
+
+        let mut journal = vec![0; 4096];
+        journal[..16].copy_from_slice(&legacy_journal_entry(14));
+        fs::write(path.join("content_data_journal"), journal)?;
+
+        // indexed by `{ofs, len, tag}` entries in arbitrary slots
+        let mut slots = vec![0; 4096];
+        for (slot, ofs, len) in [(3, 0u64, 5u32), (200, 8, 6)] {
+            let entry = &mut slots[slot * 16..][..16];
+            entry[..8].copy_from_slice(&ofs.to_ne_bytes());
+            entry[8..12].copy_from_slice(&len.to_ne_bytes());
+            entry[12..].copy_from_slice(&0xdead_beefu32.to_ne_bytes());
+        }
+        fs::write(path.join("content_index_slots_array_00"), slots)?;
+
+        let ids = {
+            let lf = Landfill::ephemeral()?;
+            let content: Content<Hasher> = lf.substructure("content")?;
+            [content.insert(b"hello")?, content.insert(b"world!")?]
+        };
+
+        let more = {
+            let lf = Landfill::open(path)?;
+            let content: Content<Hasher> = lf.substructure("content")?;
+            assert_eq!(content.get(ids[0]), Some(&b"hello"[..]));
+            assert_eq!(content.get(ids[1]), Some(&b"world!"[..]));
+            assert_eq!(content.dedup_stats().physical_bytes, 11);
+            assert!(lf.verify()?.is_ok());
+
+            content.insert(b"more")?
+        };
+
+        // the legacy index is gone, and the store opens as migrated
+        assert!(!path.join("content_index_slots_array_00").exists());
+        let lf = Landfill::open(path)?;
+        let content: Content<Hasher> = lf.substructure("content")?;
+        assert_eq!(content.get(ids[1]), Some(&b"world!"[..]));
+        assert!(content.contains(more));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn compact_keeps_alignment() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let mut content: Content<Hasher> = lf.substructure("content")?;
+
+    let dropped = content.insert(b"dropped")?;
+    content.acquire(dropped)?;
+    content.release(dropped)?;
+    let aligned: Vec<_> = [64, 4096]
+        .into_iter()
+        .map(|alignment| {
+            let bytes = vec![alignment as u8; 100];
+            Ok((content.insert_aligned(&bytes, alignment)?, alignment))
+        })
+        .collect::<io::Result<_>>()?;
+
+    assert_eq!(content.collect()?, 1);
+    assert_eq!(content.compact()?, 1);
+
+    for (id, alignment) in aligned {
+        let stored = content.get(id).unwrap();
+        assert_eq!(stored, &vec![alignment as u8; 100][..]);
+        assert_eq!(stored.as_ptr() as usize % alignment, 0);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use std::io;
+use std::sync::Arc;
+
+use landfill::{DiskVec, Landfill};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+const A_LOT: usize = 1024 * 16;
+
+#[test]
+fn push_and_get() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let vec: DiskVec<u64> = lf.substructure("vec")?;
+
+            for i in 0..A_LOT {
+                // zero is a valid element
+                assert_eq!(vec.push(i as u64)?, i);
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let vec: DiskVec<u64> = lf.substructure("vec")?;
+
+        assert_eq!(vec.len(), A_LOT);
+        for i in 0..A_LOT {
+            assert_eq!(*vec.get(i).unwrap(), i as u64);
+        }
+        assert!(vec.get(A_LOT).is_none());
+        assert!(vec.iter().eq(0..A_LOT as u64));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn set_and_pop() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let vec: DiskVec<u32> = lf.substructure("vec")?;
+
+    assert!(vec.is_empty());
+    assert_eq!(vec.pop(), None);
+    assert!(vec.set(0, 1).is_err());
+
+    vec.push(1)?;
+    vec.push(2)?;
+    vec.set(0, 10)?;
+    *vec.get_mut(1)? += 10;
+
+    assert_eq!(vec.iter().collect::<Vec<_>>(), [10, 12]);
+    assert_eq!(vec.pop(), Some(12));
+    assert_eq!(vec.len(), 1);
+    assert!(vec.get(1).is_none());
+
+    vec.push(3)?;
+    assert_eq!(vec.iter().collect::<Vec<_>>(), [10, 3]);
+
+    Ok(())
+}
+
+#[test]
+fn concurrent_push() -> io::Result<()> {
+    const N_THREADS: usize = 8;
+
+    let lf = Landfill::ephemeral()?;
+    let vec: Arc<DiskVec<u64>> = Arc::new(lf.substructure("vec")?);
+
+    let threads: Vec<_> = (0..N_THREADS)
+        .map(|_| {
+            let vec = vec.clone();
+            std::thread::spawn(move || {
+                for i in 0..A_LOT / N_THREADS {
+                    vec.push(i as u64 + 1).unwrap();
+                }
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        thread.join().unwrap()
+    }
+
+    assert_eq!(vec.len(), A_LOT);
+    assert_eq!(
+        vec.iter().sum::<u64>(),
+        N_THREADS as u64 * (1..=(A_LOT / N_THREADS) as u64).sum::<u64>()
+    );
+
+    Ok(())
+}
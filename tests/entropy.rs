@@ -0,0 +1,69 @@
+use landfill::{Entropy, Landfill};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+#[test]
+fn entropy_from_seed() -> Result<(), std::io::Error> {
+    let a: Entropy =
+        Landfill::ephemeral_with_seed([7; 32])?.substructure("e")?;
+    let b: Entropy =
+        Landfill::ephemeral_with_seed([7; 32])?.substructure("e")?;
+    let c: Entropy =
+        Landfill::ephemeral_with_seed([8; 32])?.substructure("e")?;
+
+    assert_eq!(a.checksum(&"hello"), b.checksum(&"hello"));
+    assert_ne!(a.checksum(&"hello"), c.checksum(&"hello"));
+
+    Ok(())
+}
+
+#[test]
+fn entropy_per_branch() -> Result<(), std::io::Error> {
+    let lf = Landfill::ephemeral_with_seed([7; 32])?;
+    let a: Entropy = lf.substructure("a")?;
+    let b: Entropy = lf.substructure("b")?;
+
+    assert_ne!(a.tag(), b.tag());
+
+    Ok(())
+}
+
+#[test]
+fn entropy_seed_keeps_existing() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        let tag = {
+            let lf = Landfill::open(path)?;
+            let e: Entropy = lf.substructure("e")?;
+            e.tag()
+        };
+
+        let lf = Landfill::open_with_seed(path, [1; 32])?;
+        let e: Entropy = lf.substructure("e")?;
+        assert_eq!(e.tag(), tag);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn entropy_single_root_file() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        let tags = {
+            let lf = Landfill::open(path)?;
+            let a: Entropy = lf.substructure("a")?;
+            let b: Entropy = lf.substructure("b")?;
+            (a.tag(), b.tag())
+        };
+
+        // only the root seed file is left after closing
+        assert_eq!(std::fs::read_dir(path)?.count(), 1);
+
+        let lf = Landfill::open(path)?;
+        let a: Entropy = lf.substructure("a")?;
+        let b: Entropy = lf.substructure("b")?;
+        assert_eq!((a.tag(), b.tag()), tags);
+
+        Ok(())
+    })
+}
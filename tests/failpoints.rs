@@ -0,0 +1,73 @@
+#![cfg(feature = "failpoints")]
+
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+
+use landfill::{AppendLog, AppendOnly, FailAction, Failpoint, Landfill};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+#[test]
+fn failpoint_data_written() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let log: AppendLog<u64> = lf.substructure("log")?;
+    log.push(1)?;
+
+    lf.set_failpoint(Failpoint::DataWritten, FailAction::Error);
+    assert!(log.push(2).is_err());
+    assert_eq!(log.len(), 1);
+
+    lf.clear_failpoint(Failpoint::DataWritten);
+    assert_eq!(log.push(3)?, 1);
+    assert_eq!(log.get(1), Some(&3));
+
+    Ok(())
+}
+
+#[test]
+fn failpoint_crash_before_journal_update() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let log: AppendLog<u64> = lf.substructure("log")?;
+            log.push(1)?;
+            log.push(2)?;
+
+            lf.set_failpoint(Failpoint::DataWritten, FailAction::Panic);
+            let crashed = panic::catch_unwind(AssertUnwindSafe(|| log.push(3)));
+            assert!(crashed.is_err());
+        }
+
+        // the value written without its journal update is not visible
+        let lf = Landfill::open(path)?;
+        let log: AppendLog<u64> = lf.substructure("log")?;
+        assert_eq!(log.len(), 2);
+        assert_eq!(log.push(4)?, 2);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn failpoint_journal_flush_and_lane_creation() -> io::Result<()> {
+    with_temp_path(|path| {
+        let lf = Landfill::open(path)?;
+        let ao: AppendOnly = lf.substructure("ao")?;
+
+        lf.set_failpoint(Failpoint::LaneCreation, FailAction::Error);
+        assert!(ao.write(b"no lane yet").is_err());
+        lf.clear_failpoint(Failpoint::LaneCreation);
+
+        let offset = ao.write(b"lane created")?;
+
+        lf.set_failpoint(Failpoint::JournalFlush, FailAction::Error);
+        assert!(ao.sync().is_err());
+        lf.clear_failpoint(Failpoint::JournalFlush);
+        ao.sync()?;
+
+        assert_eq!(ao.get(offset, 12), b"lane created");
+
+        Ok(())
+    })
+}
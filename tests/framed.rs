@@ -0,0 +1,179 @@
+use std::io;
+
+use landfill::{FramedAppendOnly, Landfill, Substructure};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+#[test]
+fn framed_iterate() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let framed: FramedAppendOnly = lf.substructure("framed")?;
+
+    let msgs: Vec<Vec<u8>> = (0..2048u32)
+        .map(|i| i.to_le_bytes().repeat(i as usize % 13))
+        .collect();
+
+    let mut offsets = vec![];
+    for msg in &msgs {
+        offsets.push(framed.write(msg)?);
+    }
+
+    for (msg, ofs) in msgs.iter().zip(&offsets) {
+        assert_eq!(framed.get(*ofs).unwrap(), &msg[..]);
+    }
+
+    let iterated: Vec<_> = framed.iter().collect();
+    assert_eq!(iterated.len(), msgs.len());
+
+    for ((ofs, payload), (msg, expected_ofs)) in
+        iterated.into_iter().zip(msgs.iter().zip(&offsets))
+    {
+        assert_eq!(ofs, *expected_ofs);
+        assert_eq!(payload, &msg[..]);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn framed_lane_boundaries() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let framed: FramedAppendOnly = lf.substructure("framed")?;
+
+    // records large enough to regularly skip to the next lane
+    let big = vec![0xaa; 3000];
+    let huge = vec![0xbb; 10_000];
+
+    framed.write(&big)?;
+    framed.write(&big)?;
+    framed.write(&huge)?;
+    framed.write(&big)?;
+
+    let lens: Vec<_> = framed.iter().map(|(_, p)| p.len()).collect();
+    assert_eq!(lens, vec![3000, 3000, 10_000, 3000]);
+
+    Ok(())
+}
+
+#[test]
+fn framed_reopen() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let framed: FramedAppendOnly = lf.substructure("framed")?;
+            framed.write(b"first")?;
+            framed.write(b"second")?;
+        }
+
+        let lf = Landfill::open(path)?;
+        let framed: FramedAppendOnly = lf.substructure("framed")?;
+        framed.write(b"third")?;
+
+        let payloads: Vec<_> = framed.iter().map(|(_, p)| p).collect();
+        assert_eq!(payloads, vec![&b"first"[..], b"second", b"third"]);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn framed_close() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let framed: FramedAppendOnly = lf.substructure("framed")?;
+            framed.write(b"first")?;
+            framed.write(b"second")?;
+            framed.close()?;
+        }
+
+        {
+            let lf = Landfill::open(path)?;
+            let framed: FramedAppendOnly = lf.substructure("framed")?;
+            framed.write(b"third")?;
+        }
+
+        let lf = Landfill::open(path)?;
+        let framed: FramedAppendOnly = lf.substructure("framed")?;
+        framed.write(b"fourth")?;
+
+        let payloads: Vec<_> = framed.iter().map(|(_, p)| p).collect();
+        assert_eq!(
+            payloads,
+            vec![&b"first"[..], b"second", b"third", b"fourth"]
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn framed_try_get() -> io::Result<()> {
+    with_temp_path(|path| {
+        let lf = Landfill::open(path)?;
+        let framed: FramedAppendOnly = lf.substructure("framed")?;
+        let a = framed.write(b"first")?;
+        let b = framed.write(b"second")?;
+
+        assert_eq!(framed.try_get(a)?, b"first");
+        let err = framed.try_get(b + 64).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        // flip a byte of the second payload
+        let file = path.join("framed_data_bytes_00");
+        let mut bytes = std::fs::read(&file)?;
+        bytes[b as usize + 16] ^= 0xff;
+        std::fs::write(&file, bytes)?;
+
+        assert_eq!(framed.get(b), None);
+        let err = framed.try_get(b).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(framed.try_get(a)?, b"first");
+
+        Ok(())
+    })
+}
+
+#[test]
+fn framed_iterate_past_corruption() -> io::Result<()> {
+    with_temp_path(|path| {
+        let lf = Landfill::open(path)?;
+        let framed: FramedAppendOnly = lf.substructure("framed")?;
+        let offsets = [
+            framed.write(b"first")?,
+            framed.write(b"second")?,
+            framed.write(b"third")?,
+        ];
+
+        // scramble the length in the header of the second frame
+        let file = path.join("framed_data_bytes_00");
+        let mut bytes = std::fs::read(&file)?;
+        bytes[offsets[1] as usize + 4] ^= 0xff;
+        std::fs::write(&file, bytes)?;
+
+        let found: Vec<_> = framed.iter().collect();
+        assert_eq!(
+            found,
+            vec![(offsets[0], &b"first"[..]), (offsets[2], &b"third"[..])]
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn framed_iterate_unwritten_first_lane() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let framed: FramedAppendOnly = lf.substructure("framed")?;
+
+    // too large for the first lane, which is left unwritten
+    let big = vec![7u8; 5000];
+    let a = framed.write(&big)?;
+    let b = framed.write(b"small")?;
+
+    let found: Vec<_> = framed.iter().collect();
+    assert_eq!(found, vec![(a, &big[..]), (b, &b"small"[..])]);
+
+    Ok(())
+}
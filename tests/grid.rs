@@ -0,0 +1,86 @@
+use std::io;
+use std::sync::Arc;
+
+use landfill::{Grid, Landfill};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+#[test]
+fn cells_and_tiles() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let grid: Grid<f64> = lf.substructure("grid")?;
+
+            assert!(grid.set(0, 0, 1.0).is_err());
+            grid.set_shape(100, 8, 16)?;
+
+            for row in 0..50 {
+                for col in 0..100 {
+                    grid.set(row, col, (row * 1000 + col) as f64)?;
+                }
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let grid: Grid<f64> = lf.substructure("grid")?;
+
+        grid.set_shape(100, 8, 16)?;
+        assert!(grid.set_shape(100, 16, 16).is_err());
+        assert_eq!(grid.cols(), 100);
+        assert_eq!(grid.tile_shape(), (8, 16));
+
+        assert_eq!(grid.get(7, 99), Some(7099.0));
+        assert_eq!(grid.get(49, 0), Some(49000.0));
+        assert_eq!(grid.get(1000, 5), Some(0.0));
+        assert_eq!(grid.get(0, 100), None);
+
+        // rows 8..16, columns 16..32
+        let tile = grid.tile(9, 20)?;
+        assert_eq!(tile.len(), 8 * 16);
+        assert_eq!(tile[0], 8016.0);
+        assert_eq!(tile[17], 9017.0);
+
+        // the edge tile, columns 96..112
+        let edge = grid.tile(0, 99)?;
+        assert_eq!(edge[3], 99.0);
+        assert_eq!(edge[4], 0.0);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn concurrent_updates() -> io::Result<()> {
+    const N_THREADS: u64 = 8;
+    const N: u64 = 1000;
+
+    let lf = Landfill::ephemeral()?;
+    let grid: Arc<Grid<u64>> = Arc::new(lf.substructure("grid")?);
+    grid.set_shape(4, 2, 2)?;
+
+    let threads: Vec<_> = (0..N_THREADS)
+        .map(|_| {
+            let grid = grid.clone();
+            std::thread::spawn(move || -> io::Result<()> {
+                for i in 0..N {
+                    grid.update(i % 3, i % 4, |cell| *cell += 1)?;
+                }
+                Ok(())
+            })
+        })
+        .collect();
+
+    for t in threads {
+        t.join().unwrap()?;
+    }
+
+    let total: u64 = (0..3)
+        .flat_map(|row| (0..4).map(move |col| (row, col)))
+        .map(|(row, col)| grid.get(row, col).unwrap())
+        .sum();
+    assert_eq!(total, N_THREADS * N);
+
+    Ok(())
+}
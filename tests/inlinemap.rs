@@ -0,0 +1,68 @@
+use std::io;
+
+use landfill::{InlineMap, Landfill};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+const A_LOT: u64 = 1024 * 16;
+
+#[test]
+fn insert_get() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let map: InlineMap<u64, u32> = lf.substructure("map")?;
+
+    for i in 0..A_LOT {
+        assert_eq!(map.insert(i, i as u32 * 3)?, None);
+    }
+
+    for i in 0..A_LOT {
+        assert_eq!(map.get(&i), Some(i as u32 * 3));
+    }
+
+    assert_eq!(map.get(&A_LOT), None);
+    assert_eq!(map.len(), A_LOT as usize);
+
+    Ok(())
+}
+
+#[test]
+fn zero_key_and_value() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let map: InlineMap<u64, u64> = lf.substructure("map")?;
+
+    assert!(!map.contains_key(&0));
+    assert_eq!(map.insert(0, 0)?, None);
+    assert_eq!(map.get(&0), Some(0));
+    assert_eq!(map.insert(0, 7)?, Some(0));
+    assert_eq!(map.get(&0), Some(0));
+
+    Ok(())
+}
+
+#[test]
+fn persist_restore() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let map: InlineMap<[u8; 12], u16> = lf.substructure("map")?;
+
+            for i in 0..1024u16 {
+                let mut key = [0u8; 12];
+                key[..2].copy_from_slice(&i.to_le_bytes());
+                map.insert(key, i)?;
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let map: InlineMap<[u8; 12], u16> = lf.substructure("map")?;
+
+        for i in 0..1024u16 {
+            let mut key = [0u8; 12];
+            key[..2].copy_from_slice(&i.to_le_bytes());
+            assert_eq!(map.get(&key), Some(i));
+        }
+
+        Ok(())
+    })
+}
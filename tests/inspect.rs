@@ -0,0 +1,101 @@
+#![cfg(feature = "cli")]
+
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+use blake3::Hasher;
+use landfill::{AppendOnly, Content, FramedAppendOnly, Journal, Landfill};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+fn inspect(dir: &Path, args: &[&str]) -> io::Result<(bool, String)> {
+    let output = Command::new(env!("CARGO_BIN_EXE_landfill-inspect"))
+        .arg(dir)
+        .args(args)
+        .output()?;
+    Ok((
+        output.status.success(),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+    ))
+}
+
+#[test]
+fn inspect_commands() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let journal: Journal<u64> = lf.substructure("journal")?;
+            journal.update(|v| *v = 1234);
+
+            let ao: AppendOnly = lf.substructure("ao")?;
+            ao.write(b"hello inspector")?;
+
+            let framed: FramedAppendOnly = lf.substructure("framed")?;
+            framed.write(b"first")?;
+            framed.write(b"second")?;
+
+            let content: Content<Hasher> = lf.substructure("content")?;
+            content.insert(b"some content")?;
+        }
+
+        let (ok, out) = inspect(path, &["branches"])?;
+        assert!(ok);
+        assert!(out.contains("journal"));
+        assert!(out.contains("framed_data"));
+
+        assert_eq!(
+            inspect(path, &["journal", "journal"])?,
+            (true, "1234\n".into())
+        );
+
+        let (ok, out) = inspect(path, &["dump", "ao"])?;
+        assert!(ok);
+        assert!(out.contains("hello inspector"));
+
+        let (ok, out) = inspect(path, &["records", "framed"])?;
+        assert!(ok);
+        assert_eq!(out.lines().count(), 2);
+        assert!(out.contains(&format!("{:02x}", b's')));
+
+        let (ok, out) = inspect(path, &["verify", "content"])?;
+        assert!(ok);
+        assert!(out.starts_with("checked"));
+
+        let (ok, _) = inspect(path, &["bogus"])?;
+        assert!(!ok);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn inspect_missing_branch() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let journal: Journal<u64> = lf.substructure("journal")?;
+            journal.update(|v| *v = 1234);
+        }
+        let files = || -> io::Result<Vec<_>> {
+            let mut names = std::fs::read_dir(path)?
+                .map(|entry| Ok(entry?.file_name()))
+                .collect::<io::Result<Vec<_>>>()?;
+            names.sort();
+            Ok(names)
+        };
+        let before = files()?;
+
+        for command in ["journal", "dump", "records", "verify"] {
+            let (ok, _) = inspect(path, &[command, "jurnal"])?;
+            assert!(!ok);
+        }
+
+        // the dump is left untouched
+        assert!(inspect(path, &["journal", "journal"])?.0);
+        assert_eq!(files()?, before);
+
+        Ok(())
+    })
+}
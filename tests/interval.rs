@@ -0,0 +1,88 @@
+use std::io;
+use std::ops::Range;
+
+use landfill::{IntervalTree, Landfill};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+// intervals of varied lengths, scattered over the first million
+fn interval(i: u64) -> Range<u64> {
+    let start = (i * 7919) % 1_000_000;
+    start..start + 1 + (i * 31) % 5000
+}
+
+fn brute_force(n: u64, query: Range<u64>) -> Vec<u64> {
+    let mut found: Vec<u64> = (0..n)
+        .filter(|i| {
+            let r = interval(*i);
+            r.start < query.end && r.end > query.start
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+#[test]
+fn overlap_queries() -> io::Result<()> {
+    const N: u64 = 2000;
+
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let tree: IntervalTree<u64> = lf.substructure("tree")?;
+
+            for i in 0..N {
+                tree.insert(interval(i), i)?;
+            }
+            assert!(tree.insert(5..5, 0).is_err());
+        }
+
+        let lf = Landfill::open(path)?;
+        let tree: IntervalTree<u64> = lf.substructure("tree")?;
+        assert_eq!(tree.len(), N as usize);
+
+        for query in [0..1, 500..900, 123_456..150_000, 999_999..2_000_000] {
+            let mut found: Vec<u64> = tree
+                .overlapping(query.clone())
+                .map(|(range, i)| {
+                    assert_eq!(range, interval(i));
+                    i
+                })
+                .collect();
+            found.sort();
+            assert_eq!(found, brute_force(N, query));
+        }
+
+        let mut stabbed: Vec<u64> = tree.stab(4242).map(|(_, i)| i).collect();
+        stabbed.sort();
+        assert_eq!(stabbed, brute_force(N, 4242..4243));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn extreme_ranges() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let tree: IntervalTree<u8> = lf.substructure("tree")?;
+
+    tree.insert(0..u64::MAX, 1)?;
+    tree.insert(10..11, 2)?;
+    tree.insert(10..20, 3)?;
+
+    let values = |query: Range<u64>| -> Vec<u8> {
+        let mut v: Vec<u8> = tree.overlapping(query).map(|(_, v)| v).collect();
+        v.sort();
+        v
+    };
+
+    assert_eq!(values(10..11), [1, 2, 3]);
+    assert_eq!(values(11..12), [1, 3]);
+    assert_eq!(values(20..30), [1]);
+    assert_eq!(values(u64::MAX - 1..u64::MAX), [1]);
+    assert!(values(5..5).is_empty());
+    assert_eq!(tree.stab(0).count(), 1);
+
+    Ok(())
+}
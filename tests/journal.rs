@@ -0,0 +1,317 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+
+use bytemuck_derive::*;
+use landfill::{Journal, JournalArray, Landfill, Substructure};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+#[test]
+fn journal_current() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let journal: Journal<u64> = lf.substructure("journal")?;
+
+            assert_eq!(journal.current(), 0);
+
+            for i in 1..=1000 {
+                journal.update(|v| *v = i);
+                assert_eq!(journal.current(), i);
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let journal: Journal<u64> = lf.substructure("journal")?;
+        assert_eq!(journal.current(), 1000);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn journal_try_update() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let journal: Journal<u64> = lf.substructure("journal")?;
+
+    let res: Result<(), &str> = journal.try_update(|v| {
+        *v = 10;
+        Ok(())
+    });
+    assert!(res.is_ok());
+
+    let res: Result<(), &str> = journal.try_update(|v| {
+        *v = 20;
+        Err("oh no")
+    });
+    assert_eq!(res, Err("oh no"));
+    assert_eq!(journal.current(), 10);
+
+    Ok(())
+}
+
+#[test]
+fn journal_checked_update() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let journal: Journal<u64> = lf.substructure("journal")?;
+
+    journal.checked_update(|v| *v = 10)?;
+    assert!(journal.checked_update(|v| *v = 5).is_err());
+    assert_eq!(journal.current(), 10);
+
+    Ok(())
+}
+
+#[test]
+fn journal_array() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let journals: JournalArray<u64, 3> = lf.substructure("journals")?;
+
+            for i in 0..1000 {
+                journals.update(0, |v| *v = i);
+                journals.update(1, |v| *v = i * 2);
+            }
+            journals.update(2, |v| *v = 7);
+
+            assert!(journals.checked_update(2, |v| *v = 6).is_err());
+        }
+
+        let lf = Landfill::open(path)?;
+        let journals: JournalArray<u64, 3> = lf.substructure("journals")?;
+
+        assert_eq!(journals.current(0), 999);
+        assert_eq!(journals.current(1), 1998);
+        assert_eq!(journals.current(2), 7);
+
+        Ok(())
+    })
+}
+
+#[derive(Clone, Copy, Zeroable, Pod, Default, Hash, Debug, PartialEq)]
+#[repr(C)]
+struct Counters {
+    reads: u32,
+    writes: u32,
+    generation: u64,
+}
+
+#[test]
+fn journal_non_ord() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let journal: Journal<Counters> = lf.substructure("journal")?;
+
+            for i in 0..1000 {
+                journal.modify(|c| {
+                    c.reads += 2;
+                    c.writes = 1000 - i;
+                });
+            }
+            journal.modify(|c| c.generation = 3);
+        }
+
+        let lf = Landfill::open(path)?;
+        let journal: Journal<Counters> = lf.substructure("journal")?;
+
+        assert_eq!(
+            journal.current(),
+            Counters {
+                reads: 2000,
+                writes: 1,
+                generation: 3
+            }
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn journal_verify() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let journal: Journal<u64> = lf.substructure("journal")?;
+            for i in 0..10 {
+                journal.update(|v| *v = i);
+            }
+
+            let report = journal.verify()?;
+            assert!(report.is_ok());
+            assert_eq!(report.checked, 10);
+        }
+
+        // flip a byte of the first entry
+        let file = path.join("journal");
+        let mut bytes = fs::read(&file)?;
+        bytes[0] ^= 0xff;
+        fs::write(&file, bytes)?;
+
+        let lf = Landfill::open(path)?;
+        let journal: Journal<u64> = lf.substructure("journal")?;
+        assert_eq!(journal.current(), 9);
+
+        let report = journal.verify()?;
+        assert_eq!(report.checked, 10);
+        assert_eq!(report.problems.len(), 1);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn landfill_verify() -> io::Result<()> {
+    with_temp_path(|path| {
+        let lf = Landfill::open(path)?;
+        let journal: Journal<u64> = lf.substructure("journal")?;
+        let other: Journal<u64> = lf.substructure("other")?;
+        for i in 0..10 {
+            journal.update(|v| *v = i);
+            other.update(|v| *v = i);
+        }
+        journal.flush()?;
+        other.flush()?;
+
+        let report = lf.verify()?;
+        assert!(report.is_ok());
+        assert_eq!(report.checked, 20);
+
+        // flip a byte of the first entry
+        let file = path.join("journal");
+        let mut bytes = fs::read(&file)?;
+        bytes[0] ^= 0xff;
+        fs::write(&file, bytes)?;
+
+        let report = lf.verify()?;
+        assert_eq!(report.checked, 20);
+        assert_eq!(report.problems.len(), 1);
+        assert!(report.problems[0].starts_with("journal: "));
+
+        let ephemeral = Landfill::ephemeral()?;
+        assert!(ephemeral.verify().is_err());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn journal_update_many() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let journal: Journal<u64> = lf.substructure("journal")?;
+
+            let olds = journal.update_many((1..=10).map(|i| {
+                move |v: &mut u64| {
+                    let old = *v;
+                    *v += i;
+                    old
+                }
+            }));
+            assert_eq!(olds, vec![0, 1, 3, 6, 10, 15, 21, 28, 36, 45]);
+            assert_eq!(journal.current(), 55);
+
+            journal.flush()?;
+            // nothing was written since the last flush
+            journal.flush()?;
+
+            let array: JournalArray<u64, 2> = lf.substructure("array")?;
+            array.update_many(1, [|v: &mut u64| *v = 3, |v: &mut u64| *v *= 2]);
+            assert_eq!((array.current(0), array.current(1)), (0, 6));
+        }
+
+        let lf = Landfill::open(path)?;
+        let journal: Journal<u64> = lf.substructure("journal")?;
+        assert_eq!(journal.current(), 55);
+
+        Ok(())
+    })
+}
+
+#[test]
+#[should_panic]
+fn journal_update_many_decreasing() {
+    let lf = Landfill::ephemeral().unwrap();
+    let journal: Journal<u64> = lf.substructure("journal").unwrap();
+
+    journal.update_many([|v: &mut u64| *v = 10, |v: &mut u64| *v = 5]);
+}
+
+#[test]
+fn journal_verify_on_read() -> io::Result<()> {
+    with_temp_path(|path| {
+        let lf = Landfill::open(path)?;
+        let journal: Journal<u64> = lf.substructure("journal")?;
+        journal.set(7);
+
+        // corrupt the value of the first entry while the journal is open
+        let file = path.join("journal");
+        let mut bytes = fs::read(&file)?;
+        bytes[16] ^= 0xff;
+        fs::write(&file, bytes)?;
+
+        assert_eq!(journal.try_current()?, 7 ^ 0xff);
+
+        lf.set_verify_on_read(true);
+        let err = journal.try_current().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        journal.set(8);
+        assert_eq!(journal.try_current()?, 8);
+
+        Ok(())
+    })
+}
+
+// The entry layout before sequence numbers: checksum, then value
+fn legacy_entry(value: u64) -> [u8; 16] {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = seahash::SeaHasher::new();
+    value.hash(&mut hasher);
+
+    let mut entry = [0; 16];
+    entry[..8].copy_from_slice(&hasher.finish().to_ne_bytes());
+    entry[8..].copy_from_slice(&value.to_ne_bytes());
+    entry
+}
+
+#[test]
+fn journal_legacy_migrated() -> io::Result<()> {
+    with_temp_path(|path| {
+        // wrapped around, with the newest value in the first entry
+        let mut page = vec![0; 4096];
+        for (index, value) in [50, 20, 30, 40].into_iter().enumerate() {
+            page[index * 16..][..16].copy_from_slice(&legacy_entry(value));
+        }
+        fs::write(path.join("journal"), page)?;
+
+        {
+            let lf = Landfill::open(path)?;
+            let err = lf.substructure::<Journal<u64>, _>("journal");
+            assert_eq!(
+                err.err().map(|e| e.kind()),
+                Some(ErrorKind::InvalidData)
+            );
+        }
+
+        {
+            let lf = Landfill::open(path)?;
+            let journal = Journal::<u64>::open_migrating(&lf, "journal")?;
+            assert_eq!(journal.current(), 50);
+            assert!(journal.verify()?.is_ok());
+
+            journal.update(|v| *v += 1);
+        }
+
+        let lf = Landfill::open(path)?;
+        let journal: Journal<u64> = lf.substructure("journal")?;
+        assert_eq!(journal.current(), 51);
+
+        Ok(())
+    })
+}
@@ -0,0 +1,128 @@
+use std::io;
+use std::sync::Arc;
+
+use landfill::{KVMap, Landfill, Substructure};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+const A_LOT: u64 = 1024 * 16;
+
+#[test]
+fn insert_update_remove() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let map: KVMap<u64, u64> = lf.substructure("map")?;
+
+            for i in 0..A_LOT {
+                assert_eq!(map.insert(i, i)?, None);
+            }
+            for i in 0..A_LOT {
+                assert_eq!(map.insert(i, i * 2)?, Some(i));
+            }
+            for i in (0..A_LOT).step_by(2) {
+                assert_eq!(map.remove(&i)?, Some(i * 2));
+            }
+            assert_eq!(map.remove(&A_LOT)?, None);
+        }
+
+        let lf = Landfill::open(path)?;
+        let map: KVMap<u64, u64> = lf.substructure("map")?;
+
+        assert_eq!(map.len(), A_LOT as usize / 2);
+        for i in 0..A_LOT {
+            if i % 2 == 0 {
+                assert_eq!(map.get(&i), None);
+            } else {
+                assert_eq!(map.get(&i), Some(i * 2));
+            }
+        }
+
+        // removed keys can be inserted again
+        assert_eq!(map.insert(0, 7)?, None);
+        assert_eq!(map.get(&0), Some(7));
+        assert_eq!(map.len(), A_LOT as usize / 2 + 1);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn iter() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let map: KVMap<u32, [u8; 3]> = lf.substructure("map")?;
+
+    for i in 0..100u32 {
+        map.insert(i, [i as u8; 3])?;
+    }
+    map.remove(&50)?;
+    map.insert(10, [0; 3])?;
+
+    let mut pairs: Vec<_> = map.iter().collect();
+    pairs.sort();
+
+    assert_eq!(pairs.len(), 99);
+    assert_eq!(pairs[10], (10, [0; 3]));
+    assert!(!pairs.iter().any(|(k, _)| *k == 50));
+
+    Ok(())
+}
+
+#[test]
+fn concurrent_updates() -> io::Result<()> {
+    const N_THREADS: u64 = 8;
+    const KEYS: u64 = 64;
+
+    let lf = Landfill::ephemeral()?;
+    let map: Arc<KVMap<u64, u64>> = Arc::new(lf.substructure("map")?);
+
+    let threads: Vec<_> = (0..N_THREADS)
+        .map(|t| {
+            let map = map.clone();
+            std::thread::spawn(move || {
+                for i in 0..A_LOT / N_THREADS {
+                    map.insert(i % KEYS, t).unwrap();
+                }
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        thread.join().unwrap()
+    }
+
+    assert_eq!(map.len(), KEYS as usize);
+    for k in 0..KEYS {
+        assert!(map.get(&k).unwrap() < N_THREADS);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn size_on_disk() -> io::Result<()> {
+    with_temp_path(|path| {
+        let lf = Landfill::open(path)?;
+        let a: KVMap<u64, u64> = lf.substructure("a")?;
+        let b: KVMap<u64, u64> = lf.substructure("b")?;
+
+        a.insert(1, 1)?;
+        let before = a.size_on_disk();
+        assert!(before > 0);
+
+        for i in 0..100_000 {
+            a.insert(i, i)?;
+        }
+        assert!(a.size_on_disk() > before);
+        assert!(a.size_on_disk() > b.size_on_disk());
+        assert!(lf.size_on_disk() >= a.size_on_disk() + b.size_on_disk());
+
+        let ephemeral = Landfill::ephemeral()?;
+        let c: KVMap<u64, u64> = ephemeral.substructure("c")?;
+        c.insert(1, 1)?;
+        assert_eq!(c.size_on_disk(), 0);
+
+        Ok(())
+    })
+}
@@ -0,0 +1,92 @@
+use std::io;
+
+use landfill::{Landfill, LruCache};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+// each entry takes 8 + 8 bytes plus 16 bytes of links
+const ENTRY_SIZE: u64 = 32;
+
+#[test]
+fn evicts_least_recently_used() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let cache: LruCache<u64, u64> = lf.substructure("cache")?;
+    cache.set_budget(ENTRY_SIZE * 100)?;
+    assert_eq!(cache.capacity(), 100);
+
+    for i in 0..100 {
+        cache.insert(i, i)?;
+    }
+    // touch the oldest entry so it survives
+    assert_eq!(cache.get(&0)?, Some(0));
+
+    for i in 100..150 {
+        cache.insert(i, i)?;
+    }
+
+    assert_eq!(cache.len(), 100);
+    assert_eq!(cache.get(&0)?, Some(0));
+    assert_eq!(cache.get(&1)?, None);
+    assert_eq!(cache.get(&50)?, None);
+    assert_eq!(cache.get(&51)?, Some(51));
+    assert_eq!(cache.insert(149, 0)?, Some(149));
+
+    Ok(())
+}
+
+#[test]
+fn remove_and_reuse_slots() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let cache: LruCache<u32, u32> = lf.substructure("cache")?;
+    cache.set_budget(24 * 10)?;
+
+    for i in 0..10 {
+        cache.insert(i, i)?;
+    }
+    assert_eq!(cache.remove(&3)?, Some(3));
+    assert_eq!(cache.remove(&3)?, None);
+    assert!(!cache.contains_key(&3));
+
+    // the freed slot is used without evicting anything
+    cache.insert(10, 10)?;
+    assert!(cache.contains_key(&0));
+    assert_eq!(cache.len(), 10);
+
+    // shrinking the budget evicts the oldest entries
+    cache.set_budget(24 * 5)?;
+    assert_eq!(cache.len(), 5);
+    assert!(!cache.contains_key(&4));
+    assert!(cache.contains_key(&10));
+
+    Ok(())
+}
+
+#[test]
+fn persists_contents() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let cache: LruCache<u64, [u8; 16]> = lf.substructure("cache")?;
+            cache.set_budget(40 * 64)?;
+
+            for i in 0..100u64 {
+                cache.insert(i, [i as u8; 16])?;
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let cache: LruCache<u64, [u8; 16]> = lf.substructure("cache")?;
+
+        assert_eq!(cache.len(), 64);
+        assert_eq!(cache.get(&99)?, Some([99; 16]));
+        assert_eq!(cache.get(&35)?, None);
+
+        // the recency order survives reopening
+        cache.insert(100, [0; 16])?;
+        assert!(!cache.contains_key(&36));
+        assert!(cache.contains_key(&99));
+
+        Ok(())
+    })
+}
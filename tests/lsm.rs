@@ -0,0 +1,116 @@
+use std::io;
+use std::sync::Arc;
+
+use landfill::{Landfill, LsmTree};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+const A_LOT: u64 = 1024 * 16;
+
+#[test]
+fn insert_remove_get() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let tree: LsmTree<u64, u64> = lf.substructure("tree")?;
+            tree.set_memtable_limit(500);
+
+            for i in 0..A_LOT {
+                tree.insert(i, i)?;
+            }
+            for i in (0..A_LOT).step_by(3) {
+                tree.insert(i, i * 10)?;
+            }
+            for i in (0..A_LOT).step_by(2) {
+                tree.remove(i)?;
+            }
+
+            assert!(tree.runs() > 0);
+        }
+
+        // the last writes are only in the write-ahead log
+        let lf = Landfill::open(path)?;
+        let tree: LsmTree<u64, u64> = lf.substructure("tree")?;
+
+        for i in 0..A_LOT {
+            let expected = match (i % 2, i % 3) {
+                (0, _) => None,
+                (_, 0) => Some(i * 10),
+                _ => Some(i),
+            };
+            assert_eq!(tree.get(&i)?, expected, "key {i}");
+        }
+
+        tree.compact()?;
+        assert_eq!(tree.runs(), 1);
+
+        for i in 0..A_LOT {
+            assert_eq!(tree.get(&i)?.is_some(), i % 2 == 1);
+        }
+
+        Ok(())
+    })
+}
+
+#[test]
+fn removed_keys_stay_removed() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let tree: LsmTree<u32, u32> = lf.substructure("tree")?;
+
+    tree.insert(1, 1)?;
+    tree.flush_memtable()?;
+    tree.remove(1)?;
+    tree.flush_memtable()?;
+    assert_eq!(tree.runs(), 2);
+    assert_eq!(tree.get(&1)?, None);
+
+    tree.compact()?;
+    assert_eq!(tree.runs(), 0);
+    assert_eq!(tree.get(&1)?, None);
+
+    tree.insert(1, 2)?;
+    assert_eq!(tree.get(&1)?, Some(2));
+
+    Ok(())
+}
+
+#[test]
+fn concurrent_compaction() -> io::Result<()> {
+    const N_THREADS: u64 = 4;
+
+    let lf = Landfill::ephemeral()?;
+    let tree: Arc<LsmTree<u64, u64>> = Arc::new(lf.substructure("tree")?);
+    tree.set_memtable_limit(100);
+
+    let compactor = {
+        let tree = tree.clone();
+        std::thread::spawn(move || {
+            for _ in 0..50 {
+                tree.compact().unwrap();
+            }
+        })
+    };
+
+    let threads: Vec<_> = (0..N_THREADS)
+        .map(|t| {
+            let tree = tree.clone();
+            std::thread::spawn(move || {
+                for i in 0..A_LOT / N_THREADS {
+                    tree.insert(i * N_THREADS + t, i).unwrap();
+                }
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        thread.join().unwrap()
+    }
+    compactor.join().unwrap();
+
+    for k in 0..A_LOT {
+        assert_eq!(tree.get(&k)?, Some(k / N_THREADS));
+    }
+
+    Ok(())
+}
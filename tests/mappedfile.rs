@@ -0,0 +1,40 @@
+use std::io::{self, ErrorKind};
+
+use landfill::{GuardedLandfill, Landfill, MappedFile, Substructure};
+
+struct Mapped(MappedFile);
+
+impl Substructure for Mapped {
+    fn init(lf: GuardedLandfill) -> io::Result<Self> {
+        lf.map_file_create(100)?.map(Mapped).ok_or_else(|| {
+            io::Error::other("Attempt at mapping the same file twice")
+        })
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[test]
+fn mapped_file_views() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let Mapped(file) = lf.substructure("mapped")?;
+
+    assert_eq!(file.len(), 100);
+    assert!(!file.is_empty());
+
+    // 100 bytes hold 12 whole u64s
+    let words = unsafe { file.as_mut_slice_of::<u64>()? };
+    assert_eq!(words.len(), 12);
+    words[11] = u64::MAX;
+
+    assert_eq!(file.as_slice_of::<u64>()?[11], u64::MAX);
+    assert_eq!(file.as_slice_of::<u32>()?.len(), 25);
+    assert_eq!(&file.as_ref()[88..96], &[0xff; 8]);
+
+    let err = file.as_slice_of::<()>().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use std::io;
+
+use landfill::{Landfill, MerkleMountainRange};
+use sha2::{Sha256, Sha512};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+#[test]
+fn proofs() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let mmr: MerkleMountainRange<Sha256> = lf.substructure("mmr")?;
+
+            assert!(mmr.root().is_none());
+            for i in 0..100u32 {
+                assert_eq!(mmr.append(&i.to_le_bytes())?, i as u64);
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let mmr: MerkleMountainRange<Sha256> = lf.substructure("mmr")?;
+
+        assert_eq!(mmr.len(), 100);
+        assert_eq!(mmr.get(42), Some(&42u32.to_le_bytes()[..]));
+        let root = mmr.root().unwrap();
+
+        for i in 0..100u32 {
+            let proof = mmr.prove(i as u64)?;
+            assert!(proof.verify(&i.to_le_bytes(), &root));
+            assert!(!proof.verify(&(i + 1).to_le_bytes(), &root));
+        }
+        assert!(mmr.prove(100).is_err());
+
+        // the root changes with every leaf, and old proofs no longer verify
+        let proof = mmr.prove(7)?;
+        mmr.append(b"more")?;
+        let new_root = mmr.root().unwrap();
+        assert_ne!(root, new_root);
+        assert!(!proof.verify(&7u32.to_le_bytes(), &new_root));
+        assert!(mmr.prove(7)?.verify(&7u32.to_le_bytes(), &new_root));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn same_leaves_same_root() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let a: MerkleMountainRange<Sha512> = lf.substructure("a")?;
+    let b: MerkleMountainRange<Sha512> = lf.substructure("b")?;
+
+    for leaf in [&b"x"[..], b"", b"yz"] {
+        a.append(leaf)?;
+        b.append(leaf)?;
+    }
+    assert_eq!(a.root(), b.root());
+    assert_eq!(a.get(1), Some(&b""[..]));
+
+    b.append(b"w")?;
+    assert_ne!(a.root(), b.root());
+    assert!(b.prove(3)?.verify(b"w", &b.root().unwrap()));
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use std::io;
+use std::sync::Arc;
+
+use landfill::{Landfill, MultiMap};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+const A_LOT: u64 = 1024 * 16;
+
+#[test]
+fn multiple_values() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let map: MultiMap<u64, u64> = lf.substructure("map")?;
+
+            for i in 0..A_LOT {
+                map.insert(i % 16, i)?;
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let map: MultiMap<u64, u64> = lf.substructure("map")?;
+
+        for k in 0..16 {
+            let values: Vec<_> = map.get(&k).collect();
+            let expected: Vec<_> =
+                (0..A_LOT).filter(|i| i % 16 == k).rev().collect();
+            assert_eq!(values, expected);
+        }
+
+        assert!(!map.contains_key(&16));
+        assert_eq!(map.get(&16).count(), 0);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn over_aligned_values() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let map: MultiMap<u8, u128> = lf.substructure("map")?;
+
+    for i in 0..100u128 {
+        map.insert((i % 3) as u8, i << 64 | i)?;
+    }
+
+    assert_eq!(map.get(&1).count(), 33);
+    assert!(map.get(&2).all(|v| (v >> 64) == (v & u64::MAX as u128)));
+
+    Ok(())
+}
+
+#[test]
+fn concurrent_inserts() -> io::Result<()> {
+    const N_THREADS: u64 = 8;
+
+    let lf = Landfill::ephemeral()?;
+    let map: Arc<MultiMap<u64, u64>> = Arc::new(lf.substructure("map")?);
+
+    let threads: Vec<_> = (0..N_THREADS)
+        .map(|t| {
+            let map = map.clone();
+            std::thread::spawn(move || {
+                for i in 0..A_LOT / N_THREADS {
+                    map.insert(i % 4, t).unwrap();
+                }
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        thread.join().unwrap()
+    }
+
+    let total: usize = (0..4).map(|k| map.get(&k).count()).sum();
+    assert_eq!(total, A_LOT as usize);
+
+    Ok(())
+}
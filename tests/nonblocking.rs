@@ -0,0 +1,84 @@
+use std::future::Future;
+use std::io;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake};
+use std::thread::{self, Thread};
+
+use landfill::{AppendOnly, AsyncHandle, AsyncSubstructure, Journal, Landfill};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+// A minimal executor, parking the thread until woken
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark()
+        }
+    }
+
+    let waker = Arc::new(Unpark(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(res) => return res,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[test]
+fn async_open_flush_reopen() -> io::Result<()> {
+    with_temp_path(|path| {
+        block_on(async {
+            let offset = {
+                let lf = Landfill::open(path)?;
+                let ao: AsyncHandle<AppendOnly> =
+                    lf.substructure_async("ao").await?;
+
+                let offset = ao.write(b"hello async")?;
+                ao.flush().await?;
+
+                let read = ao.run(move |ao| ao.get(offset, 11).to_vec()).await;
+                assert_eq!(read, b"hello async");
+
+                ao.close().await?;
+                offset
+            };
+
+            let lf = Landfill::open(path)?;
+            let ao: AsyncHandle<AppendOnly> =
+                lf.substructure_async("ao").await?;
+            assert_eq!(ao.get(offset, 11), b"hello async");
+
+            Ok(())
+        })
+    })
+}
+
+#[test]
+fn async_close_with_clones() -> io::Result<()> {
+    block_on(async {
+        let lf = Landfill::ephemeral()?;
+        let journal: AsyncHandle<Journal<u64>> =
+            lf.substructure_async("journal").await?;
+
+        let clone = journal.clone();
+        clone.update(|v| *v = 7);
+        assert!(journal.close().await.is_err());
+        assert_eq!(clone.current(), 7);
+
+        // the name is taken by the open structure
+        assert!(lf
+            .substructure_async::<AsyncHandle<Journal<u64>>, _>("journal")
+            .await
+            .is_err());
+
+        Ok(())
+    })
+}
@@ -1,6 +1,10 @@
 use std::io;
 
-use landfill::{Landfill, OnceMap};
+use bytemuck_derive::{Pod, Zeroable};
+use landfill::{AlreadySet, Landfill, OnceMap};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
 
 const A_LOT: usize = 1024;
 
@@ -19,3 +23,213 @@ fn lots() -> io::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn rotate_entropy() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let mut map: OnceMap<u64, u64> = lf.substructure("map")?;
+
+            for i in 0..A_LOT as u64 {
+                map.insert(i, i + 1)?;
+            }
+
+            map.rotate_entropy()?;
+            map.rotate_entropy()?;
+
+            for i in 0..A_LOT as u64 {
+                assert_eq!(map.get(&i).unwrap(), &(i + 1))
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let map: OnceMap<u64, u64> = lf.substructure("map")?;
+
+        for i in 0..A_LOT as u64 {
+            assert_eq!(map.get(&i).unwrap(), &(i + 1))
+        }
+
+        Ok(())
+    })
+}
+
+#[test]
+fn iter() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let map: OnceMap<u64, u64> = lf.substructure("map")?;
+
+            for i in 0..A_LOT as u64 {
+                map.insert(i, i * 2)?;
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let map: OnceMap<u64, u64> = lf.substructure("map")?;
+
+        let mut pairs: Vec<_> = map.iter().map(|(k, v)| (*k, *v)).collect();
+        pairs.sort();
+
+        let expected: Vec<_> = (0..A_LOT as u64).map(|i| (i, i * 2)).collect();
+        assert_eq!(pairs, expected);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn len() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let map: OnceMap<u64, u64> = lf.substructure("map")?;
+            assert!(map.is_empty());
+
+            for i in 0..A_LOT as u64 {
+                map.insert(i, i)?;
+                // setting a key again does not count
+                map.insert(i, i + 1)?;
+            }
+            assert_eq!(map.len(), A_LOT);
+        }
+
+        let lf = Landfill::open(path)?;
+        let map: OnceMap<u64, u64> = lf.substructure("map")?;
+        assert_eq!(map.len(), A_LOT);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn insert_batch() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let map: OnceMap<u32, [u8; 5]> = lf.substructure("map")?;
+
+    map.insert(7, [9; 5])?;
+
+    // duplicates within the batch and of existing keys keep the first value
+    let pairs = (0..20_000u32)
+        .map(|i| (i, [i as u8; 5]))
+        .chain(Some((3, [0; 5])));
+    map.insert_batch(pairs)?;
+
+    for i in (0..20_000u32).filter(|i| *i != 7) {
+        assert_eq!(map.get(&i).unwrap(), &[i as u8; 5]);
+    }
+    assert_eq!(map.get(&7).unwrap(), &[9; 5]);
+    assert_eq!(map.len(), 20_000);
+
+    Ok(())
+}
+
+#[test]
+fn try_insert() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let map: OnceMap<u64, u64> = lf.substructure("map")?;
+
+    assert_eq!(map.try_insert(1, 10)?, Ok(()));
+    assert_eq!(map.try_insert(1, 20)?, Err(AlreadySet { existing: 10 }));
+    assert_eq!(map.get(&1), Some(&10));
+
+    // conflicts can be propagated as io errors
+    let conflict = || -> io::Result<()> {
+        map.try_insert(1, 30)??;
+        Ok(())
+    };
+    assert_eq!(conflict().unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+
+    Ok(())
+}
+
+#[test]
+fn over_aligned_values() -> io::Result<()> {
+    #[derive(Clone, Copy, Debug, PartialEq, Zeroable, Pod)]
+    #[repr(C, align(16))]
+    struct Aligned([u8; 16]);
+
+    let lf = Landfill::ephemeral()?;
+    let map: OnceMap<[u8; 3], Aligned> = lf.substructure("map")?;
+
+    // odd-sized keys would leave the values misaligned without padding
+    for i in 0..1024u32 {
+        let [a, b, c, _] = i.to_le_bytes();
+        map.insert([a, b, c], Aligned([a; 16]))?;
+    }
+
+    for i in 0..1024u32 {
+        let [a, b, c, _] = i.to_le_bytes();
+        let value = map.get(&[a, b, c]).unwrap();
+        assert_eq!(value as *const Aligned as usize % 16, 0);
+        assert_eq!(*value, Aligned([a; 16]));
+        assert_eq!(map.get_copied(&[a, b, c]), Some(Aligned([a; 16])));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn seal() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let mut map: OnceMap<u64, u64> = lf.substructure("map")?;
+
+            for i in 0..A_LOT as u64 {
+                map.insert(i, i + 1)?;
+            }
+
+            map.seal()?;
+            assert!(map.is_sealed());
+            assert_eq!(
+                map.insert(A_LOT as u64, 0).unwrap_err().kind(),
+                io::ErrorKind::PermissionDenied
+            );
+        }
+
+        let lf = Landfill::open(path)?;
+        let map: OnceMap<u64, u64> = lf.substructure("map")?;
+
+        assert!(map.is_sealed());
+        assert!(map.insert(A_LOT as u64, 0).is_err());
+        assert!(map.insert_batch(Some((A_LOT as u64, 0))).is_err());
+
+        for i in 0..A_LOT as u64 {
+            assert_eq!(map.get(&i), Some(&(i + 1)));
+        }
+
+        Ok(())
+    })
+}
+
+#[test]
+fn len_from_baseline() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let map: OnceMap<u64, u64> = lf.substructure("map")?;
+            for i in 0..A_LOT as u64 {
+                map.insert(i, i + 1)?;
+            }
+        }
+        // as if written before index slots were counted
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            let name = entry.file_name().into_string().unwrap();
+            if name.starts_with("map_index_count")
+                || name.starts_with("map_index_slots_len")
+            {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let map: OnceMap<u64, u64> = lf.substructure("map")?;
+        assert_eq!(map.len(), A_LOT);
+        assert!(!map.is_empty());
+
+        Ok(())
+    })
+}
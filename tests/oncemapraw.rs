@@ -0,0 +1,51 @@
+use std::io;
+
+use landfill::{Landfill, OnceMapRaw};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+#[test]
+fn strings() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let map: OnceMapRaw = lf.substructure("map")?;
+
+    map.insert(b"hello", b"world")?;
+    map.insert(b"", b"empty key")?;
+    map.insert(b"empty value", b"")?;
+
+    // keys can only be set once
+    map.insert(b"hello", b"there")?;
+
+    assert_eq!(map.get(b"hello"), Some(&b"world"[..]));
+    assert_eq!(map.get(b""), Some(&b"empty key"[..]));
+    assert_eq!(map.get(b"empty value"), Some(&b""[..]));
+    assert_eq!(map.get(b"missing"), None);
+
+    Ok(())
+}
+
+#[test]
+fn persist_restore() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let map: OnceMapRaw = lf.substructure("map")?;
+
+            for i in 0..1024 {
+                let value = "x".repeat(i);
+                map.insert(format!("key {i}").as_bytes(), value.as_bytes())?;
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let map: OnceMapRaw = lf.substructure("map")?;
+
+        for i in 0..1024 {
+            let value = map.get(format!("key {i}").as_bytes()).unwrap();
+            assert_eq!(value, "x".repeat(i).as_bytes());
+        }
+
+        Ok(())
+    })
+}
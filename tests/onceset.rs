@@ -0,0 +1,74 @@
+use std::io;
+use std::sync::Arc;
+
+use landfill::{Landfill, OnceSet};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+const A_LOT: u64 = 1024 * 16;
+
+#[test]
+fn insert_and_contains() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let set: OnceSet<u64> = lf.substructure("set")?;
+
+            assert!(set.is_empty());
+            for i in 0..A_LOT {
+                assert!(set.insert(i)?);
+            }
+            assert!(!set.insert(0)?);
+            assert!(!set.insert(7)?);
+        }
+
+        let lf = Landfill::open(path)?;
+        let set: OnceSet<u64> = lf.substructure("set")?;
+
+        assert_eq!(set.len(), A_LOT as usize);
+        assert!(set.contains(&0));
+        assert!(set.contains(&(A_LOT - 1)));
+        assert!(!set.contains(&A_LOT));
+
+        let mut keys: Vec<u64> = set.iter().collect();
+        keys.sort();
+        assert_eq!(keys, (0..A_LOT).collect::<Vec<_>>());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn concurrent_inserts() -> io::Result<()> {
+    const N_THREADS: u64 = 8;
+
+    let lf = Landfill::ephemeral()?;
+    let set: Arc<OnceSet<[u8; 12]>> = Arc::new(lf.substructure("set")?);
+
+    let threads: Vec<_> = (0..N_THREADS)
+        .map(|_| {
+            let set = set.clone();
+            std::thread::spawn(move || -> io::Result<u64> {
+                let mut new = 0;
+                for i in 0..A_LOT {
+                    let mut k = [0; 12];
+                    k[..8].copy_from_slice(&i.to_le_bytes());
+                    new += set.insert(k)? as u64;
+                }
+                Ok(new)
+            })
+        })
+        .collect();
+
+    let mut total = 0;
+    for t in threads {
+        total += t.join().unwrap()?;
+    }
+
+    // every key was reported as new exactly once
+    assert_eq!(total, A_LOT);
+    assert_eq!(set.len(), A_LOT as usize);
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use landfill::{Landfill, OptionArray};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+#[test]
+fn option_array_zero_values() -> Result<(), std::io::Error> {
+    let lf = Landfill::ephemeral()?;
+    let oa: OptionArray<u64> = lf.substructure("oa")?;
+
+    assert!(oa.get(3).is_none());
+
+    oa.set(3, 0)?;
+    assert_eq!(*oa.get(3).unwrap(), 0);
+    assert!(oa.get(2).is_none());
+
+    assert_eq!(oa.take(3)?, Some(0));
+    assert_eq!(oa.take(3)?, None);
+    assert!(oa.get(3).is_none());
+
+    Ok(())
+}
+
+#[test]
+fn option_array_persist_restore() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let oa: OptionArray<u32> = lf.substructure("oa")?;
+
+            for i in 0..1024 {
+                oa.set(i * 2, i as u32)?;
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let oa: OptionArray<u32> = lf.substructure("oa")?;
+
+        for i in 0..1024 {
+            assert_eq!(*oa.get(i * 2).unwrap(), i as u32);
+            assert!(oa.get(i * 2 + 1).is_none());
+        }
+
+        Ok(())
+    })
+}
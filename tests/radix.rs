@@ -0,0 +1,122 @@
+use std::io;
+use std::sync::Arc;
+
+use landfill::{Landfill, RadixTrie};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+const PATHS: &[&str] = &[
+    "foo",
+    "foo/bar",
+    "foo/baz",
+    "foo/baz/qux",
+    "foobar",
+    "fo",
+    "bar/a",
+    "bar/b",
+    "",
+];
+
+#[test]
+fn insert_and_get() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let trie: RadixTrie<u32> = lf.substructure("trie")?;
+
+            for (i, p) in PATHS.iter().enumerate() {
+                assert_eq!(trie.insert(p.as_bytes(), i as u32)?, None);
+            }
+            assert_eq!(trie.insert(b"foo", 100)?, Some(0));
+        }
+
+        let lf = Landfill::open(path)?;
+        let trie: RadixTrie<u32> = lf.substructure("trie")?;
+
+        assert_eq!(trie.len(), PATHS.len());
+        assert_eq!(trie.get(b"foo"), Some(100));
+        assert_eq!(trie.get(b"foo/baz/qux"), Some(3));
+        assert_eq!(trie.get(b""), Some(8));
+        assert_eq!(trie.get(b"f"), None);
+        assert_eq!(trie.get(b"foo/"), None);
+        assert_eq!(trie.get(b"foo/bazz"), None);
+
+        let mut sorted = PATHS.to_vec();
+        sorted.sort();
+        let keys: Vec<Vec<u8>> = trie.iter().map(|(k, _)| k).collect();
+        assert_eq!(
+            keys,
+            sorted.iter().map(|p| p.as_bytes()).collect::<Vec<_>>()
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn prefix_iteration() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let trie: RadixTrie<u32> = lf.substructure("trie")?;
+
+    for (i, p) in PATHS.iter().enumerate() {
+        trie.insert(p.as_bytes(), i as u32)?;
+    }
+
+    let prefixed = |prefix: &[u8]| -> Vec<String> {
+        trie.iter_prefix(prefix)
+            .map(|(k, _)| String::from_utf8(k).unwrap())
+            .collect()
+    };
+
+    assert_eq!(prefixed(b"foo/"), ["foo/bar", "foo/baz", "foo/baz/qux"]);
+    assert_eq!(prefixed(b"foo/ba"), ["foo/bar", "foo/baz", "foo/baz/qux"]);
+    assert_eq!(prefixed(b"foo/baz"), ["foo/baz", "foo/baz/qux"]);
+    assert_eq!(
+        prefixed(b"fo"),
+        ["fo", "foo", "foo/bar", "foo/baz", "foo/baz/qux", "foobar"]
+    );
+    assert_eq!(prefixed(b"bar"), ["bar/a", "bar/b"]);
+    assert!(prefixed(b"baz").is_empty());
+    assert!(prefixed(b"foo/bar/x").is_empty());
+
+    assert_eq!(trie.remove(b"foo/baz")?, Some(2));
+    assert_eq!(trie.remove(b"foo/baz")?, None);
+    assert_eq!(prefixed(b"foo/"), ["foo/bar", "foo/baz/qux"]);
+    assert_eq!(trie.len(), PATHS.len() - 1);
+
+    Ok(())
+}
+
+#[test]
+fn concurrent_readers() -> io::Result<()> {
+    const N: u32 = 4096;
+
+    let lf = Landfill::ephemeral()?;
+    let trie: Arc<RadixTrie<u32>> = Arc::new(lf.substructure("trie")?);
+
+    let reader = {
+        let trie = trie.clone();
+        std::thread::spawn(move || {
+            for i in 0..N {
+                let key = format!("key/{}", i * 7 % N);
+                if let Some(v) = trie.get(key.as_bytes()) {
+                    assert_eq!(v, i * 7 % N);
+                }
+                for (k, v) in trie.iter_prefix(b"key/1") {
+                    assert_eq!(k, format!("key/{v}").into_bytes());
+                }
+            }
+        })
+    };
+
+    for i in 0..N {
+        trie.insert(format!("key/{i}").as_bytes(), i)?;
+    }
+    reader.join().unwrap();
+
+    assert_eq!(trie.len(), N as usize);
+    assert_eq!(trie.iter_prefix(b"key/40").count(), 1 + 10 + 96);
+
+    Ok(())
+}
@@ -155,3 +155,344 @@ fn random_access_persist_restore() -> Result<(), std::io::Error> {
         Ok(())
     })
 }
+
+#[test]
+fn random_access_len() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let ra: RandomAccess<u32> = lf.substructure("ra")?;
+
+            assert!(ra.is_empty());
+
+            ra.with_mut(10, |slot| *slot = 1)?;
+            ra.with_mut(3, |slot| *slot = 1)?;
+            assert_eq!(ra.len(), 11);
+
+            ra.with_mut(1000, |slot| *slot = 1)?;
+            assert_eq!(ra.len(), 1001);
+        }
+
+        let lf = Landfill::open(path)?;
+        let ra: RandomAccess<u32> = lf.substructure("ra")?;
+        assert_eq!(ra.len(), 1001);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn random_access_get_mut() -> Result<(), std::io::Error> {
+    let lf = Landfill::ephemeral()?;
+    let ra: RandomAccess<u64> = lf.substructure("ra")?;
+
+    fn bump(ra: &RandomAccess<u64>, index: usize) -> std::io::Result<u64> {
+        let mut slot = ra.get_mut(index)?;
+        *slot += 1;
+        Ok(*slot)
+    }
+
+    assert_eq!(bump(&ra, 7)?, 1);
+    assert_eq!(bump(&ra, 7)?, 2);
+    assert_eq!(*ra.get(7).unwrap(), 2);
+    assert_eq!(ra.len(), 8);
+
+    Ok(())
+}
+
+#[test]
+fn random_access_compare_and_swap() -> Result<(), std::io::Error> {
+    let lf = Landfill::ephemeral()?;
+    let ra: RandomAccess<u32> = lf.substructure("ra")?;
+
+    assert!(ra.compare_and_swap(3, 0, 10)?);
+    assert!(!ra.compare_and_swap(3, 0, 20)?);
+    assert_eq!(*ra.get(3).unwrap(), 10);
+
+    assert!(ra.compare_and_swap(3, 10, 20)?);
+    assert_eq!(*ra.get(3).unwrap(), 20);
+
+    Ok(())
+}
+
+#[test]
+fn random_access_compare_and_swap_claims() -> Result<(), std::io::Error> {
+    const N_THREADS: u64 = 16;
+    const CLAIMS_PER_THREAD: u64 = 512;
+    const N_SLOTS: usize = (N_THREADS * CLAIMS_PER_THREAD * 2) as usize;
+
+    let lf = Landfill::ephemeral()?;
+    let ra: Arc<RandomAccess<u64>> = Arc::new(lf.substructure("ra")?);
+
+    // every thread claims empty slots for its own values
+    let threads: Vec<_> = (0..N_THREADS)
+        .map(|t| {
+            let ra = ra.clone();
+            std::thread::spawn(move || {
+                let mut rng = rand::thread_rng();
+                for i in 0..CLAIMS_PER_THREAD {
+                    let value = t * CLAIMS_PER_THREAD + i + 1;
+                    while !ra
+                        .compare_and_swap(
+                            rng.gen::<usize>() % N_SLOTS,
+                            0,
+                            value,
+                        )
+                        .expect("no errors plz")
+                    {}
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap()
+    }
+
+    // no claim overwrote another
+    let mut values: Vec<u64> =
+        (0..N_SLOTS).filter_map(|i| ra.get(i).map(|v| *v)).collect();
+    values.sort_unstable();
+    let expected: Vec<u64> = (1..=N_THREADS * CLAIMS_PER_THREAD).collect();
+    assert_eq!(values, expected);
+
+    Ok(())
+}
+
+#[test]
+fn random_access_write_slice() -> Result<(), std::io::Error> {
+    let lf = Landfill::ephemeral()?;
+    let ra: RandomAccess<u64> = lf.substructure("ra")?;
+
+    let values: Vec<u64> = (1..=100_000).collect();
+    ra.write_slice(5, &values)?;
+
+    assert!(ra.get(4).is_none());
+    for (i, value) in values.iter().enumerate() {
+        assert_eq!(*ra.get(i + 5).unwrap(), *value);
+    }
+    assert_eq!(ra.len(), 100_005);
+
+    Ok(())
+}
+
+#[test]
+fn random_access_take() -> Result<(), std::io::Error> {
+    let lf = Landfill::ephemeral()?;
+    let ra: RandomAccess<u32> = lf.substructure("ra")?;
+
+    assert_eq!(ra.take(1_000_000), None);
+
+    ra.with_mut(12, |slot| *slot = 99)?;
+    assert_eq!(ra.take(12), Some(99));
+    assert_eq!(ra.take(12), None);
+    assert!(ra.get(12).is_none());
+
+    Ok(())
+}
+
+#[test]
+fn random_access_with_mut_many() -> Result<(), std::io::Error> {
+    let lf = Landfill::ephemeral()?;
+    let ra: RandomAccess<u32> = lf.substructure("ra")?;
+
+    ra.with_mut(1, |slot| *slot = 42)?;
+
+    // move the value from slot 1 to slot 257, which share a lock shard
+    ra.with_mut_many(&[1, 257], |slots| {
+        *slots[1] = std::mem::take(slots[0]);
+    })?;
+
+    assert!(ra.get(1).is_none());
+    assert_eq!(*ra.get(257).unwrap(), 42);
+
+    assert!(ra.with_mut_many(&[3, 3], |_| ()).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn random_access_get_or_init() -> Result<(), std::io::Error> {
+    let lf = Landfill::ephemeral()?;
+    let ra: RandomAccess<u32> = lf.substructure("ra")?;
+
+    assert_eq!(*ra.get_or_init(9, || 5)?, 5);
+    assert_eq!(*ra.get_or_init(9, || unreachable!())?, 5);
+    assert_eq!(*ra.get(9).unwrap(), 5);
+
+    Ok(())
+}
+
+#[test]
+fn random_access_get_slice() -> Result<(), std::io::Error> {
+    let lf = Landfill::ephemeral()?;
+    let ra: RandomAccess<u32> = lf.substructure("ra")?;
+
+    let values: Vec<u32> = (1..=512).collect();
+    ra.write_slice(0, &values)?;
+
+    assert_eq!(&*ra.get_slice(0, 512).unwrap(), &values[..]);
+    assert_eq!(&*ra.get_slice(10, 3).unwrap(), &[11, 12, 13]);
+
+    // the first lane holds 1024 u32s, runs crossing it are not contiguous
+    assert!(ra.get_slice(1000, 100).is_none());
+
+    Ok(())
+}
+
+#[test]
+fn random_access_odd_sized_elements() -> Result<(), std::io::Error> {
+    let lf = Landfill::ephemeral()?;
+    let ra: RandomAccess<[u8; 3]> = lf.substructure("ra")?;
+
+    // elements of 3 bytes do not divide the lanes evenly
+    for i in 0..10_000u32 {
+        let [a, b, c, _] = (i + 1).to_le_bytes();
+        ra.with_mut(i as usize, |slot| *slot = [a, b, c])?;
+    }
+
+    for i in 0..10_000u32 {
+        let [a, b, c, _] = (i + 1).to_le_bytes();
+        assert_eq!(*ra.get(i as usize).unwrap(), [a, b, c]);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn random_access_format_mismatch() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let ra: RandomAccess<u64> = lf.substructure("ra")?;
+            ra.with_mut(3, |v| *v = 1234)?;
+        }
+
+        {
+            let lf = Landfill::open(path)?;
+            let err = lf.substructure::<RandomAccess<u32>, _>("ra").err();
+            assert_eq!(
+                err.map(|e| e.kind()),
+                Some(std::io::ErrorKind::InvalidData)
+            );
+        }
+
+        let lf = Landfill::open(path)?;
+        let ra: RandomAccess<u64> = lf.substructure("ra")?;
+        assert_eq!(*ra.get(3).unwrap(), 1234);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn random_access_on_write() -> Result<(), std::io::Error> {
+    use std::sync::Mutex;
+
+    let lf = Landfill::ephemeral()?;
+    let ra: RandomAccess<u64> = lf.substructure("ra")?;
+
+    let writes = Arc::new(Mutex::new(vec![]));
+    let log = writes.clone();
+    lf.on_write(move |branch, offset, len| {
+        if branch == "ra_array" {
+            log.lock().unwrap().push((offset, len))
+        }
+    });
+
+    *ra.get_mut(3)? = 7;
+    ra.write_slice(0, &[1, 2])?;
+    assert_eq!(ra.take(1), Some(2));
+
+    assert_eq!(*writes.lock().unwrap(), vec![(24, 8), (0, 16), (8, 8)]);
+
+    Ok(())
+}
+
+#[test]
+fn random_access_unpacked_legacy_layout() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let ra: RandomAccess<[u8; 3]> = lf.substructure("ra")?;
+            ra.with_mut(1, |slot| *slot = [1, 1, 1])?;
+        }
+        // as if written before format headers
+        std::fs::remove_file(path.join("ra_format"))?;
+
+        {
+            let lf = Landfill::open(path)?;
+            let ra: RandomAccess<[u8; 3]> = lf.substructure("ra")?;
+            ra.with_mut(1400, |slot| *slot = [7, 8, 9])?;
+            // split between lanes, one byte in lane 0 and two in lane 1
+            ra.write_slice(1365, &[[4, 5, 6]])?;
+        }
+
+        // elements stay at `index * size`
+        let lane_1 = std::fs::read(path.join("ra_array_01"))?;
+        assert_eq!(&lane_1[..2], &[5, 6]);
+        assert_eq!(&lane_1[104..107], &[7, 8, 9]);
+
+        let lf = Landfill::open(path)?;
+        let ra: RandomAccess<[u8; 3]> = lf.substructure("ra")?;
+        assert_eq!(*ra.get(1).unwrap(), [1, 1, 1]);
+        assert_eq!(*ra.get(1400).unwrap(), [7, 8, 9]);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn random_access_len_from_baseline() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let ra: RandomAccess<u64> = lf.substructure("ra")?;
+            ra.with_mut(3, |slot| *slot = 7)?;
+            ra.with_mut(600, |slot| *slot = 9)?;
+        }
+        // as if written before the length was journaled
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            let name = entry.file_name().into_string().unwrap();
+            if name == "ra_format" || name.starts_with("ra_len") {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+
+        {
+            let lf = Landfill::open(path)?;
+            let ra: RandomAccess<u64> = lf.substructure("ra")?;
+            assert_eq!(ra.len(), 601);
+            assert!(!ra.is_empty());
+        }
+
+        // the scanned length is kept
+        let lf = Landfill::open(path)?;
+        let ra: RandomAccess<u64> = lf.substructure("ra")?;
+        assert_eq!(ra.len(), 601);
+        assert_eq!(*ra.get(600).unwrap(), 9);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn random_access_next_to_sibling() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let sibling: RandomAccess<[u8; 3]> = lf.substructure("ra_x")?;
+            sibling.with_mut(1, |slot| *slot = [1, 1, 1])?;
+        }
+
+        // the files of `ra_x` are not data of `ra`, which is packed
+        let lf = Landfill::open(path)?;
+        let ra: RandomAccess<[u8; 3]> = lf.substructure("ra")?;
+        ra.with_mut(1400, |slot| *slot = [7, 8, 9])?;
+
+        let lane_1 = std::fs::read(path.join("ra_array_01"))?;
+        assert_eq!(&lane_1[105..108], &[7, 8, 9]);
+
+        Ok(())
+    })
+}
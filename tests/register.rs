@@ -0,0 +1,45 @@
+use std::io;
+
+use landfill::{Landfill, Register};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+#[test]
+fn register_compare_and_swap() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let register: Register<[u32; 3]> = lf.substructure("register")?;
+
+    assert_eq!(register.get(), [0; 3]);
+
+    assert_eq!(register.compare_and_swap([0; 3], [3, 2, 1]), Ok([0; 3]));
+    assert_eq!(register.compare_and_swap([0; 3], [9, 9, 9]), Err([3, 2, 1]));
+
+    // values may go down as well as up
+    register.set([1, 1, 1]);
+    assert_eq!(register.get(), [1, 1, 1]);
+
+    Ok(())
+}
+
+#[test]
+fn register_reopen() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let register: Register<u64> = lf.substructure("register")?;
+            for i in (0..100).rev() {
+                register.set(i);
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let register: Register<u64> = lf.substructure("register")?;
+        assert_eq!(register.get(), 0);
+
+        register.set(7);
+        assert_eq!(register.get(), 7);
+
+        Ok(())
+    })
+}
@@ -0,0 +1,94 @@
+use std::io::ErrorKind;
+
+use landfill::{AppendOnly, FramedAppendOnly, Landfill, Substructure};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+#[test]
+fn replica_follows_writer() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        let writer = Landfill::open(path)?;
+        let ao: AppendOnly = writer.substructure("ao")?;
+
+        let a = ao.write(b"first")?;
+        ao.sync()?;
+
+        let replica = Landfill::open_read_only(path)?;
+        assert!(replica.is_read_only());
+        let ro: AppendOnly = replica.substructure("ao")?;
+
+        assert_eq!(ro.try_get(a, 5)?, b"first");
+
+        // data past the journaled writehead is not visible until a refresh
+        let b = ao.write(&[7; 100_000])?;
+        ao.sync()?;
+        assert!(ro.try_get(b, 100_000).is_err());
+
+        ro.refresh();
+        assert_eq!(ro.try_get(b, 100_000)?, &[7; 100_000][..]);
+
+        let err = ro.write(b"nope").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        let err = ro.write_batch(&[b"nope"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+        // the replica takes no lock, and leaves the writer's in place
+        drop(ro);
+        drop(replica);
+        assert!(path.join("_lock").exists());
+
+        ao.close()?;
+        drop(writer);
+
+        // a store shrunk on close can still be read
+        let replica = Landfill::open_read_only(path)?;
+        let ro: AppendOnly = replica.substructure("ao")?;
+        assert_eq!(ro.try_get(b, 100_000)?, &[7; 100_000][..]);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn replica_of_missing_structure() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        drop(Landfill::open(path)?);
+
+        let replica = Landfill::open_read_only(path)?;
+        let res: Result<AppendOnly, _> = replica.substructure("ao");
+        assert_eq!(res.err().map(|e| e.kind()), Some(ErrorKind::NotFound));
+
+        assert!(Landfill::open_read_only(path.join("nothing")).is_err());
+        Ok(())
+    })
+}
+
+#[test]
+fn replica_of_shrunk_lanes() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        {
+            let writer = Landfill::open(path)?;
+            let framed: FramedAppendOnly = writer.substructure("framed")?;
+            // the second frame ends at a block boundary, so its lane is
+            // shrunk to end right at the writehead
+            framed.write(&[1; 4000])?;
+            framed.write(&[2; 4072])?;
+            framed.close()?;
+        }
+
+        // reopened without a clean close, so the lanes stay shrunk but
+        // readers scan for frames past the journaled writehead
+        drop(
+            Landfill::open(path)?
+                .substructure::<FramedAppendOnly, _>("framed")?,
+        );
+
+        let replica = Landfill::open_read_only(path)?;
+        let framed: FramedAppendOnly = replica.substructure("framed")?;
+        assert_eq!(framed.iter().count(), 2);
+        assert!(replica.verify()?.is_ok());
+
+        Ok(())
+    })
+}
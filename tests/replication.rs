@@ -0,0 +1,110 @@
+use landfill::{AppendOnly, ChangeLog, Delta, KVMap, Landfill, RandomAccess};
+
+#[test]
+fn replication_ships_writes() -> Result<(), std::io::Error> {
+    let leader_dir = tempfile::tempdir()?;
+    let follower_dir = tempfile::tempdir()?;
+
+    let leader = Landfill::open(leader_dir.path())?;
+    let ao: AppendOnly = leader.substructure("ao")?;
+    let a = ao.write(b"before attaching")?;
+
+    // existing files are shipped in full
+    let log = ChangeLog::attach(&leader)?;
+    assert!(log.pending() > 0);
+
+    let ra: RandomAccess<u64> = leader.substructure("ra")?;
+    *ra.get_mut(3)? = 42;
+    let b = ao.write(&[9; 10_000])?;
+
+    // ship through an encoded stream
+    let mut stream = vec![];
+    for delta in log.ship()? {
+        delta.write_to(&mut stream)?;
+    }
+    assert_eq!(log.pending(), 0);
+
+    let mut reader = &stream[..];
+    let mut deltas = vec![];
+    while let Some(delta) = Delta::read_from(&mut reader)? {
+        deltas.push(delta);
+    }
+
+    let follower = Landfill::open(follower_dir.path())?;
+    follower.apply_deltas(&deltas)?;
+
+    let ao: AppendOnly = follower.substructure("ao")?;
+    let ra: RandomAccess<u64> = follower.substructure("ra")?;
+    assert_eq!(ao.try_get(a, 16)?, b"before attaching");
+    assert_eq!(ao.try_get(b, 10_000)?, &[9; 10_000][..]);
+    assert_eq!(*ra.get(3).unwrap(), 42);
+
+    Ok(())
+}
+
+#[test]
+fn replication_rejects_bad_deltas() -> Result<(), std::io::Error> {
+    let leader_dir = tempfile::tempdir()?;
+    let leader = Landfill::open(leader_dir.path())?;
+    let log = ChangeLog::attach(&leader)?;
+
+    let ao: AppendOnly = leader.substructure("ao")?;
+    ao.write(b"hello")?;
+
+    let mut stream = vec![];
+    for delta in log.ship()? {
+        delta.write_to(&mut stream)?;
+    }
+
+    // rename the first delta to point outside the landfill directory
+    let name_len = u16::from_le_bytes([stream[0], stream[1]]) as usize;
+    let mut tampered = 5u16.to_le_bytes().to_vec();
+    tampered.extend_from_slice(b"../ao");
+    tampered.extend_from_slice(&stream[2 + name_len..]);
+
+    let delta = Delta::read_from(&tampered[..])?.unwrap();
+    let follower = Landfill::ephemeral()?;
+    assert!(follower.apply_deltas(std::slice::from_ref(&delta)).is_err());
+
+    let follower_dir = tempfile::tempdir()?;
+    let follower = Landfill::open(follower_dir.path())?;
+    let err = follower.apply_deltas(&[delta]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    assert!(ChangeLog::attach(&Landfill::ephemeral()?).is_err());
+    Ok(())
+}
+
+#[test]
+fn replication_ships_atomic_cells() -> Result<(), std::io::Error> {
+    let leader_dir = tempfile::tempdir()?;
+    let follower_dir = tempfile::tempdir()?;
+
+    let leader = Landfill::open(leader_dir.path())?;
+    let log = ChangeLog::attach(&leader)?;
+    let follower = Landfill::open(follower_dir.path())?;
+
+    // the heads of a map are atomic cells
+    let map: KVMap<u64, u64> = leader.substructure("map")?;
+    for i in 0..100 {
+        map.insert(i, i)?;
+    }
+    follower.apply_deltas(&log.ship()?)?;
+
+    for i in 0..100 {
+        map.insert(i, i * 2)?;
+    }
+    for i in (0..100).step_by(2) {
+        map.remove(&i)?;
+    }
+    follower.apply_deltas(&log.ship()?)?;
+
+    let replica: KVMap<u64, u64> = follower.substructure("map")?;
+    assert_eq!(replica.len(), 50);
+    for i in 0..100 {
+        let expected = (i % 2 == 1).then_some(i * 2);
+        assert_eq!(replica.get(&i), expected);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use std::io;
+
+use landfill::{Landfill, RingLog};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+#[test]
+fn keeps_last_values() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let log: RingLog<u64, 100> = lf.substructure("log")?;
+
+            assert!(log.is_empty());
+            for i in 0..1000 {
+                assert_eq!(log.push(i)?, i);
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let log: RingLog<u64, 100> = lf.substructure("log")?;
+
+        assert_eq!(log.len(), 100);
+        assert_eq!(log.pushed(), 1000);
+        assert_eq!(log.last(), Some(999));
+        assert_eq!(log.get(899), None);
+        assert_eq!(log.get(900), Some(900));
+        assert_eq!(log.get(1000), None);
+        assert!(log.iter().map(|(_, v)| v).eq(900..1000));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn partially_filled() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let log: RingLog<[u8; 3], 10> = lf.substructure("log")?;
+
+    log.push([1; 3])?;
+    log.push([0; 3])?;
+
+    assert_eq!(log.len(), 2);
+    assert_eq!(log.iter().collect::<Vec<_>>(), [(0, [1; 3]), (1, [0; 3])]);
+
+    let empty: io::Result<RingLog<u8, 0>> = lf.substructure("empty");
+    assert!(empty.is_err());
+
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use std::collections::BTreeSet;
+use std::io;
+
+use landfill::{BitmapStore, Landfill, RoaringBitmap};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+// a mix of sparse and dense containers
+fn sample(seed: u32) -> Vec<u32> {
+    let mut values: Vec<u32> = (0..10_000).map(|i| i * 3 + seed).collect();
+    values.extend((0..1000u32).map(|i| i.wrapping_mul(7_919_993) + seed));
+    values
+}
+
+#[test]
+fn insert_and_contains() {
+    let values = sample(0);
+    let bitmap: RoaringBitmap = values.iter().copied().collect();
+    let expected: BTreeSet<u32> = values.into_iter().collect();
+
+    assert_eq!(bitmap.len(), expected.len() as u64);
+    assert!(bitmap.iter().eq(expected.iter().copied()));
+    assert!(bitmap.contains(3));
+    assert!(!bitmap.contains(4));
+
+    let mut empty = RoaringBitmap::new();
+    assert!(empty.is_empty());
+    assert!(empty.insert(u32::MAX));
+    assert!(!empty.insert(u32::MAX));
+}
+
+#[test]
+fn union_and_intersection() {
+    let a: BTreeSet<u32> = sample(0).into_iter().collect();
+    let b: BTreeSet<u32> = sample(1).into_iter().chain(0..100).collect();
+
+    let ra: RoaringBitmap = a.iter().copied().collect();
+    let rb: RoaringBitmap = b.iter().copied().collect();
+
+    assert!(ra.union(&rb).iter().eq(a.union(&b).copied()));
+    assert!(ra.intersection(&rb).iter().eq(a.intersection(&b).copied()));
+
+    // dense containers on both sides
+    let dense_a: RoaringBitmap = (0..60_000).filter(|i| i % 2 == 0).collect();
+    let dense_b: RoaringBitmap = (0..60_000).filter(|i| i % 3 == 0).collect();
+    let both = dense_a.intersection(&dense_b);
+    assert!(both.iter().eq((0..60_000).filter(|i| i % 6 == 0)));
+    assert_eq!(dense_a.union(&dense_b).len(), 40_000);
+}
+
+#[test]
+fn stored_bitmaps() -> io::Result<()> {
+    with_temp_path(|path| {
+        let bitmap: RoaringBitmap = sample(5).into_iter().collect();
+
+        let record = {
+            let lf = Landfill::open(path)?;
+            let store: BitmapStore = lf.substructure("postings")?;
+            store.insert(&RoaringBitmap::new())?;
+            store.insert(&bitmap)?
+        };
+
+        let lf = Landfill::open(path)?;
+        let store: BitmapStore = lf.substructure("postings")?;
+        assert_eq!(store.get(record)?, bitmap);
+
+        let other: BitmapStore = lf.substructure("other")?;
+        assert!(other.get(record).is_err());
+
+        Ok(())
+    })
+}
@@ -0,0 +1,68 @@
+use std::io;
+
+use landfill::{AppendLog, FramedAppendOnly, Landfill, SecondaryIndex};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+// index values by their low byte, skipping multiples of 100
+fn low_byte(v: &u64) -> Option<u8> {
+    (!v.is_multiple_of(100)).then_some(*v as u8)
+}
+
+#[test]
+fn index_log() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let log: AppendLog<u64> = lf.substructure("log")?;
+            let index: SecondaryIndex<u8> = lf.substructure("index")?;
+
+            for i in 0..500 {
+                log.push(i)?;
+            }
+            assert_eq!(index.index_log(&log, low_byte)?, 500);
+            assert_eq!(index.index_log(&log, low_byte)?, 0);
+        }
+
+        let lf = Landfill::open(path)?;
+        let log: AppendLog<u64> = lf.substructure("log")?;
+        let index: SecondaryIndex<u8> = lf.substructure("index")?;
+
+        assert_eq!(index.indexed(), 500);
+        assert!(index.get(&1).eq([257, 1]));
+        assert!(index.get(&44).eq([44]));
+        assert!(index.get(&0).eq([256]));
+
+        // written through the index, so indexed on write
+        assert_eq!(index.push(&log, 513, low_byte)?, 500);
+        assert!(index.get(&1).eq([500, 257, 1]));
+        assert!(index.contains_key(&1));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn index_framed() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let framed: FramedAppendOnly = lf.substructure("framed")?;
+    let index: SecondaryIndex<[u8; 4]> = lf.substructure("index")?;
+
+    let first_word =
+        |record: &[u8]| -> Option<[u8; 4]> { record.get(..4)?.try_into().ok() };
+
+    let a = framed.write(b"user alice")?;
+    let b = framed.write(b"team red")?;
+    let c = framed.write(b"no")?;
+    assert_eq!(index.index_framed(&framed, first_word)?, 3);
+
+    let d = index.write(&framed, b"user bob", first_word)?;
+
+    assert!(index.get(b"user").eq([d, a]));
+    assert!(index.get(b"team").eq([b]));
+    assert!(!index.contains_key(b"no\0\0"));
+    assert!(c < d);
+
+    Ok(())
+}
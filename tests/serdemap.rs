@@ -0,0 +1,30 @@
+#![cfg(feature = "serde")]
+
+use std::io;
+
+use landfill::{Landfill, SerdeOnceMap};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct User {
+    name: String,
+    groups: Vec<String>,
+}
+
+#[test]
+fn serde_values() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let map: SerdeOnceMap<String, User> = lf.substructure("users")?;
+
+    let alice = User {
+        name: "Alice".into(),
+        groups: vec!["admin".into(), "dev".into()],
+    };
+
+    map.insert(&"alice".to_string(), &alice)?;
+
+    assert_eq!(map.get(&"alice".to_string())?, Some(alice));
+    assert_eq!(map.get(&"bob".to_string())?, None);
+
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use landfill::{AppendOnly, ChangeLog, Landfill, Substructure};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+fn files_in(path: &Path) -> io::Result<Vec<String>> {
+    let mut names = vec![];
+    for entry in fs::read_dir(path)? {
+        names.push(entry?.file_name().to_string_lossy().into_owned());
+    }
+    Ok(names)
+}
+
+#[test]
+fn long_names_hashed() -> io::Result<()> {
+    with_temp_path(|path| {
+        let long = "deeply_nested_".repeat(10);
+
+        let offset = {
+            let lf = Landfill::open(path)?;
+            lf.set_name_limit(Some(64));
+
+            let short: AppendOnly = lf.substructure("short")?;
+            short.write(b"plain")?;
+            let ao: AppendOnly = lf.substructure(long.as_str())?;
+            let offset = ao.write(b"hashed")?;
+
+            for name in files_in(path)? {
+                assert!(name.len() <= 64, "{name} is too long");
+            }
+            assert!(files_in(path)?.iter().any(|n| n.starts_with("short")));
+            assert!(files_in(path)?.contains(&"_names".to_string()));
+            assert!(ao.size_on_disk() > 0);
+            offset
+        };
+
+        // found again without the limit
+        let lf = Landfill::open(path)?;
+        let ao: AppendOnly = lf.substructure(long.as_str())?;
+        assert_eq!(ao.get(offset, 6), b"hashed");
+
+        Ok(())
+    })
+}
+
+#[test]
+fn existing_names_kept() -> io::Result<()> {
+    with_temp_path(|path| {
+        let long = "x".repeat(100);
+
+        let offset = {
+            let lf = Landfill::open(path)?;
+            let ao: AppendOnly = lf.substructure(long.as_str())?;
+            ao.write(b"unhashed")?
+        };
+
+        // setting a limit later still finds the files under their full names
+        let lf = Landfill::open(path)?;
+        lf.set_name_limit(Some(64));
+        let ao: AppendOnly = lf.substructure(long.as_str())?;
+        assert_eq!(ao.get(offset, 8), b"unhashed");
+        assert!(!files_in(path)?.iter().any(|n| n.starts_with("_h")));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn hashed_temp_branch_removed() -> io::Result<()> {
+    with_temp_path(|path| {
+        let lf = Landfill::open(path)?;
+        lf.set_name_limit(Some(32));
+
+        let hashed = || -> io::Result<usize> {
+            Ok(files_in(path)?
+                .iter()
+                .filter(|name| name.starts_with("_h"))
+                .count())
+        };
+
+        let scratch = lf.temp_branch()?;
+        let ao: AppendOnly = scratch.substructure("a".repeat(40))?;
+        ao.write(b"scratch")?;
+        assert!(hashed()? > 0);
+
+        drop(scratch);
+        drop(ao);
+        assert_eq!(hashed()?, 0);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn hashed_names_replicated() -> io::Result<()> {
+    with_temp_path(|leader_path| {
+        with_temp_path(|follower_path| {
+            let long = "x".repeat(100);
+
+            let leader = Landfill::open(leader_path)?;
+            leader.set_name_limit(Some(64));
+            let log = ChangeLog::attach(&leader)?;
+
+            let ao: AppendOnly = leader.substructure(long.as_str())?;
+            let offset = ao.write(b"shipped")?;
+
+            let follower = Landfill::open(follower_path)?;
+            follower.apply_deltas(&log.ship()?)?;
+
+            let ao: AppendOnly = follower.substructure(long.as_str())?;
+            assert_eq!(ao.get(offset, 7), b"shipped");
+
+            Ok(())
+        })
+    })
+}
@@ -0,0 +1,74 @@
+use std::io;
+use std::sync::Arc;
+
+use landfill::{Landfill, SkipList};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+const A_LOT: u64 = 1024 * 16;
+
+#[test]
+fn ordered_inserts() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let list: SkipList<u64, u64> = lf.substructure("list")?;
+
+            // insert in a scrambled order
+            for i in 0..A_LOT {
+                let k = (i * 7919) % A_LOT;
+                assert_eq!(list.insert(k, k * 2)?, None);
+            }
+            assert_eq!(list.insert(5, 0)?, Some(10));
+        }
+
+        let lf = Landfill::open(path)?;
+        let list: SkipList<u64, u64> = lf.substructure("list")?;
+
+        assert_eq!(list.len(), A_LOT as usize);
+        assert_eq!(list.get(&5), Some(0));
+        assert_eq!(list.get(&6), Some(12));
+        assert_eq!(list.get(&A_LOT), None);
+
+        let keys: Vec<u64> = list.iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, (0..A_LOT).collect::<Vec<_>>());
+
+        let from: Vec<u64> =
+            list.iter_from(&100).take(3).map(|(k, _)| k).collect();
+        assert_eq!(from, [100, 101, 102]);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn concurrent_inserts() -> io::Result<()> {
+    const N_THREADS: u64 = 8;
+
+    let lf = Landfill::ephemeral()?;
+    let list: Arc<SkipList<u64, u64>> = Arc::new(lf.substructure("list")?);
+
+    let threads: Vec<_> = (0..N_THREADS)
+        .map(|t| {
+            let list = list.clone();
+            std::thread::spawn(move || {
+                for i in 0..A_LOT / N_THREADS {
+                    list.insert(i * N_THREADS + t, t).unwrap();
+                    // overlapping keys from every thread
+                    list.insert(i, t).unwrap();
+                }
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        thread.join().unwrap()
+    }
+
+    assert_eq!(list.len(), A_LOT as usize);
+    let keys: Vec<u64> = list.iter().map(|(k, _)| k).collect();
+    assert_eq!(keys, (0..A_LOT).collect::<Vec<_>>());
+
+    Ok(())
+}
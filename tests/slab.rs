@@ -0,0 +1,73 @@
+use std::io;
+
+use landfill::{Landfill, Slab, SlabHandle};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+#[test]
+fn insert_update_remove() -> io::Result<()> {
+    with_temp_path(|path| {
+        let (a, b) = {
+            let lf = Landfill::open(path)?;
+            let slab: Slab = lf.substructure("slab")?;
+
+            let a = slab.insert(b"small")?;
+            let b = slab.insert(&[7; 1000])?;
+            assert_ne!(a, b);
+
+            // grows into a bigger class, keeping the handle
+            slab.update(a, &[1; 300])?;
+            slab.update(b, b"shrunk")?;
+            (a.to_u64(), b.to_u64())
+        };
+
+        let lf = Landfill::open(path)?;
+        let slab: Slab = lf.substructure("slab")?;
+        let (a, b) = (SlabHandle::from_u64(a), SlabHandle::from_u64(b));
+
+        assert_eq!(slab.len(), 2);
+        assert_eq!(slab.get(a)?, [1; 300]);
+        assert_eq!(slab.get(b)?, b"shrunk");
+
+        slab.remove(a)?;
+        assert!(!slab.contains(a));
+        assert!(slab.get(a).is_err());
+        assert!(slab.remove(a).is_err());
+        assert_eq!(slab.len(), 1);
+
+        // the entry is reused, but the old handle stays invalid
+        let c = slab.insert(b"new")?;
+        assert_ne!(c, a);
+        assert!(slab.get(a).is_err());
+        assert_eq!(slab.get(c)?, b"new");
+
+        Ok(())
+    })
+}
+
+#[test]
+fn reuses_blocks() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let slab: Slab = lf.substructure("slab")?;
+
+    let handles: Vec<_> = (0..100u8)
+        .map(|i| slab.insert(&[i; 200]))
+        .collect::<io::Result<_>>()?;
+    for h in &handles {
+        slab.remove(*h)?;
+    }
+    assert!(slab.is_empty());
+
+    for i in 0..100u8 {
+        let h = slab.insert(&[i; 100])?;
+        assert_eq!(slab.get(h)?, [i; 100]);
+    }
+    assert_eq!(slab.len(), 100);
+
+    assert!(slab.insert(&vec![0; 2 * 1024 * 1024]).is_err());
+    let empty = slab.insert(b"")?;
+    assert!(slab.get(empty)?.is_empty());
+
+    Ok(())
+}
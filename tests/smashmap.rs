@@ -1,6 +1,9 @@
 use std::io;
 
-use landfill::{Landfill, SmashMap};
+use landfill::{Entropy, Landfill, PrehashedAdapter, SmashMap, Substructure};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
 
 #[test]
 fn trivial() -> io::Result<()> {
@@ -62,3 +65,270 @@ fn a_lot() -> io::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn len_and_occupancy() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let h: SmashMap<u32, u32> = lf.substructure("h")?;
+
+    assert!(h.is_empty());
+    assert_eq!(h.occupancy(), 0.0);
+
+    for i in 1..=100u32 {
+        h.insert(&i, |s, _| s.proceed(), |_| Ok(i))?;
+    }
+
+    // inserting an existing value halts without taking a slot
+    h.insert(&1, |s, _| s.halt(), |_| Ok(1))?;
+
+    assert_eq!(h.len(), 100);
+    assert!(h.occupancy() > 0.0 && h.occupancy() <= 1.0);
+
+    Ok(())
+}
+
+#[test]
+fn probe_stats() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let h: SmashMap<u32, u32> = lf.substructure("h")?;
+
+    for i in 1..=4096u32 {
+        h.insert(&i, |s, _| s.proceed(), |_| Ok(i))?;
+    }
+
+    let stats = h.probe_stats();
+    assert_eq!(stats.operations, 4096);
+    assert!(stats.average_probes >= 1.0);
+    assert!(stats.max_probes >= stats.average_probes as u64);
+    // more entries than the initial fanout forces deeper levels
+    assert!(stats.max_level > 0);
+
+    Ok(())
+}
+
+#[test]
+fn rehash() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let mut h: SmashMap<u32, u32> = lf.substructure("h")?;
+
+    for i in 1..=A_LOT as u32 {
+        h.insert(&i, |s, _| s.proceed(), |_| Ok(i))?;
+    }
+
+    // values are their own keys
+    h.rehash(|v| *v, |_, v| v)?;
+
+    assert_eq!(h.len(), A_LOT);
+
+    for i in 1..=A_LOT as u32 {
+        let mut found = false;
+        h.get(&i, |s, candidate| {
+            if *candidate == i {
+                found = true;
+                s.halt()
+            } else {
+                s.proceed()
+            }
+        });
+        assert_eq!(found, true);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn rehash_fresh_entropy() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let mut h: SmashMap<u32, u32> = lf.substructure("h")?;
+            for i in 1..=1024u32 {
+                h.insert(&i, |s, _| s.proceed(), |_| Ok(i))?;
+            }
+            h.rehash(|v| *v, |_, v| v)?;
+        }
+
+        {
+            let lf = Landfill::open(path)?;
+            let h: SmashMap<u32, u32> = lf.substructure("h")?;
+            for i in 1..=1024u32 {
+                let mut found = false;
+                h.get(&i, |s, candidate| {
+                    found |= *candidate == i;
+                    s.proceed()
+                });
+                assert!(found);
+            }
+        }
+
+        // the entropy of the second generation of `h`
+        let tag = {
+            let lf = Landfill::open(path)?;
+            let persisted: Entropy = lf.substructure("h_entropy1")?;
+            persisted.tag()
+        };
+
+        // not what the root seed derives for the same branch
+        std::fs::remove_file(path.join("h_entropy1"))?;
+        let lf = Landfill::open(path)?;
+        let derived: Entropy = lf.substructure("h_entropy1")?;
+        assert_ne!(derived.tag(), tag);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn prehashed_keys() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let h: SmashMap<[u8; 32], u32, PrehashedAdapter> = lf.substructure("h")?;
+
+    let keys: Vec<[u8; 32]> = (1..=1024u32)
+        .map(|i| *blake3::hash(&i.to_le_bytes()).as_bytes())
+        .collect();
+
+    for (i, key) in keys.iter().enumerate() {
+        h.insert(key, |s, _| s.proceed(), |_| Ok(i as u32 + 1))?;
+    }
+
+    for (i, key) in keys.iter().enumerate() {
+        let mut found = false;
+        h.get(key, |s, candidate| {
+            if *candidate == i as u32 + 1 {
+                found = true;
+                s.halt()
+            } else {
+                s.proceed()
+            }
+        });
+        assert_eq!(found, true);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn probe_limit() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let mut h: SmashMap<u32, u32> = lf.substructure("h")?;
+    h.set_probe_limit(4);
+
+    // every insert of the same key probes past the occupied slots
+    let mut result = Ok(());
+    for i in 1..=8 {
+        result = h.insert(&0, |s, _| s.proceed(), |_| Ok(i));
+        if result.is_err() {
+            break;
+        }
+    }
+    assert!(result.is_err());
+
+    let mut seen = 0;
+    h.get(&0, |s, _| {
+        seen += 1;
+        s.proceed()
+    });
+    assert_eq!(seen, 4);
+
+    Ok(())
+}
+
+#[test]
+fn get_first() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let h: SmashMap<u32, u32> = lf.substructure("h")?;
+
+    for i in 1..=1024u32 {
+        h.insert(&i, |s, _| s.proceed(), |_| Ok(i * 2))?;
+    }
+
+    for i in 1..=1024u32 {
+        let found = h.get_first(&i, |_, v| *v == i * 2).unwrap();
+        assert_eq!(*found, i * 2);
+    }
+
+    assert!(h.get_first(&5000, |_, v| *v == 10_000).is_none());
+
+    Ok(())
+}
+
+#[test]
+fn verify_slots() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let map: SmashMap<u64, u64> = lf.substructure("map")?;
+
+    for i in 1..=1000u64 {
+        map.insert(&i, |s, _| s.proceed(), |_| Ok(i))?;
+    }
+
+    let report = map.verify()?;
+    assert!(report.is_ok(), "{:?}", report.problems);
+    assert!(report.checked >= 1000);
+
+    Ok(())
+}
+
+#[test]
+fn len_from_baseline() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let h: SmashMap<u32, u32> = lf.substructure("h")?;
+            for i in 1..=100u32 {
+                h.insert(&i, |s, _| s.proceed(), |_| Ok(i))?;
+            }
+        }
+        // as if written before slots were counted
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            let name = entry.file_name().into_string().unwrap();
+            if name.starts_with("h_count") || name.starts_with("h_slots_len") {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+
+        {
+            let lf = Landfill::open(path)?;
+            let h: SmashMap<u32, u32> = lf.substructure("h")?;
+            assert_eq!(h.len(), 100);
+            assert!(h.occupancy() > 0.0);
+            assert!(lf.verify()?.is_ok());
+        }
+
+        let lf = Landfill::open_read_only(path)?;
+        let h: SmashMap<u32, u32> = lf.substructure("h")?;
+        assert_eq!(h.len(), 100);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn size_on_disk_excludes_siblings() -> io::Result<()> {
+    with_temp_path(|path| {
+        let size_of_h = {
+            let lf = Landfill::open(path)?;
+            let h: SmashMap<u32, u32> = lf.substructure("h")?;
+            h.insert(&1, |s, _| s.proceed(), |_| Ok(1))?;
+            h.size_on_disk()
+        };
+
+        // a sibling whose name starts with that of `h`
+        {
+            let lf = Landfill::open(path)?;
+            let h_x: SmashMap<u32, u32> = lf.substructure("h_x")?;
+            for i in 1..=10_000u32 {
+                h_x.insert(&i, |s, _| s.proceed(), |_| Ok(i))?;
+            }
+            let h: SmashMap<u32, u32> = lf.substructure("h")?;
+            assert_eq!(h.size_on_disk(), size_of_h);
+        }
+
+        // also when the sibling is not opened
+        let lf = Landfill::open(path)?;
+        let h: SmashMap<u32, u32> = lf.substructure("h")?;
+        assert_eq!(h.size_on_disk(), size_of_h);
+
+        Ok(())
+    })
+}
@@ -0,0 +1,74 @@
+use std::io;
+use std::sync::Arc;
+
+use landfill::{AppendLog, AppendOnly, Landfill, Snapshot, WriteAheadLog};
+
+const A_LOT: u64 = 1024 * 16;
+
+#[test]
+fn append_log_snapshot() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let log: AppendLog<u64> = lf.substructure("log")?;
+
+    for i in 0..100 {
+        log.push(i)?;
+    }
+
+    let snapshot = log.snapshot();
+    for i in 100..200 {
+        log.push(i)?;
+    }
+
+    assert_eq!(snapshot.len(), 100);
+    assert_eq!(snapshot.get(99), Some(&99));
+    assert_eq!(snapshot.get(100), None);
+    assert!(snapshot.iter().copied().eq(0..100));
+    assert_eq!(log.len(), 200);
+
+    Ok(())
+}
+
+#[test]
+fn append_only_snapshot() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let store: AppendOnly = lf.substructure("store")?;
+
+    let before = store.write(b"before")?;
+    let snapshot = store.snapshot();
+    let after = store.write(b"after")?;
+
+    assert_eq!(snapshot.get(before, 6)?, b"before");
+    assert!(snapshot.get(after, 5).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn scan_under_ingestion() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let wal: Arc<WriteAheadLog> = Arc::new(lf.substructure("wal")?);
+
+    for i in 0..A_LOT {
+        wal.append(&i.to_le_bytes())?;
+    }
+
+    let writer = {
+        let wal = wal.clone();
+        std::thread::spawn(move || {
+            for i in A_LOT..A_LOT * 2 {
+                wal.append(&i.to_le_bytes()).unwrap();
+            }
+        })
+    };
+
+    let snapshot = wal.snapshot();
+    assert!(snapshot.len() >= A_LOT);
+    let scanned = snapshot.replay(0).count() as u64;
+    assert_eq!(scanned, snapshot.epoch());
+
+    writer.join().unwrap();
+    assert_eq!(snapshot.replay(0).count() as u64, snapshot.len());
+    assert_eq!(wal.len(), A_LOT * 2);
+
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use std::io;
+
+use landfill::{Landfill, Sorter};
+
+const A_LOT: u64 = 1024 * 64;
+
+#[test]
+fn sorts_across_runs() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let sorter: Sorter<[u64; 2]> = lf.substructure("sorter")?;
+    sorter.set_memory_limit(4096 * 16);
+
+    sorter.extend((0..A_LOT).map(|i| [(i * 7919) % A_LOT, i]))?;
+
+    assert_eq!(sorter.len(), A_LOT as usize);
+    assert!(sorter.runs() > 1);
+
+    let keys: Vec<u64> = sorter.sorted()?.map(|[k, _]| k).collect();
+    assert_eq!(keys, (0..A_LOT).collect::<Vec<_>>());
+
+    Ok(())
+}
+
+#[test]
+fn duplicates_and_more_records() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+    let sorter: Sorter<u16> = lf.substructure("sorter")?;
+    sorter.set_memory_limit(64);
+
+    assert!(sorter.is_empty());
+    assert_eq!(sorter.sorted()?.count(), 0);
+
+    sorter.extend([5, 3, 5, 1])?;
+    assert!(sorter.sorted()?.eq([1, 3, 5, 5]));
+
+    sorter.extend((0..100).rev())?;
+    let sorted: Vec<u16> = sorter.sorted()?.collect();
+    assert_eq!(sorted.len(), 104);
+    assert!(sorted.windows(2).all(|w| w[0] <= w[1]));
+
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use std::io::{self, ErrorKind};
+
+use landfill::{Landfill, Substructure};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+#[derive(Debug)]
+struct Static(Landfill);
+
+impl Substructure for Static {
+    fn init(lf: landfill::GuardedLandfill) -> io::Result<Self> {
+        Ok(Static(lf.inner()))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn kind<T>(res: io::Result<T>) -> Option<ErrorKind> {
+    res.err().map(|e| e.kind())
+}
+
+#[test]
+fn static_size_mismatch() -> io::Result<()> {
+    with_temp_path(|path| {
+        let lf = Landfill::open(path)?;
+        let Static(value) = lf.substructure("value")?;
+
+        assert_eq!(value.get_static_or_init(|| 42u64)?, 42);
+        assert_eq!(value.get_static_or_init(|| 0u64)?, 42);
+
+        let res = value.get_static_or_init(|| 0u32);
+        assert_eq!(kind(res), Some(ErrorKind::InvalidData));
+
+        // as large as the header and value together
+        let res = value.get_static_or_init(|| [0u8; 24]);
+        assert_eq!(kind(res), Some(ErrorKind::InvalidData));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn static_tag_mismatch() -> io::Result<()> {
+    with_temp_path(|path| {
+        let lf = Landfill::open(path)?;
+        let Static(value) = lf.substructure("value")?;
+
+        assert_eq!(value.get_static_or_init_tagged(1, || 7u64)?, 7);
+        assert_eq!(value.get_static_or_init_tagged(1, || 0u64)?, 7);
+
+        let res = value.get_static_or_init_tagged(2, || 0u64);
+        assert_eq!(kind(res), Some(ErrorKind::InvalidData));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn static_legacy_files() -> io::Result<()> {
+    with_temp_path(|path| {
+        std::fs::write(path.join("legacy"), 9u64.to_ne_bytes())?;
+        std::fs::write(path.join("short"), [1, 2, 3])?;
+
+        let lf = Landfill::open(path)?;
+        let Static(legacy) = lf.substructure("legacy")?;
+        let Static(short) = lf.substructure("short")?;
+
+        assert_eq!(legacy.get_static_or_init_tagged(5, || 0u64)?, 9);
+
+        let res = short.get_static_or_init(|| 0u64);
+        assert_eq!(kind(res), Some(ErrorKind::InvalidData));
+
+        Ok(())
+    })
+}
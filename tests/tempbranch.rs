@@ -0,0 +1,74 @@
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+use landfill::{AppendOnly, Landfill, Sorter};
+
+fn files_in(path: &std::path::Path) -> std::io::Result<Vec<String>> {
+    let mut names = vec![];
+    for entry in std::fs::read_dir(path)? {
+        names.push(entry?.file_name().to_string_lossy().into_owned());
+    }
+    Ok(names)
+}
+
+#[test]
+fn temp_branch_removed_on_drop() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        let lf = Landfill::open(path)?;
+        let kept: AppendOnly = lf.substructure("kept")?;
+        kept.write(b"persistent")?;
+
+        let scratch = lf.temp_branch()?;
+        let sorter: Sorter<u64> = scratch.substructure("sort")?;
+        sorter.set_memory_limit(64);
+        for i in (0..1000u64).rev() {
+            sorter.push(i)?;
+        }
+        assert!(sorter.runs() > 1);
+
+        let tmp_files = |path| -> std::io::Result<usize> {
+            Ok(files_in(path)?
+                .iter()
+                .filter(|name| name.starts_with("_tmp"))
+                .count())
+        };
+        assert!(tmp_files(path)? > 0);
+
+        // the substructure keeps the branch alive after the handle drops
+        drop(scratch);
+        assert!(tmp_files(path)? > 0);
+
+        drop(sorter);
+        assert_eq!(tmp_files(path)?, 0);
+        assert!(files_in(path)?.iter().any(|name| name.starts_with("kept")));
+
+        // a second temporary branch does not collide with the first
+        let scratch = lf.temp_branch()?;
+        let _sorter: Sorter<u64> = scratch.substructure("sort")?;
+
+        Ok(())
+    })
+}
+
+#[test]
+fn temp_branch_leftovers_removed_on_open() -> Result<(), std::io::Error> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let scratch = lf.temp_branch()?;
+            let ao: AppendOnly = scratch.substructure("staging")?;
+            ao.write(b"scratch")?;
+            // simulate a crash, leaving the files in place
+            std::mem::forget(ao);
+            std::mem::forget(scratch);
+        }
+
+        assert!(files_in(path)?.iter().any(|name| name.starts_with("_tmp")));
+        std::fs::remove_file(path.join("_lock")).ok();
+
+        let _lf = Landfill::open(path)?;
+        assert!(!files_in(path)?.iter().any(|name| name.starts_with("_tmp")));
+
+        Ok(())
+    })
+}
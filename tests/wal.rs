@@ -0,0 +1,106 @@
+use std::io;
+use std::sync::Arc;
+
+use landfill::{Landfill, WriteAheadLog};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+const A_LOT: u64 = 1024 * 4;
+
+#[test]
+fn append_replay_checkpoint() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let wal: WriteAheadLog = lf.substructure("wal")?;
+
+            for i in 0..A_LOT {
+                assert_eq!(wal.append(format!("entry {i}").as_bytes())?, i);
+            }
+            wal.checkpoint(99)?;
+            assert!(wal.checkpoint(A_LOT).is_err());
+        }
+
+        let lf = Landfill::open(path)?;
+        let wal: WriteAheadLog = lf.substructure("wal")?;
+
+        assert_eq!(wal.len(), A_LOT);
+        assert_eq!(wal.checkpointed(), 100);
+
+        let replayed: Vec<_> = wal.replay_from_checkpoint().collect();
+        assert_eq!(replayed.len(), A_LOT as usize - 100);
+        for (seq, entry) in replayed {
+            assert_eq!(entry, format!("entry {seq}").as_bytes());
+        }
+
+        assert_eq!(wal.replay(A_LOT - 1).count(), 1);
+        assert_eq!(wal.get(7), Some(&b"entry 7"[..]));
+
+        // checkpoints never move backwards
+        wal.checkpoint(10)?;
+        assert_eq!(wal.checkpointed(), 100);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn concurrent_appends() -> io::Result<()> {
+    const N_THREADS: u64 = 8;
+
+    let lf = Landfill::ephemeral()?;
+    let wal: Arc<WriteAheadLog> = Arc::new(lf.substructure("wal")?);
+
+    let threads: Vec<_> = (0..N_THREADS)
+        .map(|t| {
+            let wal = wal.clone();
+            std::thread::spawn(move || {
+                for _ in 0..A_LOT / N_THREADS {
+                    wal.append(&t.to_le_bytes()).unwrap();
+                }
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        thread.join().unwrap()
+    }
+
+    assert_eq!(wal.replay(0).count(), A_LOT as usize);
+
+    Ok(())
+}
+
+#[test]
+fn recover_unindexed_entries() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let wal: WriteAheadLog = lf.substructure("wal")?;
+
+            for i in 0..A_LOT {
+                wal.append(&i.to_le_bytes())?;
+            }
+        }
+
+        // lose the index of the entries, as if it was never written
+        for file in std::fs::read_dir(path)? {
+            let file = file?.path();
+            let name = file.file_name().unwrap().to_string_lossy().to_string();
+            if name.starts_with("wal_offsets") {
+                std::fs::remove_file(file)?;
+            }
+        }
+
+        let lf = Landfill::open(path)?;
+        let wal: WriteAheadLog = lf.substructure("wal")?;
+
+        assert_eq!(wal.len(), A_LOT);
+        for (seq, entry) in wal.replay(0) {
+            assert_eq!(entry, seq.to_le_bytes());
+        }
+
+        Ok(())
+    })
+}
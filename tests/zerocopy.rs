@@ -0,0 +1,42 @@
+#![cfg(feature = "zerocopy")]
+
+use std::io;
+
+use landfill::{Journal, KVMap, Landfill, RandomAccess, ZeroCopy};
+
+mod with_temp_path;
+use with_temp_path::with_temp_path;
+
+#[test]
+fn zerocopy_random_access() -> io::Result<()> {
+    with_temp_path(|path| {
+        {
+            let lf = Landfill::open(path)?;
+            let ra: RandomAccess<ZeroCopy<[u16; 3]>> = lf.substructure("ra")?;
+            ra.with_mut(7, |v| *v = ZeroCopy([1, 2, 3]))?;
+        }
+
+        let lf = Landfill::open(path)?;
+        let ra: RandomAccess<ZeroCopy<[u16; 3]>> = lf.substructure("ra")?;
+        assert_eq!(ra.get(7).map(|v| *v), Some(ZeroCopy([1, 2, 3])));
+        assert_eq!(ra.get(7).unwrap()[2], 3);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn zerocopy_journal_and_map() -> io::Result<()> {
+    let lf = Landfill::ephemeral()?;
+
+    let journal: Journal<ZeroCopy<u64>> = lf.substructure("journal")?;
+    journal.update(|v| **v += 5);
+    assert_eq!(journal.current().into_inner(), 5);
+
+    let map: KVMap<ZeroCopy<[u8; 4]>, ZeroCopy<i32>> =
+        lf.substructure("map")?;
+    map.insert(ZeroCopy(*b"abcd"), ZeroCopy(-1))?;
+    assert_eq!(map.get(&ZeroCopy(*b"abcd")), Some(ZeroCopy(-1)));
+
+    Ok(())
+}